@@ -155,16 +155,20 @@ impl EntityId {
 pub struct Entity {
     pub id: u32,
     pub(crate) components: Vec<Option<Box<dyn Component>>>,
+    /// World tick at which each component slot was last added, removed or borrowed mutably.
+    pub(crate) changed: Vec<Tick>,
 }
 
 impl Entity {
     pub fn new(id: u32) -> Self {
-        let mut components =
-            Vec::with_capacity(COMPONENT_IDS.get_or_init(build_component_ids).len());
-        components.resize_with(COMPONENT_IDS.get_or_init(build_component_ids).len(), || {
-            None
-        });
-        let mut result = Self { id, components };
+        let component_count = COMPONENT_IDS.get_or_init(build_component_ids).len();
+        let mut components = Vec::with_capacity(component_count);
+        components.resize_with(component_count, || None);
+        let mut result = Self {
+            id,
+            components,
+            changed: vec![0; component_count],
+        };
         result.add_component(Box::new(EntityId { id })).unwrap();
         result
     }
@@ -207,4 +211,20 @@ impl Entity {
         let id = get_component_id::<T>();
         self.components[id].is_some()
     }
+
+    pub fn get_component_by_id(&self, id: usize) -> Option<&dyn Component> {
+        self.components.get(id)?.as_deref()
+    }
+
+    /// Tick at which the component with `id` was last added, removed or mutably queried. This
+    /// is conservative: a mutable query marks every component it hands out, written or not.
+    pub fn changed_tick(&self, id: usize) -> Tick {
+        self.changed.get(id).copied().unwrap_or(0)
+    }
+
+    pub(crate) fn mark_changed(&mut self, id: usize, tick: Tick) {
+        if let Some(changed) = self.changed.get_mut(id) {
+            *changed = tick;
+        }
+    }
 }
@@ -1,4 +1,5 @@
 use std::ops::Deref;
+use std::time::{Duration, Instant};
 
 use crate::*;
 use rayon::prelude::*;
@@ -9,6 +10,34 @@ pub struct Scheduler {
     world: *mut World,
 
     systems: HashMap<SystemStage, Vec<Vec<*mut dyn System>>>,
+    /// How long each group of each stage took the last time the stage ran.
+    timings: HashMap<SystemStage, Vec<Duration>>,
+}
+
+/// What the scheduler made of the registered systems, and how long they took on their last run.
+#[derive(Clone, Debug, Default)]
+pub struct SchedulerStats {
+    pub stages: Vec<StageStats>,
+}
+
+#[derive(Clone, Debug)]
+pub struct StageStats {
+    pub stage: SystemStage,
+    /// Systems in a group run in parallel; groups run one after another.
+    pub groups: Vec<GroupStats>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GroupStats {
+    pub systems: Vec<&'static str>,
+    /// Zero if the stage hasn't run yet.
+    pub last_run: Duration,
+}
+
+impl StageStats {
+    pub fn last_run(&self) -> Duration {
+        self.groups.iter().map(|g| g.last_run).sum()
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -36,6 +65,7 @@ impl Scheduler {
         Self {
             world,
             systems: HashMap::new(),
+            timings: HashMap::new(),
         }
     }
 
@@ -45,11 +75,14 @@ impl Scheduler {
             let Some(systems) = (*scheduler).systems.get(&stage) else {
                 return;
             };
+            let mut timings = Vec::with_capacity(systems.len());
             for group in systems {
+                let started = Instant::now();
                 if group.len() == 1 {
                     // Run single systems on main thread because they might not be Send + Sync
                     let system = group[0];
                     system.as_mut().unwrap().run_unsafe(world);
+                    timings.push(started.elapsed());
                     continue;
                 }
 
@@ -66,10 +99,42 @@ impl Scheduler {
 
                     system.as_mut().unwrap().run_unsafe(world);
                 });
+                timings.push(started.elapsed());
             }
+            (*scheduler).timings.insert(stage, timings);
         }
     }
 
+    pub fn stats(&self) -> SchedulerStats {
+        let order = [
+            SystemStage::Init,
+            SystemStage::PreUpdate,
+            SystemStage::Update,
+            SystemStage::PostUpdate,
+            SystemStage::Render,
+            SystemStage::DeInit,
+        ];
+
+        let stages = order
+            .into_iter()
+            .filter_map(|stage| {
+                let groups = self.systems.get(&stage)?;
+                let timings = self.timings.get(&stage);
+                let groups = groups
+                    .iter()
+                    .enumerate()
+                    .map(|(i, group)| GroupStats {
+                        systems: group.iter().map(|&s| unsafe { (*s).name() }).collect(),
+                        last_run: timings.and_then(|t| t.get(i)).copied().unwrap_or_default(),
+                    })
+                    .collect();
+                Some(StageStats { stage, groups })
+            })
+            .collect();
+
+        SchedulerStats { stages }
+    }
+
     pub(crate) fn add_system(&mut self, system: *mut dyn System, stage: SystemStage) {
         let entry = self.systems.entry(stage).or_default();
         if unsafe { system.as_ref() }.unwrap().runs_alone() || entry.is_empty() {
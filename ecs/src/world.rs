@@ -106,12 +106,9 @@ impl Commands {
     pub fn despawn_entity(&mut self, id: EntityId) -> Option<()> {
         unsafe {
             let world = self.world.as_mut().unwrap();
-            if (id as usize) < world.entities.len() {
-                world.entities.remove(id as usize);
-                Some(())
-            } else {
-                None
-            }
+            let index = world.entity_index(id)?;
+            world.entities.remove(index);
+            Some(())
         }
     }
 
@@ -150,34 +147,88 @@ impl Commands {
     pub fn add_component<T: Component>(&mut self, entity_id: EntityId, component: T) -> Option<()> {
         unsafe {
             let world = self.world.as_mut().unwrap();
-            if let Some(entity) = world.entities.get_mut(entity_id as usize) {
-                entity.add_component(Box::new(component))
-            } else {
-                None
-            }
+            let tick = world.tick;
+            let entity = world.get_entity_mut(entity_id)?;
+            entity.add_component(Box::new(component))?;
+            entity.mark_changed(get_component_id::<T>(), tick);
+            Some(())
+        }
+    }
+
+    /// Inserts a type-erased component, replacing whatever was in its slot.
+    pub fn insert_component_dyn(
+        &mut self,
+        entity_id: EntityId,
+        component: Box<dyn Component>,
+    ) -> Option<()> {
+        unsafe {
+            let world = self.world.as_mut().unwrap();
+            let tick = world.tick;
+            let entity = world.get_entity_mut(entity_id)?;
+            let id = component.get_type_id();
+            entity.set_component(Some(component), id);
+            entity.mark_changed(id, tick);
+            Some(())
         }
     }
 
     pub fn remove_component<T: Component>(
         &mut self,
         entity_id: EntityId,
+    ) -> Option<Box<dyn Component>> {
+        self.remove_component_by_id(entity_id, get_component_id::<T>())
+    }
+
+    pub fn remove_component_by_id(
+        &mut self,
+        entity_id: EntityId,
+        component_id: usize,
     ) -> Option<Box<dyn Component>> {
         unsafe {
             let world = self.world.as_mut().unwrap();
-            if let Some(entity) = world.entities.get_mut(entity_id as usize) {
-                entity.remove_component::<T>()
-            } else {
-                None
+            let tick = world.tick;
+            let entity = world.get_entity_mut(entity_id)?;
+            let removed = entity.components.get_mut(component_id)?.take()?;
+            entity.mark_changed(component_id, tick);
+            Some(removed)
+        }
+    }
+
+    /// One entity's component, marked changed like a mutable query would, but without marking
+    /// every other entity's along with it.
+    pub fn get_component_mut<T: Component>(&mut self, entity_id: EntityId) -> Option<&'static mut T> {
+        unsafe {
+            let world = self.world.as_mut().unwrap();
+            let tick = world.tick;
+            let entity = world.get_entity_mut(entity_id)?;
+            if entity.has_component::<T>() {
+                entity.mark_changed(get_component_id::<T>(), tick);
             }
+            entity.get_component_mut::<T>()
         }
     }
 
+    pub fn get_entity(&self, entity_id: EntityId) -> Option<&'static Entity> {
+        unsafe { World::get_entity(self.world, entity_id) }
+    }
+
+    pub fn tick(&self) -> Tick {
+        unsafe { self.world.as_ref().unwrap().tick }
+    }
+
     pub fn run_system(&mut self, system: &mut dyn System) {
         unsafe {
             system.run_unsafe(self.world);
         }
     }
 
+    pub fn scheduler_stats(&self) -> SchedulerStats {
+        unsafe {
+            let world = self.world.as_ref().unwrap();
+            world.scheduler.as_ref().unwrap().stats()
+        }
+    }
+
     pub fn should_exit(&self) -> bool {
         unsafe {
             let world = self.world.as_ref().unwrap();
@@ -229,6 +280,27 @@ impl World {
         }
     }
 
+    fn entity_index(&self, id: EntityId) -> Option<usize> {
+        // ids are handed out in increasing order and removal keeps the order intact
+        self.entities.binary_search_by_key(&id, |e| e.id).ok()
+    }
+
+    fn get_entity_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        let index = self.entity_index(id)?;
+        self.entities.get_mut(index)
+    }
+
+    /// # Safety
+    ///
+    /// `world` must be non-null and valid
+    pub unsafe fn get_entity(world: *mut World, id: EntityId) -> Option<&'static Entity> {
+        unsafe {
+            let world = world.as_ref()?;
+            let index = world.entity_index(id)?;
+            world.entities.get(index)
+        }
+    }
+
     /// # Safety
     ///
     /// `world` must be non-null and valid
@@ -302,7 +374,14 @@ impl World {
 
         unsafe {
             let world = world.as_mut().unwrap();
+            let tick = world.tick;
             for entity in &mut world.entities {
+                if let Some(changed) = entity.changed.get_mut(id)
+                    && entity.components[id].is_some()
+                {
+                    *changed = tick;
+                }
+
                 if let Some(component) = entity
                     .components
                     .get_mut(id)
@@ -117,3 +117,65 @@ fn despawn_entity_removes_components() {
     assert_eq!(positions.len(), 1);
     assert_eq!(positions[0].0, e1);
 }
+
+#[test]
+fn components_stay_addressable_after_despawn() {
+    let mut app = App::new();
+    let e0 = app.spawn_entity();
+    let e1 = app.spawn_entity();
+    let e2 = app.spawn_entity();
+
+    assert!(app.despawn_entity(e0).is_some());
+    assert!(app.despawn_entity(e0).is_none());
+
+    app.add_component(e2, Position(2.0)).unwrap();
+    app.add_component(e1, Position(1.0)).unwrap();
+
+    let e2_entity = app.get_entity(e2).expect("entity missing");
+    assert_eq!(e2_entity.get_component::<Position>(), Some(&Position(2.0)));
+    assert!(app.get_entity(e0).is_none());
+}
+
+#[test]
+fn change_ticks_track_adds_and_mutable_queries() {
+    let mut app = App::new();
+    let entity = app.spawn_entity();
+    app.add_component(entity, Position(0.0)).unwrap();
+    app.add_component(entity, Velocity(1.0)).unwrap();
+
+    let position_id = get_component_id::<Position>();
+    let velocity_id = get_component_id::<Velocity>();
+    assert_eq!(app.get_entity(entity).unwrap().changed_tick(position_id), 0);
+
+    app.add_system(touch_components, SystemStage::Update);
+    app.run();
+    app.run();
+
+    let entity = app.get_entity(entity).unwrap();
+    assert_eq!(entity.changed_tick(position_id), 2);
+    assert_eq!(entity.changed_tick(velocity_id), 0);
+}
+
+#[test]
+fn single_component_borrows_and_removals_count_as_changes() {
+    let mut app = App::new();
+    let moved = app.spawn_entity();
+    let still = app.spawn_entity();
+    for entity in [moved, still] {
+        app.add_component(entity, Position(0.0)).unwrap();
+        app.add_component(entity, Velocity(1.0)).unwrap();
+    }
+    app.run();
+
+    app.get_component_mut::<Position>(moved).unwrap().0 = 3.0;
+    assert!(app.remove_component::<Velocity>(moved).is_some());
+    assert!(app.get_component_mut::<Velocity>(moved).is_none());
+
+    let position_id = get_component_id::<Position>();
+    let velocity_id = get_component_id::<Velocity>();
+    let entity = app.get_entity(moved).unwrap();
+    assert_eq!(entity.get_component::<Position>(), Some(&Position(3.0)));
+    assert_eq!((entity.changed_tick(position_id), entity.changed_tick(velocity_id)), (1, 1));
+    let entity = app.get_entity(still).unwrap();
+    assert_eq!((entity.changed_tick(position_id), entity.changed_tick(velocity_id)), (0, 0));
+}
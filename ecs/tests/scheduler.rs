@@ -51,3 +51,26 @@ fn scheduler_runs_systems_once_per_tick_in_stage_order() {
     assert_eq!(positions.len(), 1);
     assert_eq!(positions.pop().unwrap().1.0, 1);
 }
+
+#[test]
+fn scheduler_stats_list_groups_in_stage_order() {
+    let mut app = App::new();
+    app.insert_resource(Counter::default());
+    app.add_system(writer, SystemStage::Update);
+    app.add_system(reader, SystemStage::Update);
+    app.add_system(reader, SystemStage::PreUpdate);
+
+    let before = app.scheduler_stats();
+    let stages: Vec<SystemStage> = before.stages.iter().map(|s| s.stage).collect();
+    assert_eq!(stages, vec![SystemStage::PreUpdate, SystemStage::Update]);
+    assert!(before.stages.iter().all(|s| s.last_run().is_zero()));
+
+    // both systems write Counter, so they can't share a group
+    let update = &before.stages[1];
+    let systems: Vec<Vec<&str>> = update.groups.iter().map(|g| g.systems.clone()).collect();
+    assert_eq!(systems, vec![vec!["writer"], vec!["reader"]]);
+
+    app.run();
+    let after = app.scheduler_stats();
+    assert!(after.stages.iter().all(|s| !s.last_run().is_zero()));
+}
@@ -19,7 +19,7 @@ pub fn derive_net_send(input: TokenStream) -> TokenStream {
             }
 
             fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
-                Ok(bincode::serde::decode_from_slice::<#name, bincode::config::Configuration>(bytes, bincode::config::Configuration::default())?.0)
+                Ok(bincode::serde::decode_from_slice::<#name, DecodeConfig>(bytes, decode_config())?.0)
             }
         }
 
@@ -33,3 +33,57 @@ pub fn derive_net_send(input: TokenStream) -> TokenStream {
     }
     .into()
 }
+
+/// Opts a component into replication. Place it above the `#[derive]`, and derive `Serialize` and
+/// `Deserialize` alongside `Component`.
+#[proc_macro_attribute]
+pub fn replicate(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    let name = &input.ident;
+    let type_name = name.to_string();
+
+    quote! {
+        #input
+
+        submit! {
+            ReplicatedRegistration {
+                type_id: ConstTypeId::of::<#name>(),
+                name: #type_name,
+                component_id: get_component_id::<#name>,
+                encode: |component: &dyn Component| -> Vec<u8> {
+                    let component = component.as_any().downcast_ref::<#name>().expect("Replicated component type mismatch");
+                    bincode::serde::encode_to_vec::<&#name, bincode::config::Configuration>(component, bincode::config::Configuration::default()).unwrap()
+                },
+                decode: |bytes: &[u8]| -> anyhow::Result<Box<dyn Component>> {
+                    Ok(Box::new(bincode::serde::decode_from_slice::<#name, DecodeConfig>(bytes, decode_config())?.0))
+                },
+            }
+        }
+    }
+    .into()
+}
+
+/// Implements `NetRequest` for a type that also derives `NetSend`. The response type is named
+/// with `#[response(Type)]`.
+#[proc_macro_derive(NetRequest, attributes(response))]
+pub fn derive_net_request(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let Some(attr) = input.attrs.iter().find(|a| a.path().is_ident("response")) else {
+        return syn::Error::new_spanned(name, "NetRequest needs a #[response(Type)] attribute")
+            .to_compile_error()
+            .into();
+    };
+    let response: syn::Type = match attr.parse_args() {
+        Ok(response) => response,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    quote! {
+        impl NetRequest for #name {
+            type Response = #response;
+        }
+    }
+    .into()
+}
@@ -0,0 +1,217 @@
+use crate::*;
+
+use anyhow::{Result, anyhow};
+use ring::hmac;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Peers that haven't presented a token this long after connecting are turned away, and clients
+/// give up on servers that haven't answered theirs, unless the plugin sets its own with
+/// `with_auth_timeout`.
+pub const DEFAULT_AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a rejected peer has to read why before the server drops the connection itself.
+const REJECT_GRACE: Duration = Duration::from_millis(500);
+const KEY_LEN: usize = 32;
+
+/// Secret shared by whoever issues connect tokens and the servers that check them.
+#[derive(Clone)]
+pub struct TokenKey {
+    secret: Vec<u8>,
+    key: hmac::Key,
+}
+
+impl TokenKey {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            secret: secret.to_vec(),
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+        }
+    }
+
+    pub fn generate() -> Self {
+        Self::new(&rand::random::<[u8; KEY_LEN]>())
+    }
+
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let secret = from_hex(hex)?;
+        if secret.len() < KEY_LEN {
+            return Err(anyhow!("token keys need at least {} bytes", KEY_LEN));
+        }
+        Ok(Self::new(&secret))
+    }
+
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.secret)
+    }
+
+    /// Signs a token that lets `player_id` join until `valid_for` from now.
+    pub fn issue(&self, player_id: u64, valid_for: Duration) -> ConnectToken {
+        let expires = (SystemTime::now() + valid_for)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let signature = hmac::sign(&self.key, &ConnectToken::message(player_id, expires));
+
+        ConnectToken {
+            player_id,
+            expires,
+            signature: signature.as_ref().to_vec(),
+        }
+    }
+
+    /// Checks the signature and expiry, returning why the token isn't good.
+    pub fn verify(&self, token: &ConnectToken) -> Result<u64, String> {
+        let message = ConnectToken::message(token.player_id, token.expires);
+        if hmac::verify(&self.key, &message, &token.signature).is_err() {
+            return Err("bad token signature".to_string());
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        if token.expires <= now {
+            return Err("token expired".to_string());
+        }
+        Ok(token.player_id)
+    }
+}
+
+/// Proof that a player may join, presented right after connecting. Tokens can be reused until
+/// they expire, so keep their lifetime short.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ConnectToken {
+    pub player_id: u64,
+    /// Unix time in seconds.
+    pub expires: u64,
+    pub signature: Vec<u8>,
+}
+
+impl ConnectToken {
+    fn message(player_id: u64, expires: u64) -> [u8; 16] {
+        let mut message = [0; 16];
+        message[..8].copy_from_slice(&player_id.to_le_bytes());
+        message[8..].copy_from_slice(&expires.to_le_bytes());
+        message
+    }
+
+    /// A single hex string, for passing tokens around on command lines.
+    pub fn to_hex(&self) -> String {
+        let mut bytes = Self::message(self.player_id, self.expires).to_vec();
+        bytes.extend_from_slice(&self.signature);
+        to_hex(&bytes)
+    }
+
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let bytes = from_hex(hex)?;
+        if bytes.len() <= 16 {
+            return Err(anyhow!("connect token is too short"));
+        }
+
+        Ok(Self {
+            player_id: u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            expires: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+            signature: bytes[16..].to_vec(),
+        })
+    }
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(anyhow!("not valid hex"));
+    }
+    Ok((0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()?)
+}
+
+/// Sent by a client with a token as soon as it connects.
+#[derive(NetSend, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct AuthRequest(pub ConnectToken);
+
+#[derive(NetSend, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct AuthAccepted {
+    pub player_id: Option<u64>,
+}
+
+#[derive(NetSend, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct AuthRejected(pub String);
+
+/// The join handshake. A server with a key holds every new peer back until it presents a valid
+/// token; a client with a token holds the server back until it is accepted. Held peers don't
+/// show up in `peers()` or as `Connected` events, and anything they send is dropped.
+pub(crate) struct Auth {
+    pub(crate) is_server: bool,
+    pub(crate) key: Option<TokenKey>,
+    pub(crate) timeout: Duration,
+    pub(crate) token: Option<ConnectToken>,
+    /// Connected peers that haven't been accepted yet, with when they connected.
+    pub(crate) pending: BTreeMap<u32, Instant>,
+    pub(crate) players: BTreeMap<u32, u64>,
+    /// Rejected peers and when to drop them.
+    pub(crate) closing: Vec<(u32, Instant)>,
+}
+
+impl Default for Auth {
+    fn default() -> Self {
+        Self {
+            is_server: false,
+            key: None,
+            timeout: DEFAULT_AUTH_TIMEOUT,
+            token: None,
+            pending: BTreeMap::new(),
+            players: BTreeMap::new(),
+            closing: Vec::new(),
+        }
+    }
+}
+
+impl Auth {
+    pub(crate) fn holds_new_peers(&self) -> bool {
+        self.key.is_some() || self.token.is_some()
+    }
+
+    pub(crate) fn is_handshake(type_id: usize) -> bool {
+        type_id == get_net_id::<AuthRequest>()
+            || type_id == get_net_id::<AuthAccepted>()
+            || type_id == get_net_id::<AuthRejected>()
+    }
+
+    /// Whether `peer` is a server we presented our token to and haven't heard back from. Only
+    /// then do `AuthAccepted` and `AuthRejected` count; a side with a key only takes tokens.
+    pub(crate) fn awaits_verdict_from(&self, peer: u32) -> bool {
+        !self.is_server && self.key.is_none() && self.token.is_some() && self.pending.contains_key(&peer)
+    }
+
+    pub(crate) fn reject(&mut self, peer: u32) {
+        self.pending.remove(&peer);
+        self.closing.push((peer, Instant::now() + REJECT_GRACE));
+    }
+
+    /// Forgets a peer that disconnected, returning whether it was held back or turned away, and
+    /// so never reported as connected.
+    pub(crate) fn forget(&mut self, peer: u32) -> bool {
+        self.players.remove(&peer);
+        let rejected = self.closing.iter().any(|&(p, _)| p == peer);
+        self.closing.retain(|&(p, _)| p != peer);
+        self.pending.remove(&peer).is_some() || rejected
+    }
+
+    /// Peers that took too long to present a token, or servers that took too long to answer ours.
+    pub(crate) fn timed_out(&self) -> Vec<u32> {
+        self.pending
+            .iter()
+            .filter(|(_, connected)| connected.elapsed() >= self.timeout)
+            .map(|(&peer, _)| peer)
+            .collect()
+    }
+
+    pub(crate) fn due_for_closing(&mut self) -> Vec<u32> {
+        let now = Instant::now();
+        let (due, waiting) = self.closing.drain(..).partition(|(_, at)| *at <= now);
+        self.closing = waiting;
+        due.into_iter().map(|(peer, _)| peer).collect()
+    }
+}
@@ -0,0 +1,243 @@
+use crate::*;
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Packets are kept under this so an unreliable one fits a single QUIC datagram.
+pub const MAX_PACKET_SIZE: usize = 1100;
+/// Frames longer than this are split into [`Fragment`]s.
+pub const FRAGMENT_SIZE: usize = 1000;
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+/// Frames waiting for the end of the tick. Past this, `send` reports `BufferFull`.
+const MAX_PENDING_BYTES: usize = 16 * 1024 * 1024;
+/// Partially received messages are dropped if they don't complete in time.
+const FRAGMENT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Messages one peer may have half received at once. Starting another drops its oldest.
+const MAX_PARTIAL_MESSAGES: usize = 8;
+/// Bytes one peer's half received messages may claim, in multiples of the message size limit.
+const MAX_PARTIAL_MESSAGE_SIZES: usize = 2;
+
+/// One piece of a frame that was too long to travel whole.
+#[derive(NetSend, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Fragment {
+    pub message: u32,
+    pub index: u32,
+    pub count: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// A packet is a run of frames, each prefixed with its length as a varint.
+pub fn encode_packet<'a>(frames: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut packet = Vec::new();
+    for frame in frames {
+        write_varint(&mut packet, frame.len());
+        packet.extend_from_slice(frame);
+    }
+    packet
+}
+
+/// Splits a packet back into frames. Never panics, whatever the input.
+pub fn decode_packet(packet: &[u8]) -> Result<Vec<&[u8]>, NetError> {
+    let mut frames = Vec::new();
+    let mut cursor = packet;
+    while !cursor.is_empty() {
+        let len = read_varint(&mut cursor).ok_or(NetError::Truncated { len: cursor.len() })?;
+        if len > cursor.len() {
+            return Err(NetError::Truncated { len: cursor.len() });
+        }
+        let (frame, rest) = cursor.split_at(len);
+        frames.push(frame);
+        cursor = rest;
+    }
+    Ok(frames)
+}
+
+/// Frames queued by `send` during a tick, grouped by where they're going.
+#[derive(Default)]
+pub(crate) struct Outgoing {
+    batches: Vec<(Reliability, Target, Vec<Vec<u8>>)>,
+    pending: usize,
+    next_message: u32,
+}
+
+impl Outgoing {
+    pub(crate) fn push(&mut self, reliability: Reliability, target: Target, frame: Vec<u8>) -> Result<(), NetError> {
+        if self.pending + frame.len() > MAX_PENDING_BYTES {
+            return Err(NetError::BufferFull);
+        }
+        self.pending += frame.len();
+
+        match self
+            .batches
+            .iter_mut()
+            .find(|(r, t, _)| *r == reliability && *t == target)
+        {
+            Some((_, _, frames)) => frames.push(frame),
+            None => self.batches.push((reliability, target, vec![frame])),
+        }
+        Ok(())
+    }
+
+    /// Packs everything queued this tick into as few packets as possible per destination.
+    pub(crate) fn take_packets(&mut self) -> Vec<(Reliability, Target, Vec<u8>)> {
+        let mut packets = Vec::new();
+        for (reliability, target, frames) in std::mem::take(&mut self.batches) {
+            let mut packet = Vec::new();
+
+            for frame in frames {
+                for piece in self.fragment(frame) {
+                    if !packet.is_empty() && packet.len() + piece.len() + 4 > MAX_PACKET_SIZE {
+                        packets.push((reliability, target, std::mem::take(&mut packet)));
+                    }
+                    write_varint(&mut packet, piece.len());
+                    packet.extend_from_slice(&piece);
+                }
+            }
+
+            if !packet.is_empty() {
+                packets.push((reliability, target, packet));
+            }
+        }
+        self.pending = 0;
+        packets
+    }
+
+    fn fragment(&mut self, frame: Vec<u8>) -> Vec<Vec<u8>> {
+        if frame.len() <= FRAGMENT_SIZE {
+            return vec![frame];
+        }
+
+        let message = self.next_message;
+        self.next_message = self.next_message.wrapping_add(1);
+        let count = frame.len().div_ceil(FRAGMENT_SIZE) as u32;
+        frame
+            .chunks(FRAGMENT_SIZE)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let fragment = Fragment {
+                    message,
+                    index: index as u32,
+                    count,
+                    bytes: chunk.to_vec(),
+                };
+                encode_frame(fragment.get_type_id(), &fragment.get_bytes())
+            })
+            .collect()
+    }
+}
+
+struct Partial {
+    parts: Vec<Option<Vec<u8>>>,
+    received: usize,
+    started: Instant,
+}
+
+impl Partial {
+    /// Bytes the message will take once complete, going by its fragment count.
+    fn claimed(&self) -> usize {
+        self.parts.len() * FRAGMENT_SIZE
+    }
+}
+
+/// Collects fragments until a whole frame can be handed back.
+#[derive(Default)]
+pub(crate) struct Reassembler {
+    partial: HashMap<(Target, u32), Partial>,
+}
+
+impl Reassembler {
+    /// Returns the original frame once its last fragment arrives.
+    pub(crate) fn insert(&mut self, from: Target, fragment: Fragment, max_message_size: usize) -> Result<Option<Vec<u8>>, NetError> {
+        let count = fragment.count as usize;
+        let claimed = count.saturating_mul(FRAGMENT_SIZE);
+        // the shortest message split into `count` fragments fills all but the last one
+        if count == 0 || claimed - FRAGMENT_SIZE >= max_message_size {
+            return Err(NetError::TooLarge {
+                len: claimed,
+                max: max_message_size,
+            });
+        }
+        if fragment.index as usize >= count || fragment.bytes.len() > FRAGMENT_SIZE {
+            return Err(NetError::Decode {
+                type_id: get_net_id::<Fragment>(),
+                reason: format!("fragment {} of {} is malformed", fragment.index, count),
+            });
+        }
+
+        let key = (from, fragment.message);
+        if !self.partial.contains_key(&key) {
+            self.make_room(from, claimed, max_message_size);
+        }
+        let partial = self.partial.entry(key).or_insert_with(|| Partial {
+            parts: vec![None; count],
+            received: 0,
+            started: Instant::now(),
+        });
+        if partial.parts.len() != count {
+            self.partial.remove(&key);
+            return Err(NetError::Decode {
+                type_id: get_net_id::<Fragment>(),
+                reason: "fragment count changed mid-message".to_string(),
+            });
+        }
+
+        let slot = &mut partial.parts[fragment.index as usize];
+        if slot.is_none() {
+            *slot = Some(fragment.bytes);
+            partial.received += 1;
+        }
+        if partial.received < count {
+            return Ok(None);
+        }
+
+        let partial = self.partial.remove(&key).unwrap();
+        let frame: Vec<u8> = partial.parts.into_iter().flatten().flatten().collect();
+        if frame.len() > max_message_size {
+            return Err(NetError::TooLarge {
+                len: frame.len(),
+                max: max_message_size,
+            });
+        }
+        Ok(Some(frame))
+    }
+
+    /// Drops a peer's oldest half received messages until one claiming `claimed` bytes fits
+    /// within its limits.
+    fn make_room(&mut self, from: Target, claimed: usize, max_message_size: usize) {
+        let max_bytes = max_message_size.saturating_mul(MAX_PARTIAL_MESSAGE_SIZES);
+        loop {
+            let mut count = 0;
+            let mut bytes = claimed;
+            let mut oldest: Option<(u32, Instant)> = None;
+            for ((peer, message), partial) in &self.partial {
+                if *peer != from {
+                    continue;
+                }
+                count += 1;
+                bytes += partial.claimed();
+                if oldest.is_none_or(|(_, started)| partial.started < started) {
+                    oldest = Some((*message, partial.started));
+                }
+            }
+
+            let Some((message, _)) = oldest else {
+                return;
+            };
+            if count < MAX_PARTIAL_MESSAGES && bytes <= max_bytes {
+                return;
+            }
+            println!("Dropping half received message {} from {:?} to make room", message, from);
+            self.partial.remove(&(from, message));
+        }
+    }
+
+    pub(crate) fn expire(&mut self) {
+        self.partial
+            .retain(|_, partial| partial.started.elapsed() < FRAGMENT_TIMEOUT);
+    }
+
+    /// Forgets everything a peer was in the middle of sending.
+    pub(crate) fn forget(&mut self, peer: Target) {
+        self.partial.retain(|(from, _), _| *from != peer);
+    }
+}
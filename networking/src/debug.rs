@@ -0,0 +1,226 @@
+use crate::*;
+
+use anyhow::{Context, Result, anyhow};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+/// One message as it crossed the wire. Outgoing messages are recorded before batching and
+/// fragmentation, incoming ones after reassembly.
+///
+/// Captures are text, one record per line:
+/// `<micros> <in|out> <peer> <reliable|unreliable> <type name> <decoded size> <frame hex>`,
+/// where the peer is an id, `all`, `group:<id>` or `except:<id>`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaptureRecord {
+    /// Since the capture started.
+    pub time: Duration,
+    pub direction: Direction,
+    pub peer: Target,
+    pub reliability: Reliability,
+    pub type_name: String,
+    pub size: usize,
+    pub frame: Vec<u8>,
+}
+
+impl CaptureRecord {
+    fn new(time: Duration, direction: Direction, peer: Target, reliability: Reliability, frame: &[u8]) -> Self {
+        let type_name = frame::split_frame(frame)
+            .ok()
+            .and_then(|(header, _)| get_net_name(header.type_id))
+            .unwrap_or("?");
+
+        Self {
+            time,
+            direction,
+            peer,
+            reliability,
+            type_name: type_name.to_string(),
+            size: frame::message_size(frame).unwrap_or(frame.len()),
+            frame: frame.to_vec(),
+        }
+    }
+
+    pub fn to_line(&self) -> String {
+        let direction = match self.direction {
+            Direction::In => "in",
+            Direction::Out => "out",
+        };
+        let peer = match self.peer {
+            Target::All => "all".to_string(),
+            Target::Single(peer) => peer.to_string(),
+            Target::This => "this".to_string(),
+            Target::Group(group) => format!("group:{}", group.0),
+            Target::AllExcept(peer) => format!("except:{}", peer),
+        };
+        let reliability = match self.reliability {
+            Reliability::Reliable => "reliable",
+            Reliability::Unreliable => "unreliable",
+        };
+        let hex = auth::to_hex(&self.frame);
+
+        format!(
+            "{} {} {} {} {} {} {}",
+            self.time.as_micros(),
+            direction,
+            peer,
+            reliability,
+            self.type_name,
+            self.size,
+            hex
+        )
+    }
+
+    pub fn parse(line: &str) -> Result<Self> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [time, direction, peer, reliability, type_name, size, hex] = fields[..] else {
+            return Err(anyhow!("expected 7 fields, found {}", fields.len()));
+        };
+
+        let direction = match direction {
+            "in" => Direction::In,
+            "out" => Direction::Out,
+            other => return Err(anyhow!("unknown direction {:?}", other)),
+        };
+        let peer = match peer.split_once(':') {
+            _ if peer == "all" => Target::All,
+            _ if peer == "this" => Target::This,
+            Some(("group", id)) => Target::Group(GroupId(id.parse()?)),
+            Some(("except", id)) => Target::AllExcept(id.parse()?),
+            _ => Target::Single(peer.parse().with_context(|| format!("bad peer {:?}", peer))?),
+        };
+        let reliability = match reliability {
+            "reliable" => Reliability::Reliable,
+            "unreliable" => Reliability::Unreliable,
+            other => return Err(anyhow!("unknown reliability {:?}", other)),
+        };
+        let frame = auth::from_hex(hex).context("frame is not valid hex")?;
+
+        Ok(Self {
+            time: Duration::from_micros(time.parse()?),
+            direction,
+            peer,
+            reliability,
+            type_name: type_name.to_string(),
+            size: size.parse()?,
+            frame,
+        })
+    }
+}
+
+/// Reads a capture written with `NetworkingPlugin::with_capture` or `Networking::start_capture`.
+pub fn read_capture(path: impl AsRef<Path>) -> Result<Vec<CaptureRecord>> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("failed to open capture {}", path.display()))?;
+
+    let mut records = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = CaptureRecord::parse(&line)
+            .with_context(|| format!("{}:{}: bad capture record", path.display(), number + 1))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+pub(crate) struct Capture {
+    started: Instant,
+    out: BufWriter<File>,
+}
+
+impl Capture {
+    pub(crate) fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            started: Instant::now(),
+            out: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    pub(crate) fn record(&mut self, direction: Direction, peer: Target, reliability: Reliability, frame: &[u8]) {
+        let record = CaptureRecord::new(self.started.elapsed(), direction, peer, reliability, frame);
+        if let Err(e) = writeln!(self.out, "{}", record.to_line()) {
+            println!("Failed to write network capture: {}", e);
+        }
+    }
+
+    pub(crate) fn flush(&mut self) {
+        if let Err(e) = self.out.flush() {
+            println!("Failed to flush network capture: {}", e);
+        }
+    }
+}
+
+/// Simulated bad network conditions applied to outgoing packets, for testing over a real
+/// transport on one machine. Loss and duplication only hit unreliable packets, and reliable
+/// packets stay in order.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FaultInjection {
+    pub latency: Duration,
+    /// Extra delay picked uniformly between zero and this.
+    pub jitter: Duration,
+    pub loss: f32,
+    pub duplication: f32,
+}
+
+type Packet = (Reliability, Target, Vec<u8>);
+
+pub(crate) struct Faults {
+    conditions: FaultInjection,
+    rng: StdRng,
+    delayed: Vec<(Instant, Packet)>,
+    last_reliable: Option<Instant>,
+}
+
+impl Faults {
+    pub(crate) fn new(conditions: FaultInjection) -> Self {
+        Self {
+            conditions,
+            rng: StdRng::from_os_rng(),
+            delayed: Vec::new(),
+            last_reliable: None,
+        }
+    }
+
+    pub(crate) fn push(&mut self, packet: Packet) {
+        let conditions = self.conditions;
+        let copies = match packet.0 {
+            Reliability::Reliable => 1,
+            Reliability::Unreliable if self.rng.random_range(0.0..1.0) < conditions.loss => 0,
+            Reliability::Unreliable if self.rng.random_range(0.0..1.0) < conditions.duplication => 2,
+            Reliability::Unreliable => 1,
+        };
+
+        for _ in 0..copies {
+            let mut at = Instant::now() + conditions.latency;
+            if !conditions.jitter.is_zero() {
+                at += conditions.jitter.mul_f32(self.rng.random_range(0.0..=1.0));
+            }
+            if packet.0 == Reliability::Reliable {
+                at = self.last_reliable.map_or(at, |last| at.max(last));
+                self.last_reliable = Some(at);
+            }
+            self.delayed.push((at, packet.clone()));
+        }
+    }
+
+    /// Packets whose delay has passed, oldest first.
+    pub(crate) fn due(&mut self) -> Vec<Packet> {
+        let now = Instant::now();
+        let (mut due, delayed): (Vec<_>, Vec<_>) = self.delayed.drain(..).partition(|(at, _)| *at <= now);
+        self.delayed = delayed;
+
+        due.sort_by_key(|(at, _)| *at);
+        due.into_iter().map(|(_, packet)| packet).collect()
+    }
+}
@@ -0,0 +1,85 @@
+use crate::*;
+
+/// Encodes `new` relative to `base` as the XOR of the two, stored as alternating runs of
+/// unchanged (zero) bytes and literal bytes. Fields that didn't change cost next to nothing.
+pub fn delta_encode(base: &[u8], new: &[u8]) -> Vec<u8> {
+    let xor: Vec<u8> = new
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| byte ^ base.get(i).copied().unwrap_or(0))
+        .collect();
+
+    let mut out = Vec::new();
+    write_varint(&mut out, new.len());
+
+    let mut i = 0;
+    while i < xor.len() {
+        let zeros = xor[i..].iter().take_while(|&&b| b == 0).count();
+        i += zeros;
+        let literals = xor[i..].iter().take_while(|&&b| b != 0).count();
+
+        write_varint(&mut out, zeros);
+        write_varint(&mut out, literals);
+        out.extend_from_slice(&xor[i..i + literals]);
+        i += literals;
+    }
+
+    out
+}
+
+/// Reverses [`delta_encode`]. Never panics, whatever the input.
+pub fn delta_decode(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, NetError> {
+    let corrupt = |reason: &str| NetError::Decode {
+        type_id: get_net_id::<ReplicationUpdate>(),
+        reason: format!("bad component delta: {}", reason),
+    };
+
+    let mut cursor = delta;
+    let len = read_varint(&mut cursor).ok_or_else(|| corrupt("missing length"))?;
+    if len > MAX_DECODE_BYTES {
+        return Err(corrupt("length over limit"));
+    }
+
+    let mut xor = Vec::new();
+    while !cursor.is_empty() {
+        let zeros = read_varint(&mut cursor).ok_or_else(|| corrupt("truncated run"))?;
+        let literals = read_varint(&mut cursor).ok_or_else(|| corrupt("truncated run"))?;
+        if zeros > len - xor.len() || literals > len - xor.len() - zeros || literals > cursor.len() {
+            return Err(corrupt("run out of bounds"));
+        }
+
+        xor.resize(xor.len() + zeros, 0);
+        xor.extend_from_slice(&cursor[..literals]);
+        cursor = &cursor[literals..];
+    }
+    if xor.len() != len {
+        return Err(corrupt("runs don't cover the length"));
+    }
+
+    Ok(xor
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| byte ^ base.get(i).copied().unwrap_or(0))
+        .collect())
+}
+
+pub(crate) fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+pub(crate) fn read_varint(cursor: &mut &[u8]) -> Option<usize> {
+    let mut value = 0usize;
+    for shift in (0..usize::BITS).step_by(7) {
+        let (&byte, rest) = cursor.split_first()?;
+        *cursor = rest;
+        value |= ((byte & 0x7f) as usize).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
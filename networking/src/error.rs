@@ -0,0 +1,43 @@
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetError {
+    /// The request channel and the local backlog are both full, so the message was dropped.
+    BufferFull,
+    /// The networking task has shut down and can no longer accept requests.
+    Disconnected,
+    /// A frame was too short to contain a header.
+    Truncated { len: usize },
+    /// A frame referenced a type id that isn't registered on this side.
+    UnknownType { type_id: usize },
+    /// The payload didn't deserialize into the registered type.
+    Decode { type_id: usize, reason: String },
+    /// A message is larger than the configured maximum message size.
+    TooLarge { len: usize, max: usize },
+    /// No response arrived before the request's deadline.
+    TimedOut,
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetError::BufferFull => write!(f, "networking request buffer full"),
+            NetError::Disconnected => write!(f, "networking task disconnected"),
+            NetError::Truncated { len } => {
+                write!(f, "frame of {} bytes is shorter than its header", len)
+            }
+            NetError::UnknownType { type_id } => {
+                write!(f, "frame has unregistered type id {}", type_id)
+            }
+            NetError::Decode { type_id, reason } => {
+                write!(f, "failed to decode type id {}: {}", type_id, reason)
+            }
+            NetError::TooLarge { len, max } => {
+                write!(f, "message of {} bytes exceeds the {} byte limit", len, max)
+            }
+            NetError::TimedOut => write!(f, "request timed out waiting for a response"),
+        }
+    }
+}
+
+impl std::error::Error for NetError {}
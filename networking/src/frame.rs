@@ -0,0 +1,183 @@
+use crate::*;
+
+use bincode::config::{Configuration, LittleEndian, Limit, Varint};
+use std::borrow::Cow;
+
+/// Every frame starts with a little-endian `u32` holding the net id of the payload type, with
+/// the top bit flagging an LZ4-compressed payload. Requests and responses set one of the next two
+/// bits and follow the header with a little-endian `u32` request id.
+pub const HEADER_LEN: usize = 4;
+
+pub const COMPRESSED_FLAG: u32 = 1 << 31;
+pub const REQUEST_FLAG: u32 = 1 << 30;
+pub const RESPONSE_FLAG: u32 = 1 << 29;
+const TYPE_MASK: u32 = RESPONSE_FLAG - 1;
+
+/// Payloads smaller than this aren't worth compressing.
+pub const COMPRESS_THRESHOLD: usize = 256;
+
+/// Upper bound on what a single payload may claim while decoding, so a forged length prefix
+/// can't make bincode allocate arbitrary amounts of memory.
+pub const MAX_DECODE_BYTES: usize = 16 * 1024 * 1024;
+
+pub type DecodeConfig = Configuration<LittleEndian, Varint, Limit<MAX_DECODE_BYTES>>;
+
+/// Same wire format as `bincode::config::standard()`, with [`MAX_DECODE_BYTES`] enforced.
+pub fn decode_config() -> DecodeConfig {
+    bincode::config::standard().with_limit::<MAX_DECODE_BYTES>()
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    /// LZ4 for payloads of at least [`COMPRESS_THRESHOLD`] bytes, when it actually helps.
+    #[default]
+    Lz4,
+}
+
+/// Ties a frame to a request made with [`Networking::request`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Correlation {
+    #[default]
+    None,
+    Request(u32),
+    Response(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub type_id: usize,
+    pub compressed: bool,
+    pub correlation: Correlation,
+}
+
+pub fn encode_frame(type_id: usize, payload: &[u8]) -> Vec<u8> {
+    encode_frame_with(type_id, payload, Compression::None)
+}
+
+pub fn encode_frame_with(type_id: usize, payload: &[u8], compression: Compression) -> Vec<u8> {
+    encode_correlated_frame(type_id, payload, compression, Correlation::None)
+}
+
+pub fn encode_correlated_frame(
+    type_id: usize,
+    payload: &[u8],
+    compression: Compression,
+    correlation: Correlation,
+) -> Vec<u8> {
+    let mut header = type_id as u32;
+    let request_id = match correlation {
+        Correlation::None => None,
+        Correlation::Request(id) => {
+            header |= REQUEST_FLAG;
+            Some(id)
+        }
+        Correlation::Response(id) => {
+            header |= RESPONSE_FLAG;
+            Some(id)
+        }
+    };
+    let mut body = Cow::Borrowed(payload);
+
+    if compression == Compression::Lz4 && payload.len() >= COMPRESS_THRESHOLD {
+        let compressed = lz4_flex::block::compress_prepend_size(payload);
+        if compressed.len() < payload.len() {
+            header |= COMPRESSED_FLAG;
+            body = Cow::Owned(compressed);
+        }
+    }
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + 4 + body.len());
+    bytes.extend_from_slice(&header.to_le_bytes());
+    if let Some(id) = request_id {
+        bytes.extend_from_slice(&id.to_le_bytes());
+    }
+    bytes.extend_from_slice(&body);
+    bytes
+}
+
+/// Splits a frame into its header and payload, rejecting anything that doesn't name a
+/// registered type. Never panics, whatever the input.
+pub fn split_frame(frame: &[u8]) -> Result<(FrameHeader, &[u8]), NetError> {
+    if frame.len() < HEADER_LEN {
+        return Err(NetError::Truncated { len: frame.len() });
+    }
+
+    let word = u32::from_le_bytes([frame[0], frame[1], frame[2], frame[3]]);
+    let type_id = (word & TYPE_MASK) as usize;
+    if type_id >= registry::FROM_BYTES.len() {
+        return Err(NetError::UnknownType { type_id });
+    }
+
+    let mut payload = &frame[HEADER_LEN..];
+    let mut request_id = || match payload.split_first_chunk::<4>() {
+        Some((id, rest)) => {
+            payload = rest;
+            Ok(u32::from_le_bytes(*id))
+        }
+        None => Err(NetError::Truncated { len: frame.len() }),
+    };
+    let correlation = match (word & REQUEST_FLAG != 0, word & RESPONSE_FLAG != 0) {
+        (false, false) => Correlation::None,
+        (true, false) => Correlation::Request(request_id()?),
+        (false, true) => Correlation::Response(request_id()?),
+        (true, true) => {
+            return Err(NetError::Decode {
+                type_id,
+                reason: "frame is flagged as both a request and a response".to_string(),
+            });
+        }
+    };
+
+    let header = FrameHeader {
+        type_id,
+        compressed: word & COMPRESSED_FLAG != 0,
+        correlation,
+    };
+    Ok((header, payload))
+}
+
+/// Size of the frame once decompressed, as claimed by its header, without decompressing it.
+pub fn message_size(frame: &[u8]) -> Result<usize, NetError> {
+    let (header, payload) = split_frame(frame)?;
+    if !header.compressed {
+        return Ok(frame.len());
+    }
+
+    match payload.first_chunk::<4>() {
+        Some(size) => Ok(frame.len() - payload.len() + u32::from_le_bytes(*size) as usize),
+        None => Err(NetError::Decode {
+            type_id: header.type_id,
+            reason: "compressed payload is missing its size".to_string(),
+        }),
+    }
+}
+
+pub fn decode_frame(frame: &[u8]) -> Result<(usize, Box<dyn Any>), NetError> {
+    let (header, payload) = split_frame(frame)?;
+    let type_id = header.type_id;
+    let decode_error = |reason: String| NetError::Decode { type_id, reason };
+
+    let payload = if header.compressed {
+        Cow::Owned(decompress(payload).map_err(decode_error)?)
+    } else {
+        Cow::Borrowed(payload)
+    };
+
+    let obj = registry::FROM_BYTES[type_id](&payload).map_err(|e| decode_error(e.to_string()))?;
+    Ok((type_id, obj))
+}
+
+/// Checks the claimed size before allocating, for the same reason as [`MAX_DECODE_BYTES`].
+fn decompress(payload: &[u8]) -> Result<Vec<u8>, String> {
+    let Some((size, compressed)) = payload.split_first_chunk::<4>() else {
+        return Err("compressed payload is missing its size".to_string());
+    };
+
+    let size = u32::from_le_bytes(*size) as usize;
+    if size > MAX_DECODE_BYTES {
+        return Err(format!("compressed payload claims {} bytes", size));
+    }
+
+    lz4_flex::block::decompress(compressed, size).map_err(|e| e.to_string())
+}
@@ -0,0 +1,43 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A server-side room of peers, addressed with `Target::Group`.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct GroupId(pub u32);
+
+#[derive(Default)]
+pub(crate) struct Groups {
+    members: BTreeMap<GroupId, BTreeSet<u32>>,
+}
+
+impl Groups {
+    pub(crate) fn join(&mut self, peer: u32, group: GroupId) {
+        self.members.entry(group).or_default().insert(peer);
+    }
+
+    pub(crate) fn leave(&mut self, peer: u32, group: GroupId) {
+        if let Some(members) = self.members.get_mut(&group) {
+            members.remove(&peer);
+            if members.is_empty() {
+                self.members.remove(&group);
+            }
+        }
+    }
+
+    pub(crate) fn leave_all(&mut self, peer: u32) {
+        self.members.retain(|_, members| {
+            members.remove(&peer);
+            !members.is_empty()
+        });
+    }
+
+    pub(crate) fn members(&self, group: GroupId) -> impl Iterator<Item = u32> + '_ {
+        self.members.get(&group).into_iter().flatten().copied()
+    }
+
+    pub(crate) fn groups_of(&self, peer: u32) -> impl Iterator<Item = GroupId> + '_ {
+        self.members
+            .iter()
+            .filter(move |(_, members)| members.contains(&peer))
+            .map(|(&group, _)| group)
+    }
+}
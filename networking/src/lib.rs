@@ -1,18 +1,55 @@
 use ecs::*;
 
+mod auth;
+mod batch;
+mod debug;
+mod delta;
+mod error;
+mod frame;
+mod group;
+mod lobby;
 mod registry;
+mod replication;
+mod request;
+mod stats;
+mod transport;
 
+pub use auth::*;
+pub use batch::*;
+pub use debug::*;
+pub use delta::*;
+pub use error::*;
+pub use frame::*;
+pub use group::*;
+pub use lobby::*;
 pub use registry::*;
+pub use replication::*;
+pub use request::*;
+pub use stats::*;
+pub use transport::*;
 
 use anyhow::Result;
 use std::any::Any;
-use std::collections::VecDeque;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
 
 pub use bincode;
 pub use net_derive::*;
 pub use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tokio::runtime::{Handle, Runtime};
 use tokio::sync::mpsc::*;
+use tokio::sync::mpsc::error::TrySendError;
+
+/// Requests that couldn't fit in the channel are held here until the networking task catches up.
+const MAX_REQUEST_BACKLOG: usize = 1024;
+/// Milliseconds `Networking::shutdown` waits for the networking task to take its last requests.
+const SHUTDOWN_ATTEMPTS: usize = 100;
+/// Worker threads of the runtime the plugin starts when it isn't built inside one.
+const NETWORKING_THREADS: usize = 2;
 
 pub trait NetSend: Any + Sized + DeserializeOwned {
     fn get_type_id(&self) -> usize;
@@ -20,46 +57,247 @@ pub trait NetSend: Any + Sized + DeserializeOwned {
     fn from_bytes(bytes: &[u8]) -> Result<Self>;
 }
 
+/// Starts the networking task, given the largest frame the transport should accept.
+type Launcher = Box<dyn FnOnce(Sender<NetworkingEvent>, Receiver<NetworkingRequest>, usize) + Send>;
+
 pub struct NetworkingPlugin {
     is_server: bool,
+    compression: Compression,
+    max_message_size: usize,
+    capture: Option<PathBuf>,
+    faults: Option<FaultInjection>,
+    token_key: Option<TokenKey>,
+    auth_timeout: Duration,
+    token: Option<ConnectToken>,
+    runtime: Option<Handle>,
+    launcher: Mutex<Option<Launcher>>,
 }
 
 impl NetworkingPlugin {
+    /// Connects over QUIC to a server on this machine's default port.
     pub fn client() -> Self {
-        Self { is_server: false }
+        Self::client_to(SocketAddr::from((Ipv4Addr::LOCALHOST, DEFAULT_PORT)))
     }
 
+    /// Listens for QUIC connections on the default port.
     pub fn server() -> Self {
-        Self { is_server: true }
+        Self::server_at(SocketAddr::from((Ipv4Addr::UNSPECIFIED, DEFAULT_PORT)))
     }
-}
 
-impl Plugin for NetworkingPlugin {
-    fn build(&self, app: &mut App) {
+    pub fn client_to(addr: SocketAddr) -> Self {
+        Self::client_with_trust(addr, ServerTrust::AnyCertificate)
+    }
+
+    /// Connects over QUIC, only to a server whose certificate `trust` accepts.
+    pub fn client_with_trust(addr: SocketAddr, trust: ServerTrust) -> Self {
+        Self::with_launcher(false, Box::new(move |tx_event, rx_request, max_frame_size| {
+            match QuicTransport::client_with_trust(addr, trust) {
+                Ok(transport) => {
+                    let transport = transport.with_max_frame_size(max_frame_size);
+                    tokio::spawn(handle_networking(transport, tx_event, rx_request));
+                }
+                Err(e) => println!("Failed to start QUIC client for {}: {}", addr, e),
+            }
+        }))
+    }
+
+    pub fn server_at(addr: SocketAddr) -> Self {
+        Self::with_launcher(true, Box::new(move |tx_event, rx_request, max_frame_size| {
+            match QuicTransport::server(addr) {
+                Ok(transport) => {
+                    let transport = transport.with_max_frame_size(max_frame_size);
+                    tokio::spawn(handle_networking(transport, tx_event, rx_request));
+                }
+                Err(e) => println!("Failed to start QUIC server on {}: {}", addr, e),
+            }
+        }))
+    }
+
+    /// Listens for QUIC connections, presenting `identity` so clients can pin its fingerprint.
+    pub fn server_with_identity(addr: SocketAddr, identity: ServerIdentity) -> Self {
+        Self::with_launcher(true, Box::new(move |tx_event, rx_request, max_frame_size| {
+            match QuicTransport::server_with_identity(addr, identity) {
+                Ok(transport) => {
+                    let transport = transport.with_max_frame_size(max_frame_size);
+                    tokio::spawn(handle_networking(transport, tx_event, rx_request));
+                }
+                Err(e) => println!("Failed to start QUIC server on {}: {}", addr, e),
+            }
+        }))
+    }
+
+    /// Replaces the default QUIC transport, e.g. with a [`LoopbackTransport`] in tests.
+    pub fn with_transport<T: Transport>(self, transport: T) -> Self {
+        *self.launcher.lock().unwrap() = Some(Box::new(move |tx_event, rx_request, _| {
+            tokio::spawn(handle_networking(transport, tx_event, rx_request));
+        }));
+        self
+    }
+
+    /// Runs the networking task on `runtime`. Without one, the plugin uses the runtime it's
+    /// built in, or starts its own if there is none, which lives as long as the `Networking`.
+    pub fn with_runtime(mut self, runtime: Handle) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Largest message, in bytes before compression, accepted from a peer. Anything bigger is
+    /// dropped on receive, including fragmented messages that claim to be.
+    pub fn with_max_message_size(mut self, bytes: usize) -> Self {
+        self.max_message_size = bytes;
+        self
+    }
+
+    /// Records every message sent and received to a capture file, see [`CaptureRecord`].
+    pub fn with_capture(mut self, path: impl Into<PathBuf>) -> Self {
+        self.capture = Some(path.into());
+        self
+    }
+
+    pub fn with_faults(mut self, faults: FaultInjection) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    /// Only lets in peers that present a connect token signed with `key`. Peers are held back
+    /// until they do, and turned away with a `Rejected` event if they don't.
+    pub fn with_token_key(mut self, key: TokenKey) -> Self {
+        self.token_key = Some(key);
+        self
+    }
+
+    /// How long a peer has to present its token before it's turned away, and how long a client
+    /// with a token waits for the server to accept it.
+    pub fn with_auth_timeout(mut self, timeout: Duration) -> Self {
+        self.auth_timeout = timeout;
+        self
+    }
+
+    /// Presents `token` to the server on connecting. The server only shows up as connected once
+    /// it has accepted the token.
+    pub fn with_token(mut self, token: ConnectToken) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    pub fn is_server(&self) -> bool {
+        self.is_server
+    }
+
+    fn with_launcher(is_server: bool, launcher: Launcher) -> Self {
+        Self {
+            is_server,
+            compression: Compression::default(),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            capture: None,
+            faults: None,
+            token_key: None,
+            auth_timeout: DEFAULT_AUTH_TIMEOUT,
+            token: None,
+            runtime: None,
+            launcher: Mutex::new(Some(launcher)),
+        }
+    }
+
+    /// Starts the transport and returns its `Networking` without an `App`, for connections that
+    /// live next to the main one. Call [`Networking::update`] every frame in place of the
+    /// systems the plugin would add.
+    pub fn into_networking(self) -> Networking {
+        self.start()
+    }
+
+    fn start(&self) -> Networking {
         let (tx_event, rx_event) = channel(256);
         let (tx_request, rx_request) = channel(256);
 
-        tokio::spawn(handle_networking(tx_event, rx_request));
+        let launcher = self
+            .launcher
+            .lock()
+            .unwrap()
+            .take()
+            .expect("NetworkingPlugin can only be built once");
+
+        let mut runtime = None;
+        let handle = match self.runtime.clone().or_else(|| Handle::try_current().ok()) {
+            Some(handle) => handle,
+            None => {
+                let owned = tokio::runtime::Builder::new_multi_thread()
+                    .worker_threads(NETWORKING_THREADS)
+                    .thread_name("networking")
+                    .enable_all()
+                    .build()
+                    .expect("failed to start the networking runtime");
+                runtime.insert(owned).handle().clone()
+            }
+        };
+        {
+            let _entered = handle.enter();
+            // packets are kept small whatever the message limit, so they always fit
+            launcher(tx_event, rx_request, self.max_message_size.max(MAX_PACKET_SIZE));
+        }
+
+        let mut networking = Networking::new(tx_request, rx_event, self.compression, self.max_message_size);
+        networking.runtime = runtime;
+        networking.auth.key = self.token_key.clone();
+        networking.auth.timeout = self.auth_timeout;
+        networking.auth.token = self.token.clone();
+        networking.auth.is_server = self.is_server;
+        if let Some(path) = &self.capture
+            && let Err(e) = networking.start_capture(path)
+        {
+            println!("Failed to start network capture at {}: {}", path.display(), e);
+        }
+        networking.set_faults(self.faults);
+        networking
+    }
+}
+
+impl Plugin for NetworkingPlugin {
+    fn build(&self, app: &mut App) {
+        let networking = self.start();
 
-        app.insert_resource(Networking::new(tx_request, rx_event));
+        app.insert_resource(networking);
+        app.insert_resource(NetworkStats::default());
+        app.insert_resource(RequestHandlers::default());
         app.add_system(gather_events, SystemStage::PreUpdate);
+        app.add_system(answer_requests, SystemStage::Update);
+        // Render is the last stage of a frame, so everything sent this tick goes out together.
+        app.add_system(flush_outgoing, SystemStage::Render);
     }
 }
 
 system! {
     fn gather_events(
         networking: res &mut Networking,
+        stats: res &mut NetworkStats,
     ) {
         let Some(networking) = networking else {
             return;
         };
 
-        networking.gather_recv();
-        networking.serialize_recv();
+        networking.update();
+
+        if let Some(stats) = stats {
+            *stats = networking.stats();
+        }
+    }
+}
+
+system! {
+    fn flush_outgoing(networking: res &mut Networking) {
+        if let Some(networking) = networking {
+            networking.flush_outgoing();
+        }
     }
 }
 
 type RecvChannel = Mutex<VecDeque<(Target, Box<dyn Any>)>>;
+type RequestChannel = Mutex<VecDeque<(Target, u32, Box<dyn Any>)>>;
 
 #[derive(Resource)]
 pub struct Networking {
@@ -67,22 +305,66 @@ pub struct Networking {
     rx_event: Receiver<NetworkingEvent>,
 
     recv_buffer: Vec<RecvChannel>,
+    request_buffer: Vec<RequestChannel>,
+    backlog: Mutex<VecDeque<NetworkingRequest>>,
     events: Vec<NetworkingEvent>,
+
+    outgoing: Mutex<Outgoing>,
+    reassembler: Reassembler,
+    capture: Mutex<Option<Capture>>,
+    faults: Mutex<Option<Faults>>,
+    max_message_size: usize,
+
+    next_request_id: AtomicU32,
+    /// Deadlines of requests still waiting on a response, and the responses that arrived.
+    outstanding: Mutex<HashMap<u32, Instant>>,
+    responses: Mutex<HashMap<u32, Box<dyn Any>>>,
+
+    compression: Compression,
+    peers: BTreeSet<u32>,
+    auth: Auth,
+    groups: Groups,
+    stats: Mutex<NetworkStats>,
+    /// Set when the plugin had to start its own runtime.
+    runtime: Option<Runtime>,
 }
 
 impl Networking {
-    fn new(tx_request: Sender<NetworkingRequest>, rx_event: Receiver<NetworkingEvent>) -> Self {
+    fn new(
+        tx_request: Sender<NetworkingRequest>,
+        rx_event: Receiver<NetworkingEvent>,
+        compression: Compression,
+        max_message_size: usize,
+    ) -> Self {
         let mut recv_buffer = Vec::new();
+        let mut request_buffer = Vec::new();
         let recv_count = registry::NET_IDS.len();
         for _ in 0..recv_count {
             recv_buffer.push(Mutex::new(VecDeque::new()));
+            request_buffer.push(Mutex::new(VecDeque::new()));
         }
 
         Self {
             tx_request,
             rx_event,
             recv_buffer,
+            request_buffer,
+            backlog: Mutex::new(VecDeque::new()),
             events: Vec::new(),
+            outgoing: Mutex::new(Outgoing::default()),
+            reassembler: Reassembler::default(),
+            capture: Mutex::new(None),
+            faults: Mutex::new(None),
+            max_message_size,
+            next_request_id: AtomicU32::new(0),
+            outstanding: Mutex::new(HashMap::new()),
+            responses: Mutex::new(HashMap::new()),
+            compression,
+            peers: BTreeSet::new(),
+            auth: Auth::default(),
+            groups: Groups::default(),
+            stats: Mutex::new(NetworkStats::default()),
+            runtime: None,
         }
     }
 
@@ -90,12 +372,179 @@ impl Networking {
         let mut events = Vec::new();
 
         while let Ok(event) = self.rx_event.try_recv() {
+            match event {
+                NetworkingEvent::Connected { target: Target::Single(peer) } if self.auth.holds_new_peers() => {
+                    self.auth.pending.insert(peer, Instant::now());
+                    if let Some(token) = self.auth.token.clone() {
+                        let request = AuthRequest(token);
+                        if let Err(e) = self.send_frame(Reliability::Reliable, Target::Single(peer), &request, Correlation::None) {
+                            println!("Failed to send connect token to {}: {}", peer, e);
+                        }
+                    }
+                    // reported once the handshake is done
+                    continue;
+                }
+                NetworkingEvent::Connected { target: Target::Single(peer) } => {
+                    self.peers.insert(peer);
+                }
+                NetworkingEvent::Disconnected { target: Target::Single(peer) } => {
+                    self.reassembler.forget(Target::Single(peer));
+                    if self.auth.forget(peer) {
+                        // never reported as connected
+                        continue;
+                    }
+                    self.peers.remove(&peer);
+                    self.groups.leave_all(peer);
+                }
+                _ => {}
+            }
             events.push(event);
         }
 
         self.events = events;
     }
 
+    fn handshake(&mut self, from: Target, frame: &[u8]) -> Result<(), NetError> {
+        let Target::Single(peer) = from else {
+            return Ok(());
+        };
+        let (_, obj) = frame::decode_frame(frame)?;
+
+        if let Some(AuthRequest(token)) = obj.downcast_ref::<AuthRequest>() {
+            return self.check_token(peer, token);
+        }
+        // the server's verdict only matters to a client waiting on it, so a peer can't admit itself
+        if !self.auth.awaits_verdict_from(peer) {
+            return Ok(());
+        }
+
+        if obj.downcast_ref::<AuthAccepted>().is_some() {
+            self.accept(peer);
+        } else if let Some(AuthRejected(reason)) = obj.downcast_ref::<AuthRejected>() {
+            println!("Server {} turned us away: {}", peer, reason);
+            self.auth.reject(peer);
+            self.events.push(NetworkingEvent::Rejected { target: from, reason: reason.clone() });
+            self.disconnect(peer)?;
+        }
+        Ok(())
+    }
+
+    fn check_token(&mut self, peer: u32, token: &ConnectToken) -> Result<(), NetError> {
+        let target = Target::Single(peer);
+        let Some(key) = &self.auth.key else {
+            // a server that doesn't check tokens still answers, so the client isn't left waiting
+            return self.send_frame(Reliability::Reliable, target, &AuthAccepted { player_id: None }, Correlation::None);
+        };
+        if !self.auth.pending.contains_key(&peer) {
+            return Ok(());
+        }
+
+        match key.verify(token) {
+            Ok(player_id) => {
+                self.auth.players.insert(peer, player_id);
+                self.accept(peer);
+                let accepted = AuthAccepted { player_id: Some(player_id) };
+                self.send_frame(Reliability::Reliable, target, &accepted, Correlation::None)?;
+            }
+            Err(reason) => self.reject(peer, reason),
+        }
+        Ok(())
+    }
+
+    fn accept(&mut self, peer: u32) {
+        self.auth.pending.remove(&peer);
+        self.peers.insert(peer);
+        self.events.push(NetworkingEvent::Connected { target: Target::Single(peer) });
+    }
+
+    fn reject(&mut self, peer: u32, reason: String) {
+        println!("Turning away peer {}: {}", peer, reason);
+        self.auth.reject(peer);
+        let target = Target::Single(peer);
+        if let Err(e) = self.send_frame(Reliability::Reliable, target, &AuthRejected(reason.clone()), Correlation::None) {
+            println!("Failed to tell peer {} why: {}", peer, e);
+        }
+        self.events.push(NetworkingEvent::Rejected { target, reason });
+    }
+
+    /// Turns away peers that never presented a token, gives up on servers that never answered
+    /// ours, and drops rejected peers that didn't leave.
+    fn update_auth(&mut self) {
+        for peer in self.auth.timed_out() {
+            if self.auth.key.is_some() {
+                self.reject(peer, "no connect token".to_string());
+                continue;
+            }
+
+            let reason = "no answer to our connect token".to_string();
+            println!("Giving up on server {}: {}", peer, reason);
+            self.auth.reject(peer);
+            self.events.push(NetworkingEvent::Rejected { target: Target::Single(peer), reason });
+            if let Err(e) = self.disconnect(peer) {
+                println!("Failed to drop server {}: {}", peer, e);
+            }
+        }
+        for peer in self.auth.due_for_closing() {
+            if let Err(e) = self.disconnect(peer) {
+                println!("Failed to drop rejected peer {}: {}", peer, e);
+            }
+        }
+    }
+
+    /// The player id from the connect token `peer` joined with, on a server with a token key.
+    pub fn player_id(&self, peer: u32) -> Option<u64> {
+        self.auth.players.get(&peer).copied()
+    }
+
+    /// Sends what is queued and takes in what arrived since the last frame. The plugin does this
+    /// at the start of every frame.
+    pub fn update(&mut self) {
+        // anything sent outside of a frame, or after the flush last frame
+        self.flush_outgoing();
+        self.flush_backlog();
+        self.gather_recv();
+        self.serialize_recv();
+        self.update_auth();
+    }
+
+    /// Peers that are currently connected.
+    pub fn peers(&self) -> impl Iterator<Item = u32> + '_ {
+        self.peers.iter().copied()
+    }
+
+    /// Closes the connection to a peer once everything already sent to it has gone out. The
+    /// usual `Disconnected` event follows.
+    pub fn disconnect(&self, peer: u32) -> Result<(), NetError> {
+        self.flush_outgoing();
+        self.queue(Reliability::Reliable, NetworkingRequest::Disconnect { peer })
+    }
+
+    /// Adds a peer to a group. Peers leave all their groups when they disconnect.
+    pub fn join_group(&mut self, peer: u32, group: GroupId) {
+        self.groups.join(peer, group);
+    }
+
+    pub fn leave_group(&mut self, peer: u32, group: GroupId) {
+        self.groups.leave(peer, group);
+    }
+
+    pub fn group_members(&self, group: GroupId) -> impl Iterator<Item = u32> + '_ {
+        self.groups.members(group)
+    }
+
+    pub fn groups_of(&self, peer: u32) -> impl Iterator<Item = GroupId> + '_ {
+        self.groups.groups_of(peer)
+    }
+
+    pub fn stats(&self) -> NetworkStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Connection events gathered this frame. Received data is routed to `next`/`collect`.
+    pub fn events(&self) -> &[NetworkingEvent] {
+        &self.events
+    }
+
     fn split_off_events(&mut self, cond: fn(&NetworkingEvent) -> bool) -> Vec<NetworkingEvent> {
         let (split_off, events) = self.events.drain(..).partition(cond);
 
@@ -105,27 +554,206 @@ impl Networking {
 
     fn serialize_recv(&mut self) {
         let split_events = self.split_off_events(|e| matches!(e, NetworkingEvent::RecvData { .. }));
+        self.reassembler.expire();
+        self.expire_requests();
 
         for event in split_events {
-            let NetworkingEvent::RecvData { from, data } = &event else {
-                panic!("Event type mismatch in serialize_recv");
+            let NetworkingEvent::RecvData { from, reliability, data } = event else {
+                continue;
             };
 
-            debug_assert!(data.len() > 4);
-            let type_id = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+            let frames = match batch::decode_packet(&data) {
+                Ok(frames) => frames,
+                Err(e) => {
+                    println!("Dropping network packet from {:?}: {}", from, e);
+                    continue;
+                }
+            };
 
-            debug_assert!(type_id < self.recv_buffer.len());
-            let data = &data[4..];
+            for frame in frames {
+                if let Err(e) = self.receive_frame(from, reliability, frame) {
+                    println!("Dropping network frame from {:?}: {}", from, e);
+                }
+            }
+        }
+    }
 
-            let Ok(obj) = registry::FROM_BYTES[type_id](data) else {
-                println!(
-                    "Failed to deserialize network object of type id: {}",
-                    type_id,
-                );
-                continue;
+    fn receive_frame(&mut self, from: Target, reliability: Reliability, frame: &[u8]) -> Result<(), NetError> {
+        if let Target::Single(peer) = from {
+            self.stats.lock().unwrap().record_in(peer, frame.len());
+        }
+
+        let (header, _) = frame::split_frame(frame)?;
+        if Auth::is_handshake(header.type_id) {
+            return self.handshake(from, frame);
+        }
+        if let Target::Single(peer) = from
+            && self.auth.pending.contains_key(&peer)
+        {
+            // nothing gets through before the handshake
+            return Ok(());
+        }
+        if header.type_id == get_net_id::<Fragment>() {
+            let (_, fragment) = frame::decode_frame(frame)?;
+            let fragment = *fragment.downcast::<Fragment>().unwrap();
+            let Some(whole) = self.reassembler.insert(from, fragment, self.max_message_size)? else {
+                return Ok(());
             };
-            let mut buffer = self.recv_buffer[type_id].lock().unwrap();
-            buffer.push_back((*from, obj));
+
+            // fragments never nest, so a reassembled fragment can't build up another one
+            if frame::split_frame(&whole)?.0.type_id == get_net_id::<Fragment>() {
+                return Err(NetError::Decode {
+                    type_id: header.type_id,
+                    reason: "fragment reassembled into another fragment".to_string(),
+                });
+            }
+            return self.deliver(from, reliability, &whole);
+        }
+
+        self.deliver(from, reliability, frame)
+    }
+
+    fn deliver(&self, from: Target, reliability: Reliability, frame: &[u8]) -> Result<(), NetError> {
+        if let Some(capture) = self.capture.lock().unwrap().as_mut() {
+            capture.record(Direction::In, from, reliability, frame);
+        }
+
+        let len = frame::message_size(frame)?;
+        if len > self.max_message_size {
+            return Err(NetError::TooLarge {
+                len,
+                max: self.max_message_size,
+            });
+        }
+
+        let (header, _) = frame::split_frame(frame)?;
+        let (type_id, obj) = frame::decode_frame(frame)?;
+        match header.correlation {
+            Correlation::None => {
+                let mut buffer = self.recv_buffer[type_id].lock().unwrap();
+                buffer.push_back((from, obj));
+            }
+            Correlation::Request(id) => {
+                let mut buffer = self.request_buffer[type_id].lock().unwrap();
+                buffer.push_back((from, id, obj));
+            }
+            Correlation::Response(id) => {
+                // responses nobody is waiting for anymore are dropped
+                if self.outstanding.lock().unwrap().contains_key(&id) {
+                    self.responses.lock().unwrap().insert(id, obj);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn expire_requests(&self) {
+        let now = Instant::now();
+        let mut outstanding = self.outstanding.lock().unwrap();
+        let mut responses = self.responses.lock().unwrap();
+        outstanding.retain(|id, deadline| {
+            let keep = now < *deadline + request::STALE_REQUEST_GRACE;
+            if !keep {
+                responses.remove(id);
+            }
+            keep
+        });
+    }
+
+    /// Processes an event as if it had just arrived from the networking task.
+    pub fn inject(&mut self, event: NetworkingEvent) {
+        self.events.push(event);
+        self.serialize_recv();
+    }
+
+    /// Feeds the incoming messages of a capture back in, as if they had just arrived.
+    pub fn replay(&mut self, records: impl IntoIterator<Item = CaptureRecord>) {
+        for record in records {
+            if record.direction != Direction::In {
+                continue;
+            }
+            self.events.push(NetworkingEvent::RecvData {
+                from: record.peer,
+                reliability: record.reliability,
+                data: batch::encode_packet([record.frame.as_slice()]),
+            });
+        }
+        self.serialize_recv();
+    }
+
+    /// Starts recording every message to `path`, replacing any capture already running.
+    pub fn start_capture(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let capture = Capture::create(path.as_ref())?;
+        *self.capture.lock().unwrap() = Some(capture);
+        Ok(())
+    }
+
+    pub fn stop_capture(&self) {
+        if let Some(mut capture) = self.capture.lock().unwrap().take() {
+            capture.flush();
+        }
+    }
+
+    /// Delays, drops and duplicates outgoing packets. `None` turns it off; packets already
+    /// delayed are dropped.
+    pub fn set_faults(&self, faults: Option<FaultInjection>) {
+        *self.faults.lock().unwrap() = faults.map(Faults::new);
+    }
+
+    /// Sends everything still queued, then asks the networking task to close the transport and
+    /// stop. Gives up on anything the task doesn't accept within a short grace period.
+    pub fn shutdown(&mut self) {
+        self.flush_outgoing();
+        for _ in 0..SHUTDOWN_ATTEMPTS {
+            self.flush_backlog();
+            if self.backlog.lock().unwrap().is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let mut request = NetworkingRequest::Exit;
+        for _ in 0..SHUTDOWN_ATTEMPTS {
+            match self.tx_request.try_send(request) {
+                Ok(()) | Err(TrySendError::Closed(_)) => return,
+                Err(TrySendError::Full(returned)) => request = returned,
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        println!("Networking task didn't accept the exit request");
+    }
+
+    /// Waits for the networking task to close its transport, then stops the runtime it ran on
+    /// if it was started for it.
+    fn stop_runtime(&mut self) {
+        let Some(runtime) = self.runtime.take() else {
+            return;
+        };
+        for _ in 0..SHUTDOWN_ATTEMPTS {
+            // the task drops its request receiver once it's done
+            if self.tx_request.is_closed() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        runtime.shutdown_background();
+    }
+
+    fn flush_backlog(&mut self) {
+        let mut backlog = self.backlog.lock().unwrap();
+
+        while let Some(request) = backlog.pop_front() {
+            match self.tx_request.try_send(request) {
+                Ok(()) => {}
+                Err(TrySendError::Full(request)) => {
+                    backlog.push_front(request);
+                    break;
+                }
+                Err(TrySendError::Closed(_)) => {
+                    backlog.clear();
+                    break;
+                }
+            }
         }
     }
 
@@ -155,38 +783,239 @@ impl Networking {
         results
     }
 
-    pub fn send<T: NetSend>(&self, reliability: Reliability, target: Target, data: T) {
+    /// Queues `data` to go out at the end of the frame, batched with everything else sent to the
+    /// same target. Frames too large for one packet are fragmented. When the request channel is
+    /// full, unreliable packets are dropped and reliable ones wait in a bounded backlog that is
+    /// flushed every frame.
+    pub fn send<T: NetSend>(
+        &self,
+        reliability: Reliability,
+        target: Target,
+        data: T,
+    ) -> Result<(), NetError> {
         debug_assert!(target != Target::This, "Cannot send data to 'This' target");
 
-        let mut bytes = Vec::new();
-        let type_id = data.get_type_id() as u32;
-        bytes.extend_from_slice(&type_id.to_le_bytes());
-        bytes.extend_from_slice(&data.get_bytes());
+        self.send_frame(reliability, target, &data, Correlation::None)
+    }
+
+    /// Sends `request` reliably and returns a handle to poll for the response, which times out
+    /// after [`DEFAULT_REQUEST_TIMEOUT`]. When sent to `Target::All`, the first response wins.
+    pub fn request<T: NetRequest>(
+        &self,
+        target: Target,
+        request: T,
+    ) -> Result<PendingRequest<T::Response>, NetError> {
+        self.request_with_timeout(target, request, DEFAULT_REQUEST_TIMEOUT)
+    }
+
+    pub fn request_with_timeout<T: NetRequest>(
+        &self,
+        target: Target,
+        request: T,
+        timeout: Duration,
+    ) -> Result<PendingRequest<T::Response>, NetError> {
+        let id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+        self.send_frame(Reliability::Reliable, target, &request, Correlation::Request(id))?;
 
-        let request = NetworkingRequest::SendData {
-            reliability,
-            target,
-            data: bytes,
+        let deadline = Instant::now() + timeout;
+        self.outstanding.lock().unwrap().insert(id, deadline);
+        Ok(PendingRequest::new(id, deadline))
+    }
+
+    /// Requests of type `T` received since they were last collected. Requests with a handler in
+    /// [`RequestHandlers`] are answered automatically and never show up here.
+    pub fn collect_requests<T: NetRequest>(&self) -> Vec<IncomingRequest<T>> {
+        let type_id = registry::get_net_id::<T>();
+        debug_assert!(type_id < self.request_buffer.len());
+
+        let mut buffer = self.request_buffer[type_id].lock().unwrap();
+        buffer
+            .drain(..)
+            .map(|(from, id, obj)| IncomingRequest {
+                from,
+                request: *obj.downcast::<T>().unwrap(),
+                id,
+            })
+            .collect()
+    }
+
+    pub fn respond<T: NetRequest>(
+        &self,
+        request: &IncomingRequest<T>,
+        response: T::Response,
+    ) -> Result<(), NetError> {
+        self.respond_to(request.from, request.id, response)
+    }
+
+    pub(crate) fn respond_to<R: NetSend>(&self, to: Target, id: u32, response: R) -> Result<(), NetError> {
+        self.send_frame(Reliability::Reliable, to, &response, Correlation::Response(id))
+    }
+
+    pub(crate) fn take_response<R: NetSend>(
+        &self,
+        id: u32,
+        deadline: Instant,
+    ) -> Option<Result<R, NetError>> {
+        if let Some(obj) = self.responses.lock().unwrap().remove(&id) {
+            self.outstanding.lock().unwrap().remove(&id);
+            return Some(obj.downcast::<R>().map(|r| *r).map_err(|_| NetError::Decode {
+                type_id: registry::get_net_id::<R>(),
+                reason: format!("response to request {} has the wrong type", id),
+            }));
+        }
+
+        if Instant::now() >= deadline {
+            self.outstanding.lock().unwrap().remove(&id);
+            return Some(Err(NetError::TimedOut));
+        }
+        None
+    }
+
+    fn send_frame<T: NetSend>(
+        &self,
+        reliability: Reliability,
+        target: Target,
+        data: &T,
+        correlation: Correlation,
+    ) -> Result<(), NetError> {
+        if self.tx_request.is_closed() {
+            return Err(NetError::Disconnected);
+        }
+
+        let frame = frame::encode_correlated_frame(
+            data.get_type_id(),
+            &data.get_bytes(),
+            self.compression,
+            correlation,
+        );
+        let len = frame.len();
+        if let Some(capture) = self.capture.lock().unwrap().as_mut() {
+            capture.record(Direction::Out, target, reliability, &frame);
+        }
+
+        // transports only know about single peers and everyone, so narrower targets are
+        // expanded into one batch per peer here; so is everyone while the handshake holds
+        // peers back, as the transport's everyone includes them
+        let peers: Vec<u32> = match target {
+            Target::Group(group) => self.groups.members(group).collect(),
+            Target::AllExcept(except) => self.peers().filter(|&peer| peer != except).collect(),
+            Target::All if self.auth.holds_new_peers() => self.peers().collect(),
+            Target::All | Target::Single(_) | Target::This => {
+                self.outgoing.lock().unwrap().push(reliability, target, frame)?;
+                self.record_out(target, len);
+                return Ok(());
+            }
         };
 
-        self.tx_request
-            .try_send(request)
-            .expect("Networking request buffer full");
+        let mut outgoing = self.outgoing.lock().unwrap();
+        for peer in peers {
+            outgoing.push(reliability, Target::Single(peer), frame.clone())?;
+            self.record_out(Target::Single(peer), len);
+        }
+        Ok(())
+    }
+
+    /// Sends every batch queued this frame. Runs automatically at the end of each frame.
+    pub fn flush_outgoing(&self) {
+        let mut packets = self.outgoing.lock().unwrap().take_packets();
+        if let Some(faults) = self.faults.lock().unwrap().as_mut() {
+            for packet in packets {
+                faults.push(packet);
+            }
+            packets = faults.due();
+        }
+
+        for (reliability, target, data) in packets {
+            let request = NetworkingRequest::SendData {
+                reliability,
+                target,
+                data,
+            };
+            if let Err(e) = self.queue(reliability, request) {
+                println!("Dropping {:?} packet to {:?}: {}", reliability, target, e);
+            }
+        }
+
+        if let Some(capture) = self.capture.lock().unwrap().as_mut() {
+            capture.flush();
+        }
+    }
+
+    fn queue(&self, reliability: Reliability, request: NetworkingRequest) -> Result<(), NetError> {
+        let mut backlog = self.backlog.lock().unwrap();
+        if reliability == Reliability::Reliable && !backlog.is_empty() {
+            // keep reliable messages in order behind the ones already waiting
+            return Self::push_backlog(&mut backlog, request);
+        }
+
+        match self.tx_request.try_send(request) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(request)) => match reliability {
+                Reliability::Reliable => Self::push_backlog(&mut backlog, request),
+                Reliability::Unreliable => Err(NetError::BufferFull),
+            },
+            Err(TrySendError::Closed(_)) => Err(NetError::Disconnected),
+        }
+    }
+
+    fn record_out(&self, target: Target, len: usize) {
+        let mut stats = self.stats.lock().unwrap();
+        match target {
+            Target::All => {
+                for &peer in &self.peers {
+                    stats.record_out(peer, len);
+                }
+            }
+            Target::Single(peer) => stats.record_out(peer, len),
+            Target::This | Target::Group(_) | Target::AllExcept(_) => {}
+        }
+    }
+
+    fn push_backlog(
+        backlog: &mut VecDeque<NetworkingRequest>,
+        request: NetworkingRequest,
+    ) -> Result<(), NetError> {
+        if backlog.len() >= MAX_REQUEST_BACKLOG {
+            return Err(NetError::BufferFull);
+        }
+
+        backlog.push_back(request);
+        Ok(())
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+/// Dropping the `App` drops its `Networking`, which closes the connections on the way out.
+impl Drop for Networking {
+    fn drop(&mut self) {
+        if !self.tx_request.is_closed() {
+            self.shutdown();
+        }
+        self.stop_runtime();
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Target {
     All,
     Single(u32),
     This,
+    /// Every peer in a group joined with [`Networking::join_group`].
+    Group(GroupId),
+    AllExcept(u32),
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum NetworkingEvent {
-    RecvData { from: Target, data: Vec<u8> },
+    RecvData {
+        from: Target,
+        reliability: Reliability,
+        data: Vec<u8>,
+    },
     Disconnected { target: Target },
     Connected { target: Target },
+    /// A join that was turned away, on both ends: the server that refused the peer and the
+    /// client that was refused.
+    Rejected { target: Target, reason: String },
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -198,31 +1027,12 @@ pub enum Reliability {
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum NetworkingRequest {
     Exit,
+    Disconnect {
+        peer: u32,
+    },
     SendData {
         reliability: Reliability,
         target: Target,
         data: Vec<u8>,
     },
 }
-
-async fn handle_networking(
-    mut tx_event: Sender<NetworkingEvent>,
-    mut rx_request: Receiver<NetworkingRequest>,
-) {
-    loop {
-        tokio::select! {
-            request = rx_request.recv() => {
-                let Some(request) = request else {
-                    break;
-                };
-
-                match request {
-                    NetworkingRequest::Exit => break,
-                    NetworkingRequest::SendData { reliability, target, data } => {
-                        // Handle sending data over the network
-                    }
-                }
-            }
-        }
-    }
-}
@@ -0,0 +1,439 @@
+use crate::*;
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_LOBBY_PORT: u16 = 27016;
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(2);
+/// Servers that haven't sent a heartbeat for this long are dropped from the list.
+pub const DEFAULT_LISTING_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the connect tokens a lobby hands out stay valid.
+pub const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ServerId(pub u64);
+
+/// What a game server tells the lobby about itself.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ServerInfo {
+    pub name: String,
+    /// Where players should connect to.
+    pub addr: SocketAddr,
+    pub game_mode: String,
+    pub players: u32,
+    pub max_players: u32,
+}
+
+#[derive(NetSend, NetRequest, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[response(Registered)]
+pub struct RegisterServer(pub ServerInfo);
+
+#[derive(NetSend, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Registered(pub ServerId);
+
+/// Keeps a listing alive and refreshes its player count.
+#[derive(NetSend, NetRequest, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[response(HeartbeatAck)]
+pub struct Heartbeat {
+    pub id: ServerId,
+    pub players: u32,
+}
+
+/// `known` is false once the listing has expired, and the server should register again.
+#[derive(NetSend, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct HeartbeatAck {
+    pub known: bool,
+}
+
+#[derive(NetSend, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Unregister(pub ServerId);
+
+/// Lists servers, optionally only those running one game mode.
+#[derive(NetSend, NetRequest, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[response(ServerList)]
+pub struct ListServers {
+    pub game_mode: Option<String>,
+}
+
+#[derive(NetSend, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ServerList(pub Vec<(ServerId, ServerInfo)>);
+
+#[derive(NetSend, NetRequest, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[response(JoinTicket)]
+pub struct JoinServer(pub ServerId);
+
+#[derive(NetSend, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum JoinTicket {
+    /// `token` is set when the lobby issues connect tokens, and is what the player presents to
+    /// the server with `NetworkingPlugin::with_token`.
+    Accepted { addr: SocketAddr, token: Option<ConnectToken> },
+    Rejected { reason: String },
+}
+
+/// Turns an `App` with a server `NetworkingPlugin` into a lobby that game servers register with
+/// and players find them through.
+pub struct LobbyPlugin {
+    timeout: Duration,
+    token_key: Option<TokenKey>,
+}
+
+impl LobbyPlugin {
+    pub fn new() -> Self {
+        Self {
+            timeout: DEFAULT_LISTING_TIMEOUT,
+            token_key: None,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Hands out a connect token signed with `key` with every accepted join. Each connection to
+    /// the lobby is given its own player id.
+    pub fn with_token_key(mut self, key: TokenKey) -> Self {
+        self.token_key = Some(key);
+        self
+    }
+}
+
+impl Default for LobbyPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Lobby {
+            timeout: self.timeout,
+            listings: BTreeMap::new(),
+            next_id: 0,
+            token_key: self.token_key.clone(),
+            player_ids: HashMap::new(),
+            next_player_id: 1,
+        });
+
+        let handlers = app
+            .get_resource_mut::<RequestHandlers>()
+            .expect("LobbyPlugin must be added after NetworkingPlugin");
+        handlers.add::<RegisterServer>(register_server);
+        handlers.add::<Heartbeat>(heartbeat);
+        handlers.add::<ListServers>(list_servers);
+        handlers.add::<JoinServer>(join_server);
+
+        app.add_system(maintain_lobby, SystemStage::Update);
+    }
+}
+
+struct Listing {
+    info: ServerInfo,
+    owner: Target,
+    last_heartbeat: Instant,
+}
+
+/// The servers a lobby currently knows about.
+#[derive(Resource)]
+pub struct Lobby {
+    timeout: Duration,
+    listings: BTreeMap<ServerId, Listing>,
+    next_id: u64,
+    token_key: Option<TokenKey>,
+    player_ids: HashMap<Target, u64>,
+    next_player_id: u64,
+}
+
+impl Lobby {
+    pub fn servers(&self) -> impl Iterator<Item = (ServerId, &ServerInfo)> {
+        self.listings.iter().map(|(&id, listing)| (id, &listing.info))
+    }
+
+    /// Removes listings whose server stopped sending heartbeats.
+    fn expire(&mut self) {
+        let timeout = self.timeout;
+        self.listings.retain(|id, listing| {
+            let alive = listing.last_heartbeat.elapsed() < timeout;
+            if !alive {
+                println!("Server {} ({}) timed out", id.0, listing.info.name);
+            }
+            alive
+        });
+    }
+
+    /// A token for whoever is joining from `from`, with the same player id every time.
+    fn issue_token(&mut self, from: Target) -> Option<ConnectToken> {
+        let key = self.token_key.as_ref()?;
+        let player_id = *self.player_ids.entry(from).or_insert_with(|| {
+            self.next_player_id += 1;
+            self.next_player_id - 1
+        });
+        Some(key.issue(player_id, DEFAULT_TOKEN_LIFETIME))
+    }
+}
+
+fn lobby(commands: &mut Commands) -> &'static mut Lobby {
+    commands.get_resource_mut::<Lobby>().expect("Lobby resource missing")
+}
+
+fn register_server(commands: &mut Commands, from: Target, request: RegisterServer) -> Registered {
+    let lobby = lobby(commands);
+    let id = ServerId(lobby.next_id);
+    lobby.next_id += 1;
+
+    println!("Server {} ({}) registered at {}", id.0, request.0.name, request.0.addr);
+    lobby.listings.insert(
+        id,
+        Listing {
+            info: request.0,
+            owner: from,
+            last_heartbeat: Instant::now(),
+        },
+    );
+    Registered(id)
+}
+
+fn heartbeat(commands: &mut Commands, from: Target, request: Heartbeat) -> HeartbeatAck {
+    let lobby = lobby(commands);
+    let Some(listing) = lobby.listings.get_mut(&request.id).filter(|l| l.owner == from) else {
+        return HeartbeatAck { known: false };
+    };
+
+    listing.info.players = request.players;
+    listing.last_heartbeat = Instant::now();
+    HeartbeatAck { known: true }
+}
+
+fn list_servers(commands: &mut Commands, _from: Target, request: ListServers) -> ServerList {
+    let lobby = lobby(commands);
+    lobby.expire();
+
+    let servers = lobby
+        .servers()
+        .filter(|(_, info)| request.game_mode.as_ref().is_none_or(|mode| *mode == info.game_mode))
+        .map(|(id, info)| (id, info.clone()))
+        .collect();
+    ServerList(servers)
+}
+
+fn join_server(commands: &mut Commands, from: Target, request: JoinServer) -> JoinTicket {
+    let lobby = lobby(commands);
+    lobby.expire();
+
+    let addr = match lobby.listings.get(&request.0) {
+        None => {
+            return JoinTicket::Rejected {
+                reason: "no such server".to_string(),
+            };
+        }
+        Some(listing) if listing.info.players >= listing.info.max_players => {
+            return JoinTicket::Rejected {
+                reason: "server is full".to_string(),
+            };
+        }
+        Some(listing) => listing.info.addr,
+    };
+    JoinTicket::Accepted {
+        addr,
+        token: lobby.issue_token(from),
+    }
+}
+
+system! {
+    fn maintain_lobby(networking: res &Networking, lobby: res &mut Lobby) {
+        let (Some(networking), Some(lobby)) = (networking, lobby) else {
+            return;
+        };
+
+        for (from, Unregister(id)) in networking.collect::<Unregister>() {
+            if lobby.listings.get(&id).is_some_and(|l| l.owner == from) {
+                lobby.listings.remove(&id);
+            }
+        }
+
+        // a server that drops its connection to the lobby is gone too
+        for event in networking.events() {
+            if let NetworkingEvent::Disconnected { target } = event {
+                lobby.listings.retain(|_, listing| listing.owner != *target);
+                lobby.player_ids.remove(target);
+            }
+        }
+
+        lobby.expire();
+    }
+}
+
+/// Talks to a lobby from a game server or a player. It holds its own connection, separate from
+/// the game's `Networking`, so call [`update`](Self::update) every frame, or insert it as a
+/// resource and add [`update_lobby_client`].
+#[derive(Resource)]
+pub struct LobbyClient {
+    networking: Networking,
+    heartbeat_interval: Duration,
+    registration: Option<Registration>,
+}
+
+struct Registration {
+    info: ServerInfo,
+    id: Option<ServerId>,
+    pending: Option<PendingRequest<Registered>>,
+    heartbeat: Option<PendingRequest<HeartbeatAck>>,
+    last_heartbeat: Instant,
+}
+
+impl LobbyClient {
+    /// Connects over QUIC, on the current Tokio runtime or one of its own if there is none.
+    pub fn connect(addr: SocketAddr) -> Self {
+        Self::from_plugin(NetworkingPlugin::client_to(addr))
+    }
+
+    pub fn with_transport<T: Transport>(transport: T) -> Self {
+        Self::from_plugin(NetworkingPlugin::client().with_transport(transport))
+    }
+
+    fn from_plugin(plugin: NetworkingPlugin) -> Self {
+        Self {
+            networking: plugin.into_networking(),
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            registration: None,
+        }
+    }
+
+    /// Should be comfortably below the lobby's listing timeout.
+    pub fn with_heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.heartbeat_interval = interval;
+        self
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.networking.peers().next().is_some()
+    }
+
+    /// Lists this server with the lobby and keeps it listed until [`unregister`](Self::unregister)
+    /// is called or the client is dropped. Registers again if the listing expires.
+    pub fn register(&mut self, info: ServerInfo) {
+        self.unregister();
+        self.registration = Some(Registration {
+            info,
+            id: None,
+            pending: None,
+            heartbeat: None,
+            last_heartbeat: Instant::now(),
+        });
+    }
+
+    /// The id the lobby gave this server, once registration went through.
+    pub fn server_id(&self) -> Option<ServerId> {
+        self.registration.as_ref()?.id
+    }
+
+    /// Reported with the next heartbeat.
+    pub fn set_players(&mut self, players: u32) {
+        if let Some(registration) = &mut self.registration {
+            registration.info.players = players;
+        }
+    }
+
+    pub fn unregister(&mut self) {
+        let Some(registration) = self.registration.take() else {
+            return;
+        };
+        if let Some(id) = registration.id {
+            let _ = self.networking.send(Reliability::Reliable, Target::All, Unregister(id));
+        }
+    }
+
+    pub fn list(&self, game_mode: Option<&str>) -> Result<PendingRequest<ServerList>, NetError> {
+        let request = ListServers {
+            game_mode: game_mode.map(str::to_string),
+        };
+        self.networking.request(Target::All, request)
+    }
+
+    /// Asks where to connect to a listed server.
+    pub fn join(&self, id: ServerId) -> Result<PendingRequest<JoinTicket>, NetError> {
+        self.networking.request(Target::All, JoinServer(id))
+    }
+
+    pub fn poll<R: NetSend>(&self, pending: &PendingRequest<R>) -> Option<Result<R, NetError>> {
+        pending.poll(&self.networking)
+    }
+
+    pub fn update(&mut self) {
+        self.networking.update();
+        if self.is_connected() {
+            self.update_registration();
+        }
+        self.networking.flush_outgoing();
+    }
+
+    fn update_registration(&mut self) {
+        let Some(registration) = &mut self.registration else {
+            return;
+        };
+        let networking = &self.networking;
+
+        if let Some(pending) = &registration.pending
+            && let Some(result) = pending.poll(networking)
+        {
+            registration.pending = None;
+            match result {
+                Ok(Registered(id)) => {
+                    registration.id = Some(id);
+                    registration.last_heartbeat = Instant::now();
+                }
+                Err(e) => println!("Failed to register with the lobby: {}", e),
+            }
+        }
+
+        if let Some(heartbeat) = &registration.heartbeat
+            && let Some(result) = heartbeat.poll(networking)
+        {
+            registration.heartbeat = None;
+            if let Ok(HeartbeatAck { known: false }) = result {
+                println!("Lobby forgot about this server, registering again");
+                registration.id = None;
+            }
+        }
+
+        match registration.id {
+            None if registration.pending.is_none() => {
+                match networking.request(Target::All, RegisterServer(registration.info.clone())) {
+                    Ok(pending) => registration.pending = Some(pending),
+                    Err(e) => println!("Failed to register with the lobby: {}", e),
+                }
+            }
+            Some(id)
+                if registration.heartbeat.is_none()
+                    && registration.last_heartbeat.elapsed() >= self.heartbeat_interval =>
+            {
+                registration.last_heartbeat = Instant::now();
+                let heartbeat = Heartbeat {
+                    id,
+                    players: registration.info.players,
+                };
+                match networking.request(Target::All, heartbeat) {
+                    Ok(pending) => registration.heartbeat = Some(pending),
+                    Err(e) => println!("Failed to send a lobby heartbeat: {}", e),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Drop for LobbyClient {
+    fn drop(&mut self) {
+        // the connection closes once `networking` is dropped right after
+        self.unregister();
+    }
+}
+
+system! {
+    fn update_lobby_client(client: res &mut LobbyClient) {
+        if let Some(client) = client {
+            client.update();
+        }
+    }
+}
@@ -36,6 +36,16 @@ lazy_static! {
         entries.sort_by_key(|e| NET_IDS[&e.type_id]);
         entries.into_iter().map(|r| r.from_bytes).collect()
     };
+    pub static ref NAMES: Vec<&'static str> = {
+        let mut entries: Vec<_> = inventory::iter::<NetRegistration>.into_iter().collect();
+        entries.sort_by_key(|e| NET_IDS[&e.type_id]);
+        entries.into_iter().map(|r| r.name).collect()
+    };
+}
+
+/// The registered name of a net id, as given by [`NetRegistration::name`].
+pub fn get_net_name(type_id: usize) -> Option<&'static str> {
+    NAMES.get(type_id).copied()
 }
 
 pub type NetId = ConstTypeId;
@@ -0,0 +1,621 @@
+use crate::*;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+/// Bytes of replication data each peer may be sent per tick. Sized so an update stays under
+/// `FRAGMENT_SIZE` and travels in one packet; entities that don't fit wait for a later tick.
+const DEFAULT_BUDGET: usize = 900;
+/// Unacknowledged updates kept per peer. A peer that falls further behind than this gets a
+/// full state instead of a delta.
+const MAX_UNACKED: usize = 64;
+/// Rough framing cost of an entity change on top of its component bytes.
+const CHANGE_OVERHEAD: usize = 8;
+
+/// Submitted by `#[replicate]` for every component type that should be mirrored to clients.
+pub struct ReplicatedRegistration {
+    pub type_id: ConstTypeId,
+    pub name: &'static str,
+    pub component_id: fn() -> usize,
+    pub encode: fn(&dyn Component) -> Vec<u8>,
+    pub decode: fn(&[u8]) -> Result<Box<dyn Component>>,
+}
+
+inventory::collect!(ReplicatedRegistration);
+
+lazy_static! {
+    /// Replicated component types in name order. Both ends must be built with the same set of
+    /// `#[replicate]` components, since the index into this list is what goes over the wire.
+    pub static ref REPLICATED: Vec<&'static ReplicatedRegistration> = {
+        let mut entries: Vec<_> = inventory::iter::<ReplicatedRegistration>.into_iter().collect();
+        entries.sort_by_key(|e| e.name); // ensures deterministic ordering
+        entries
+    };
+}
+
+/// Marks a server entity whose `#[replicate]` components are sent to clients.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Replicated;
+
+/// Added to client entities that mirror a server entity, holding the server's entity id.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ServerEntity(pub u32);
+
+/// How urgently a replicated entity's changes should go out when a peer's budget is tight.
+/// Entities without one have a priority of 1.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct NetPriority(pub f32);
+
+/// Scores how much `entity` matters to `peer`. Zero means the peer shouldn't know about it at
+/// all; anything else scales the entity's priority.
+pub type RelevanceFn = fn(&Commands, u32, u32) -> f32;
+
+/// Sent unreliably. `changes` take the peer from the state it acknowledged at `baseline` to the
+/// server's state at `tick`; without a baseline they describe the whole state from scratch.
+/// `partial` updates had entities left out for lack of budget, so an entity missing from them
+/// is only gone if it was despawned explicitly.
+#[derive(NetSend, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ReplicationUpdate {
+    pub tick: Tick,
+    pub baseline: Option<Tick>,
+    pub changes: Vec<EntityChange>,
+    pub partial: bool,
+}
+
+/// Tells the server which update the client now holds, so it can be used as the next baseline.
+#[derive(NetSend, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ReplicationAck {
+    pub tick: Tick,
+}
+
+/// Components are sent as `(index into REPLICATED, data)`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum EntityChange {
+    Spawn {
+        entity: u32,
+        components: Vec<(u16, ComponentData)>,
+    },
+    Update {
+        entity: u32,
+        components: Vec<(u16, ComponentData)>,
+        removed: Vec<u16>,
+    },
+    Despawn {
+        entity: u32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ComponentData {
+    Full(Vec<u8>),
+    /// [`delta_encode`]d against the same component in the baseline.
+    Delta(Vec<u8>),
+}
+
+impl ComponentData {
+    fn encode(base: Option<&[u8]>, bytes: &[u8]) -> Self {
+        if let Some(base) = base {
+            let delta = delta_encode(base, bytes);
+            if delta.len() < bytes.len() {
+                return ComponentData::Delta(delta);
+            }
+        }
+        ComponentData::Full(bytes.to_vec())
+    }
+
+    fn decode(&self, base: Option<&[u8]>) -> Result<Vec<u8>, NetError> {
+        match self {
+            ComponentData::Full(bytes) => Ok(bytes.clone()),
+            ComponentData::Delta(delta) => delta_decode(base.unwrap_or_default(), delta),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            ComponentData::Full(bytes) | ComponentData::Delta(bytes) => bytes.len(),
+        }
+    }
+}
+
+/// Encoded replicated components of one entity, indexed like `REPLICATED`.
+type EntityState = Vec<Option<Arc<Vec<u8>>>>;
+type WorldState = BTreeMap<u32, EntityState>;
+
+pub struct ReplicationPlugin {
+    is_server: bool,
+}
+
+impl ReplicationPlugin {
+    pub fn client() -> Self {
+        Self { is_server: false }
+    }
+
+    pub fn server() -> Self {
+        Self { is_server: true }
+    }
+}
+
+impl Plugin for ReplicationPlugin {
+    fn build(&self, app: &mut App) {
+        if self.is_server {
+            app.insert_resource(ReplicationServer::default());
+            app.add_system(replicate_to_clients, SystemStage::PostUpdate);
+        } else {
+            app.insert_resource(ReplicationClient::default());
+            app.add_system(apply_replication, SystemStage::PreUpdate);
+        }
+    }
+}
+
+#[derive(Default)]
+struct PeerReplication {
+    acked: Option<Tick>,
+    /// What the peer will hold after each update that hasn't been acknowledged yet.
+    sent: VecDeque<(Tick, Arc<WorldState>)>,
+    /// Priority built up by entities that were left out for lack of budget.
+    waiting: BTreeMap<u32, f32>,
+}
+
+impl PeerReplication {
+    fn baseline(&self) -> Option<(Tick, Arc<WorldState>)> {
+        let acked = self.acked?;
+        self.sent
+            .iter()
+            .find(|(tick, _)| *tick == acked)
+            .map(|(tick, state)| (*tick, state.clone()))
+    }
+
+    fn acknowledge(&mut self, tick: Tick) {
+        if self.acked.is_some_and(|acked| acked >= tick) {
+            return;
+        }
+        self.acked = Some(tick);
+        self.sent.retain(|(sent, _)| *sent >= tick);
+    }
+}
+
+/// A change to one entity for one peer, waiting to see if it fits the budget.
+struct Candidate {
+    entity: u32,
+    score: f32,
+    size: usize,
+    change: EntityChange,
+    /// The entity as the peer will have it if the change is sent.
+    state: Option<EntityState>,
+}
+
+#[derive(Resource)]
+pub struct ReplicationServer {
+    /// Per-peer bytes per tick.
+    pub budget: usize,
+    relevance: RelevanceFn,
+    /// Encoding of every replicated entity as of the last run.
+    current: WorldState,
+    last_run: Tick,
+    peers: BTreeMap<u32, PeerReplication>,
+}
+
+impl Default for ReplicationServer {
+    fn default() -> Self {
+        Self {
+            budget: DEFAULT_BUDGET,
+            relevance: |_, _, _| 1.0,
+            current: WorldState::new(),
+            last_run: 0,
+            peers: BTreeMap::new(),
+        }
+    }
+}
+
+impl ReplicationServer {
+    pub fn set_relevance(&mut self, relevance: RelevanceFn) {
+        self.relevance = relevance;
+    }
+
+    /// Newest update `peer` has confirmed.
+    pub fn acked(&self, peer: u32) -> Option<Tick> {
+        self.peers.get(&peer)?.acked
+    }
+
+    /// Re-encodes the replicated components that were touched since the previous run.
+    fn refresh(&mut self, commands: &Commands) {
+        let mut current = WorldState::new();
+
+        for (id, _) in unsafe { World::get_components::<Replicated>(commands.world) } {
+            let Some(entity) = commands.get_entity(id) else {
+                continue;
+            };
+            let previous = self.current.remove(&id);
+
+            let state = REPLICATED
+                .iter()
+                .enumerate()
+                .map(|(index, registration)| {
+                    let component_id = (registration.component_id)();
+                    let component = entity.get_component_by_id(component_id)?;
+                    let cached = previous.as_ref().and_then(|p| p[index].clone());
+                    match cached {
+                        Some(bytes) if entity.changed_tick(component_id) < self.last_run => Some(bytes),
+                        _ => Some(Arc::new((registration.encode)(component))),
+                    }
+                })
+                .collect();
+            current.insert(id, state);
+        }
+
+        self.current = current;
+        self.last_run = commands.tick();
+    }
+
+    /// Picks the changes that take `peer` from its baseline toward the current state, most
+    /// important first, until the budget runs out.
+    fn update_for(&mut self, commands: &Commands, peer: u32, tick: Tick) -> Option<ReplicationUpdate> {
+        let relevance = self.relevance;
+        let budget = self.budget;
+        let current = &self.current;
+        let replication = self.peers.get_mut(&peer)?;
+
+        let (baseline_tick, baseline) = match replication.baseline() {
+            Some((tick, state)) => (Some(tick), state),
+            None => (None, Arc::new(WorldState::new())),
+        };
+
+        let mut candidates = Vec::new();
+        for (&entity, state) in current {
+            let score = relevance(commands, peer, entity);
+            if score <= 0.0 {
+                continue;
+            }
+            let priority = commands
+                .get_entity(entity)
+                .and_then(|e| e.get_component::<NetPriority>())
+                .map_or(1.0, |p| p.0);
+            let score = score * priority;
+
+            if let Some(candidate) = diff_entity(entity, baseline.get(&entity), state) {
+                let waited = replication.waiting.get(&entity).copied().unwrap_or(0.0);
+                candidates.push(Candidate { score: waited + score, ..candidate });
+            }
+        }
+        for &entity in baseline.keys() {
+            let relevant = current.contains_key(&entity) && relevance(commands, peer, entity) > 0.0;
+            if !relevant {
+                candidates.push(Candidate {
+                    entity,
+                    // cheap, and a stale entity is worse than a late one
+                    score: f32::INFINITY,
+                    size: CHANGE_OVERHEAD,
+                    change: EntityChange::Despawn { entity },
+                    state: None,
+                });
+            }
+        }
+
+        if candidates.is_empty() {
+            return None;
+        }
+        // highest score first, ties broken by entity id so every run picks the same way
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.entity.cmp(&b.entity)));
+
+        let mut state = (*baseline).clone();
+        let mut changes = Vec::new();
+        let mut used = 0;
+        let mut partial = false;
+        for candidate in candidates {
+            if used + candidate.size > budget && !changes.is_empty() {
+                if candidate.score.is_finite() {
+                    replication.waiting.insert(candidate.entity, candidate.score);
+                }
+                partial = true;
+                continue;
+            }
+
+            used += candidate.size;
+            replication.waiting.remove(&candidate.entity);
+            match candidate.state {
+                Some(entity_state) => state.insert(candidate.entity, entity_state),
+                None => state.remove(&candidate.entity),
+            };
+            changes.push(candidate.change);
+        }
+
+        replication.sent.push_back((tick, Arc::new(state)));
+        if replication.sent.len() > MAX_UNACKED {
+            replication.sent.pop_front();
+        }
+
+        Some(ReplicationUpdate {
+            tick,
+            baseline: baseline_tick,
+            changes,
+            partial,
+        })
+    }
+}
+
+/// The change that brings `base` up to `state`, or `None` if they already match.
+fn diff_entity(entity: u32, base: Option<&EntityState>, state: &EntityState) -> Option<Candidate> {
+    let Some(base) = base else {
+        let components: Vec<_> = state
+            .iter()
+            .enumerate()
+            .filter_map(|(index, bytes)| Some((index as u16, ComponentData::Full(bytes.as_ref()?.to_vec()))))
+            .collect();
+        return Some(Candidate {
+            entity,
+            score: 0.0,
+            size: change_size(&components),
+            change: EntityChange::Spawn { entity, components },
+            state: Some(state.clone()),
+        });
+    };
+
+    let mut components = Vec::new();
+    let mut removed = Vec::new();
+    for (index, (old, new)) in base.iter().zip(state.iter()).enumerate() {
+        match (old, new) {
+            (Some(old), Some(new)) if Arc::ptr_eq(old, new) || old == new => {}
+            (old, Some(new)) => {
+                let data = ComponentData::encode(old.as_ref().map(|o| o.as_slice()), new);
+                components.push((index as u16, data));
+            }
+            (Some(_), None) => removed.push(index as u16),
+            (None, None) => {}
+        }
+    }
+
+    if components.is_empty() && removed.is_empty() {
+        return None;
+    }
+    Some(Candidate {
+        entity,
+        score: 0.0,
+        size: change_size(&components) + removed.len(),
+        change: EntityChange::Update {
+            entity,
+            components,
+            removed,
+        },
+        state: Some(state.clone()),
+    })
+}
+
+fn change_size(components: &[(u16, ComponentData)]) -> usize {
+    CHANGE_OVERHEAD + components.iter().map(|(_, data)| data.len() + 2).sum::<usize>()
+}
+
+#[derive(Resource, Default)]
+pub struct ReplicationClient {
+    entity_map: HashMap<u32, u32>,
+    last_tick: Tick,
+    /// Received states that the server may still use as a baseline, newest last.
+    states: VecDeque<(Tick, WorldState)>,
+    /// `(local entity, server tick, component id)` for every component written this frame.
+    updated: Vec<(u32, Tick, usize)>,
+}
+
+impl ReplicationClient {
+    pub fn local_entity(&self, server_entity: u32) -> Option<u32> {
+        self.entity_map.get(&server_entity).copied()
+    }
+
+    /// Server tick of the most recent update that was applied.
+    pub fn last_tick(&self) -> Tick {
+        self.last_tick
+    }
+
+    /// Local entities whose `T` was overwritten by the server this frame, with the server tick.
+    pub fn updated<T: Component>(&self) -> impl Iterator<Item = (u32, Tick)> + '_ {
+        let id = get_component_id::<T>();
+        self.updated
+            .iter()
+            .filter(move |(_, _, component)| *component == id)
+            .map(|&(entity, tick, _)| (entity, tick))
+    }
+
+    /// Rebuilds the server state at `update.tick` and brings the world in line with it.
+    /// Returns `false` for updates that are stale or can't be decoded.
+    fn apply(&mut self, commands: &mut Commands, update: ReplicationUpdate) -> bool {
+        if self.states.back().is_some_and(|(tick, _)| *tick >= update.tick) {
+            return false;
+        }
+
+        let base = match update.baseline {
+            Some(baseline) => {
+                let Some((_, base)) = self.states.iter().find(|(tick, _)| *tick == baseline) else {
+                    println!("Replication baseline {} is no longer available", baseline);
+                    return false;
+                };
+                base.clone()
+            }
+            None => WorldState::new(),
+        };
+
+        let despawned: HashSet<u32> = update
+            .changes
+            .iter()
+            .filter_map(|change| match change {
+                EntityChange::Despawn { entity } => Some(*entity),
+                _ => None,
+            })
+            .collect();
+        let state = match rebuild(base, update.changes) {
+            Ok(state) => state,
+            Err(e) => {
+                println!("Dropping replication update {}: {}", update.tick, e);
+                return false;
+            }
+        };
+
+        let previous = self.states.back().map(|(_, state)| state.clone()).unwrap_or_default();
+        self.sync_world(commands, &previous, &state, update.tick);
+        // an update that left entities out for lack of budget says nothing about the ones
+        // missing from it, so only explicit despawns count until a complete one arrives
+        let gone: Vec<u32> = self
+            .entity_map
+            .keys()
+            .filter(|entity| !state.contains_key(entity))
+            .filter(|entity| !update.partial || despawned.contains(entity))
+            .copied()
+            .collect();
+        for entity in gone {
+            if let Some(local) = self.entity_map.remove(&entity) {
+                commands.despawn_entity(local);
+            }
+        }
+
+        // the server only ever moves its baseline forward, so older states are dead weight
+        if let Some(baseline) = update.baseline {
+            self.states.retain(|(tick, _)| *tick >= baseline);
+        }
+        self.states.push_back((update.tick, state));
+        if self.states.len() > MAX_UNACKED {
+            self.states.pop_front();
+        }
+        self.last_tick = update.tick;
+        true
+    }
+
+    fn sync_world(&mut self, commands: &mut Commands, previous: &WorldState, state: &WorldState, tick: Tick) {
+        for (&entity, components) in state {
+            let old = previous.get(&entity);
+            let local = *self.entity_map.entry(entity).or_insert_with(|| {
+                let local = commands.spawn_entity();
+                commands.add_component(local, ServerEntity(entity));
+                local
+            });
+
+            for (index, registration) in REPLICATED.iter().enumerate() {
+                let before = old.and_then(|o| o[index].as_ref());
+                match (before, &components[index]) {
+                    (Some(before), Some(bytes)) if before == bytes => {}
+                    (_, Some(bytes)) => match (registration.decode)(bytes) {
+                        Ok(component) => {
+                            commands.insert_component_dyn(local, component);
+                            self.updated.push((local, tick, (registration.component_id)()));
+                        }
+                        Err(e) => println!("Failed to decode {}: {}", registration.name, e),
+                    },
+                    (Some(_), None) => {
+                        commands.remove_component_by_id(local, (registration.component_id)());
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+    }
+}
+
+fn rebuild(mut state: WorldState, changes: Vec<EntityChange>) -> Result<WorldState, NetError> {
+    for change in changes {
+        match change {
+            EntityChange::Spawn { entity, components } => {
+                let mut fresh = vec![None; REPLICATED.len()];
+                apply_components(&mut fresh, components)?;
+                state.insert(entity, fresh);
+            }
+            EntityChange::Update {
+                entity,
+                components,
+                removed,
+            } => {
+                let Some(existing) = state.get_mut(&entity) else {
+                    continue;
+                };
+                apply_components(existing, components)?;
+                for index in removed {
+                    if let Some(slot) = existing.get_mut(index as usize) {
+                        *slot = None;
+                    }
+                }
+            }
+            EntityChange::Despawn { entity } => {
+                state.remove(&entity);
+            }
+        }
+    }
+    Ok(state)
+}
+
+fn apply_components(entity: &mut EntityState, components: Vec<(u16, ComponentData)>) -> Result<(), NetError> {
+    for (index, data) in components {
+        let Some(slot) = entity.get_mut(index as usize) else {
+            println!("Unknown replicated component index: {}", index);
+            continue;
+        };
+        let bytes = data.decode(slot.as_deref().map(|b| b.as_slice()))?;
+        *slot = Some(Arc::new(bytes));
+    }
+    Ok(())
+}
+
+system! {
+    fn replicate_to_clients(
+        networking: res &Networking,
+        replication: res &mut ReplicationServer,
+        commands: commands,
+    ) {
+        let (Some(networking), Some(replication)) = (networking, replication) else {
+            return;
+        };
+
+        for event in networking.events() {
+            match event {
+                NetworkingEvent::Connected { target: Target::Single(peer) } => {
+                    replication.peers.insert(*peer, PeerReplication::default());
+                }
+                NetworkingEvent::Disconnected { target: Target::Single(peer) } => {
+                    replication.peers.remove(peer);
+                }
+                _ => {}
+            }
+        }
+
+        for (from, ack) in networking.collect::<ReplicationAck>() {
+            if let Target::Single(peer) = from
+                && let Some(peer) = replication.peers.get_mut(&peer)
+            {
+                peer.acknowledge(ack.tick);
+            }
+        }
+
+        replication.refresh(&commands);
+
+        let tick = commands.tick();
+        let peers: Vec<u32> = replication.peers.keys().copied().collect();
+        for peer in peers {
+            let Some(update) = replication.update_for(&commands, peer, tick) else {
+                continue;
+            };
+            if let Err(e) = networking.send(Reliability::Unreliable, Target::Single(peer), update) {
+                println!("Failed to send replication update to {}: {}", peer, e);
+            }
+        }
+    }
+}
+
+system! {
+    fn apply_replication(
+        networking: res &mut Networking,
+        replication: res &mut ReplicationClient,
+        commands: commands,
+    ) {
+        let (Some(networking), Some(replication)) = (networking, replication) else {
+            return;
+        };
+
+        replication.updated.clear();
+        let mut updates = networking.collect::<ReplicationUpdate>();
+        updates.sort_by_key(|(_, update)| update.tick);
+
+        for (from, update) in updates {
+            let tick = update.tick;
+            if !replication.apply(&mut commands, update) {
+                continue;
+            }
+            if let Err(e) = networking.send(Reliability::Unreliable, from, ReplicationAck { tick }) {
+                println!("Failed to acknowledge replication update {}: {}", tick, e);
+            }
+        }
+    }
+}
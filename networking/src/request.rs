@@ -0,0 +1,87 @@
+use crate::*;
+
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Requests whose handle was dropped without being polled are forgotten this long after their
+/// deadline.
+pub(crate) const STALE_REQUEST_GRACE: Duration = Duration::from_secs(30);
+
+/// A message that expects an answer. Derive it alongside `NetSend` and name the answer with
+/// `#[response(Type)]`.
+pub trait NetRequest: NetSend {
+    type Response: NetSend;
+}
+
+/// A request waiting on its response. Keep it around and poll it every frame until it resolves.
+pub struct PendingRequest<R> {
+    id: u32,
+    deadline: Instant,
+    _response: PhantomData<fn() -> R>,
+}
+
+impl<R: NetSend> PendingRequest<R> {
+    pub(crate) fn new(id: u32, deadline: Instant) -> Self {
+        Self {
+            id,
+            deadline,
+            _response: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// `None` while the response is still on its way. Resolves once, with the response or
+    /// `NetError::TimedOut`; the handle should be dropped afterwards.
+    pub fn poll(&self, networking: &Networking) -> Option<Result<R, NetError>> {
+        networking.take_response(self.id, self.deadline)
+    }
+}
+
+/// A request received from a peer, answered with [`Networking::respond`].
+pub struct IncomingRequest<T> {
+    pub from: Target,
+    pub request: T,
+    pub(crate) id: u32,
+}
+
+type Handler = Box<dyn Fn(&mut Commands, &Networking) + Send + Sync>;
+
+/// Handlers that answer requests automatically every frame, in `SystemStage::Update`.
+#[derive(Resource, Default)]
+pub struct RequestHandlers {
+    handlers: Vec<Handler>,
+}
+
+impl RequestHandlers {
+    pub fn add<T: NetRequest>(&mut self, handler: fn(&mut Commands, Target, T) -> T::Response) {
+        self.handlers.push(Box::new(move |commands, networking| {
+            for incoming in networking.collect_requests::<T>() {
+                let IncomingRequest { from, request, id } = incoming;
+                let response = handler(commands, from, request);
+                if let Err(e) = networking.respond_to(from, id, response) {
+                    println!("Failed to answer request {} from {:?}: {}", id, from, e);
+                }
+            }
+        }));
+    }
+}
+
+system! {
+    fn answer_requests(
+        networking: res &Networking,
+        handlers: res &RequestHandlers,
+        commands: commands,
+    ) {
+        let (Some(networking), Some(handlers)) = (networking, handlers) else {
+            return;
+        };
+
+        for handler in &handlers.handlers {
+            handler(&mut commands, networking);
+        }
+    }
+}
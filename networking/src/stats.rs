@@ -0,0 +1,44 @@
+use crate::*;
+
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PeerStats {
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub frames_in: u64,
+    pub frames_out: u64,
+}
+
+/// Running totals of frame bytes exchanged with every peer, after compression. Refreshed from
+/// `Networking` at the start of every frame.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct NetworkStats {
+    pub peers: BTreeMap<u32, PeerStats>,
+}
+
+impl NetworkStats {
+    pub fn peer(&self, peer: u32) -> Option<&PeerStats> {
+        self.peers.get(&peer)
+    }
+
+    pub fn bytes_in(&self) -> u64 {
+        self.peers.values().map(|p| p.bytes_in).sum()
+    }
+
+    pub fn bytes_out(&self) -> u64 {
+        self.peers.values().map(|p| p.bytes_out).sum()
+    }
+
+    pub(crate) fn record_in(&mut self, peer: u32, len: usize) {
+        let stats = self.peers.entry(peer).or_default();
+        stats.bytes_in += len as u64;
+        stats.frames_in += 1;
+    }
+
+    pub(crate) fn record_out(&mut self, peer: u32, len: usize) {
+        let stats = self.peers.entry(peer).or_default();
+        stats.bytes_out += len as u64;
+        stats.frames_out += 1;
+    }
+}
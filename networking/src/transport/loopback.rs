@@ -0,0 +1,334 @@
+use crate::*;
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Peer id the server end of a [`LoopbackHub`] is reachable under.
+pub const LOOPBACK_SERVER_ID: u32 = 0;
+
+/// Imperfections applied to frames as they cross a loopback link.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LinkConditions {
+    pub latency: Duration,
+    /// Extra delay picked uniformly from `0..=jitter` for every frame.
+    pub jitter: Duration,
+    /// Chance in `0..=1` that an unreliable frame is dropped.
+    pub loss: f32,
+    /// Chance in `0..=1` that an unreliable frame is held back behind frames sent after it.
+    pub reorder: f32,
+}
+
+/// In-process stand-in for a network: one server and any number of clients exchanging frames
+/// through channels, so several `App`s can talk to each other inside one test. Reliable frames
+/// are never lost and always arrive in order, whatever the link conditions.
+#[derive(Clone, Default)]
+pub struct LoopbackHub {
+    state: Arc<Mutex<HubState>>,
+}
+
+#[derive(Default)]
+struct HubState {
+    conditions: LinkConditions,
+    seed: u64,
+    server: Option<UnboundedSender<Delivery>>,
+    clients: BTreeMap<u32, UnboundedSender<Delivery>>,
+    next_client_id: u32,
+    next_seq: u64,
+    /// Delivery time of the last reliable frame on each `(from, to)` link.
+    last_reliable: HashMap<(u32, u32), Instant>,
+}
+
+struct Delivery {
+    at: Instant,
+    seq: u64,
+    event: NetworkingEvent,
+}
+
+impl PartialEq for Delivery {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Delivery {}
+
+impl PartialOrd for Delivery {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Delivery {
+    // reversed so the BinaryHeap pops the earliest delivery first
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.at, other.seq).cmp(&(self.at, self.seq))
+    }
+}
+
+impl HubState {
+    fn deliver(&mut self, to: &UnboundedSender<Delivery>, at: Instant, event: NetworkingEvent) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let _ = to.send(Delivery { at, seq, event });
+    }
+
+    fn connect(&mut self, to: &UnboundedSender<Delivery>, peer: u32) {
+        let event = NetworkingEvent::Connected {
+            target: Target::Single(peer),
+        };
+        self.deliver(to, Instant::now(), event);
+    }
+
+    fn disconnect(&mut self, to: &UnboundedSender<Delivery>, peer: u32) {
+        let event = NetworkingEvent::Disconnected {
+            target: Target::Single(peer),
+        };
+        self.deliver(to, Instant::now(), event);
+    }
+}
+
+impl LoopbackHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Default conditions for every endpoint that doesn't set its own.
+    pub fn with_conditions(self, conditions: LinkConditions) -> Self {
+        self.state.lock().unwrap().conditions = conditions;
+        self
+    }
+
+    /// Seeds the per-endpoint RNGs that decide loss, jitter and reordering.
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.state.lock().unwrap().seed = seed;
+        self
+    }
+
+    fn join(&self, is_server: bool, inbox: UnboundedSender<Delivery>) -> (u32, LinkConditions, u64) {
+        let mut state = self.state.lock().unwrap();
+        let conditions = state.conditions;
+        let seed = state.seed;
+
+        if is_server {
+            let clients: Vec<_> = state
+                .clients
+                .iter()
+                .map(|(&id, tx)| (id, tx.clone()))
+                .collect();
+            for (id, client) in clients {
+                state.connect(&inbox, id);
+                state.connect(&client, LOOPBACK_SERVER_ID);
+            }
+            state.server = Some(inbox);
+            return (LOOPBACK_SERVER_ID, conditions, seed);
+        }
+
+        state.next_client_id += 1;
+        let id = state.next_client_id;
+        if let Some(server) = state.server.clone() {
+            state.connect(&server, id);
+            state.connect(&inbox, LOOPBACK_SERVER_ID);
+        }
+        state.clients.insert(id, inbox);
+        (id, conditions, seed.wrapping_add(id as u64))
+    }
+
+    fn leave(&self, id: u32) {
+        let mut state = self.state.lock().unwrap();
+
+        if id == LOOPBACK_SERVER_ID {
+            state.server = None;
+            let clients: Vec<_> = state.clients.values().cloned().collect();
+            for client in clients {
+                state.disconnect(&client, LOOPBACK_SERVER_ID);
+            }
+        } else if state.clients.remove(&id).is_some()
+            && let Some(server) = state.server.clone()
+        {
+            state.disconnect(&server, id);
+        }
+    }
+
+    /// Cuts the link between a client and the server, telling both ends.
+    fn cut(&self, client: u32) {
+        let mut state = self.state.lock().unwrap();
+
+        let Some(inbox) = state.clients.remove(&client) else {
+            return;
+        };
+        state.disconnect(&inbox, LOOPBACK_SERVER_ID);
+        if let Some(server) = state.server.clone() {
+            state.disconnect(&server, client);
+        }
+    }
+
+    fn recipients(&self, from: u32, target: Target) -> Vec<(u32, UnboundedSender<Delivery>)> {
+        let state = self.state.lock().unwrap();
+
+        if from != LOOPBACK_SERVER_ID {
+            if !state.clients.contains_key(&from) {
+                // cut off from the server
+                return Vec::new();
+            }
+            // clients only ever talk to the server
+            return state
+                .server
+                .iter()
+                .map(|tx| (LOOPBACK_SERVER_ID, tx.clone()))
+                .collect();
+        }
+
+        match target {
+            Target::All => state
+                .clients
+                .iter()
+                .map(|(&id, tx)| (id, tx.clone()))
+                .collect(),
+            Target::Single(id) => state
+                .clients
+                .get(&id)
+                .map(|tx| vec![(id, tx.clone())])
+                .unwrap_or_default(),
+            // `Networking` expands groups and exclusions into single peers before sending
+            Target::This | Target::Group(_) | Target::AllExcept(_) => Vec::new(),
+        }
+    }
+}
+
+/// One endpoint on a [`LoopbackHub`].
+pub struct LoopbackTransport {
+    hub: LoopbackHub,
+    id: u32,
+    conditions: LinkConditions,
+    rng: StdRng,
+    inbox: UnboundedReceiver<Delivery>,
+    pending: BinaryHeap<Delivery>,
+}
+
+impl LoopbackTransport {
+    pub fn server(hub: &LoopbackHub) -> Self {
+        Self::join(hub, true)
+    }
+
+    pub fn client(hub: &LoopbackHub) -> Self {
+        Self::join(hub, false)
+    }
+
+    fn join(hub: &LoopbackHub, is_server: bool) -> Self {
+        let (tx_inbox, inbox) = unbounded_channel();
+        let (id, conditions, seed) = hub.join(is_server, tx_inbox);
+
+        Self {
+            hub: hub.clone(),
+            id,
+            conditions,
+            rng: StdRng::seed_from_u64(seed),
+            inbox,
+            pending: BinaryHeap::new(),
+        }
+    }
+
+    /// Conditions for frames sent from this endpoint, overriding the hub's defaults.
+    pub fn with_conditions(mut self, conditions: LinkConditions) -> Self {
+        self.conditions = conditions;
+        self
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn delay(&mut self, reliability: Reliability) -> Option<Duration> {
+        let conditions = self.conditions;
+        let mut delay = conditions.latency;
+        if !conditions.jitter.is_zero() {
+            delay += conditions.jitter.mul_f32(self.rng.random_range(0.0..=1.0));
+        }
+
+        if reliability == Reliability::Unreliable {
+            if self.rng.random_range(0.0..1.0) < conditions.loss {
+                return None;
+            }
+            if self.rng.random_range(0.0..1.0) < conditions.reorder {
+                delay = delay * 2 + conditions.jitter + Duration::from_millis(1);
+            }
+        }
+
+        Some(delay)
+    }
+}
+
+impl Transport for LoopbackTransport {
+    async fn send(
+        &mut self,
+        reliability: Reliability,
+        target: Target,
+        data: Vec<u8>,
+    ) -> Result<(), NetError> {
+        for (to, inbox) in self.hub.recipients(self.id, target) {
+            let Some(delay) = self.delay(reliability) else {
+                continue;
+            };
+
+            let mut state = self.hub.state.lock().unwrap();
+            let mut at = Instant::now() + delay;
+            if reliability == Reliability::Reliable {
+                let last = state.last_reliable.entry((self.id, to)).or_insert(at);
+                at = at.max(*last);
+                *last = at;
+            }
+
+            let event = NetworkingEvent::RecvData {
+                from: Target::Single(self.id),
+                reliability,
+                data: data.clone(),
+            };
+            state.deliver(&inbox, at, event);
+        }
+
+        Ok(())
+    }
+
+    async fn disconnect(&mut self, peer: u32) {
+        match self.id {
+            LOOPBACK_SERVER_ID => self.hub.cut(peer),
+            id if peer == LOOPBACK_SERVER_ID => self.hub.cut(id),
+            _ => {}
+        }
+    }
+
+    async fn recv(&mut self) -> Option<NetworkingEvent> {
+        loop {
+            while let Ok(delivery) = self.inbox.try_recv() {
+                self.pending.push(delivery);
+            }
+
+            let Some(next_at) = self.pending.peek().map(|d| d.at) else {
+                let delivery = self.inbox.recv().await?;
+                self.pending.push(delivery);
+                continue;
+            };
+
+            if next_at <= Instant::now() {
+                return self.pending.pop().map(|d| d.event);
+            }
+
+            tokio::select! {
+                delivery = self.inbox.recv() => {
+                    self.pending.push(delivery?);
+                }
+                _ = tokio::time::sleep_until(next_at) => {}
+            }
+        }
+    }
+}
+
+impl Drop for LoopbackTransport {
+    fn drop(&mut self) {
+        self.hub.leave(self.id);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::*;
+
+use std::future::Future;
+
+mod loopback;
+mod quic;
+
+pub use loopback::*;
+pub use quic::*;
+
+/// Moves frames between this process and its peers. `handle_networking` drives a transport
+/// from the networking task, so implementations only deal in raw frames and peer ids.
+pub trait Transport: Send + 'static {
+    fn send(
+        &mut self,
+        reliability: Reliability,
+        target: Target,
+        data: Vec<u8>,
+    ) -> impl Future<Output = Result<(), NetError>> + Send;
+
+    /// Resolves with the next connection change or received frame, or `None` once the
+    /// transport can't produce any more events. `handle_networking` drops this future whenever a
+    /// request comes in first, so it mustn't lose anything when cancelled.
+    fn recv(&mut self) -> impl Future<Output = Option<NetworkingEvent>> + Send;
+
+    /// Drops the connection to one peer. The transport reports it with a `Disconnected` event
+    /// like any other lost connection.
+    fn disconnect(&mut self, peer: u32) -> impl Future<Output = ()> + Send;
+
+    fn close(&mut self) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+}
+
+pub(crate) async fn handle_networking<T: Transport>(
+    mut transport: T,
+    tx_event: Sender<NetworkingEvent>,
+    mut rx_request: Receiver<NetworkingRequest>,
+) {
+    loop {
+        tokio::select! {
+            request = rx_request.recv() => {
+                match request {
+                    None | Some(NetworkingRequest::Exit) => break,
+                    Some(NetworkingRequest::Disconnect { peer }) => transport.disconnect(peer).await,
+                    Some(NetworkingRequest::SendData { reliability, target, data }) => {
+                        if let Err(e) = transport.send(reliability, target, data).await {
+                            println!("Failed to send to {:?}: {}", target, e);
+                        }
+                    }
+                }
+            }
+            event = transport.recv() => {
+                let Some(event) = event else {
+                    break;
+                };
+                if tx_event.send(event).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    transport.close().await;
+}
@@ -0,0 +1,564 @@
+use crate::*;
+
+use quinn::crypto::rustls::QuicClientConfig;
+use quinn::{ClientConfig, Connection, Endpoint, RecvStream, SendStream, ServerConfig};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::task::JoinHandle;
+
+pub const DEFAULT_PORT: u16 = 27015;
+
+/// Peer id a client uses for the server it connected to.
+pub const QUIC_SERVER_ID: u32 = 0;
+
+/// Name the self-signed server certificate is issued for.
+const SERVER_NAME: &str = "localhost";
+/// Reliable frames are read in pieces of at most this, so a peer has to actually send the
+/// bytes it announces before they're allocated.
+const READ_CHUNK: usize = 64 * 1024;
+/// Reliable frames waiting for a peer's writer. A peer that falls this far behind is dropped
+/// rather than left to hold up everyone else.
+const MAX_QUEUED_FRAMES: usize = 1024;
+/// How long closing a connection waits for the peer to take the frames still queued for it.
+const CLOSE_GRACE: Duration = Duration::from_millis(50);
+
+/// A connected peer, or on a client, the server it's connecting to.
+struct Peer {
+    /// `None` on a client until its handshake completes.
+    connection: Option<Connection>,
+    /// Reliable frames for the peer's writer task, which sends them in order on its stream.
+    frames: Sender<Vec<u8>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Peer {
+    fn new(frames: Sender<Vec<u8>>) -> Self {
+        Self {
+            connection: None,
+            frames,
+            writer: None,
+        }
+    }
+
+    /// Lets the writer send what's queued, then closes the connection.
+    async fn close(self, reason: &'static [u8]) {
+        let Peer {
+            connection,
+            frames,
+            writer,
+        } = self;
+        // the writer finishes its stream once the queue runs dry
+        drop(frames);
+        if let Some(writer) = writer {
+            let _ = tokio::time::timeout(CLOSE_GRACE, writer).await;
+        }
+        if let Some(connection) = connection {
+            connection.close(0u32.into(), reason);
+        }
+    }
+}
+
+type Connections = Arc<Mutex<BTreeMap<u32, Peer>>>;
+
+/// The certificate a server presents and its private key. Keep one around to give clients a
+/// fingerprint to pin.
+#[derive(Clone)]
+pub struct ServerIdentity {
+    cert: CertificateDer<'static>,
+    key: Vec<u8>,
+}
+
+impl ServerIdentity {
+    /// A fresh self-signed certificate.
+    pub fn generate() -> Result<Self> {
+        let cert = rcgen::generate_simple_self_signed(vec![SERVER_NAME.to_string()])?;
+        Ok(Self {
+            cert: cert.cert.into(),
+            key: cert.key_pair.serialize_der(),
+        })
+    }
+
+    /// Reads a DER certificate and PKCS#8 key, as written by [`ServerIdentity::save`].
+    pub fn load(cert_path: impl AsRef<Path>, key_path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            cert: CertificateDer::from(std::fs::read(cert_path)?),
+            key: std::fs::read(key_path)?,
+        })
+    }
+
+    pub fn save(&self, cert_path: impl AsRef<Path>, key_path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(cert_path, &self.cert)?;
+        std::fs::write(key_path, &self.key)?;
+        Ok(())
+    }
+
+    /// Hex SHA-256 of the certificate, what clients pin with [`ServerTrust::Pinned`].
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.cert)
+    }
+}
+
+/// Which server certificates a client accepts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ServerTrust {
+    /// Whatever the server presents, which is only safe on a network you trust.
+    AnyCertificate,
+    /// Only certificates with one of these fingerprints, see [`ServerIdentity::fingerprint`].
+    Pinned(Vec<String>),
+}
+
+fn fingerprint(cert: &CertificateDer<'_>) -> String {
+    auth::to_hex(ring::digest::digest(&ring::digest::SHA256, cert).as_ref())
+}
+
+/// Reliable frames travel length-prefixed on one unidirectional stream per direction, so they
+/// keep their order. Each peer's stream is written by a task of its own, so a slow peer only
+/// holds up itself. Unreliable frames are sent as QUIC datagrams.
+pub struct QuicTransport {
+    endpoint: Endpoint,
+    connections: Connections,
+    rx_events: UnboundedReceiver<NetworkingEvent>,
+    /// Longest reliable frame accepted from a peer; longer ones close the stream.
+    max_frame_size: Arc<AtomicUsize>,
+}
+
+impl QuicTransport {
+    /// Listens on `addr` with a freshly generated self-signed certificate. Must be called from
+    /// inside a Tokio runtime.
+    pub fn server(addr: SocketAddr) -> Result<Self> {
+        Self::server_with_identity(addr, ServerIdentity::generate()?)
+    }
+
+    /// Listens on `addr`, presenting `identity` to clients.
+    pub fn server_with_identity(addr: SocketAddr, identity: ServerIdentity) -> Result<Self> {
+        let key_der = PrivatePkcs8KeyDer::from(identity.key);
+        let config = ServerConfig::with_single_cert(vec![identity.cert], key_der.into())?;
+
+        let endpoint = Endpoint::server(config, addr)?;
+        let connections = Connections::default();
+        let (tx_events, rx_events) = unbounded_channel();
+        let max_frame_size = Arc::new(AtomicUsize::new(DEFAULT_MAX_MESSAGE_SIZE));
+
+        tokio::spawn(accept_connections(
+            endpoint.clone(),
+            connections.clone(),
+            tx_events,
+            max_frame_size.clone(),
+        ));
+
+        Ok(Self {
+            endpoint,
+            connections,
+            rx_events,
+            max_frame_size,
+        })
+    }
+
+    /// Connects to the server at `addr` in the background; a `Connected` event for
+    /// [`QUIC_SERVER_ID`] follows once the handshake completes. Must be called from inside a
+    /// Tokio runtime.
+    pub fn client(addr: SocketAddr) -> Result<Self> {
+        Self::client_with_trust(addr, ServerTrust::AnyCertificate)
+    }
+
+    /// Like [`QuicTransport::client`], but only completes the handshake with a server whose
+    /// certificate `trust` accepts. A refused server is reported as `Rejected`, then
+    /// `Disconnected`.
+    pub fn client_with_trust(addr: SocketAddr, trust: ServerTrust) -> Result<Self> {
+        let crypto = rustls::ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(CertificateCheck(trust)))
+        .with_no_client_auth();
+
+        let bind: SocketAddr = match addr {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (std::net::Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let mut endpoint = Endpoint::client(bind)?;
+        endpoint.set_default_client_config(ClientConfig::new(Arc::new(
+            QuicClientConfig::try_from(crypto)?,
+        )));
+
+        let connections = Connections::default();
+        let (tx_events, rx_events) = unbounded_channel();
+        let max_frame_size = Arc::new(AtomicUsize::new(DEFAULT_MAX_MESSAGE_SIZE));
+
+        // reliable frames sent before the handshake completes wait in the server's queue
+        let (tx_frames, rx_frames) = channel(MAX_QUEUED_FRAMES);
+        connections
+            .lock()
+            .unwrap()
+            .insert(QUIC_SERVER_ID, Peer::new(tx_frames));
+
+        let connecting = endpoint.connect(addr, SERVER_NAME)?;
+        let client_connections = connections.clone();
+        let client_max_frame_size = max_frame_size.clone();
+        tokio::spawn(async move {
+            match connecting.await {
+                Ok(connection) => {
+                    run_connection(
+                        QUIC_SERVER_ID,
+                        connection,
+                        Some(rx_frames),
+                        client_connections,
+                        tx_events,
+                        client_max_frame_size,
+                    )
+                    .await
+                }
+                Err(e) => {
+                    println!("Failed to connect to {}: {}", addr, e);
+                    client_connections.lock().unwrap().remove(&QUIC_SERVER_ID);
+                    if let quinn::ConnectionError::TransportError(error) = e {
+                        let _ = tx_events.send(NetworkingEvent::Rejected {
+                            target: Target::Single(QUIC_SERVER_ID),
+                            reason: error.reason,
+                        });
+                    }
+                    let _ = tx_events.send(NetworkingEvent::Disconnected {
+                        target: Target::Single(QUIC_SERVER_ID),
+                    });
+                }
+            }
+        });
+
+        Ok(Self {
+            endpoint,
+            connections,
+            rx_events,
+            max_frame_size,
+        })
+    }
+
+    /// Longest reliable frame, in bytes, accepted from a peer. A peer announcing a longer one
+    /// has its stream closed. Defaults to [`DEFAULT_MAX_MESSAGE_SIZE`].
+    pub fn with_max_frame_size(self, bytes: usize) -> Self {
+        self.max_frame_size.store(bytes, Ordering::Relaxed);
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.endpoint.local_addr()?)
+    }
+
+    /// Closes the connection to a peer without waiting for its queue, for peers that can't be
+    /// sent to any more. The read side reports it as `Disconnected`.
+    fn drop_peer(&mut self, peer: u32, reason: &'static [u8]) {
+        if let Some(peer) = self.connections.lock().unwrap().remove(&peer)
+            && let Some(connection) = peer.connection
+        {
+            connection.close(0u32.into(), reason);
+        }
+    }
+}
+
+impl Transport for QuicTransport {
+    async fn send(
+        &mut self,
+        reliability: Reliability,
+        target: Target,
+        data: Vec<u8>,
+    ) -> Result<(), NetError> {
+        let peers: Vec<(u32, Option<Connection>, Sender<Vec<u8>>)> = {
+            let connections = self.connections.lock().unwrap();
+            let peer =
+                |(&id, peer): (&u32, &Peer)| (id, peer.connection.clone(), peer.frames.clone());
+
+            match target {
+                Target::All => connections.iter().map(peer).collect(),
+                Target::Single(id) => connections
+                    .get_key_value(&id)
+                    .map(peer)
+                    .into_iter()
+                    .collect(),
+                // `Networking` expands groups and exclusions into single peers before sending
+                Target::This | Target::Group(_) | Target::AllExcept(_) => Vec::new(),
+            }
+        };
+
+        for (peer, connection, frames) in peers {
+            match reliability {
+                Reliability::Reliable => match frames.try_send(data.clone()) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(_)) => {
+                        println!("Peer {} can't keep up with reliable frames", peer);
+                        self.drop_peer(peer, b"too slow");
+                    }
+                    Err(TrySendError::Closed(_)) => {
+                        println!("Failed to send {} bytes to peer {}", data.len(), peer);
+                    }
+                },
+                Reliability::Unreliable => {
+                    // datagrams sent before a client's handshake completes are lost, like any
+                    // other datagram could be
+                    let Some(connection) = connection else {
+                        continue;
+                    };
+                    if let Err(e) = connection.send_datagram(data.clone().into()) {
+                        println!("Failed to send {} bytes to peer {}: {}", data.len(), peer, e);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> Option<NetworkingEvent> {
+        self.rx_events.recv().await
+    }
+
+    async fn disconnect(&mut self, peer: u32) {
+        let Some(peer) = self.connections.lock().unwrap().remove(&peer) else {
+            return;
+        };
+        tokio::spawn(peer.close(b"disconnected"));
+    }
+
+    async fn close(&mut self) {
+        let peers = std::mem::take(&mut *self.connections.lock().unwrap());
+        let closing: Vec<_> = peers
+            .into_values()
+            .map(|peer| tokio::spawn(peer.close(b"exit")))
+            .collect();
+        for peer in closing {
+            let _ = peer.await;
+        }
+        self.endpoint.close(0u32.into(), b"exit");
+    }
+}
+
+async fn accept_connections(
+    endpoint: Endpoint,
+    connections: Connections,
+    tx_events: UnboundedSender<NetworkingEvent>,
+    max_frame_size: Arc<AtomicUsize>,
+) {
+    // id 0 is what clients call the server, so connected clients start at 1
+    let mut next_id = QUIC_SERVER_ID + 1;
+
+    while let Some(incoming) = endpoint.accept().await {
+        let id = next_id;
+        next_id += 1;
+
+        let connections = connections.clone();
+        let tx_events = tx_events.clone();
+        let max_frame_size = max_frame_size.clone();
+        tokio::spawn(async move {
+            match incoming.await {
+                Ok(connection) => {
+                    run_connection(id, connection, None, connections, tx_events, max_frame_size)
+                        .await
+                }
+                Err(e) => println!("Incoming connection failed: {}", e),
+            }
+        });
+    }
+}
+
+/// Serves one connection until it closes. `frames` is the queue a client set up for the server
+/// before connecting; servers start a fresh one for each peer.
+async fn run_connection(
+    id: u32,
+    connection: Connection,
+    frames: Option<Receiver<Vec<u8>>>,
+    connections: Connections,
+    tx_events: UnboundedSender<NetworkingEvent>,
+    max_frame_size: Arc<AtomicUsize>,
+) {
+    {
+        let mut connections = connections.lock().unwrap();
+        let frames = match frames {
+            Some(frames) => frames,
+            None => {
+                let (tx_frames, rx_frames) = channel(MAX_QUEUED_FRAMES);
+                connections.insert(id, Peer::new(tx_frames));
+                rx_frames
+            }
+        };
+        let Some(peer) = connections.get_mut(&id) else {
+            // the transport closed while the handshake was in flight
+            connection.close(0u32.into(), b"exit");
+            return;
+        };
+        peer.connection = Some(connection.clone());
+        peer.writer = Some(tokio::spawn(write_frames(id, connection.clone(), frames)));
+    }
+    let _ = tx_events.send(NetworkingEvent::Connected {
+        target: Target::Single(id),
+    });
+
+    // the peer's writer opens one stream for all its reliable frames; any more would each cost a
+    // reader task, so they're stopped unread
+    let mut reading = false;
+    loop {
+        tokio::select! {
+            stream = connection.accept_uni() => {
+                let Ok(mut stream) = stream else {
+                    break;
+                };
+                if reading {
+                    println!("Peer {} opened more than one reliable stream", id);
+                    let _ = stream.stop(0u32.into());
+                    continue;
+                }
+                reading = true;
+                let max_frame_size = max_frame_size.load(Ordering::Relaxed);
+                tokio::spawn(read_frames(id, stream, tx_events.clone(), max_frame_size));
+            }
+            datagram = connection.read_datagram() => {
+                let Ok(datagram) = datagram else {
+                    break;
+                };
+                let _ = tx_events.send(NetworkingEvent::RecvData {
+                    from: Target::Single(id),
+                    reliability: Reliability::Unreliable,
+                    data: datagram.to_vec(),
+                });
+            }
+        }
+    }
+
+    connections.lock().unwrap().remove(&id);
+    let _ = tx_events.send(NetworkingEvent::Disconnected {
+        target: Target::Single(id),
+    });
+}
+
+/// Sends a peer's reliable frames in the order they were queued.
+async fn write_frames(id: u32, connection: Connection, mut frames: Receiver<Vec<u8>>) {
+    let mut stream = match connection.open_uni().await {
+        Ok(stream) => stream,
+        Err(e) => {
+            println!("Failed to open a stream to peer {}: {}", id, e);
+            return;
+        }
+    };
+
+    while let Some(data) = frames.recv().await {
+        if let Err(e) = write_frame(&mut stream, &data).await {
+            println!("Failed to send {} bytes to peer {}: {}", data.len(), id, e);
+            // the frames after a lost one can't be delivered in order any more
+            connection.close(0u32.into(), b"send failed");
+            return;
+        }
+    }
+
+    // the queue was dropped, so the connection is closing: wait for the peer to take it all
+    if stream.finish().is_ok() {
+        let _ = stream.stopped().await;
+    }
+}
+
+async fn write_frame(stream: &mut SendStream, data: &[u8]) -> Result<()> {
+    stream.write_all(&(data.len() as u32).to_le_bytes()).await?;
+    stream.write_all(data).await?;
+    Ok(())
+}
+
+async fn read_frames(
+    id: u32,
+    mut stream: RecvStream,
+    tx_events: UnboundedSender<NetworkingEvent>,
+    max_frame_size: usize,
+) {
+    loop {
+        let mut len = [0u8; 4];
+        if stream.read_exact(&mut len).await.is_err() {
+            return;
+        }
+
+        let len = u32::from_le_bytes(len) as usize;
+        if len > max_frame_size {
+            println!("Peer {} sent an oversized frame of {} bytes", id, len);
+            let _ = stream.stop(0u32.into());
+            return;
+        }
+
+        // grows as the bytes come in rather than trusting the announced length up front
+        let mut data = Vec::with_capacity(len.min(READ_CHUNK));
+        while data.len() < len {
+            let start = data.len();
+            data.resize(start + (len - start).min(READ_CHUNK), 0);
+            if stream.read_exact(&mut data[start..]).await.is_err() {
+                return;
+            }
+        }
+
+        let _ = tx_events.send(NetworkingEvent::RecvData {
+            from: Target::Single(id),
+            reliability: Reliability::Reliable,
+            data,
+        });
+    }
+}
+
+/// Checks the server certificate against a [`ServerTrust`]. Self-signed certificates have no
+/// chain to verify, so the handshake signatures are all that's checked beyond that.
+#[derive(Debug)]
+struct CertificateCheck(ServerTrust);
+
+impl ServerCertVerifier for CertificateCheck {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let ServerTrust::Pinned(fingerprints) = &self.0 else {
+            return Ok(ServerCertVerified::assertion());
+        };
+
+        let presented = fingerprint(end_entity);
+        if fingerprints.iter().any(|f| f.eq_ignore_ascii_case(&presented)) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(format!("server certificate {} isn't pinned", presented)))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &rustls::crypto::ring::default_provider().signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &rustls::crypto::ring::default_provider().signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        rustls::crypto::ring::default_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
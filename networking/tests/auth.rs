@@ -0,0 +1,343 @@
+use ecs::*;
+use networking::*;
+
+use std::net::SocketAddr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(NetSend, Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Chat(String);
+
+fn app_with(plugin: NetworkingPlugin) -> App {
+    let mut app = App::new();
+    app.add_plugin(plugin);
+    app
+}
+
+fn networking(app: &mut App) -> &'static mut Networking {
+    app.get_resource_mut::<Networking>()
+        .expect("Networking resource missing")
+}
+
+/// Runs every app once per millisecond until `done` holds, collecting their events on the way.
+fn pump(apps: &mut [&mut App], log: &mut [Vec<NetworkingEvent>], mut done: impl FnMut(&mut [&mut App], &[Vec<NetworkingEvent>]) -> bool) {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        for (app, log) in apps.iter_mut().zip(log.iter_mut()) {
+            app.run();
+            log.extend(networking(app).events().iter().filter(|e| !matches!(e, NetworkingEvent::RecvData { .. })).cloned());
+        }
+        if done(apps, log) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("never reached the expected state, saw {:?}", log);
+}
+
+fn rejected(log: &[NetworkingEvent]) -> Option<&str> {
+    log.iter().find_map(|e| match e {
+        NetworkingEvent::Rejected { reason, .. } => Some(reason.as_str()),
+        _ => None,
+    })
+}
+
+fn loopback_pair(hub: &LoopbackHub, key: &TokenKey, token: Option<ConnectToken>) -> (App, App, u32) {
+    let server = app_with(
+        NetworkingPlugin::server()
+            .with_transport(LoopbackTransport::server(hub))
+            .with_token_key(key.clone())
+            .with_auth_timeout(Duration::from_millis(100)),
+    );
+    let transport = LoopbackTransport::client(hub);
+    let id = transport.id();
+    let mut client = NetworkingPlugin::client().with_transport(transport);
+    if let Some(token) = token {
+        client = client.with_token(token);
+    }
+    (server, app_with(client), id)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn valid_tokens_let_players_in() {
+    let hub = LoopbackHub::new();
+    let key = TokenKey::generate();
+    let (mut server, mut client, id) = loopback_pair(&hub, &key, Some(key.issue(42, Duration::from_secs(30))));
+
+    let mut log = [Vec::new(), Vec::new()];
+    pump(&mut [&mut server, &mut client], &mut log, |_, log| {
+        log[0].contains(&NetworkingEvent::Connected { target: Target::Single(id) })
+            && log[1].contains(&NetworkingEvent::Connected { target: Target::Single(LOOPBACK_SERVER_ID) })
+    });
+    assert_eq!(networking(&mut server).player_id(id), Some(42));
+    assert_eq!(networking(&mut server).peers().collect::<Vec<_>>(), vec![id]);
+
+    networking(&mut client)
+        .send(Reliability::Reliable, Target::All, Chat("hi".to_string()))
+        .unwrap();
+    let mut received = Vec::new();
+    pump(&mut [&mut server, &mut client], &mut log, |apps, _| {
+        received.extend(networking(apps[0]).collect::<Chat>());
+        !received.is_empty()
+    });
+    assert_eq!(received, vec![(Target::Single(id), Chat("hi".to_string()))]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn servers_without_a_key_wave_tokens_through() {
+    let hub = LoopbackHub::new();
+    let mut server = app_with(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&hub)));
+    let token = TokenKey::generate().issue(3, Duration::from_secs(30));
+    let mut client = app_with(NetworkingPlugin::client().with_transport(LoopbackTransport::client(&hub)).with_token(token));
+
+    let mut log = [Vec::new(), Vec::new()];
+    pump(&mut [&mut server, &mut client], &mut log, |_, log| {
+        log[1].contains(&NetworkingEvent::Connected { target: Target::Single(LOOPBACK_SERVER_ID) })
+    });
+    assert!(rejected(&log[0]).is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn forged_and_expired_tokens_are_rejected_on_both_ends() {
+    let hub = LoopbackHub::new();
+    let key = TokenKey::generate();
+    let forged = TokenKey::generate().issue(1, Duration::from_secs(30));
+    let expired = key.issue(2, Duration::ZERO);
+
+    for (token, reason) in [(forged, "bad token signature"), (expired, "token expired")] {
+        let (mut server, mut client, id) = loopback_pair(&hub, &key, Some(token));
+        let mut log = [Vec::new(), Vec::new()];
+        pump(&mut [&mut server, &mut client], &mut log, |_, log| {
+            rejected(&log[0]).is_some() && rejected(&log[1]).is_some()
+        });
+        assert_eq!(log[0], vec![NetworkingEvent::Rejected { target: Target::Single(id), reason: reason.to_string() }]);
+        assert_eq!(rejected(&log[1]), Some(reason));
+
+        // the connection is dropped, and neither end ever saw it as connected
+        let rejected_at = Instant::now();
+        pump(&mut [&mut server, &mut client], &mut log, |_, _| rejected_at.elapsed() > Duration::from_millis(600));
+        assert_eq!(networking(&mut server).peers().count(), 0);
+        assert_eq!(networking(&mut client).peers().count(), 0);
+        assert!(log.iter().flatten().all(|e| matches!(e, NetworkingEvent::Rejected { .. })), "{:?}", log);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn peers_without_a_token_are_held_back_then_turned_away() {
+    let hub = LoopbackHub::new();
+    let key = TokenKey::generate();
+    let (mut server, mut client, id) = loopback_pair(&hub, &key, None);
+
+    let mut log = [Vec::new(), Vec::new()];
+    pump(&mut [&mut server, &mut client], &mut log, |_, log| {
+        log[1].contains(&NetworkingEvent::Connected { target: Target::Single(LOOPBACK_SERVER_ID) })
+    });
+    networking(&mut client)
+        .send(Reliability::Reliable, Target::All, Chat("let me in".to_string()))
+        .unwrap();
+
+    pump(&mut [&mut server, &mut client], &mut log, |_, log| rejected(&log[0]).is_some());
+    assert_eq!(rejected(&log[0]), Some("no connect token"));
+    assert!(networking(&mut server).collect::<Chat>().is_empty());
+    assert_eq!(networking(&mut server).player_id(id), None);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn broadcasts_skip_peers_still_held_back() {
+    let hub = LoopbackHub::new();
+    let key = TokenKey::generate();
+    let mut server = app_with(
+        NetworkingPlugin::server()
+            .with_transport(LoopbackTransport::server(&hub))
+            .with_token_key(key.clone()),
+    );
+    let transport = LoopbackTransport::client(&hub);
+    let player_id = transport.id();
+    let mut player = app_with(NetworkingPlugin::client().with_transport(transport).with_token(key.issue(1, Duration::from_secs(30))));
+    let mut lurker = app_with(NetworkingPlugin::client().with_transport(LoopbackTransport::client(&hub)));
+
+    let mut log = [Vec::new(), Vec::new(), Vec::new()];
+    pump(&mut [&mut server, &mut player, &mut lurker], &mut log, |_, log| {
+        log[0].contains(&NetworkingEvent::Connected { target: Target::Single(player_id) })
+            && log[2].contains(&NetworkingEvent::Connected { target: Target::Single(LOOPBACK_SERVER_ID) })
+    });
+    networking(&mut server)
+        .send(Reliability::Reliable, Target::All, Chat("players only".to_string()))
+        .unwrap();
+
+    let (mut received, mut overheard) = (Vec::new(), Vec::new());
+    pump(&mut [&mut server, &mut player, &mut lurker], &mut log, |apps, _| {
+        received.extend(networking(apps[1]).collect::<Chat>());
+        overheard.extend(networking(apps[2]).collect::<Chat>());
+        !received.is_empty()
+    });
+    // give a stray copy time to arrive
+    let sent_at = Instant::now();
+    pump(&mut [&mut server, &mut player, &mut lurker], &mut log, |apps, _| {
+        overheard.extend(networking(apps[2]).collect::<Chat>());
+        sent_at.elapsed() > Duration::from_millis(50)
+    });
+    assert_eq!(received, vec![(Target::Single(LOOPBACK_SERVER_ID), Chat("players only".to_string()))]);
+    assert!(overheard.is_empty(), "{:?}", overheard);
+    assert!(rejected(&log[0]).is_none(), "the lurker's wait shouldn't be over yet");
+}
+
+/// Lays out the same bytes as the crate's own `AuthAccepted`, which tests can't name.
+#[derive(NetSend, Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct ForgedAccept {
+    player_id: Option<u64>,
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn peers_cannot_accept_themselves() {
+    let hub = LoopbackHub::new();
+    let key = TokenKey::generate();
+    let (mut server, mut client, id) = loopback_pair(&hub, &key, None);
+
+    let mut log = [Vec::new(), Vec::new()];
+    pump(&mut [&mut server, &mut client], &mut log, |_, log| {
+        log[1].contains(&NetworkingEvent::Connected { target: Target::Single(LOOPBACK_SERVER_ID) })
+    });
+
+    // a tokenless client hands the server the verdict only a server should give
+    let accepted = (0..NAMES.len()).find(|&type_id| get_net_name(type_id) == Some("AuthAccepted")).unwrap();
+    let forged = ForgedAccept { player_id: None };
+    networking(&mut server).inject(NetworkingEvent::RecvData {
+        from: Target::Single(id),
+        reliability: Reliability::Reliable,
+        data: encode_packet([encode_frame(accepted, &forged.get_bytes()).as_slice()]),
+    });
+
+    pump(&mut [&mut server, &mut client], &mut log, |_, log| rejected(&log[0]).is_some());
+    assert_eq!(rejected(&log[0]), Some("no connect token"));
+    assert!(!log[0].contains(&NetworkingEvent::Connected { target: Target::Single(id) }), "{:?}", log[0]);
+    assert_eq!(networking(&mut server).peers().count(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn clients_give_up_on_servers_that_never_answer() {
+    let hub = LoopbackHub::new();
+    let key = TokenKey::generate();
+    // joins the hub, but nothing ever reads the token or answers it
+    let _silent = LoopbackTransport::server(&hub);
+    let mut client = app_with(
+        NetworkingPlugin::client()
+            .with_transport(LoopbackTransport::client(&hub))
+            .with_token(key.issue(1, Duration::from_secs(30)))
+            .with_auth_timeout(Duration::from_millis(100)),
+    );
+
+    let mut log = [Vec::new()];
+    pump(&mut [&mut client], &mut log, |_, log| rejected(&log[0]).is_some());
+    assert_eq!(rejected(&log[0]), Some("no answer to our connect token"));
+    assert_eq!(networking(&mut client).peers().count(), 0);
+    assert!(log[0].iter().all(|e| matches!(e, NetworkingEvent::Rejected { .. })), "{:?}", log[0]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn lobbies_hand_out_tokens_with_their_joins() {
+    let hub = LoopbackHub::new();
+    let key = TokenKey::generate();
+    let mut lobby = app_with(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&hub)));
+    lobby.add_plugin(LobbyPlugin::new().with_token_key(key.clone()));
+    let mut server = LobbyClient::with_transport(LoopbackTransport::client(&hub));
+    let player = LobbyClient::with_transport(LoopbackTransport::client(&hub));
+    server.register(ServerInfo {
+        name: "dm".to_string(),
+        addr: SocketAddr::from(([127, 0, 0, 1], 4000)),
+        game_mode: "deathmatch".to_string(),
+        players: 0,
+        max_players: 8,
+    });
+
+    let mut clients = [server, player];
+    let mut tickets = Vec::new();
+    let start = Instant::now();
+    while tickets.len() < 2 {
+        assert!(start.elapsed() < Duration::from_secs(5), "lobby never answered");
+        let pending = match clients[0].server_id() {
+            Some(id) if clients[1].is_connected() => Some(clients[1].join(id).unwrap()),
+            _ => None,
+        };
+        let Some(pending) = pending else {
+            lobby.run();
+            clients.iter_mut().for_each(LobbyClient::update);
+            thread::sleep(Duration::from_millis(1));
+            continue;
+        };
+        loop {
+            lobby.run();
+            clients.iter_mut().for_each(LobbyClient::update);
+            if let Some(ticket) = clients[1].poll(&pending) {
+                tickets.push(ticket.unwrap());
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    let ids: Vec<u64> = tickets
+        .iter()
+        .map(|ticket| match ticket {
+            JoinTicket::Accepted { token: Some(token), .. } => key.verify(token).unwrap(),
+            other => panic!("expected a token, got {:?}", other),
+        })
+        .collect();
+    // the same connection keeps its player id
+    assert_eq!(ids[0], ids[1]);
+}
+
+#[test]
+fn tokens_and_keys_survive_hex() {
+    let key = TokenKey::generate();
+    assert_eq!(TokenKey::from_hex(&key.to_hex()).unwrap().to_hex(), key.to_hex());
+    assert!(TokenKey::from_hex("abcd").is_err());
+
+    let token = key.issue(7, Duration::from_secs(60));
+    let parsed = ConnectToken::from_hex(&token.to_hex()).unwrap();
+    assert_eq!(parsed, token);
+    assert_eq!(key.verify(&parsed), Ok(7));
+    assert!(ConnectToken::from_hex("zz").is_err());
+}
+
+fn quic_pair(trust: ServerTrust, identity: ServerIdentity) -> (App, App) {
+    let transport = QuicTransport::server_with_identity("127.0.0.1:0".parse().unwrap(), identity).unwrap();
+    let addr = transport.local_addr().unwrap();
+    let server = app_with(NetworkingPlugin::server().with_transport(transport));
+    let client = app_with(NetworkingPlugin::client_with_trust(addr, trust));
+    (server, client)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn clients_only_connect_to_pinned_certificates() {
+    let identity = ServerIdentity::generate().unwrap();
+    let pinned = ServerTrust::Pinned(vec![identity.fingerprint().to_uppercase()]);
+    let (mut server, mut client) = quic_pair(pinned, identity.clone());
+    let mut log = [Vec::new(), Vec::new()];
+    pump(&mut [&mut server, &mut client], &mut log, |_, log| {
+        log[1].contains(&NetworkingEvent::Connected { target: Target::Single(QUIC_SERVER_ID) })
+    });
+
+    let other = ServerIdentity::generate().unwrap().fingerprint();
+    let (mut server, mut client) = quic_pair(ServerTrust::Pinned(vec![other]), identity.clone());
+    let mut log = [Vec::new(), Vec::new()];
+    pump(&mut [&mut server, &mut client], &mut log, |_, log| {
+        log[1].contains(&NetworkingEvent::Disconnected { target: Target::Single(QUIC_SERVER_ID) })
+    });
+    let reason = rejected(&log[1]).expect("no Rejected event");
+    assert!(reason.contains(&identity.fingerprint()), "{}", reason);
+    assert_eq!(networking(&mut client).peers().count(), 0);
+}
+
+#[test]
+fn identities_round_trip_through_files() {
+    let identity = ServerIdentity::generate().unwrap();
+    let dir = std::env::temp_dir();
+    let cert = dir.join(format!("identity-{}.der", std::process::id()));
+    let key = dir.join(format!("identity-{}.key", std::process::id()));
+    identity.save(&cert, &key).unwrap();
+    let loaded = ServerIdentity::load(&cert, &key).unwrap();
+    std::fs::remove_file(cert).unwrap();
+    std::fs::remove_file(key).unwrap();
+    assert_eq!(loaded.fingerprint(), identity.fingerprint());
+}
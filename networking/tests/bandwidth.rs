@@ -0,0 +1,245 @@
+use ecs::*;
+use networking::*;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use std::thread;
+use std::time::Duration;
+
+#[replicate]
+#[derive(Component, Serialize, Deserialize, Debug, PartialEq)]
+struct Stats {
+    health: u32,
+    mana: u32,
+    armor: u32,
+    title: String,
+}
+
+#[replicate]
+#[derive(Component, Serialize, Deserialize, Debug, PartialEq)]
+struct Blob(Vec<u8>);
+
+#[derive(Component, Debug)]
+struct Hidden;
+
+fn server_app(hub: &LoopbackHub) -> App {
+    let mut app = App::new();
+    app.add_plugin(NetworkingPlugin::server().with_transport(LoopbackTransport::server(hub)));
+    app.add_plugin(ReplicationPlugin::server());
+    app
+}
+
+fn client_app(hub: &LoopbackHub) -> App {
+    let mut app = App::new();
+    app.add_plugin(NetworkingPlugin::client().with_transport(LoopbackTransport::client(hub)));
+    app.add_plugin(ReplicationPlugin::client());
+    app
+}
+
+fn pump_until(server: &mut App, client: &mut App, mut done: impl FnMut(&mut App) -> bool) {
+    for _ in 0..2000 {
+        server.run();
+        client.run();
+        if done(client) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("client never reached the expected state");
+}
+
+fn mirrored(client: &mut App, server_entity: u32) -> Option<&'static Entity> {
+    let local = client
+        .get_resource::<ReplicationClient>()?
+        .local_entity(server_entity)?;
+    client.get_entity(local)
+}
+
+fn stats(health: u32) -> Stats {
+    Stats {
+        health,
+        mana: 50,
+        armor: 7,
+        title: "the long-winded".to_string(),
+    }
+}
+
+#[test]
+fn deltas_round_trip_and_shrink_small_changes() {
+    let base = bincode::serde::encode_to_vec(stats(100), bincode::config::standard()).unwrap();
+    let next = bincode::serde::encode_to_vec(stats(99), bincode::config::standard()).unwrap();
+
+    let delta = delta_encode(&base, &next);
+    assert!(delta.len() < next.len() / 2, "{} vs {}", delta.len(), next.len());
+    assert_eq!(delta_decode(&base, &delta).unwrap(), next);
+
+    // lengths may differ in either direction
+    assert_eq!(delta_decode(&next, &delta_encode(&next, b"ab")).unwrap(), b"ab");
+    assert_eq!(delta_decode(b"ab", &delta_encode(b"ab", &next)).unwrap(), next);
+}
+
+#[test]
+fn corrupt_deltas_are_rejected_without_panicking() {
+    let mut rng = StdRng::seed_from_u64(0xDE17A);
+    for _ in 0..20_000 {
+        let len = rng.random_range(0..32);
+        let delta: Vec<u8> = (0..len).map(|_| rng.random()).collect();
+        let _ = delta_decode(b"baseline", &delta);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn budget_sends_high_priority_entities_first() {
+    let hub = LoopbackHub::new();
+    let mut server = server_app(&hub);
+    server.get_resource_mut::<ReplicationServer>().unwrap().budget = 600;
+
+    // each blob takes most of a tick's budget on its own
+    let mut blobs = Vec::new();
+    for i in 0..4u8 {
+        let entity = server.spawn_entity();
+        server.add_component(entity, Replicated).unwrap();
+        server.add_component(entity, Blob(vec![i; 500])).unwrap();
+        blobs.push(entity);
+    }
+    server.add_component(blobs[3], NetPriority(10.0)).unwrap();
+
+    let mut client = client_app(&hub);
+    pump_until(&mut server, &mut client, |client| mirrored(client, blobs[3]).is_some());
+    let arrived = blobs.iter().filter(|&&b| mirrored(&mut client, b).is_some()).count();
+    assert_eq!(arrived, 1, "the budget should only fit one blob per update");
+
+    // the rest trickle in over the following ticks
+    pump_until(&mut server, &mut client, |client| {
+        blobs.iter().all(|&b| mirrored(client, b).is_some())
+    });
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn partial_snapshots_never_despawn_what_they_left_out() {
+    // lost acknowledgements keep the server sending snapshots without a baseline
+    let hub = LoopbackHub::new()
+        .with_conditions(LinkConditions {
+            loss: 0.5,
+            ..Default::default()
+        })
+        .with_seed(5);
+    let mut server = server_app(&hub);
+    server.get_resource_mut::<ReplicationServer>().unwrap().budget = 600;
+
+    let mut blobs = Vec::new();
+    for i in 0..4u8 {
+        let entity = server.spawn_entity();
+        server.add_component(entity, Replicated).unwrap();
+        server.add_component(entity, Blob(vec![i; 500])).unwrap();
+        blobs.push(entity);
+    }
+
+    let mut client = client_app(&hub);
+    let mut seen = Vec::new();
+    pump_until(&mut server, &mut client, |client| {
+        for &blob in &blobs {
+            let mirrored = mirrored(client, blob).is_some();
+            assert!(mirrored || !seen.contains(&blob), "blob {} flickered out", blob);
+            if mirrored && !seen.contains(&blob) {
+                seen.push(blob);
+            }
+        }
+        seen.len() == blobs.len()
+    });
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn irrelevant_entities_are_withheld() {
+    let hub = LoopbackHub::new();
+    let mut server = server_app(&hub);
+    server
+        .get_resource_mut::<ReplicationServer>()
+        .unwrap()
+        .set_relevance(|commands, _peer, entity| {
+            let hidden = commands
+                .get_entity(entity)
+                .is_some_and(|e| e.has_component::<Hidden>());
+            if hidden { 0.0 } else { 1.0 }
+        });
+
+    let visible = server.spawn_entity();
+    server.add_component(visible, Replicated).unwrap();
+    server.add_component(visible, stats(1)).unwrap();
+    let secret = server.spawn_entity();
+    server.add_component(secret, Replicated).unwrap();
+    server.add_component(secret, stats(2)).unwrap();
+    server.add_component(secret, Hidden).unwrap();
+
+    let mut client = client_app(&hub);
+    pump_until(&mut server, &mut client, |client| mirrored(client, visible).is_some());
+    assert!(mirrored(&mut client, secret).is_none());
+
+    // becoming irrelevant despawns the mirror
+    server.add_component(visible, Hidden).unwrap();
+    pump_until(&mut server, &mut client, |client| mirrored(client, visible).is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn lost_updates_are_recovered_from_the_acked_baseline() {
+    let hub = LoopbackHub::new()
+        .with_conditions(LinkConditions {
+            latency: Duration::from_millis(2),
+            jitter: Duration::from_millis(4),
+            loss: 0.4,
+            reorder: 0.2,
+        })
+        .with_seed(11);
+    let mut server = server_app(&hub);
+    let entity = server.spawn_entity();
+    server.add_component(entity, Replicated).unwrap();
+    server.add_component(entity, stats(0)).unwrap();
+
+    let mut client = client_app(&hub);
+    for health in 1..=200 {
+        for (id, stats) in unsafe { World::get_components_mut::<Stats>(server.world) } {
+            if id == entity {
+                stats.health = health;
+            }
+        }
+        server.run();
+        client.run();
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    pump_until(&mut server, &mut client, |client| {
+        mirrored(client, entity).and_then(|e| e.get_component::<Stats>()) == Some(&stats(200))
+    });
+    assert!(server.get_resource::<ReplicationServer>().unwrap().acked(1).is_some());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn network_stats_count_bytes_per_peer() {
+    let hub = LoopbackHub::new();
+    let mut server = server_app(&hub);
+    let entity = server.spawn_entity();
+    server.add_component(entity, Replicated).unwrap();
+    // compressible, so the stats have to reflect the compressed size
+    server.add_component(entity, Blob(vec![0; 4000])).unwrap();
+    server.get_resource_mut::<ReplicationServer>().unwrap().budget = 8192;
+
+    let mut client = client_app(&hub);
+    pump_until(&mut server, &mut client, |client| mirrored(client, entity).is_some());
+    // the client's acknowledgements are what the server counts as incoming
+    for _ in 0..2000 {
+        if server.get_resource::<NetworkStats>().unwrap().bytes_in() > 0 {
+            break;
+        }
+        pump_until(&mut server, &mut client, |_| true);
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    let server_stats = server.get_resource::<NetworkStats>().unwrap().clone();
+    let client_stats = client.get_resource::<NetworkStats>().unwrap().clone();
+
+    let to_client = server_stats.peer(1).expect("server should track the client");
+    let from_server = client_stats.peer(LOOPBACK_SERVER_ID).expect("client should track the server");
+    assert!(to_client.bytes_out > 0 && to_client.bytes_in > 0);
+    assert!(to_client.bytes_out < 4000, "blob went out uncompressed: {:?}", to_client);
+    assert!(from_server.bytes_in > 0 && from_server.bytes_out > 0);
+    assert_eq!(server_stats.bytes_out(), to_client.bytes_out);
+}
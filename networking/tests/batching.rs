@@ -0,0 +1,213 @@
+use ecs::*;
+use networking::*;
+
+use std::thread;
+use std::time::Duration;
+
+#[derive(NetSend, Serialize, Deserialize, Debug, PartialEq)]
+struct Chat(u32);
+
+#[derive(NetSend, Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Level {
+    name: String,
+    tiles: Vec<u32>,
+}
+
+fn app_with(plugin: NetworkingPlugin) -> App {
+    let mut app = App::new();
+    app.add_plugin(plugin);
+    app
+}
+
+fn networking(app: &mut App) -> &'static mut Networking {
+    app.get_resource_mut::<Networking>()
+        .expect("Networking resource missing")
+}
+
+fn pump(apps: &mut [&mut App], mut done: impl FnMut(&mut [&mut App]) -> bool) {
+    for _ in 0..2000 {
+        for app in apps.iter_mut() {
+            app.run();
+        }
+        if done(apps) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("apps never reached the expected state");
+}
+
+fn connected(hub: &LoopbackHub) -> (App, App) {
+    let mut server = app_with(NetworkingPlugin::server().with_transport(LoopbackTransport::server(hub)));
+    let mut client = app_with(NetworkingPlugin::client().with_transport(LoopbackTransport::client(hub)));
+    pump(&mut [&mut server, &mut client], |apps| networking(apps[0]).peers().count() == 1);
+    (server, client)
+}
+
+/// Level data that doesn't compress well, so it really has to be fragmented.
+fn level(tiles: usize) -> Level {
+    let mut seed = 0x2545_f491u32;
+    Level {
+        name: "catacombs".to_string(),
+        tiles: (0..tiles)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                seed
+            })
+            .collect(),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn small_messages_share_one_packet_per_tick() {
+    // a link this jittery would scramble separate datagrams
+    let hub = LoopbackHub::new()
+        .with_conditions(LinkConditions {
+            latency: Duration::from_millis(1),
+            jitter: Duration::from_millis(5),
+            loss: 0.0,
+            reorder: 0.5,
+        })
+        .with_seed(3);
+    let (mut server, mut client) = connected(&hub);
+
+    for i in 0..50 {
+        networking(&mut server).send(Reliability::Unreliable, Target::All, Chat(i)).unwrap();
+    }
+
+    let mut received = Vec::new();
+    pump(&mut [&mut server, &mut client], |apps| {
+        received = networking(apps[1]).collect::<Chat>();
+        !received.is_empty()
+    });
+    let received: Vec<u32> = received.into_iter().map(|(_, chat)| chat.0).collect();
+    assert_eq!(received, (0..50).collect::<Vec<_>>(), "all 50 should arrive together, in order");
+
+    let stats = networking(&mut client).stats();
+    assert_eq!(stats.peer(LOOPBACK_SERVER_ID).unwrap().frames_in, 50);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn large_reliable_messages_are_fragmented_and_reassembled() {
+    let hub = LoopbackHub::new().with_conditions(LinkConditions {
+        latency: Duration::from_millis(1),
+        jitter: Duration::from_millis(3),
+        loss: 0.2,
+        reorder: 0.2,
+    });
+    let (mut server, mut client) = connected(&hub);
+
+    let sent = level(50_000);
+    networking(&mut server).send(Reliability::Reliable, Target::All, sent.clone()).unwrap();
+
+    let mut received = None;
+    pump(&mut [&mut server, &mut client], |apps| {
+        received = received.take().or_else(|| networking(apps[1]).next::<Level>());
+        received.is_some()
+    });
+    assert_eq!(received.unwrap().1, sent);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn large_unreliable_messages_arrive_over_a_clean_link() {
+    let hub = LoopbackHub::new();
+    let (mut server, mut client) = connected(&hub);
+
+    let sent = level(2_000);
+    networking(&mut client).send(Reliability::Unreliable, Target::Single(LOOPBACK_SERVER_ID), sent.clone()).unwrap();
+
+    let mut received = None;
+    pump(&mut [&mut server, &mut client], |apps| {
+        received = received.take().or_else(|| networking(apps[0]).next::<Level>());
+        received.is_some()
+    });
+    assert_eq!(received.unwrap(), (Target::Single(1), sent));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn max_message_size_is_enforced_on_receive() {
+    let hub = LoopbackHub::new();
+    let mut server = app_with(
+        NetworkingPlugin::server()
+            .with_transport(LoopbackTransport::server(&hub))
+            .with_max_message_size(16 * 1024),
+    );
+    let mut client = app_with(NetworkingPlugin::client().with_transport(LoopbackTransport::client(&hub)));
+    pump(&mut [&mut server, &mut client], |apps| networking(apps[0]).peers().count() == 1);
+
+    // the client has no such limit, so it sends happily
+    networking(&mut client).send(Reliability::Reliable, Target::All, level(10_000)).unwrap();
+    networking(&mut client).send(Reliability::Reliable, Target::All, level(100)).unwrap();
+
+    let mut received = None;
+    pump(&mut [&mut server, &mut client], |apps| {
+        received = received.take().or_else(|| networking(apps[0]).next::<Level>());
+        received.is_some()
+    });
+    assert_eq!(received.unwrap().1.tiles.len(), 100, "the oversized level should be dropped");
+
+    // a forged fragment claiming an enormous message is refused before anything is buffered
+    let forged = Fragment {
+        message: 7,
+        index: 0,
+        count: u32::MAX,
+        bytes: vec![0; 16],
+    };
+    let frame = encode_frame(forged.get_type_id(), &forged.get_bytes());
+    let server_net = networking(&mut server);
+    server_net.inject(NetworkingEvent::RecvData {
+        from: Target::Single(1),
+        reliability: Reliability::Reliable,
+        data: encode_packet([frame.as_slice()]),
+    });
+    assert!(server_net.next::<Level>().is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn half_sent_messages_are_capped_per_peer() {
+    let hub = LoopbackHub::new();
+    let (mut server, _client) = connected(&hub);
+    let server_net = networking(&mut server);
+
+    let frame = encode_frame(get_net_id::<Level>(), &level(1000).get_bytes());
+    let count = frame.len().div_ceil(FRAGMENT_SIZE) as u32;
+    let fragment = |message: u32, index: u32| {
+        let start = index as usize * FRAGMENT_SIZE;
+        let bytes = frame[start..frame.len().min(start + FRAGMENT_SIZE)].to_vec();
+        let fragment = Fragment { message, index, count, bytes };
+        NetworkingEvent::RecvData {
+            from: Target::Single(1),
+            reliability: Reliability::Reliable,
+            data: encode_packet([encode_frame(fragment.get_type_id(), &fragment.get_bytes()).as_slice()]),
+        }
+    };
+    let deliver_rest = |server_net: &mut Networking, message: u32| {
+        for index in 1..count {
+            server_net.inject(fragment(message, index));
+        }
+    };
+
+    // a peer starting message after message only keeps its newest few
+    for message in 0..1000 {
+        server_net.inject(fragment(message, 0));
+    }
+    deliver_rest(server_net, 0);
+    assert!(server_net.next::<Level>().is_none(), "the oldest message should have been dropped");
+    deliver_rest(server_net, 999);
+    assert_eq!(server_net.next::<Level>().unwrap().1, level(1000));
+}
+
+#[test]
+fn packets_reject_truncated_frames() {
+    let frame = encode_frame(get_net_id::<Chat>(), &Chat(3).get_bytes());
+    let packet = encode_packet([frame.as_slice(), frame.as_slice()]);
+    assert_eq!(decode_packet(&packet).unwrap(), vec![frame.as_slice(); 2]);
+
+    assert!(matches!(
+        decode_packet(&packet[..packet.len() - 1]),
+        Err(NetError::Truncated { .. })
+    ));
+    assert!(matches!(decode_packet(&[0xff; 12]), Err(NetError::Truncated { .. })));
+}
@@ -0,0 +1,154 @@
+use ecs::*;
+use networking::*;
+
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(NetSend, Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Chat(String);
+
+#[derive(NetSend, Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Seq(u32);
+
+fn app_with(plugin: NetworkingPlugin) -> App {
+    let mut app = App::new();
+    app.add_plugin(plugin);
+    app
+}
+
+fn networking(app: &mut App) -> &'static mut Networking {
+    app.get_resource_mut::<Networking>()
+        .expect("Networking resource missing")
+}
+
+fn pump(apps: &mut [&mut App], timeout: Duration, mut done: impl FnMut(&mut [&mut App]) -> bool) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        for app in apps.iter_mut() {
+            app.run();
+        }
+        if done(apps) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    false
+}
+
+fn capture_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{}-{}.netcap", name, std::process::id()))
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn captures_record_both_directions_and_replay() {
+    let path = capture_path("both-directions");
+    let hub = LoopbackHub::new();
+    let mut server = app_with(
+        NetworkingPlugin::server()
+            .with_transport(LoopbackTransport::server(&hub))
+            .with_capture(&path),
+    );
+    let mut client = app_with(NetworkingPlugin::client().with_transport(LoopbackTransport::client(&hub)));
+
+    networking(&mut client).send(Reliability::Reliable, Target::All, Chat("hello".into())).unwrap();
+    networking(&mut client).send(Reliability::Unreliable, Target::All, Seq(3)).unwrap();
+    let mut received = 0;
+    assert!(pump(&mut [&mut server, &mut client], Duration::from_secs(2), |apps| {
+        received += networking(apps[0]).collect::<Chat>().len() + networking(apps[0]).collect::<Seq>().len();
+        received == 2
+    }));
+    networking(&mut server).send(Reliability::Reliable, Target::Single(1), Chat("welcome".into())).unwrap();
+    server.run();
+    networking(&mut server).stop_capture();
+
+    let records = read_capture(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(records.len(), 3);
+
+    let chat = &records[0];
+    assert_eq!(chat.direction, Direction::In);
+    assert_eq!(chat.peer, Target::Single(1));
+    assert_eq!(chat.reliability, Reliability::Reliable);
+    assert_eq!(chat.type_name, "Chat");
+    assert_eq!(chat.size, chat.frame.len());
+    assert_eq!(records[1].type_name, "Seq");
+    assert_eq!(records[1].reliability, Reliability::Unreliable);
+    assert_eq!((records[2].direction, records[2].peer), (Direction::Out, Target::Single(1)));
+
+    for record in &records {
+        assert_eq!(&CaptureRecord::parse(&record.to_line()).unwrap(), record);
+    }
+
+    // replaying into a fresh app reproduces what the server received
+    let mut replayed = app_with(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&LoopbackHub::new())));
+    let replay_net = networking(&mut replayed);
+    replay_net.replay(records);
+    assert_eq!(replay_net.collect::<Chat>(), vec![(Target::Single(1), Chat("hello".into()))]);
+    assert_eq!(replay_net.collect::<Seq>(), vec![(Target::Single(1), Seq(3))]);
+}
+
+#[test]
+fn malformed_capture_lines_are_rejected() {
+    assert!(CaptureRecord::parse("12 in 1 reliable Chat 5").is_err());
+    assert!(CaptureRecord::parse("12 sideways 1 reliable Chat 5 00").is_err());
+    assert!(CaptureRecord::parse("12 in 1 reliable Chat 5 0g").is_err());
+    assert!(CaptureRecord::parse("12 in 1 reliable Chat 5 abc").is_err());
+
+    let record = CaptureRecord::parse("12 out group:4 unreliable Chat 5 00ff").unwrap();
+    assert_eq!(record.peer, Target::Group(GroupId(4)));
+    assert_eq!(record.frame, vec![0x00, 0xff]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn fault_injection_delays_drops_and_duplicates() {
+    let hub = LoopbackHub::new();
+    let mut server = app_with(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&hub)));
+    let mut client = app_with(
+        NetworkingPlugin::client()
+            .with_transport(LoopbackTransport::client(&hub))
+            .with_faults(FaultInjection {
+                latency: Duration::from_millis(30),
+                ..Default::default()
+            }),
+    );
+
+    let sent = Instant::now();
+    networking(&mut client).send(Reliability::Reliable, Target::All, Chat("late".into())).unwrap();
+    assert!(pump(&mut [&mut server, &mut client], Duration::from_secs(2), |apps| {
+        !networking(apps[0]).collect::<Chat>().is_empty()
+    }));
+    assert!(sent.elapsed() >= Duration::from_millis(30));
+
+    // one packet per tick so each message is dropped or duplicated on its own
+    networking(&mut client).set_faults(Some(FaultInjection {
+        loss: 0.3,
+        duplication: 0.3,
+        ..Default::default()
+    }));
+    for i in 0..300 {
+        networking(&mut client).send(Reliability::Unreliable, Target::All, Seq(i)).unwrap();
+        networking(&mut client).send(Reliability::Reliable, Target::All, Chat(i.to_string())).unwrap();
+        client.run();
+        server.run();
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    let mut unreliable = Vec::new();
+    let mut reliable = Vec::new();
+    assert!(pump(&mut [&mut server, &mut client], Duration::from_secs(2), |apps| {
+        unreliable.extend(networking(apps[0]).collect::<Seq>().into_iter().map(|(_, s)| s.0));
+        reliable.extend(networking(apps[0]).collect::<Chat>().into_iter().map(|(_, c)| c.0));
+        reliable.len() == 300
+    }));
+    // give unreliable stragglers time to land
+    pump(&mut [&mut server, &mut client], Duration::from_millis(20), |apps| {
+        unreliable.extend(networking(apps[0]).collect::<Seq>().into_iter().map(|(_, s)| s.0));
+        false
+    });
+
+    assert_eq!(reliable, (0..300).map(|i| i.to_string()).collect::<Vec<_>>());
+    let distinct: std::collections::BTreeSet<u32> = unreliable.iter().copied().collect();
+    assert!(distinct.len() > 150 && distinct.len() < 270, "{} distinct", distinct.len());
+    assert!(unreliable.len() > distinct.len() + 25, "{} duplicates", unreliable.len() - distinct.len());
+}
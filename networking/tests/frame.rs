@@ -0,0 +1,124 @@
+use ecs::*;
+use networking::*;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+#[derive(NetSend, Serialize, Deserialize, Debug, PartialEq)]
+struct Ping {
+    seq: u32,
+    note: String,
+}
+
+// Only registered so the fuzz test also hits a sequence decoder.
+#[derive(NetSend, Serialize, Deserialize, Debug, PartialEq)]
+struct Positions(Vec<(f32, f32)>);
+
+fn networking(app: &mut App) -> &'static mut Networking {
+    app.get_resource_mut::<Networking>()
+        .expect("Networking resource missing")
+}
+
+#[test]
+fn frames_round_trip_through_decode() {
+    let ping = Ping {
+        seq: 7,
+        note: "hello".to_string(),
+    };
+    let frame = encode_frame(ping.get_type_id(), &ping.get_bytes());
+
+    let (type_id, obj) = decode_frame(&frame).expect("frame should decode");
+    assert_eq!(type_id, get_net_id::<Ping>());
+    assert_eq!(*obj.downcast::<Ping>().unwrap(), ping);
+}
+
+#[test]
+fn malformed_frames_are_rejected_with_typed_errors() {
+    assert_eq!(
+        split_frame(&[1, 2]).unwrap_err(),
+        NetError::Truncated { len: 2 }
+    );
+
+    let unknown = encode_frame(0x00ff_ffff, &[]);
+    assert_eq!(
+        split_frame(&unknown).unwrap_err(),
+        NetError::UnknownType { type_id: 0x00ff_ffff }
+    );
+
+    let truncated_payload = encode_frame(get_net_id::<Ping>(), &[200]);
+    assert!(matches!(
+        decode_frame(&truncated_payload),
+        Err(NetError::Decode { .. })
+    ));
+}
+
+#[test]
+fn large_payloads_are_compressed_when_it_helps() {
+    let positions = Positions(vec![(1.0, 2.0); 512]);
+    let plain = encode_frame(positions.get_type_id(), &positions.get_bytes());
+    let packed = encode_frame_with(positions.get_type_id(), &positions.get_bytes(), Compression::Lz4);
+    assert!(packed.len() * 4 < plain.len(), "{} vs {}", packed.len(), plain.len());
+    assert!(split_frame(&packed).unwrap().0.compressed);
+
+    let (_, obj) = decode_frame(&packed).expect("compressed frame should decode");
+    assert_eq!(*obj.downcast::<Positions>().unwrap(), positions);
+
+    let ping = Ping {
+        seq: 1,
+        note: "short".to_string(),
+    };
+    let small = encode_frame_with(ping.get_type_id(), &ping.get_bytes(), Compression::Lz4);
+    assert!(!split_frame(&small).unwrap().0.compressed);
+}
+
+#[test]
+fn compressed_frames_cannot_claim_huge_sizes() {
+    let mut forged = (get_net_id::<Positions>() as u32 | COMPRESSED_FLAG).to_le_bytes().to_vec();
+    forged.extend_from_slice(&u32::MAX.to_le_bytes());
+    forged.extend_from_slice(&[0x10, 0x00]);
+
+    assert!(matches!(decode_frame(&forged), Err(NetError::Decode { .. })));
+}
+
+#[tokio::test]
+async fn serialize_recv_survives_random_bytes() {
+    let hub = LoopbackHub::new();
+    let mut app = App::new();
+    app.add_plugin(NetworkingPlugin::client().with_transport(LoopbackTransport::client(&hub)));
+    let networking = networking(&mut app);
+
+    let mut rng = StdRng::seed_from_u64(0xF022);
+    let type_count = FROM_BYTES.len();
+    for i in 0..20_000 {
+        let len = rng.random_range(0..64);
+        let mut data: Vec<u8> = (0..len).map(|_| rng.random()).collect();
+
+        // aim half of the inputs at registered ids, in a well-formed packet, so the payload
+        // decoders get exercised
+        if i % 2 == 0 && data.len() >= HEADER_LEN {
+            let type_id = rng.random_range(0..type_count) as u32;
+            data[..HEADER_LEN].copy_from_slice(&type_id.to_le_bytes());
+            if i % 4 == 0 {
+                data[HEADER_LEN - 1] |= (COMPRESSED_FLAG >> 24) as u8;
+            }
+            data = encode_packet([data.as_slice()]);
+        }
+
+        networking.inject(NetworkingEvent::RecvData {
+            from: Target::Single(1),
+            reliability: Reliability::Unreliable,
+            data,
+        });
+    }
+
+    let valid = Ping {
+        seq: 1,
+        note: "still alive".to_string(),
+    };
+    networking.inject(NetworkingEvent::RecvData {
+        from: Target::Single(2),
+        reliability: Reliability::Reliable,
+        data: encode_packet([encode_frame(valid.get_type_id(), &valid.get_bytes()).as_slice()]),
+    });
+
+    let received = networking.collect::<Ping>();
+    assert_eq!(received.last(), Some(&(Target::Single(2), valid)));
+}
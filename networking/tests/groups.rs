@@ -0,0 +1,110 @@
+use ecs::*;
+use networking::*;
+
+use std::thread;
+use std::time::Duration;
+
+#[derive(NetSend, Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Chat(String);
+
+const LOBBY: GroupId = GroupId(1);
+const ARENA: GroupId = GroupId(2);
+
+fn app_with(plugin: NetworkingPlugin) -> App {
+    let mut app = App::new();
+    app.add_plugin(plugin);
+    app
+}
+
+fn networking(app: &mut App) -> &'static mut Networking {
+    app.get_resource_mut::<Networking>()
+        .expect("Networking resource missing")
+}
+
+fn pump(apps: &mut [&mut App], mut done: impl FnMut(&mut [&mut App]) -> bool) {
+    for _ in 0..2000 {
+        for app in apps.iter_mut() {
+            app.run();
+        }
+        if done(apps) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("apps never reached the expected state");
+}
+
+/// A server and three clients, with the client ids as the server sees them.
+fn connected(hub: &LoopbackHub) -> (App, Vec<(App, u32)>) {
+    let mut server = app_with(NetworkingPlugin::server().with_transport(LoopbackTransport::server(hub)));
+    let mut clients: Vec<(App, u32)> = (0..3)
+        .map(|_| {
+            let transport = LoopbackTransport::client(hub);
+            let id = transport.id();
+            (app_with(NetworkingPlugin::client().with_transport(transport)), id)
+        })
+        .collect();
+
+    let [(a, _), (b, _), (c, _)] = &mut clients[..] else { unreachable!() };
+    pump(&mut [&mut server, a, b, c], |apps| networking(apps[0]).peers().count() == 3);
+    (server, clients)
+}
+
+/// Pumps a few frames and returns what each client received.
+fn deliver(server: &mut App, clients: &mut [(App, u32)]) -> Vec<Vec<String>> {
+    let mut inboxes = vec![Vec::new(); clients.len()];
+    let [(a, _), (b, _), (c, _)] = clients else { unreachable!() };
+    let mut frames = 0;
+    pump(&mut [server, a, b, c], |apps| {
+        for (inbox, app) in inboxes.iter_mut().zip(apps[1..].iter_mut()) {
+            inbox.extend(networking(app).collect::<Chat>().into_iter().map(|(_, chat)| chat.0));
+        }
+        frames += 1;
+        frames == 20
+    });
+    inboxes
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn groups_and_exclusions_pick_the_right_peers() {
+    let hub = LoopbackHub::new();
+    let (mut server, mut clients) = connected(&hub);
+    let ids: Vec<u32> = clients.iter().map(|(_, id)| *id).collect();
+
+    let server_net = networking(&mut server);
+    server_net.join_group(ids[0], LOBBY);
+    server_net.join_group(ids[1], LOBBY);
+    server_net.join_group(ids[2], ARENA);
+    assert_eq!(server_net.group_members(LOBBY).collect::<Vec<_>>(), vec![ids[0], ids[1]]);
+    assert_eq!(server_net.groups_of(ids[2]).collect::<Vec<_>>(), vec![ARENA]);
+
+    server_net.send(Reliability::Reliable, Target::Group(LOBBY), Chat("lobby".into())).unwrap();
+    server_net.send(Reliability::Reliable, Target::AllExcept(ids[1]), Chat("not b".into())).unwrap();
+    let inboxes = deliver(&mut server, &mut clients);
+    assert_eq!(inboxes[0], vec!["lobby", "not b"]);
+    assert_eq!(inboxes[1], vec!["lobby"]);
+    assert_eq!(inboxes[2], vec!["not b"]);
+
+    // leaving a group stops its traffic
+    let server_net = networking(&mut server);
+    server_net.leave_group(ids[0], LOBBY);
+    server_net.send(Reliability::Reliable, Target::Group(LOBBY), Chat("lobby again".into())).unwrap();
+    server_net.send(Reliability::Reliable, Target::Group(GroupId(99)), Chat("nobody".into())).unwrap();
+    let inboxes = deliver(&mut server, &mut clients);
+    assert_eq!(inboxes, vec![Vec::<String>::new(), vec!["lobby again".to_string()], Vec::new()]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn disconnected_peers_leave_their_groups() {
+    let hub = LoopbackHub::new();
+    let (mut server, mut clients) = connected(&hub);
+    let ids: Vec<u32> = clients.iter().map(|(_, id)| *id).collect();
+
+    networking(&mut server).join_group(ids[0], ARENA);
+    networking(&mut server).join_group(ids[1], ARENA);
+
+    drop(clients.remove(0));
+    let [(b, _), (c, _)] = &mut clients[..] else { unreachable!() };
+    pump(&mut [&mut server, b, c], |apps| networking(apps[0]).peers().count() == 2);
+    assert_eq!(networking(&mut server).group_members(ARENA).collect::<Vec<_>>(), vec![ids[1]]);
+}
@@ -0,0 +1,139 @@
+use ecs::*;
+use networking::*;
+
+use std::net::SocketAddr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn lobby_app(transport: impl Transport, timeout: Duration) -> App {
+    let mut app = App::new();
+    app.add_plugin(NetworkingPlugin::server().with_transport(transport));
+    app.add_plugin(LobbyPlugin::new().with_timeout(timeout));
+    app
+}
+
+fn info(name: &str, port: u16, game_mode: &str, players: u32, max_players: u32) -> ServerInfo {
+    ServerInfo {
+        name: name.to_string(),
+        addr: SocketAddr::from(([127, 0, 0, 1], port)),
+        game_mode: game_mode.to_string(),
+        players,
+        max_players,
+    }
+}
+
+/// Runs the lobby and every client until `done` holds.
+fn pump(lobby: &mut App, clients: &mut [&mut LobbyClient], mut done: impl FnMut(&mut App, &mut [&mut LobbyClient]) -> bool) {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        lobby.run();
+        for client in clients.iter_mut() {
+            client.update();
+        }
+        if done(lobby, clients) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("lobby never reached the expected state");
+}
+
+/// Sends a request from `client` and polls it until it resolves.
+fn wait<R: NetSend>(
+    lobby: &mut App,
+    client: &mut LobbyClient,
+    request: impl FnOnce(&LobbyClient) -> Result<PendingRequest<R>, NetError>,
+) -> R {
+    let pending = request(client).unwrap();
+    let mut result = None;
+    pump(lobby, &mut [client], |_, clients| {
+        result = clients[0].poll(&pending);
+        result.is_some()
+    });
+    result.unwrap().unwrap()
+}
+
+fn listed(lobby: &mut App) -> Vec<(ServerId, ServerInfo)> {
+    let lobby = lobby.get_resource::<Lobby>().unwrap();
+    lobby.servers().map(|(id, info)| (id, info.clone())).collect()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn servers_register_and_players_list_and_join_them() {
+    let hub = LoopbackHub::new();
+    let mut lobby = lobby_app(LoopbackTransport::server(&hub), DEFAULT_LISTING_TIMEOUT);
+    let mut deathmatch = LobbyClient::with_transport(LoopbackTransport::client(&hub));
+    let mut full = LobbyClient::with_transport(LoopbackTransport::client(&hub));
+    let mut player = LobbyClient::with_transport(LoopbackTransport::client(&hub));
+
+    deathmatch.register(info("dm", 4000, "deathmatch", 3, 8));
+    full.register(info("ctf", 4001, "ctf", 8, 8));
+    pump(&mut lobby, &mut [&mut deathmatch, &mut full, &mut player], |_, clients| {
+        clients[0].server_id().is_some() && clients[1].server_id().is_some() && clients[2].is_connected()
+    });
+    let dm_id = deathmatch.server_id().unwrap();
+    let full_id = full.server_id().unwrap();
+
+    let all = wait(&mut lobby, &mut player, |p| p.list(None));
+    assert_eq!(all.0.len(), 2);
+    let ctf = wait(&mut lobby, &mut player, |p| p.list(Some("ctf")));
+    assert_eq!(ctf.0, vec![(full_id, info("ctf", 4001, "ctf", 8, 8))]);
+
+    let ticket = wait(&mut lobby, &mut player, |p| p.join(dm_id));
+    assert_eq!(ticket, JoinTicket::Accepted { addr: "127.0.0.1:4000".parse().unwrap(), token: None });
+    let ticket = wait(&mut lobby, &mut player, |p| p.join(full_id));
+    assert_eq!(ticket, JoinTicket::Rejected { reason: "server is full".to_string() });
+    let ticket = wait(&mut lobby, &mut player, |p| p.join(ServerId(99)));
+    assert_eq!(ticket, JoinTicket::Rejected { reason: "no such server".to_string() });
+
+    // unregistering and disconnecting both take a server off the list
+    deathmatch.unregister();
+    drop(full);
+    pump(&mut lobby, &mut [&mut deathmatch, &mut player], |lobby, _| listed(lobby).is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn heartbeats_keep_listings_fresh_and_silent_servers_expire() {
+    let hub = LoopbackHub::new();
+    let mut lobby = lobby_app(LoopbackTransport::server(&hub), Duration::from_millis(100));
+    let mut server = LobbyClient::with_transport(LoopbackTransport::client(&hub))
+        .with_heartbeat_interval(Duration::from_millis(20));
+
+    server.register(info("coop", 4002, "coop", 0, 4));
+    pump(&mut lobby, &mut [&mut server], |_, clients| clients[0].server_id().is_some());
+    let first_id = server.server_id().unwrap();
+
+    // player counts travel with the heartbeats, which keep the listing past the timeout
+    server.set_players(2);
+    let started = Instant::now();
+    pump(&mut lobby, &mut [&mut server], |_, _| started.elapsed() > Duration::from_millis(300));
+    assert_eq!(listed(&mut lobby), vec![(first_id, info("coop", 4002, "coop", 2, 4))]);
+
+    // a server that stops sending heartbeats is dropped, even while still connected
+    pump(&mut lobby, &mut [], |lobby, _| listed(lobby).is_empty());
+
+    // and registers again once it notices
+    pump(&mut lobby, &mut [&mut server], |_, clients| {
+        clients[0].server_id().is_some_and(|id| id != first_id)
+    });
+    assert_eq!(listed(&mut lobby).len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn lobby_works_over_quic_on_localhost() {
+    let transport = QuicTransport::server("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = transport.local_addr().unwrap();
+    let mut lobby = lobby_app(transport, DEFAULT_LISTING_TIMEOUT);
+    let mut server = LobbyClient::connect(addr);
+    let mut player = LobbyClient::connect(addr);
+
+    server.register(info("jam", 4003, "race", 1, 16));
+    pump(&mut lobby, &mut [&mut server, &mut player], |_, clients| {
+        clients[0].server_id().is_some() && clients[1].is_connected()
+    });
+
+    let list = wait(&mut lobby, &mut player, |p| p.list(Some("race")));
+    assert_eq!(list.0.len(), 1);
+    let ticket = wait(&mut lobby, &mut player, |p| p.join(list.0[0].0));
+    assert_eq!(ticket, JoinTicket::Accepted { addr: "127.0.0.1:4003".parse().unwrap(), token: None });
+}
@@ -0,0 +1,122 @@
+use ecs::*;
+use networking::*;
+
+use std::thread;
+use std::time::Duration;
+
+#[replicate]
+#[derive(Component, Serialize, Deserialize, Debug, PartialEq)]
+struct Health(u32);
+
+#[replicate]
+#[derive(Component, Serialize, Deserialize, Debug, PartialEq)]
+struct Name(String);
+
+#[derive(Component, Debug, PartialEq)]
+struct ServerOnly(u32);
+
+fn server_app(hub: &LoopbackHub) -> App {
+    let mut app = App::new();
+    app.add_plugin(NetworkingPlugin::server().with_transport(LoopbackTransport::server(hub)));
+    app.add_plugin(ReplicationPlugin::server());
+    app
+}
+
+fn client_app(hub: &LoopbackHub) -> App {
+    let mut app = App::new();
+    app.add_plugin(NetworkingPlugin::client().with_transport(LoopbackTransport::client(hub)));
+    app.add_plugin(ReplicationPlugin::client());
+    app
+}
+
+/// Runs both apps until `done` holds for the client, failing after a generous timeout.
+fn pump_until(server: &mut App, client: &mut App, mut done: impl FnMut(&mut App) -> bool) {
+    for _ in 0..1000 {
+        server.run();
+        client.run();
+        if done(client) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("client never reached the expected state");
+}
+
+fn mirrored(client: &mut App, server_entity: u32) -> Option<&'static Entity> {
+    let local = client
+        .get_resource::<ReplicationClient>()?
+        .local_entity(server_entity)?;
+    client.get_entity(local)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn replicated_components_follow_server_changes() {
+    let hub = LoopbackHub::new();
+    let mut server = server_app(&hub);
+
+    let entity = server.spawn_entity();
+    server.add_component(entity, Replicated).unwrap();
+    server.add_component(entity, Health(10)).unwrap();
+    server.add_component(entity, Name("orc".to_string())).unwrap();
+    server.add_component(entity, ServerOnly(99)).unwrap();
+
+    let hidden = server.spawn_entity();
+    server.add_component(hidden, Health(1)).unwrap();
+
+    // the client joins after the entity exists, so it has to be caught up with a snapshot
+    server.run();
+    let mut client = client_app(&hub);
+
+    pump_until(&mut server, &mut client, |client| {
+        mirrored(client, entity).is_some()
+    });
+    let mirror = mirrored(&mut client, entity).unwrap();
+    assert_eq!(mirror.get_component::<Health>(), Some(&Health(10)));
+    assert_eq!(mirror.get_component::<Name>(), Some(&Name("orc".to_string())));
+    assert_eq!(mirror.get_component::<ServerEntity>(), Some(&ServerEntity(entity)));
+    assert!(!mirror.has_component::<ServerOnly>());
+    assert!(mirrored(&mut client, hidden).is_none());
+
+    for (id, health) in unsafe { World::get_components_mut::<Health>(server.world) } {
+        if id == entity {
+            health.0 -= 3;
+        }
+    }
+    pump_until(&mut server, &mut client, |client| {
+        mirrored(client, entity).unwrap().get_component::<Health>() == Some(&Health(7))
+    });
+
+    server.remove_component::<Name>(entity).unwrap();
+    pump_until(&mut server, &mut client, |client| {
+        !mirrored(client, entity).unwrap().has_component::<Name>()
+    });
+
+    server.despawn_entity(entity).unwrap();
+    pump_until(&mut server, &mut client, |client| {
+        mirrored(client, entity).is_none()
+    });
+    assert!(unsafe { World::get_components::<Health>(client.world) }.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn entities_spawned_later_reach_every_client() {
+    let hub = LoopbackHub::new();
+    let mut server = server_app(&hub);
+    let mut first = client_app(&hub);
+    let mut second = client_app(&hub);
+
+    let entity = server.spawn_entity();
+    server.add_component(entity, Replicated).unwrap();
+    server.add_component(entity, Health(3)).unwrap();
+
+    for _ in 0..1000 {
+        server.run();
+        first.run();
+        second.run();
+        if mirrored(&mut first, entity).is_some() && mirrored(&mut second, entity).is_some() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("both clients should have received the spawn");
+}
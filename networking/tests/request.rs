@@ -0,0 +1,165 @@
+use ecs::*;
+use networking::*;
+
+use std::thread;
+use std::time::Duration;
+
+#[derive(NetSend, NetRequest, Serialize, Deserialize, Debug, PartialEq)]
+#[response(Score)]
+struct GetScore {
+    player: String,
+}
+
+#[derive(NetSend, Serialize, Deserialize, Debug, PartialEq)]
+struct Score(u32);
+
+#[derive(NetSend, NetRequest, Serialize, Deserialize, Debug, PartialEq)]
+#[response(Score)]
+struct Unanswered;
+
+#[derive(Resource, Default)]
+struct Lookups(u32);
+
+fn app_with(plugin: NetworkingPlugin) -> App {
+    let mut app = App::new();
+    app.add_plugin(plugin);
+    app
+}
+
+fn networking(app: &mut App) -> &'static mut Networking {
+    app.get_resource_mut::<Networking>()
+        .expect("Networking resource missing")
+}
+
+fn pump(apps: &mut [&mut App], mut done: impl FnMut(&mut [&mut App]) -> bool) {
+    for _ in 0..2000 {
+        for app in apps.iter_mut() {
+            app.run();
+        }
+        if done(apps) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("apps never reached the expected state");
+}
+
+fn connected(hub: &LoopbackHub) -> (App, App) {
+    let mut server = app_with(NetworkingPlugin::server().with_transport(LoopbackTransport::server(hub)));
+    let mut client = app_with(NetworkingPlugin::client().with_transport(LoopbackTransport::client(hub)));
+    pump(&mut [&mut server, &mut client], |apps| networking(apps[0]).peers().count() == 1);
+    (server, client)
+}
+
+fn score_of(commands: &mut Commands, _from: Target, request: GetScore) -> Score {
+    let lookups = commands.get_resource_mut::<Lookups>().unwrap();
+    lookups.0 += 1;
+    Score(request.player.len() as u32 * 10)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn handlers_answer_requests_automatically() {
+    let hub = LoopbackHub::new().with_conditions(LinkConditions {
+        latency: Duration::from_millis(1),
+        jitter: Duration::from_millis(3),
+        loss: 0.3,
+        reorder: 0.3,
+    });
+    let (mut server, mut client) = connected(&hub);
+    server.insert_resource(Lookups::default());
+    server.get_resource_mut::<RequestHandlers>().unwrap().add::<GetScore>(score_of);
+
+    let client_net = networking(&mut client);
+    let short = client_net
+        .request(Target::Single(LOOPBACK_SERVER_ID), GetScore { player: "al".to_string() })
+        .unwrap();
+    let long = client_net
+        .request(Target::Single(LOOPBACK_SERVER_ID), GetScore { player: "bartholomew".to_string() })
+        .unwrap();
+    assert_ne!(short.id(), long.id());
+
+    let (mut short_score, mut long_score) = (None, None);
+    pump(&mut [&mut server, &mut client], |apps| {
+        let net = networking(apps[1]);
+        short_score = short_score.take().or_else(|| short.poll(net));
+        long_score = long_score.take().or_else(|| long.poll(net));
+        short_score.is_some() && long_score.is_some()
+    });
+
+    assert_eq!(short_score.unwrap(), Ok(Score(20)));
+    assert_eq!(long_score.unwrap(), Ok(Score(110)));
+    assert_eq!(server.get_resource::<Lookups>().unwrap().0, 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn requests_can_be_answered_by_hand() {
+    let hub = LoopbackHub::new();
+    let (mut server, mut client) = connected(&hub);
+
+    let pending = networking(&mut client)
+        .request(Target::Single(LOOPBACK_SERVER_ID), GetScore { player: "cy".to_string() })
+        .unwrap();
+
+    let mut response = None;
+    pump(&mut [&mut server, &mut client], |apps| {
+        let server_net = networking(apps[0]);
+        for incoming in server_net.collect_requests::<GetScore>() {
+            assert_eq!(incoming.from, Target::Single(1));
+            server_net.respond(&incoming, Score(7)).unwrap();
+        }
+
+        response = pending.poll(networking(apps[1]));
+        response.is_some()
+    });
+    assert_eq!(response.unwrap(), Ok(Score(7)));
+
+    // a plain message of the response type doesn't satisfy anything
+    assert!(networking(&mut client).next::<Score>().is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn unanswered_requests_time_out() {
+    let hub = LoopbackHub::new();
+    let (mut server, mut client) = connected(&hub);
+
+    let pending = networking(&mut client)
+        .request_with_timeout(Target::Single(LOOPBACK_SERVER_ID), Unanswered, Duration::from_millis(30))
+        .unwrap();
+
+    let mut response = None;
+    pump(&mut [&mut server, &mut client], |apps| {
+        response = pending.poll(networking(apps[1]));
+        response.is_some()
+    });
+    assert_eq!(response.unwrap(), Err(NetError::TimedOut));
+
+    // answering late is harmless, and the late response is dropped
+    let server_net = networking(&mut server);
+    let incoming = server_net.collect_requests::<Unanswered>();
+    assert_eq!(incoming.len(), 1);
+    server_net.respond(&incoming[0], Score(1)).unwrap();
+    pump(&mut [&mut server, &mut client], |_| true);
+    assert_eq!(pending.poll(networking(&mut client)), Some(Err(NetError::TimedOut)));
+}
+
+#[test]
+fn request_ids_travel_in_the_frame_header() {
+    let request = GetScore { player: "di".to_string() };
+    let frame = encode_correlated_frame(
+        request.get_type_id(),
+        &request.get_bytes(),
+        Compression::None,
+        Correlation::Request(0xdead_beef),
+    );
+
+    let (header, payload) = split_frame(&frame).unwrap();
+    assert_eq!(header.type_id, get_net_id::<GetScore>());
+    assert_eq!(header.correlation, Correlation::Request(0xdead_beef));
+    assert_eq!(payload, request.get_bytes());
+
+    let (_, obj) = decode_frame(&frame).unwrap();
+    assert_eq!(*obj.downcast::<GetScore>().unwrap(), request);
+
+    // a request frame cut off inside its id is rejected
+    assert!(matches!(split_frame(&frame[..6]), Err(NetError::Truncated { .. })));
+}
@@ -0,0 +1,85 @@
+use ecs::*;
+use networking::*;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(NetSend, Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Chat(String);
+
+fn app_with(plugin: NetworkingPlugin) -> App {
+    let mut app = App::new();
+    app.add_plugin(plugin);
+    app
+}
+
+fn networking(app: &mut App) -> &'static mut Networking {
+    app.get_resource_mut::<Networking>()
+        .expect("Networking resource missing")
+}
+
+/// Runs every app once per millisecond until `done` holds.
+fn pump(apps: &mut [&mut App], mut done: impl FnMut(&mut [&mut App]) -> bool) {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        for app in apps.iter_mut() {
+            app.run();
+        }
+        if done(apps) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("never reached the expected state");
+}
+
+#[test]
+fn plugin_starts_its_own_runtime_outside_of_one() {
+    let hub = LoopbackHub::new();
+    let mut server = app_with(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&hub)));
+    let mut client = app_with(NetworkingPlugin::client().with_transport(LoopbackTransport::client(&hub)));
+    pump(&mut [&mut server, &mut client], |apps| networking(apps[0]).peers().count() == 1);
+
+    networking(&mut client)
+        .send(Reliability::Reliable, Target::All, Chat("hi".to_string()))
+        .unwrap();
+    let mut received = Vec::new();
+    pump(&mut [&mut server, &mut client], |apps| {
+        received.extend(networking(apps[0]).collect::<Chat>());
+        !received.is_empty()
+    });
+    assert_eq!(received[0].1, Chat("hi".to_string()));
+}
+
+#[test]
+fn plugin_runs_on_a_given_runtime() {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    let transport = {
+        let _entered = runtime.enter();
+        QuicTransport::server("127.0.0.1:0".parse().unwrap()).unwrap()
+    };
+    let addr = transport.local_addr().unwrap();
+    let mut server = app_with(
+        NetworkingPlugin::server()
+            .with_transport(transport)
+            .with_runtime(runtime.handle().clone()),
+    );
+    let mut client = app_with(NetworkingPlugin::client_to(addr).with_runtime(runtime.handle().clone()));
+
+    pump(&mut [&mut server, &mut client], |apps| {
+        networking(apps[0]).peers().count() == 1 && networking(apps[1]).peers().count() == 1
+    });
+}
+
+#[test]
+fn dropping_the_app_closes_its_connections() {
+    // a free port, so the server can be started by the plugin itself
+    let addr = std::net::UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let mut server = app_with(NetworkingPlugin::server_at(addr));
+    let mut client = app_with(NetworkingPlugin::client_to(addr));
+    pump(&mut [&mut server, &mut client], |apps| networking(apps[0]).peers().count() == 1);
+
+    // QUIC would only notice a vanished client after its idle timeout
+    drop(client);
+    pump(&mut [&mut server], |apps| networking(apps[0]).peers().count() == 0);
+}
@@ -0,0 +1,323 @@
+use ecs::*;
+use networking::*;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(NetSend, Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Chat(String);
+
+#[derive(NetSend, Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Seq(u32);
+
+fn app_with(plugin: NetworkingPlugin) -> App {
+    let mut app = App::new();
+    app.add_plugin(plugin);
+    app
+}
+
+fn networking(app: &mut App) -> &'static mut Networking {
+    app.get_resource_mut::<Networking>()
+        .expect("Networking resource missing")
+}
+
+/// Runs every app once per millisecond until `done` holds or `timeout` passes.
+fn pump(apps: &mut [&mut App], timeout: Duration, mut done: impl FnMut(&mut [&mut App]) -> bool) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        for app in apps.iter_mut() {
+            app.run();
+        }
+        if done(apps) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    false
+}
+
+fn connected(app: &mut App, log: &mut Vec<Target>) {
+    for event in networking(app).events() {
+        if let NetworkingEvent::Connected { target } = event {
+            log.push(*target);
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn server_and_clients_exchange_messages() {
+    let hub = LoopbackHub::new();
+    let mut server = app_with(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&hub)));
+    let mut clients: Vec<App> = (0..3)
+        .map(|_| app_with(NetworkingPlugin::client().with_transport(LoopbackTransport::client(&hub))))
+        .collect();
+
+    let mut peers = Vec::new();
+    let [a, b, c] = &mut clients[..] else { unreachable!() };
+    assert!(pump(&mut [&mut server, a, b, c], Duration::from_secs(2), |apps| {
+        connected(apps[0], &mut peers);
+        peers.len() == 3
+    }));
+
+    for (i, client) in clients.iter_mut().enumerate() {
+        networking(client)
+            .send(Reliability::Reliable, Target::All, Chat(format!("hi from {}", i)))
+            .unwrap();
+    }
+
+    let mut received = Vec::new();
+    let [a, b, c] = &mut clients[..] else { unreachable!() };
+    assert!(pump(&mut [&mut server, a, b, c], Duration::from_secs(2), |apps| {
+        received.extend(networking(apps[0]).collect::<Chat>());
+        received.len() == 3
+    }));
+    let mut senders: Vec<Target> = received.iter().map(|(from, _)| *from).collect();
+    senders.sort_by_key(|t| format!("{:?}", t));
+    peers.sort_by_key(|t| format!("{:?}", t));
+    assert_eq!(senders, peers);
+
+    let Target::Single(first) = peers[0] else { panic!("expected a single peer") };
+    networking(&mut server)
+        .send(Reliability::Reliable, Target::All, Chat("everyone".to_string()))
+        .unwrap();
+    networking(&mut server)
+        .send(Reliability::Reliable, Target::Single(first), Chat("just you".to_string()))
+        .unwrap();
+
+    let mut inboxes = vec![Vec::new(); 3];
+    let [a, b, c] = &mut clients[..] else { unreachable!() };
+    assert!(pump(&mut [&mut server, a, b, c], Duration::from_secs(2), |apps| {
+        for (inbox, app) in inboxes.iter_mut().zip(apps[1..].iter_mut()) {
+            inbox.extend(networking(app).collect::<Chat>().into_iter().map(|(_, chat)| chat.0));
+        }
+        inboxes.iter().map(Vec::len).sum::<usize>() == 4
+    }));
+    assert_eq!(inboxes.iter().filter(|inbox| inbox.contains(&"just you".to_string())).count(), 1);
+    assert!(inboxes.iter().all(|inbox| inbox.contains(&"everyone".to_string())));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn lossy_links_keep_reliable_frames_in_order() {
+    let conditions = LinkConditions {
+        latency: Duration::from_millis(2),
+        jitter: Duration::from_millis(6),
+        loss: 0.5,
+        reorder: 0.3,
+    };
+    let hub = LoopbackHub::new().with_conditions(conditions).with_seed(7);
+    let mut server = app_with(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&hub)));
+    let mut client = app_with(NetworkingPlugin::client().with_transport(LoopbackTransport::client(&hub)));
+
+    // one tick per message, since everything sent in the same tick shares a packet
+    for i in 0..100 {
+        networking(&mut client).send(Reliability::Reliable, Target::All, Seq(i)).unwrap();
+        networking(&mut client).send(Reliability::Unreliable, Target::All, Chat(i.to_string())).unwrap();
+        client.run();
+    }
+
+    let mut reliable = Vec::new();
+    let mut unreliable = Vec::new();
+    assert!(pump(&mut [&mut server, &mut client], Duration::from_secs(2), |apps| {
+        reliable.extend(networking(apps[0]).collect::<Seq>().into_iter().map(|(_, s)| s.0));
+        unreliable.extend(networking(apps[0]).collect::<Chat>());
+        reliable.len() == 100
+    }));
+
+    assert_eq!(reliable, (0..100).collect::<Vec<_>>());
+    // give stragglers time to land, then check that roughly half the unreliable frames vanished
+    pump(&mut [&mut server, &mut client], Duration::from_millis(50), |apps| {
+        unreliable.extend(networking(apps[0]).collect::<Chat>());
+        false
+    });
+    assert!(unreliable.len() > 20 && unreliable.len() < 80, "got {}", unreliable.len());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn latency_delays_delivery() {
+    let hub = LoopbackHub::new();
+    let mut server = app_with(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&hub)));
+    let slow = LoopbackTransport::client(&hub).with_conditions(LinkConditions {
+        latency: Duration::from_millis(40),
+        ..Default::default()
+    });
+    let mut client = app_with(NetworkingPlugin::client().with_transport(slow));
+
+    let sent_at = Instant::now();
+    networking(&mut client)
+        .send(Reliability::Unreliable, Target::All, Chat("late".to_string()))
+        .unwrap();
+
+    assert!(pump(&mut [&mut server, &mut client], Duration::from_secs(2), |apps| {
+        !networking(apps[0]).collect::<Chat>().is_empty()
+    }));
+    assert!(sent_at.elapsed() >= Duration::from_millis(40));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn quic_round_trip_on_localhost() {
+    let transport = QuicTransport::server("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = transport.local_addr().unwrap();
+    let mut server = app_with(NetworkingPlugin::server().with_transport(transport));
+    let mut client = app_with(NetworkingPlugin::client_to(addr));
+
+    let mut peer = None;
+    assert!(pump(&mut [&mut server, &mut client], Duration::from_secs(5), |apps| {
+        for event in networking(apps[0]).events() {
+            if let NetworkingEvent::Connected { target } = event {
+                peer = Some(*target);
+            }
+        }
+        peer.is_some()
+    }));
+
+    networking(&mut client)
+        .send(Reliability::Reliable, Target::All, Chat("ping".to_string()))
+        .unwrap();
+
+    // datagrams may be dropped, so keep sending until one makes it
+    let mut got_ping = false;
+    let mut got_pong = false;
+    assert!(pump(&mut [&mut server, &mut client], Duration::from_secs(5), |apps| {
+        networking(apps[0])
+            .send(Reliability::Unreliable, peer.unwrap(), Chat("pong".to_string()))
+            .unwrap();
+        got_ping |= networking(apps[0]).collect::<Chat>().iter().any(|(_, c)| c.0 == "ping");
+        got_pong |= networking(apps[1]).collect::<Chat>().iter().any(|(_, c)| c.0 == "pong");
+        got_ping && got_pong
+    }));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn disconnecting_a_peer_closes_both_ends() {
+    let hub = LoopbackHub::new();
+    let mut server = app_with(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&hub)));
+    let kicked = LoopbackTransport::client(&hub);
+    let kicked_id = kicked.id();
+    let mut a = app_with(NetworkingPlugin::client().with_transport(kicked));
+    let mut b = app_with(NetworkingPlugin::client().with_transport(LoopbackTransport::client(&hub)));
+    assert!(pump(&mut [&mut server, &mut a, &mut b], Duration::from_secs(2), |apps| {
+        networking(apps[0]).peers().count() == 2
+    }));
+
+    networking(&mut server).send(Reliability::Reliable, Target::Single(kicked_id), Chat("bye".into())).unwrap();
+    networking(&mut server).disconnect(kicked_id).unwrap();
+    let mut farewell = Vec::new();
+    assert!(pump(&mut [&mut server, &mut a, &mut b], Duration::from_secs(2), |apps| {
+        farewell.extend(networking(apps[1]).collect::<Chat>().into_iter().map(|(_, c)| c.0));
+        networking(apps[0]).peers().count() == 1 && networking(apps[1]).peers().count() == 0
+    }));
+    // queued messages go out before the connection closes
+    assert_eq!(farewell, vec!["bye"]);
+
+    // the kicked client can't reach the server any more, the other one still can. Its
+    // networking task may already have stopped, in which case the send fails outright.
+    let _ = networking(&mut a).send(Reliability::Reliable, Target::All, Chat("let me in".into()));
+    networking(&mut b).send(Reliability::Reliable, Target::All, Chat("still here".into())).unwrap();
+    let mut received = Vec::new();
+    pump(&mut [&mut server, &mut a, &mut b], Duration::from_millis(50), |apps| {
+        received.extend(networking(apps[0]).collect::<Chat>().into_iter().map(|(_, c)| c.0));
+        false
+    });
+    assert_eq!(received, vec!["still here"]);
+
+    // dropping the kicked client doesn't report it again
+    drop(a);
+    let mut disconnects = 0;
+    pump(&mut [&mut server, &mut b], Duration::from_millis(50), |apps| {
+        disconnects += networking(apps[0])
+            .events()
+            .iter()
+            .filter(|e| matches!(e, NetworkingEvent::Disconnected { .. }))
+            .count();
+        false
+    });
+    assert_eq!(disconnects, 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn quic_disconnect_closes_the_connection() {
+    let transport = QuicTransport::server("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = transport.local_addr().unwrap();
+    let mut server = app_with(NetworkingPlugin::server().with_transport(transport));
+    let mut client = app_with(NetworkingPlugin::client_to(addr));
+    assert!(pump(&mut [&mut server, &mut client], Duration::from_secs(5), |apps| {
+        networking(apps[0]).peers().count() == 1 && networking(apps[1]).peers().count() == 1
+    }));
+
+    let peer = networking(&mut server).peers().next().unwrap();
+    networking(&mut server).send(Reliability::Reliable, Target::Single(peer), Chat("bye".into())).unwrap();
+    networking(&mut server).disconnect(peer).unwrap();
+    let mut farewell = Vec::new();
+    assert!(pump(&mut [&mut server, &mut client], Duration::from_secs(5), |apps| {
+        farewell.extend(networking(apps[1]).collect::<Chat>().into_iter().map(|(_, c)| c.0));
+        networking(apps[0]).peers().count() == 0 && networking(apps[1]).peers().count() == 0
+    }));
+    // the writer sends what was queued before the connection closes
+    assert_eq!(farewell, vec!["bye"]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn quic_reads_one_reliable_stream_per_peer() {
+    let identity = ServerIdentity::generate().unwrap();
+    let dir = std::env::temp_dir().join(format!("quic-streams-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    identity.save(dir.join("cert.der"), dir.join("key.der")).unwrap();
+    let cert = std::fs::read(dir.join("cert.der")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let mut server = QuicTransport::server_with_identity("127.0.0.1:0".parse().unwrap(), identity).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    // a hand-rolled peer, since the transport's own writer never opens a second stream
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(cert.into()).unwrap();
+    let crypto = rustls::ClientConfig::builder_with_provider(std::sync::Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    let mut endpoint = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+    endpoint.set_default_client_config(quinn::ClientConfig::new(std::sync::Arc::new(
+        quinn::crypto::rustls::QuicClientConfig::try_from(crypto).unwrap(),
+    )));
+    let connection = endpoint.connect(addr, "localhost").unwrap().await.unwrap();
+
+    async fn write_frame(stream: &mut quinn::SendStream, data: &[u8]) {
+        stream.write_all(&(data.len() as u32).to_le_bytes()).await.unwrap();
+        stream.write_all(data).await.unwrap();
+    }
+
+    let mut reliable = connection.open_uni().await.unwrap();
+    write_frame(&mut reliable, b"first").await;
+    let mut extras = Vec::new();
+    for _ in 0..20 {
+        let mut extra = connection.open_uni().await.unwrap();
+        write_frame(&mut extra, b"extra").await;
+        extras.push(extra);
+    }
+    write_frame(&mut reliable, b"last").await;
+
+    let mut received = Vec::new();
+    let collect = async {
+        while let Some(event) = server.recv().await {
+            if let NetworkingEvent::RecvData { data, .. } = event {
+                let done = data == b"last";
+                received.push(String::from_utf8(data).unwrap());
+                if done {
+                    break;
+                }
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), collect).await.unwrap();
+    assert_eq!(received, vec!["first", "last"]);
+
+    // the extra streams are turned away rather than left for a reader
+    for extra in extras {
+        let stopped = tokio::time::timeout(Duration::from_secs(5), extra.stopped()).await.unwrap();
+        assert!(matches!(stopped, Ok(Some(_))), "{:?}", stopped);
+    }
+    connection.close(0u32.into(), b"done");
+}
@@ -12,11 +12,13 @@ pub use ecs::*;
 pub use networking::*;
 
 pub mod audio;
+pub mod netcode;
 pub mod physics;
 pub mod render;
 pub mod utils;
 
 pub use audio::*;
+pub use netcode::*;
 pub use physics::*;
 pub use render::model::ModelHandle;
 use render::sprite::*;
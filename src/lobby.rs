@@ -0,0 +1,135 @@
+use anyhow::{Result, anyhow};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+pub use ecs::*;
+pub use networking::*;
+
+pub mod audio;
+pub mod netcode;
+pub mod physics;
+pub mod render;
+pub mod utils;
+
+pub use audio::*;
+pub use netcode::*;
+pub use physics::*;
+pub use render::*;
+pub use utils::time::*;
+pub use utils::*;
+
+const USAGE: &str = "usage: lobby [--port PORT] [--timeout SECONDS] [--token-key HEX] [--ticks N] [--tick-rate HZ]
+       lobby keygen
+       lobby token --key HEX --player ID [--valid SECONDS]";
+/// A lobby only shuffles a few small messages around, so it doesn't need the game's tick rate.
+const LOBBY_TICK_RATE: f64 = 20.0;
+
+struct LobbyArgs {
+    port: u16,
+    timeout: Duration,
+    token_key: Option<TokenKey>,
+}
+
+/// Takes the lobby's own flags out of `args`, leaving the runner's.
+fn parse_args(args: &mut Vec<String>) -> Result<LobbyArgs> {
+    let mut parsed = LobbyArgs {
+        port: DEFAULT_LOBBY_PORT,
+        timeout: DEFAULT_LISTING_TIMEOUT,
+        token_key: None,
+    };
+
+    let mut i = 0;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        if !matches!(flag, "--port" | "--timeout" | "--token-key") {
+            i += 1;
+            continue;
+        }
+        let value = args.get(i + 1).ok_or_else(|| anyhow!("{} needs a value", flag))?;
+        match flag {
+            "--port" => parsed.port = value.parse()?,
+            "--timeout" => parsed.timeout = Duration::from_secs_f64(value.parse()?),
+            _ => parsed.token_key = Some(TokenKey::from_hex(value)?),
+        }
+        args.drain(i..i + 2);
+    }
+
+    Ok(parsed)
+}
+
+/// `lobby token`: signs a connect token by hand, e.g. for testing a server without a lobby.
+fn issue_token(args: &[String]) -> Result<String> {
+    let mut key = None;
+    let mut player_id = None;
+    let mut valid_for = DEFAULT_TOKEN_LIFETIME;
+
+    for pair in args.chunks(2) {
+        let [flag, value] = pair else {
+            return Err(anyhow!("{} needs a value", pair[0]));
+        };
+        match flag.as_str() {
+            "--key" => key = Some(TokenKey::from_hex(value)?),
+            "--player" => player_id = Some(value.parse()?),
+            "--valid" => valid_for = Duration::from_secs_f64(value.parse()?),
+            _ => return Err(anyhow!("unknown flag {}", flag)),
+        }
+    }
+
+    let key = key.ok_or_else(|| anyhow!("token needs --key"))?;
+    let player_id = player_id.ok_or_else(|| anyhow!("token needs --player"))?;
+    Ok(key.issue(player_id, valid_for).to_hex())
+}
+
+#[tokio::main]
+async fn main() {
+    let mut args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("keygen") => {
+            println!("{}", TokenKey::generate().to_hex());
+            return;
+        }
+        Some("token") => match issue_token(&args[2..]) {
+            Ok(token) => {
+                println!("{}", token);
+                return;
+            }
+            Err(e) => {
+                println!("{}", e);
+                println!("{}", USAGE);
+                std::process::exit(2);
+            }
+        },
+        _ => {}
+    }
+
+    let has_tick_rate = args.iter().any(|a| a == "--tick-rate");
+    let parsed = parse_args(&mut args).and_then(|lobby_args| {
+        let mut runner = utils::runner::ServerRunner::from_args(args)?;
+        if !has_tick_rate {
+            runner = runner.with_tick_rate(LOBBY_TICK_RATE);
+        }
+        Ok((lobby_args, runner))
+    });
+    let (lobby_args, runner) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            println!("{}", e);
+            println!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, lobby_args.port));
+    let mut lobby = LobbyPlugin::new().with_timeout(lobby_args.timeout);
+    if let Some(key) = lobby_args.token_key {
+        lobby = lobby.with_token_key(key);
+    }
+
+    let mut app = App::new();
+    app.add_plugin(NetworkingPlugin::server_at(addr));
+    app.add_plugin(lobby);
+
+    println!("Lobby listening on {}", addr);
+    runner.run(&mut app);
+    std::process::exit(0);
+}
@@ -0,0 +1,89 @@
+use crate::*;
+use glam::Vec3;
+use std::collections::BTreeMap;
+
+/// Replicates entities with a `Transform` only to peers whose player is within range. Add it
+/// after `ReplicationPlugin::server()` and `PredictionPlugin::server()`.
+pub struct InterestPlugin {
+    radius: f32,
+}
+
+impl InterestPlugin {
+    pub fn new(radius: f32) -> Self {
+        Self { radius }
+    }
+}
+
+impl Plugin for InterestPlugin {
+    fn build(&self, app: &mut App) {
+        // without it nothing would be filtered, and every peer would see everything
+        app.get_resource_mut::<ReplicationServer>()
+            .expect("InterestPlugin needs ReplicationPlugin::server() to be added first")
+            .set_relevance(spatial_relevance);
+        app.insert_resource(SpatialInterest::new(self.radius));
+        app.add_system(track_player_positions, SystemStage::Update);
+    }
+}
+
+/// Where every peer's player stands, refreshed each frame from the `InputOwner` entities.
+#[derive(Resource)]
+pub struct SpatialInterest {
+    pub radius: f32,
+    players: BTreeMap<u32, Vec3>,
+}
+
+impl SpatialInterest {
+    pub fn new(radius: f32) -> Self {
+        Self {
+            radius,
+            players: BTreeMap::new(),
+        }
+    }
+
+    pub fn player_pos(&self, peer: u32) -> Option<Vec3> {
+        self.players.get(&peer).copied()
+    }
+
+    /// 0 when out of range, otherwise between 1 and 2 with closer entities scoring higher.
+    /// Peers without a player yet see everything.
+    pub fn relevance(&self, peer: u32, pos: Vec3) -> f32 {
+        let Some(player) = self.player_pos(peer) else {
+            return 1.0;
+        };
+
+        let distance = player.distance(pos);
+        if distance > self.radius {
+            0.0
+        } else {
+            2.0 - distance / self.radius
+        }
+    }
+}
+
+fn spatial_relevance(commands: &Commands, peer: u32, entity: u32) -> f32 {
+    let interest = unsafe { World::get_resource::<SpatialInterest>(commands.world) };
+    let transform = commands
+        .get_entity(entity)
+        .and_then(|e| e.get_component::<Transform>());
+
+    match (interest, transform) {
+        (Some(interest), Some(transform)) => interest.relevance(peer, transform.pos),
+        _ => 1.0,
+    }
+}
+
+system! {
+    fn track_player_positions(
+        interest: res &mut SpatialInterest,
+        players: query (&Transform, &InputOwner),
+    ) {
+        let Some(interest) = interest else {
+            return;
+        };
+
+        interest.players.clear();
+        for (transform, owner) in players {
+            interest.players.insert(owner.0, transform.pos);
+        }
+    }
+}
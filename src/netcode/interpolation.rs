@@ -0,0 +1,194 @@
+use crate::*;
+use glam::{Quat, Vec3};
+use std::collections::{BTreeMap, VecDeque};
+
+const DEFAULT_DELAY: f32 = 0.1;
+const DEFAULT_TICK_RATE: f32 = 60.0;
+const DEFAULT_MAX_EXTRAPOLATION: f32 = 0.25;
+/// Snapshots kept per entity; at the default rate this is about half a second.
+const MAX_SNAPSHOTS: usize = 32;
+/// If the render clock drifts further than this from where it should be, it jumps instead of
+/// easing back.
+const MAX_CLOCK_DRIFT: f32 = 0.25;
+/// Fraction of the clock error corrected per frame.
+const CLOCK_CORRECTION: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Snapshot {
+    pub tick: Tick,
+    pub pos: Vec3,
+    pub rot: Quat,
+}
+
+/// Server transforms for one remote entity, oldest first.
+#[derive(Default, Debug)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl SnapshotBuffer {
+    /// Inserts in tick order. A second snapshot for the same tick replaces the first.
+    pub fn push(&mut self, snapshot: Snapshot) {
+        let index = self.snapshots.partition_point(|s| s.tick < snapshot.tick);
+        match self.snapshots.get_mut(index) {
+            Some(existing) if existing.tick == snapshot.tick => *existing = snapshot,
+            _ => self.snapshots.insert(index, snapshot),
+        }
+
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Transform at `tick`, which may fall between snapshots. Past the newest snapshot the
+    /// position keeps moving at the last known velocity for at most `max_extrapolation` ticks,
+    /// while the rotation holds.
+    pub fn sample(&self, tick: f32, max_extrapolation: f32) -> Option<(Vec3, Quat)> {
+        let first = self.snapshots.front()?;
+        if tick <= first.tick as f32 || self.snapshots.len() == 1 {
+            return Some((first.pos, first.rot));
+        }
+
+        let next = self.snapshots.partition_point(|s| (s.tick as f32) < tick);
+        if let Some(b) = self.snapshots.get(next) {
+            let a = &self.snapshots[next - 1];
+            let t = (tick - a.tick as f32) / (b.tick - a.tick) as f32;
+            return Some((a.pos.lerp(b.pos, t), a.rot.slerp(b.rot, t)));
+        }
+
+        let b = &self.snapshots[self.snapshots.len() - 1];
+        let a = &self.snapshots[self.snapshots.len() - 2];
+        let velocity = (b.pos - a.pos) / (b.tick - a.tick) as f32;
+        let ahead = (tick - b.tick as f32).min(max_extrapolation);
+        Some((b.pos + velocity * ahead, b.rot))
+    }
+
+    /// Drops snapshots that can no longer be sampled, keeping one at or before `tick`.
+    fn discard_before(&mut self, tick: f32) {
+        while self.snapshots.len() > 2 && (self.snapshots[1].tick as f32) <= tick {
+            self.snapshots.pop_front();
+        }
+    }
+}
+
+pub struct InterpolationPlugin;
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Interpolation::default());
+        app.add_system(interpolate_remote_transforms, SystemStage::Update);
+    }
+}
+
+/// Renders replicated `Transform`s at `delay` seconds behind the newest server tick, so there
+/// is usually a snapshot on either side to blend between.
+#[derive(Resource)]
+pub struct Interpolation {
+    pub delay: f32,
+    /// Server ticks per second, used to convert between ticks and `Time`.
+    pub tick_rate: f32,
+    /// How far past the newest snapshot an entity may be extrapolated, in seconds.
+    pub max_extrapolation: f32,
+    render_tick: Option<f32>,
+    buffers: BTreeMap<u32, SnapshotBuffer>,
+}
+
+impl Default for Interpolation {
+    fn default() -> Self {
+        Self {
+            delay: DEFAULT_DELAY,
+            tick_rate: DEFAULT_TICK_RATE,
+            max_extrapolation: DEFAULT_MAX_EXTRAPOLATION,
+            render_tick: None,
+            buffers: BTreeMap::new(),
+        }
+    }
+}
+
+impl Interpolation {
+    /// Server tick currently being rendered.
+    pub fn render_tick(&self) -> Option<f32> {
+        self.render_tick
+    }
+
+    pub fn buffer(&self, entity: u32) -> Option<&SnapshotBuffer> {
+        self.buffers.get(&entity)
+    }
+
+    /// Moves the render clock forward by `dt` and eases it toward `delay` behind `latest`.
+    fn advance(&mut self, latest: Tick, dt: f32) -> f32 {
+        let target = latest as f32 - self.delay * self.tick_rate;
+        let tick = match self.render_tick {
+            Some(tick) => {
+                let step = dt * self.tick_rate;
+                let error = target - (tick + step);
+                if error.abs() > MAX_CLOCK_DRIFT * self.tick_rate {
+                    target
+                } else {
+                    // slow down at most to a standstill, so entities never visibly reverse
+                    tick + step + (error * CLOCK_CORRECTION).max(-step)
+                }
+            }
+            None => target,
+        };
+
+        self.render_tick = Some(tick);
+        tick
+    }
+}
+
+system! {
+    fn interpolate_remote_transforms(
+        time: res &Time,
+        replication: res &ReplicationClient,
+        interpolation: res &mut Interpolation,
+        commands: commands,
+    ) {
+        let (Some(time), Some(replication), Some(interpolation)) = (time, replication, interpolation) else {
+            return;
+        };
+
+        let mut transforms: BTreeMap<u32, &mut Transform> =
+            unsafe { World::get_components_mut::<Transform>(commands.world) }
+                .into_iter()
+                .collect();
+
+        // whatever replication wrote this frame is the server's value, not ours
+        for (entity, tick) in replication.updated::<Transform>() {
+            let Some(transform) = transforms.get(&entity) else {
+                continue;
+            };
+            interpolation.buffers.entry(entity).or_default().push(Snapshot {
+                tick,
+                pos: transform.pos,
+                rot: transform.rot,
+            });
+        }
+        interpolation.buffers.retain(|entity, _| transforms.contains_key(entity));
+
+        if interpolation.buffers.is_empty() {
+            return;
+        }
+
+        let render_tick = interpolation.advance(replication.last_tick(), time.delta_seconds);
+        let max_extrapolation = interpolation.max_extrapolation * interpolation.tick_rate;
+        for (entity, buffer) in interpolation.buffers.iter_mut() {
+            let (Some(transform), Some((pos, rot))) =
+                (transforms.get_mut(entity), buffer.sample(render_tick, max_extrapolation))
+            else {
+                continue;
+            };
+            transform.pos = pos;
+            transform.rot = rot;
+            buffer.discard_before(render_tick);
+        }
+    }
+}
@@ -0,0 +1,7 @@
+pub mod interest;
+pub mod interpolation;
+pub mod prediction;
+
+pub use interest::*;
+pub use interpolation::*;
+pub use prediction::*;
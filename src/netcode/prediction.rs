@@ -0,0 +1,425 @@
+use crate::*;
+use glam::Vec3;
+use std::collections::{BTreeMap, VecDeque};
+
+/// How many of the newest unacknowledged inputs go out with every step, to ride out packet loss.
+const INPUT_REDUNDANCY: usize = 8;
+/// Inputs are forgotten after this many steps even if the server never acknowledges them.
+const MAX_HISTORY: usize = 256;
+/// Predictions this close to the server's answer are left alone.
+const RECONCILE_TOLERANCE: f32 = 0.001;
+/// Steps of input the server lets a client bank while its packets are late. Inputs past that
+/// wait for the server's clock to catch up.
+const MAX_BUFFERED_INPUTS: u32 = 2 * INPUT_REDUNDANCY as u32;
+/// How many steps a client's ticks may run ahead of the server's clock before its inputs are
+/// dropped. Also caps the inputs the server keeps queued for a peer.
+const MAX_INPUT_LEAD: u32 = 2 * INPUT_REDUNDANCY as u32;
+/// Fastest a player input may move, in units per second, unless set otherwise.
+pub const DEFAULT_MAX_SPEED: f32 = 20.0;
+
+/// Advances a position by one fixed step. Client and server must use the same function, or every
+/// acknowledgement turns into a correction.
+pub type StepFn = fn(Vec3, &PlayerInput, f32) -> Vec3;
+
+pub fn default_step(pos: Vec3, input: &PlayerInput, dt: f32) -> Vec3 {
+    pos + input.velocity * dt
+}
+
+/// Runs `step` on `input` slowed down to `max_speed`, with non-finite velocities treated as
+/// standing still. Both sides go through here so the client predicts what the server will do.
+fn limited_step(step: StepFn, pos: Vec3, input: &PlayerInput, dt: f32, max_speed: f32) -> Vec3 {
+    let velocity = if input.velocity.is_finite() {
+        input.velocity.clamp_length_max(max_speed)
+    } else {
+        Vec3::ZERO
+    };
+    step(pos, &PlayerInput { velocity, ..*input }, dt)
+}
+
+/// The input for a single fixed step, tagged with the client's step number.
+#[derive(NetSend, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub struct PlayerInput {
+    pub tick: u32,
+    pub velocity: Vec3,
+}
+
+/// Sent every step with the newest input and a few older ones the server hasn't acknowledged.
+#[derive(NetSend, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PlayerInputs(pub Vec<PlayerInput>);
+
+/// Authoritative position after the server has applied input `tick`.
+#[derive(NetSend, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct PlayerState {
+    pub tick: u32,
+    pub pos: Vec3,
+}
+
+/// Marks the entity the local player controls. Its `Transform` is moved by prediction.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Predicted;
+
+/// Spawned on the server for every connected peer; the peer's inputs move this entity.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputOwner(pub u32);
+
+pub struct PredictionPlugin {
+    is_server: bool,
+    step: StepFn,
+    max_speed: f32,
+}
+
+impl PredictionPlugin {
+    pub fn client() -> Self {
+        Self {
+            is_server: false,
+            step: default_step,
+            max_speed: DEFAULT_MAX_SPEED,
+        }
+    }
+
+    pub fn server() -> Self {
+        Self {
+            is_server: true,
+            step: default_step,
+            max_speed: DEFAULT_MAX_SPEED,
+        }
+    }
+
+    pub fn with_step(mut self, step: StepFn) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// Fastest a player input may move; faster inputs are slowed down to it. Client and server
+    /// must agree on it, like on the step function.
+    pub fn with_max_speed(mut self, max_speed: f32) -> Self {
+        self.max_speed = max_speed;
+        self
+    }
+}
+
+impl Plugin for PredictionPlugin {
+    fn build(&self, app: &mut App) {
+        if self.is_server {
+            let mut prediction = ServerPrediction::new(self.step);
+            prediction.max_speed = self.max_speed;
+            app.insert_resource(prediction);
+            app.add_system(process_player_inputs, SystemStage::Update);
+        } else {
+            let mut prediction = ClientPrediction::new(self.step);
+            prediction.max_speed = self.max_speed;
+            app.insert_resource(prediction);
+            app.add_system(predict_player, SystemStage::Update);
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct ClientPrediction {
+    /// Steps at `fixed_delta`, which follows the `PhysicsTime` resource when there is one.
+    pub clock: PhysicsTime,
+    /// Longest distance a player input may move per second.
+    pub max_speed: f32,
+    step: StepFn,
+    velocity: Vec3,
+    next_tick: u32,
+    /// Unacknowledged inputs along with the position predicted after each one.
+    history: VecDeque<(PlayerInput, Vec3)>,
+    acked: Option<u32>,
+    corrections: u32,
+}
+
+impl ClientPrediction {
+    pub fn new(step: StepFn) -> Self {
+        Self {
+            clock: PhysicsTime::default(),
+            max_speed: DEFAULT_MAX_SPEED,
+            step,
+            velocity: Vec3::ZERO,
+            next_tick: 0,
+            history: VecDeque::new(),
+            acked: None,
+            corrections: 0,
+        }
+    }
+
+    /// Sets the velocity sampled by every following step until it is changed again.
+    pub fn set_input(&mut self, velocity: Vec3) {
+        self.velocity = velocity;
+    }
+
+    /// Tick the next input will be tagged with.
+    pub fn tick(&self) -> u32 {
+        self.next_tick
+    }
+
+    /// Newest input the server has applied.
+    pub fn acked(&self) -> Option<u32> {
+        self.acked
+    }
+
+    pub fn pending(&self) -> impl Iterator<Item = &PlayerInput> {
+        self.history.iter().map(|(input, _)| input)
+    }
+
+    /// Number of times a server state disagreed with the prediction and forced a replay.
+    pub fn corrections(&self) -> u32 {
+        self.corrections
+    }
+
+    /// Runs one step from `pos` with the current input and remembers it for replay.
+    pub fn predict(&mut self, pos: Vec3) -> Vec3 {
+        let input = PlayerInput {
+            tick: self.next_tick,
+            velocity: self.velocity,
+        };
+        self.next_tick += 1;
+
+        let pos = limited_step(self.step, pos, &input, self.clock.fixed_delta, self.max_speed);
+        self.history.push_back((input, pos));
+        if self.history.len() > MAX_HISTORY {
+            self.history.pop_front();
+        }
+        pos
+    }
+
+    /// Forgets inputs the server has applied. If the server ended up somewhere other than
+    /// predicted, rewinds to its position and replays the remaining inputs on top.
+    pub fn reconcile(&mut self, state: PlayerState, pos: Vec3) -> Vec3 {
+        if self.acked.is_some_and(|acked| state.tick <= acked) {
+            return pos;
+        }
+        self.acked = Some(state.tick);
+
+        let predicted = self
+            .history
+            .iter()
+            .find(|(input, _)| input.tick == state.tick)
+            .map(|(_, predicted)| *predicted);
+        self.history.retain(|(input, _)| input.tick > state.tick);
+
+        if predicted.is_some_and(|predicted| predicted.distance(state.pos) <= RECONCILE_TOLERANCE) {
+            return pos;
+        }
+
+        self.corrections += 1;
+        let mut pos = state.pos;
+        for (input, predicted) in self.history.iter_mut() {
+            pos = limited_step(self.step, pos, input, self.clock.fixed_delta, self.max_speed);
+            *predicted = pos;
+        }
+        pos
+    }
+
+    fn recent_inputs(&self) -> PlayerInputs {
+        let skip = self.history.len().saturating_sub(INPUT_REDUNDANCY);
+        PlayerInputs(self.pending().skip(skip).copied().collect())
+    }
+}
+
+/// Where a peer's inputs have got to, and how many more the server's clock allows it.
+#[derive(Default)]
+struct PeerInputs {
+    last_processed: Option<u32>,
+    /// Inputs the peer may still apply, one per server step.
+    budget: u32,
+    /// Server steps since the last input was applied.
+    idle_steps: u32,
+    /// Received inputs the clock hasn't allowed yet, by tick.
+    queued: BTreeMap<u32, PlayerInput>,
+}
+
+#[derive(Resource)]
+pub struct ServerPrediction {
+    /// Steps at `fixed_delta`, which follows the `PhysicsTime` resource when there is one. Every
+    /// step lets each peer apply one more input.
+    pub clock: PhysicsTime,
+    /// Longest distance a player input may move per second.
+    pub max_speed: f32,
+    step: StepFn,
+    players: BTreeMap<u32, u32>,
+    inputs: BTreeMap<u32, PeerInputs>,
+}
+
+impl ServerPrediction {
+    pub fn new(step: StepFn) -> Self {
+        Self {
+            clock: PhysicsTime::default(),
+            max_speed: DEFAULT_MAX_SPEED,
+            step,
+            players: BTreeMap::new(),
+            inputs: BTreeMap::new(),
+        }
+    }
+
+    /// Entity driven by `peer`, if it is connected.
+    pub fn player(&self, peer: u32) -> Option<u32> {
+        self.players.get(&peer).copied()
+    }
+
+    /// Lets every peer apply one more input.
+    fn advance(&mut self) {
+        for inputs in self.inputs.values_mut() {
+            inputs.budget = (inputs.budget + 1).min(MAX_BUFFERED_INPUTS);
+            inputs.idle_steps = inputs.idle_steps.saturating_add(1);
+        }
+    }
+
+    /// Queues the inputs newer than the last one processed for `peer`. Past `MAX_INPUT_LEAD`
+    /// queued inputs the newest are dropped; the client sends them again until acknowledged.
+    fn queue(&mut self, peer: u32, inputs: Vec<PlayerInput>) {
+        let Some(state) = self.inputs.get_mut(&peer) else {
+            return;
+        };
+        for input in inputs {
+            if state.last_processed.is_none_or(|last| input.tick > last) {
+                state.queued.insert(input.tick, input);
+            }
+        }
+        while state.queued.len() > MAX_INPUT_LEAD as usize {
+            state.queued.pop_last();
+        }
+    }
+
+    /// Applies queued inputs for `peer` in tick order, as many as the server's clock allows, and
+    /// returns the tick of the newest, or `None` if nothing new was applied.
+    fn apply(&mut self, peer: u32, pos: &mut Vec3) -> Option<u32> {
+        let state = self.inputs.get_mut(&peer)?;
+        let mut applied = None;
+        while state.budget > 0 {
+            let Some(entry) = state.queued.first_entry() else {
+                break;
+            };
+            // lost packets leave gaps, but no more than the time since the last input
+            if state.last_processed.is_some_and(|last| {
+                entry.get().tick - last > state.idle_steps.saturating_add(MAX_INPUT_LEAD)
+            }) {
+                break;
+            }
+            let input = entry.remove();
+            *pos = limited_step(self.step, *pos, &input, self.clock.fixed_delta, self.max_speed);
+
+            state.budget -= 1;
+            state.idle_steps = 0;
+            state.last_processed = Some(input.tick);
+            applied = Some(input.tick);
+        }
+        applied
+    }
+}
+
+system! {
+    fn predict_player(
+        time: res &Time,
+        physics_time: res &PhysicsTime,
+        networking: res &Networking,
+        prediction: res &mut ClientPrediction,
+        player: query (&mut Transform, &Predicted),
+    ) {
+        let (Some(time), Some(prediction)) = (time, prediction) else {
+            return;
+        };
+        let Some((transform, _)) = player.next() else {
+            return;
+        };
+
+        if let Some(physics_time) = physics_time {
+            prediction.clock.fixed_delta = physics_time.fixed_delta;
+        }
+
+        if let Some(networking) = networking {
+            let newest = networking
+                .collect::<PlayerState>()
+                .into_iter()
+                .map(|(_, state)| state)
+                .max_by_key(|state| state.tick);
+            if let Some(state) = newest {
+                transform.pos = prediction.reconcile(state, transform.pos);
+            }
+        }
+
+        prediction.clock.accumulate(time.delta_seconds);
+        while prediction.clock.consume_step() {
+            transform.pos = prediction.predict(transform.pos);
+
+            let Some(networking) = networking else {
+                continue;
+            };
+            if let Err(e) = networking.send(Reliability::Unreliable, Target::All, prediction.recent_inputs()) {
+                println!("Failed to send player input: {}", e);
+            }
+        }
+    }
+}
+
+system! {
+    fn process_player_inputs(
+        time: res &Time,
+        networking: res &Networking,
+        physics_time: res &PhysicsTime,
+        prediction: res &mut ServerPrediction,
+        commands: commands,
+    ) {
+        let (Some(networking), Some(prediction)) = (networking, prediction) else {
+            return;
+        };
+
+        if let Some(physics_time) = physics_time {
+            prediction.clock.fixed_delta = physics_time.fixed_delta;
+        }
+
+        for event in networking.events() {
+            match event {
+                NetworkingEvent::Connected { target: Target::Single(peer) } => {
+                    let entity = commands.spawn_entity();
+                    commands.add_component(entity, Transform::default());
+                    commands.add_component(entity, InputOwner(*peer));
+                    prediction.players.insert(*peer, entity);
+                    prediction.inputs.insert(*peer, PeerInputs::default());
+                }
+                NetworkingEvent::Disconnected { target: Target::Single(peer) } => {
+                    if let Some(entity) = prediction.players.remove(peer) {
+                        commands.despawn_entity(entity);
+                    }
+                    prediction.inputs.remove(peer);
+                }
+                _ => {}
+            }
+        }
+
+        // without a clock no steps pass, so inputs wait rather than run unmetered
+        if let Some(time) = time {
+            prediction.clock.accumulate(time.delta_seconds);
+            while prediction.clock.consume_step() {
+                prediction.advance();
+            }
+        }
+
+        let mut transforms: BTreeMap<u32, &mut Transform> =
+            unsafe { World::get_components_mut::<Transform>(commands.world) }
+                .into_iter()
+                .collect();
+
+        for (from, PlayerInputs(inputs)) in networking.collect::<PlayerInputs>() {
+            if let Target::Single(peer) = from {
+                prediction.queue(peer, inputs);
+            }
+        }
+
+        // queued inputs keep draining on steps where no packet arrives
+        let players: Vec<(u32, u32)> = prediction.players.iter().map(|(&peer, &entity)| (peer, entity)).collect();
+        for (peer, entity) in players {
+            let Some(transform) = transforms.get_mut(&entity) else {
+                continue;
+            };
+            let Some(tick) = prediction.apply(peer, &mut transform.pos) else {
+                continue;
+            };
+
+            let state = PlayerState { tick, pos: transform.pos };
+            if let Err(e) = networking.send(Reliability::Unreliable, Target::Single(peer), state) {
+                println!("Failed to send player state: {}", e);
+            }
+        }
+    }
+}
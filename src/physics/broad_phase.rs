@@ -0,0 +1,482 @@
+use super::aabb_overlap;
+use glam::{IVec3, Vec3};
+use std::collections::HashMap;
+use std::fmt;
+
+/// How far a body's stored AABB reaches past its real one, so small moves don't touch the
+/// broad phase at all.
+pub const DEFAULT_AABB_MARGIN: f32 = 0.1;
+/// A body covering more grid cells than this is checked against every other body instead of
+/// being filed into each cell.
+const MAX_CELLS_PER_BODY: i32 = 64;
+
+/// Finds which bodies might touch from their AABBs alone. Each body is stored with a fattened
+/// AABB, reaching [`DEFAULT_AABB_MARGIN`] or so past its real one, which only has to change
+/// once the body leaves it.
+pub trait BroadPhase: fmt::Debug {
+    /// Tells the broad phase where a body's AABB is now, adding the body if it's new.
+    fn update(&mut self, entity: u32, min: Vec3, max: Vec3);
+
+    fn remove(&mut self, entity: u32);
+
+    /// Every pair of bodies whose fattened AABBs overlap, lower entity first, sorted and
+    /// without repeats.
+    fn pairs(&self) -> Vec<(u32, u32)>;
+
+    /// Bodies whose fattened AABBs overlap `min`..`max`, sorted by entity.
+    fn query(&self, min: Vec3, max: Vec3) -> Vec<u32>;
+}
+
+fn contains(outer_min: Vec3, outer_max: Vec3, min: Vec3, max: Vec3) -> bool {
+    outer_min.cmple(min).all() && max.cmple(outer_max).all()
+}
+
+fn surface_area(min: Vec3, max: Vec3) -> f32 {
+    let size = max - min;
+    2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+}
+
+const NULL: usize = usize::MAX;
+
+#[derive(Clone, Debug)]
+struct TreeNode {
+    min: Vec3,
+    max: Vec3,
+    parent: usize,
+    children: [usize; 2],
+    /// Leaves are at height 0.
+    height: i32,
+    entity: u32,
+}
+
+impl TreeNode {
+    fn is_leaf(&self) -> bool {
+        self.children[0] == NULL
+    }
+}
+
+/// A bounding volume hierarchy kept balanced as bodies come, go and move. Good all round, and
+/// the one to use when bodies differ a lot in size or are spread unevenly.
+#[derive(Debug)]
+pub struct DynamicTree {
+    margin: f32,
+    nodes: Vec<TreeNode>,
+    free: Vec<usize>,
+    root: usize,
+    leaves: HashMap<u32, usize>,
+}
+
+impl Default for DynamicTree {
+    fn default() -> Self {
+        Self::new(DEFAULT_AABB_MARGIN)
+    }
+}
+
+impl DynamicTree {
+    pub fn new(margin: f32) -> Self {
+        Self {
+            margin: margin.max(0.0),
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: NULL,
+            leaves: HashMap::new(),
+        }
+    }
+
+    fn allocate(&mut self, node: TreeNode) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if parent == NULL {
+            self.root = new;
+            return;
+        }
+        let children = &mut self.nodes[parent].children;
+        let slot = if children[0] == old { 0 } else { 1 };
+        children[slot] = new;
+    }
+
+    /// Recomputes a branch's AABB and height from its children.
+    fn refit(&mut self, index: usize) {
+        let [left, right] = self.nodes[index].children;
+        let (left, right) = (&self.nodes[left], &self.nodes[right]);
+        let (min, max) = (left.min.min(right.min), left.max.max(right.max));
+        let height = 1 + left.height.max(right.height);
+        let node = &mut self.nodes[index];
+        node.min = min;
+        node.max = max;
+        node.height = height;
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        if self.root == NULL {
+            self.root = leaf;
+            self.nodes[leaf].parent = NULL;
+            return;
+        }
+
+        // walk down to the sibling that grows the tree's surface area the least
+        let (leaf_min, leaf_max) = (self.nodes[leaf].min, self.nodes[leaf].max);
+        let mut index = self.root;
+        while !self.nodes[index].is_leaf() {
+            let node = &self.nodes[index];
+            let area = surface_area(node.min, node.max);
+            let combined = surface_area(node.min.min(leaf_min), node.max.max(leaf_max));
+            let cost = 2.0 * combined;
+            let inherited = 2.0 * (combined - area);
+
+            let descend_cost = |child: &TreeNode| {
+                let grown = surface_area(child.min.min(leaf_min), child.max.max(leaf_max));
+                if child.is_leaf() {
+                    grown + inherited
+                } else {
+                    grown - surface_area(child.min, child.max) + inherited
+                }
+            };
+            let [left, right] = node.children;
+            let (left_cost, right_cost) = (
+                descend_cost(&self.nodes[left]),
+                descend_cost(&self.nodes[right]),
+            );
+            if cost < left_cost && cost < right_cost {
+                break;
+            }
+            index = if left_cost <= right_cost { left } else { right };
+        }
+
+        let sibling = index;
+        let old_parent = self.nodes[sibling].parent;
+        let branch = self.allocate(TreeNode {
+            min: self.nodes[sibling].min.min(leaf_min),
+            max: self.nodes[sibling].max.max(leaf_max),
+            parent: old_parent,
+            children: [sibling, leaf],
+            height: self.nodes[sibling].height + 1,
+            entity: 0,
+        });
+        self.replace_child(old_parent, sibling, branch);
+        self.nodes[sibling].parent = branch;
+        self.nodes[leaf].parent = branch;
+        self.fix_upwards(old_parent);
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        if leaf == self.root {
+            self.root = NULL;
+            return;
+        }
+        let parent = self.nodes[leaf].parent;
+        let grandparent = self.nodes[parent].parent;
+        let [left, right] = self.nodes[parent].children;
+        let sibling = if left == leaf { right } else { left };
+
+        self.replace_child(grandparent, parent, sibling);
+        self.nodes[sibling].parent = grandparent;
+        self.free.push(parent);
+        self.fix_upwards(grandparent);
+    }
+
+    /// Refits and rebalances every branch from `index` up to the root.
+    fn fix_upwards(&mut self, mut index: usize) {
+        while index != NULL {
+            index = self.balance(index);
+            self.refit(index);
+            index = self.nodes[index].parent;
+        }
+    }
+
+    /// Rotates the taller child of `index` up when its children's heights differ by more than
+    /// one, returning whichever node now stands where `index` did.
+    fn balance(&mut self, index: usize) -> usize {
+        let node = &self.nodes[index];
+        if node.is_leaf() || node.height < 2 {
+            return index;
+        }
+        let [left, right] = node.children;
+        let difference = self.nodes[right].height - self.nodes[left].height;
+        if difference > 1 {
+            self.rotate_up(index, right)
+        } else if difference < -1 {
+            self.rotate_up(index, left)
+        } else {
+            index
+        }
+    }
+
+    fn rotate_up(&mut self, index: usize, child: usize) -> usize {
+        let [grandchild_a, grandchild_b] = self.nodes[child].children;
+        let (kept, handed_down) =
+            if self.nodes[grandchild_a].height > self.nodes[grandchild_b].height {
+                (grandchild_a, grandchild_b)
+            } else {
+                (grandchild_b, grandchild_a)
+            };
+
+        let parent = self.nodes[index].parent;
+        self.replace_child(parent, index, child);
+        self.nodes[child].parent = parent;
+        self.nodes[child].children = [index, kept];
+        self.nodes[index].parent = child;
+        self.replace_child(index, child, handed_down);
+        self.nodes[handed_down].parent = index;
+
+        self.refit(index);
+        self.refit(child);
+        child
+    }
+
+    /// Calls `found` with every leaf whose AABB overlaps `min`..`max`.
+    fn visit(&self, min: Vec3, max: Vec3, mut found: impl FnMut(&TreeNode)) {
+        if self.root == NULL {
+            return;
+        }
+        let mut stack = vec![self.root];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !aabb_overlap(node.min, node.max, min, max) {
+                continue;
+            }
+            if node.is_leaf() {
+                found(node);
+            } else {
+                stack.extend(node.children);
+            }
+        }
+    }
+}
+
+impl BroadPhase for DynamicTree {
+    fn update(&mut self, entity: u32, min: Vec3, max: Vec3) {
+        let (fat_min, fat_max) = (
+            min - Vec3::splat(self.margin),
+            max + Vec3::splat(self.margin),
+        );
+        let leaf = match self.leaves.get(&entity) {
+            Some(&leaf) => {
+                let node = &self.nodes[leaf];
+                if contains(node.min, node.max, min, max) {
+                    return;
+                }
+                self.remove_leaf(leaf);
+                leaf
+            }
+            None => {
+                let leaf = self.allocate(TreeNode {
+                    min: fat_min,
+                    max: fat_max,
+                    parent: NULL,
+                    children: [NULL; 2],
+                    height: 0,
+                    entity,
+                });
+                self.leaves.insert(entity, leaf);
+                leaf
+            }
+        };
+        self.nodes[leaf].min = fat_min;
+        self.nodes[leaf].max = fat_max;
+        self.insert_leaf(leaf);
+    }
+
+    fn remove(&mut self, entity: u32) {
+        if let Some(leaf) = self.leaves.remove(&entity) {
+            self.remove_leaf(leaf);
+            self.free.push(leaf);
+        }
+    }
+
+    fn pairs(&self) -> Vec<(u32, u32)> {
+        let mut pairs = Vec::new();
+        for &leaf in self.leaves.values() {
+            let node = &self.nodes[leaf];
+            self.visit(node.min, node.max, |other| {
+                if node.entity < other.entity {
+                    pairs.push((node.entity, other.entity));
+                }
+            });
+        }
+        pairs.sort_unstable();
+        pairs
+    }
+
+    fn query(&self, min: Vec3, max: Vec3) -> Vec<u32> {
+        let mut entities = Vec::new();
+        self.visit(min, max, |node| entities.push(node.entity));
+        entities.sort_unstable();
+        entities
+    }
+}
+
+#[derive(Clone, Debug)]
+struct GridProxy {
+    min: Vec3,
+    max: Vec3,
+    /// The first and last cell the body is filed in, or `None` for oversized bodies.
+    cells: Option<(IVec3, IVec3)>,
+}
+
+/// Files bodies into the cubic cells their AABBs cover. Fast when bodies are of a similar size
+/// a bit under `cell_size` and spread fairly evenly, as in crowds and particle piles.
+#[derive(Debug)]
+pub struct UniformGrid {
+    cell_size: f32,
+    margin: f32,
+    cells: HashMap<IVec3, Vec<u32>>,
+    proxies: HashMap<u32, GridProxy>,
+    /// Bodies spanning too many cells to file, such as the ground, sorted by entity.
+    oversized: Vec<u32>,
+}
+
+impl UniformGrid {
+    pub fn new(cell_size: f32, margin: f32) -> Self {
+        Self {
+            cell_size: cell_size.max(f32::EPSILON),
+            margin: margin.max(0.0),
+            cells: HashMap::new(),
+            proxies: HashMap::new(),
+            oversized: Vec::new(),
+        }
+    }
+
+    fn cell_range(&self, min: Vec3, max: Vec3) -> (IVec3, IVec3) {
+        (
+            (min / self.cell_size).floor().as_ivec3(),
+            (max / self.cell_size).floor().as_ivec3(),
+        )
+    }
+
+    fn cell_count((first, last): (IVec3, IVec3)) -> i64 {
+        let size = (last - first + IVec3::ONE).as_i64vec3();
+        size.x * size.y * size.z
+    }
+
+    fn cells_in((first, last): (IVec3, IVec3)) -> impl Iterator<Item = IVec3> {
+        (first.x..=last.x).flat_map(move |x| {
+            (first.y..=last.y)
+                .flat_map(move |y| (first.z..=last.z).map(move |z| IVec3::new(x, y, z)))
+        })
+    }
+
+    fn file(&mut self, entity: u32, proxy: &GridProxy) {
+        match proxy.cells {
+            Some(range) => {
+                for cell in Self::cells_in(range) {
+                    self.cells.entry(cell).or_default().push(entity);
+                }
+            }
+            None => {
+                let index = self.oversized.partition_point(|&other| other < entity);
+                self.oversized.insert(index, entity);
+            }
+        }
+    }
+
+    fn unfile(&mut self, entity: u32, proxy: &GridProxy) {
+        match proxy.cells {
+            Some(range) => {
+                for cell in Self::cells_in(range) {
+                    if let Some(entities) = self.cells.get_mut(&cell) {
+                        entities.retain(|&other| other != entity);
+                        if entities.is_empty() {
+                            self.cells.remove(&cell);
+                        }
+                    }
+                }
+            }
+            None => self.oversized.retain(|&other| other != entity),
+        }
+    }
+}
+
+impl BroadPhase for UniformGrid {
+    fn update(&mut self, entity: u32, min: Vec3, max: Vec3) {
+        if let Some(proxy) = self.proxies.get(&entity) {
+            if contains(proxy.min, proxy.max, min, max) {
+                return;
+            }
+            let proxy = proxy.clone();
+            self.unfile(entity, &proxy);
+        }
+
+        let (min, max) = (
+            min - Vec3::splat(self.margin),
+            max + Vec3::splat(self.margin),
+        );
+        let range = self.cell_range(min, max);
+        let cells = (Self::cell_count(range) <= MAX_CELLS_PER_BODY as i64).then_some(range);
+        let proxy = GridProxy { min, max, cells };
+        self.file(entity, &proxy);
+        self.proxies.insert(entity, proxy);
+    }
+
+    fn remove(&mut self, entity: u32) {
+        if let Some(proxy) = self.proxies.remove(&entity) {
+            self.unfile(entity, &proxy);
+        }
+    }
+
+    fn pairs(&self) -> Vec<(u32, u32)> {
+        let mut pairs = Vec::new();
+        let overlap = |a: u32, b: u32| {
+            let (a, b) = (&self.proxies[&a], &self.proxies[&b]);
+            aabb_overlap(a.min, a.max, b.min, b.max)
+        };
+
+        for entities in self.cells.values() {
+            for (i, &a) in entities.iter().enumerate() {
+                for &b in &entities[i + 1..] {
+                    if overlap(a, b) {
+                        pairs.push((a.min(b), a.max(b)));
+                    }
+                }
+            }
+        }
+        for &a in &self.oversized {
+            for &b in self.proxies.keys() {
+                // two oversized bodies are paired once, from the lower one
+                let checked = self.proxies[&b].cells.is_none() && b <= a;
+                if a != b && !checked && overlap(a, b) {
+                    pairs.push((a.min(b), a.max(b)));
+                }
+            }
+        }
+        // bodies sharing several cells are found in each of them
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+
+    fn query(&self, min: Vec3, max: Vec3) -> Vec<u32> {
+        let mut entities = Vec::new();
+        let range = self.cell_range(min, max);
+        let overlaps = |entity: &u32| {
+            let proxy = &self.proxies[entity];
+            aabb_overlap(proxy.min, proxy.max, min, max)
+        };
+
+        // a query covering more cells than there are bodies is quicker checked body by body
+        if Self::cell_count(range) > self.proxies.len() as i64 {
+            entities.extend(self.proxies.keys().filter(|entity| overlaps(entity)));
+        } else {
+            for cell in Self::cells_in(range) {
+                if let Some(filed) = self.cells.get(&cell) {
+                    entities.extend(filed.iter().filter(|entity| overlaps(entity)));
+                }
+            }
+            entities.extend(self.oversized.iter().filter(|entity| overlaps(entity)));
+        }
+        entities.sort_unstable();
+        entities.dedup();
+        entities
+    }
+}
@@ -0,0 +1,53 @@
+use super::PhysicsBody;
+use crate::*;
+use std::fmt;
+
+/// Which groups a body belongs to and which groups it collides with, as bit masks. Two bodies
+/// only collide when each is a member of a group the other's filter lets through.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CollisionGroups {
+    pub memberships: u32,
+    pub filter: u32,
+}
+
+impl CollisionGroups {
+    pub const ALL: u32 = u32::MAX;
+    pub const NONE: u32 = 0;
+
+    pub fn new(memberships: u32, filter: u32) -> Self {
+        Self { memberships, filter }
+    }
+
+    pub fn interacts_with(&self, other: &CollisionGroups) -> bool {
+        self.memberships & other.filter != 0 && other.memberships & self.filter != 0
+    }
+}
+
+/// Bodies without one are in every group and collide with everything.
+impl Default for CollisionGroups {
+    fn default() -> Self {
+        Self::new(Self::ALL, Self::ALL)
+    }
+}
+
+type PairCallback = dyn Fn(&PhysicsBody, &PhysicsBody) -> bool;
+
+/// Gets the last word on pairs whose collision groups interact: returning false keeps them
+/// out of the broad phase, so they never touch or show up in contact events.
+pub struct PairFilter(Box<PairCallback>);
+
+impl PairFilter {
+    pub fn new(filter: impl Fn(&PhysicsBody, &PhysicsBody) -> bool + 'static) -> Self {
+        Self(Box::new(filter))
+    }
+
+    pub(super) fn allows(&self, a: &PhysicsBody, b: &PhysicsBody) -> bool {
+        (self.0)(a, b)
+    }
+}
+
+impl fmt::Debug for PairFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PairFilter(..)")
+    }
+}
@@ -0,0 +1,544 @@
+use super::solver::{BAUMGARTE, relative_velocity};
+use super::{PhysicsBody, PhysicsWorld};
+use crate::*;
+use glam::{Mat3, Quat, Vec3};
+use std::collections::HashSet;
+
+/// How a joint lets its two bodies move relative to each other.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JointKind {
+    /// Welds the bodies together.
+    Fixed,
+    /// Lets the bodies turn about `axis`, given in the first body's space, optionally only
+    /// between two angles in radians from where they started.
+    Hinge {
+        axis: Vec3,
+        limits: Option<(f32, f32)>,
+    },
+    /// Lets the bodies turn any way about the anchors.
+    Ball,
+    /// Keeps the anchors between `min` and `max` apart: a rope with `min` at zero, a rod with
+    /// the two equal.
+    Distance { min: f32, max: f32 },
+    /// Pulls or pushes the anchors towards `rest_length` apart, with `stiffness` per unit of
+    /// stretch and `damping` per unit of stretching speed.
+    Spring {
+        rest_length: f32,
+        stiffness: f32,
+        damping: f32,
+    },
+}
+
+/// Joins two bodies. It goes on an entity of its own, so a body can have any number of them,
+/// and the bodies it joins don't collide with each other unless `collide_connected` is set.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct Joint {
+    pub entity_a: u32,
+    pub entity_b: u32,
+    /// Where the joint attaches, in each body's own space, before scaling.
+    pub anchor_a: Vec3,
+    pub anchor_b: Vec3,
+    pub kind: JointKind,
+    /// Force and torque beyond which the joint breaks, or `None` for a joint that never does.
+    pub break_force: Option<f32>,
+    pub break_torque: Option<f32>,
+    pub collide_connected: bool,
+    /// Set by the physics once the joint breaks. Clearing it joins the bodies again.
+    pub broken: bool,
+}
+
+impl Joint {
+    pub fn new(entity_a: u32, entity_b: u32, kind: JointKind) -> Self {
+        Self {
+            entity_a,
+            entity_b,
+            anchor_a: Vec3::ZERO,
+            anchor_b: Vec3::ZERO,
+            kind,
+            break_force: None,
+            break_torque: None,
+            collide_connected: false,
+            broken: false,
+        }
+    }
+
+    pub fn with_anchors(mut self, anchor_a: Vec3, anchor_b: Vec3) -> Self {
+        self.anchor_a = anchor_a;
+        self.anchor_b = anchor_b;
+        self
+    }
+
+    pub fn with_break_force(mut self, force: f32) -> Self {
+        self.break_force = Some(force);
+        self
+    }
+
+    pub fn with_break_torque(mut self, torque: f32) -> Self {
+        self.break_torque = Some(torque);
+        self
+    }
+
+    pub fn with_collide_connected(mut self) -> Self {
+        self.collide_connected = true;
+        self
+    }
+}
+
+/// A joint that broke this frame, by the entity holding its [`Joint`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JointBroken {
+    pub joint: u32,
+    pub entity_a: u32,
+    pub entity_b: u32,
+}
+
+/// A joint the physics world is simulating.
+#[derive(Debug)]
+pub(super) struct PhysicsJoint {
+    pub(super) entity: u32,
+    pub(super) joint: Joint,
+    /// The second body's rotation relative to the first's when they were joined, which fixed
+    /// joints hold and hinges measure their angle from.
+    reference: Quat,
+    /// Each row's impulse from last step, to start the next step from.
+    impulses: Vec<f32>,
+}
+
+impl PhysicsJoint {
+    pub(super) fn new(entity: u32, joint: Joint, a: &PhysicsBody, b: &PhysicsBody) -> Self {
+        Self {
+            entity,
+            joint,
+            reference: a.rotation.inverse() * b.rotation,
+            impulses: Vec::new(),
+        }
+    }
+}
+
+/// One degree of freedom a joint takes away: the relative velocity of the anchors along
+/// `linear` plus the relative angular velocity about `angular` is held at zero, or kept from
+/// going negative for one-sided rows.
+struct JointRow {
+    linear: Vec3,
+    angular: Vec3,
+    mass: f32,
+    bias: f32,
+    /// How far the row gives under its own impulse; zero for rigid rows.
+    softness: f32,
+    lower: f32,
+    upper: f32,
+    impulse: f32,
+}
+
+pub(super) struct JointConstraint {
+    joint: usize,
+    a: usize,
+    b: usize,
+    r_a: Vec3,
+    r_b: Vec3,
+    inverse_mass_a: f32,
+    inverse_mass_b: f32,
+    inverse_inertia_a: Mat3,
+    inverse_inertia_b: Mat3,
+    rows: Vec<JointRow>,
+}
+
+impl JointConstraint {
+    /// A row for a constraint that's at `error` now and grows with the row's relative
+    /// velocity. Two-sided rows hold it at zero; one-sided rows keep it from going negative,
+    /// letting a positive error close within the step before pushing.
+    fn row(
+        &self,
+        linear: Vec3,
+        angular: Vec3,
+        error: f32,
+        (lower, upper): (f32, f32),
+        dt: f32,
+    ) -> JointRow {
+        let k = self.inverse_effective_mass(linear, angular);
+        let bias = if error < 0.0 || lower < 0.0 {
+            BAUMGARTE / dt * error
+        } else {
+            error / dt
+        };
+        JointRow {
+            linear,
+            angular,
+            mass: if k > f32::EPSILON { 1.0 / k } else { 0.0 },
+            bias,
+            softness: 0.0,
+            lower,
+            upper,
+            impulse: 0.0,
+        }
+    }
+
+    /// A spring along `direction` that's `stretch` past its rest length, solved as a soft row
+    /// so stiff springs stay stable at any step size.
+    fn spring_row(
+        &self,
+        direction: Vec3,
+        stretch: f32,
+        stiffness: f32,
+        damping: f32,
+        dt: f32,
+    ) -> JointRow {
+        let k = self.inverse_effective_mass(direction, Vec3::ZERO);
+        let softness = 1.0 / (dt * (damping + dt * stiffness));
+        JointRow {
+            linear: direction,
+            angular: Vec3::ZERO,
+            mass: 1.0 / (k + softness),
+            bias: stretch * dt * stiffness * softness,
+            softness,
+            lower: BILATERAL.0,
+            upper: BILATERAL.1,
+            impulse: 0.0,
+        }
+    }
+
+    fn inverse_effective_mass(&self, linear: Vec3, angular: Vec3) -> f32 {
+        let angular_a = self.inverse_inertia_a * self.r_a.cross(linear);
+        let angular_b = self.inverse_inertia_b * self.r_b.cross(linear);
+        (self.inverse_mass_a + self.inverse_mass_b) * linear.length_squared()
+            + self.r_a.cross(linear).dot(angular_a)
+            + self.r_b.cross(linear).dot(angular_b)
+            + angular.dot((self.inverse_inertia_a + self.inverse_inertia_b) * angular)
+    }
+
+    fn linear_force(&self, dt: f32) -> f32 {
+        let impulse: Vec3 = self.rows.iter().map(|row| row.linear * row.impulse).sum();
+        impulse.length() / dt
+    }
+
+    fn torque(&self, dt: f32) -> f32 {
+        let impulse: Vec3 = self.rows.iter().map(|row| row.angular * row.impulse).sum();
+        impulse.length() / dt
+    }
+}
+
+const BILATERAL: (f32, f32) = (f32::NEG_INFINITY, f32::INFINITY);
+const PUSH_ONLY: (f32, f32) = (0.0, f32::INFINITY);
+
+impl PhysicsWorld {
+    pub(super) fn build_joint_constraints(&mut self, dt: f32) -> Vec<JointConstraint> {
+        let mut constraints = Vec::with_capacity(self.joints.len());
+
+        for (index, physics_joint) in self.joints.iter().enumerate() {
+            let joint = &physics_joint.joint;
+            let (Some(&a), Some(&b)) = (
+                self.entity_map.get(&joint.entity_a),
+                self.entity_map.get(&joint.entity_b),
+            ) else {
+                continue;
+            };
+            if a == b {
+                continue;
+            }
+            let (body_a, body_b) = (&self.bodies[a], &self.bodies[b]);
+            if !body_a.is_awake() && !body_b.is_awake() {
+                continue;
+            }
+            let inverse_mass_a = body_a.rigid_body.inverse_mass();
+            let inverse_mass_b = body_b.rigid_body.inverse_mass();
+            if inverse_mass_a + inverse_mass_b <= 0.0 {
+                continue;
+            }
+
+            let mut constraint = JointConstraint {
+                joint: index,
+                a,
+                b,
+                r_a: body_a.rotation * (joint.anchor_a * body_a.scale),
+                r_b: body_b.rotation * (joint.anchor_b * body_b.scale),
+                inverse_mass_a,
+                inverse_mass_b,
+                inverse_inertia_a: body_a.inverse_inertia_world(),
+                inverse_inertia_b: body_b.inverse_inertia_world(),
+                rows: Vec::new(),
+            };
+            let separation =
+                (body_b.position + constraint.r_b) - (body_a.position + constraint.r_a);
+            // the rotation taking b from where the joint wants it to where it is
+            let mut twist = body_b.rotation * (body_a.rotation * physics_joint.reference).inverse();
+            if twist.w < 0.0 {
+                twist = -twist;
+            }
+            let rotation_error = 2.0 * twist.xyz();
+
+            match joint.kind {
+                JointKind::Fixed | JointKind::Ball | JointKind::Hinge { .. } => {
+                    for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+                        let row =
+                            constraint.row(axis, Vec3::ZERO, separation.dot(axis), BILATERAL, dt);
+                        constraint.rows.push(row);
+                    }
+                }
+                JointKind::Distance { min, max } => {
+                    let length = separation.length();
+                    let direction = if length > f32::EPSILON {
+                        separation / length
+                    } else {
+                        Vec3::Y
+                    };
+                    if min >= max {
+                        constraint.rows.push(constraint.row(
+                            direction,
+                            Vec3::ZERO,
+                            length - min,
+                            BILATERAL,
+                            dt,
+                        ));
+                    } else {
+                        constraint.rows.push(constraint.row(
+                            direction,
+                            Vec3::ZERO,
+                            length - min,
+                            PUSH_ONLY,
+                            dt,
+                        ));
+                        constraint.rows.push(constraint.row(
+                            -direction,
+                            Vec3::ZERO,
+                            max - length,
+                            PUSH_ONLY,
+                            dt,
+                        ));
+                    }
+                }
+                JointKind::Spring {
+                    rest_length,
+                    stiffness,
+                    damping,
+                } => {
+                    let length = separation.length();
+                    let direction = if length > f32::EPSILON {
+                        separation / length
+                    } else {
+                        Vec3::Y
+                    };
+                    if stiffness > 0.0 || damping > 0.0 {
+                        constraint.rows.push(constraint.spring_row(
+                            direction,
+                            length - rest_length,
+                            stiffness.max(0.0),
+                            damping.max(0.0),
+                            dt,
+                        ));
+                    }
+                }
+            }
+
+            match joint.kind {
+                JointKind::Fixed => {
+                    for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+                        let row = constraint.row(
+                            Vec3::ZERO,
+                            axis,
+                            rotation_error.dot(axis),
+                            BILATERAL,
+                            dt,
+                        );
+                        constraint.rows.push(row);
+                    }
+                }
+                JointKind::Hinge { axis, limits } => {
+                    let axis_a = (body_a.rotation * axis).normalize_or(Vec3::Y);
+                    let axis_b = (body_b.rotation * (physics_joint.reference.inverse() * axis))
+                        .normalize_or(Vec3::Y);
+                    let misalignment = axis_a.cross(axis_b);
+                    let (t1, t2) = axis_a.any_orthonormal_pair();
+                    for tangent in [t1, t2] {
+                        let row = constraint.row(
+                            Vec3::ZERO,
+                            tangent,
+                            misalignment.dot(tangent),
+                            BILATERAL,
+                            dt,
+                        );
+                        constraint.rows.push(row);
+                    }
+                    if let Some((lower, upper)) = limits {
+                        let angle = 2.0 * twist.xyz().dot(axis_a).atan2(twist.w);
+                        constraint.rows.push(constraint.row(
+                            Vec3::ZERO,
+                            axis_a,
+                            angle - lower,
+                            PUSH_ONLY,
+                            dt,
+                        ));
+                        constraint.rows.push(constraint.row(
+                            Vec3::ZERO,
+                            -axis_a,
+                            upper - angle,
+                            PUSH_ONLY,
+                            dt,
+                        ));
+                    }
+                }
+                _ => {}
+            }
+
+            if physics_joint.impulses.len() == constraint.rows.len() {
+                for (row, &impulse) in constraint.rows.iter_mut().zip(&physics_joint.impulses) {
+                    row.impulse = impulse.clamp(row.lower, row.upper);
+                }
+            }
+            constraints.push(constraint);
+        }
+
+        for constraint in &constraints {
+            for row in &constraint.rows {
+                self.apply_joint_impulse(
+                    constraint,
+                    row.linear * row.impulse,
+                    row.angular * row.impulse,
+                );
+            }
+        }
+        constraints
+    }
+
+    pub(super) fn solve_joint(&mut self, constraint: &mut JointConstraint) {
+        let (a, b) = (constraint.a, constraint.b);
+        for i in 0..constraint.rows.len() {
+            let row = &constraint.rows[i];
+            let (body_a, body_b) = (&self.bodies[a], &self.bodies[b]);
+            let velocity = relative_velocity(body_a, body_b, constraint.r_a, constraint.r_b)
+                .dot(row.linear)
+                + (body_b.angular_velocity.0 - body_a.angular_velocity.0).dot(row.angular);
+            let lambda = -(velocity + row.bias + row.softness * row.impulse) * row.mass;
+            let accumulated = (row.impulse + lambda).clamp(row.lower, row.upper);
+            let applied = accumulated - row.impulse;
+            let (linear, angular) = (row.linear * applied, row.angular * applied);
+            constraint.rows[i].impulse = accumulated;
+            self.apply_joint_impulse(constraint, linear, angular);
+        }
+    }
+
+    /// Keeps each joint's impulses for the next step, and breaks the joints that had to pull
+    /// harder than they can take.
+    pub(super) fn finish_joints(&mut self, constraints: &[JointConstraint], dt: f32) {
+        let mut broken = Vec::new();
+        for constraint in constraints {
+            let physics_joint = &mut self.joints[constraint.joint];
+            physics_joint.impulses = constraint.rows.iter().map(|row| row.impulse).collect();
+
+            let joint = &physics_joint.joint;
+            let too_much_force = joint
+                .break_force
+                .is_some_and(|limit| constraint.linear_force(dt) > limit);
+            let too_much_torque = joint
+                .break_torque
+                .is_some_and(|limit| constraint.torque(dt) > limit);
+            if too_much_force || too_much_torque {
+                broken.push(constraint.joint);
+            }
+        }
+
+        if broken.is_empty() {
+            return;
+        }
+        for &index in &broken {
+            let physics_joint = &self.joints[index];
+            self.joint_events.push(JointBroken {
+                joint: physics_joint.entity,
+                entity_a: physics_joint.joint.entity_a,
+                entity_b: physics_joint.joint.entity_b,
+            });
+        }
+        // removing from the back keeps the earlier indices valid
+        for index in broken.into_iter().rev() {
+            self.joints.remove(index);
+        }
+        self.update_jointed_pairs();
+    }
+
+    /// Takes the joints from the ECS, keeping the ones that haven't changed as they were. A joint
+    /// waits for both of its bodies to exist, and wakes them when it's added or taken away.
+    /// Returns whether any joint was added, changed or taken away.
+    pub(super) fn sync_joints<'j>(&mut self, joints: impl Iterator<Item = (u32, &'j Joint)>) -> bool {
+        let mut seen = HashSet::new();
+        let mut changed = false;
+        for (entity, joint) in joints {
+            let (Some(&a), Some(&b)) = (
+                self.entity_map.get(&joint.entity_a),
+                self.entity_map.get(&joint.entity_b),
+            ) else {
+                continue;
+            };
+            if joint.broken {
+                continue;
+            }
+            seen.insert(entity);
+
+            let index = self
+                .joints
+                .partition_point(|physics_joint| physics_joint.entity < entity);
+            if self
+                .joints
+                .get(index)
+                .is_some_and(|physics_joint| physics_joint.entity == entity)
+            {
+                if self.joints[index].joint == *joint {
+                    continue;
+                }
+                self.joints.remove(index);
+            }
+            let physics_joint =
+                PhysicsJoint::new(entity, joint.clone(), &self.bodies[a], &self.bodies[b]);
+            self.joints.insert(index, physics_joint);
+            self.bodies[a].wake();
+            self.bodies[b].wake();
+            changed = true;
+        }
+
+        let mut removed = Vec::new();
+        self.joints.retain(|physics_joint| {
+            let keep = seen.contains(&physics_joint.entity);
+            if !keep {
+                removed.push((physics_joint.joint.entity_a, physics_joint.joint.entity_b));
+            }
+            keep
+        });
+        for (entity_a, entity_b) in removed {
+            changed = true;
+            for entity in [entity_a, entity_b] {
+                if let Some(&index) = self.entity_map.get(&entity) {
+                    self.bodies[index].wake();
+                }
+            }
+        }
+        if changed {
+            self.update_jointed_pairs();
+        }
+        changed
+    }
+
+    /// Pairs of bodies a joint keeps from colliding with each other.
+    pub(super) fn update_jointed_pairs(&mut self) {
+        self.jointed_pairs = self
+            .joints
+            .iter()
+            .filter(|physics_joint| !physics_joint.joint.collide_connected)
+            .map(|physics_joint| {
+                let (a, b) = (physics_joint.joint.entity_a, physics_joint.joint.entity_b);
+                (a.min(b), a.max(b))
+            })
+            .collect();
+    }
+
+    /// Pushes body b by `linear` at its anchor and turns it by `angular`, and body a back by as
+    /// much.
+    fn apply_joint_impulse(&mut self, constraint: &JointConstraint, linear: Vec3, angular: Vec3) {
+        let body_a = &mut self.bodies[constraint.a];
+        body_a.velocity.0 -= linear * constraint.inverse_mass_a;
+        body_a.angular_velocity.0 -=
+            constraint.inverse_inertia_a * (constraint.r_a.cross(linear) + angular);
+
+        let body_b = &mut self.bodies[constraint.b];
+        body_b.velocity.0 += linear * constraint.inverse_mass_b;
+        body_b.angular_velocity.0 +=
+            constraint.inverse_inertia_b * (constraint.r_b.cross(linear) + angular);
+    }
+}
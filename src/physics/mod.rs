@@ -1,11 +1,28 @@
 use crate::*;
-use glam::{Mat4, Quat, Vec3};
-use std::{cmp::Ordering, collections::HashMap};
-
+use glam::{Mat3, Mat4, Quat, Vec3};
+use std::collections::{HashMap, HashSet};
+
+mod broad_phase;
+mod groups;
+mod joints;
+pub mod narrow_phase;
+mod query;
+mod sleep;
+mod solver;
 pub mod test;
-
+mod triggers;
+
+pub use broad_phase::{BroadPhase, DEFAULT_AABB_MARGIN, DynamicTree, UniformGrid};
+pub use groups::{CollisionGroups, PairFilter};
+pub use joints::{Joint, JointBroken, JointKind};
+pub use narrow_phase::{ContactManifold, collide};
+pub use query::{QueryFilter, QueryHit};
+pub use sleep::SleepThresholds;
+pub use triggers::TriggerEvent;
 pub use test::{BodyHandle, BodyInit, BodyState, PhysicsTestWorld};
 
+use narrow_phase::Shape;
+
 const DEFAULT_GRAVITY: Vec3 = Vec3::new(0.0, -9.81, 0.0);
 const DEFAULT_FIXED_DT: f32 = 1.0 / 60.0;
 
@@ -25,7 +42,8 @@ impl Plugin for PhysicsPlugin {
     }
 }
 
-#[derive(Component)]
+#[replicate]
+#[derive(Component, Serialize, Deserialize)]
 pub struct Transform {
     pub pos: Vec3,
     pub scale: Vec3,
@@ -95,7 +113,7 @@ pub enum BodyType {
     Static,
 }
 
-#[derive(Component, Clone, Debug)]
+#[derive(Component, Clone, Debug, PartialEq)]
 pub struct RigidBody {
     pub body_type: BodyType,
     pub mass: f32,
@@ -129,7 +147,7 @@ impl RigidBody {
     }
 }
 
-#[derive(Component, Clone, Debug)]
+#[derive(Component, Clone, Debug, PartialEq)]
 pub enum Collider {
     Sphere { radius: f32 },
     Box { half_extents: Vec3 },
@@ -137,6 +155,45 @@ pub enum Collider {
 }
 
 impl Collider {
+    /// Moments of inertia about the collider's own axes, for a solid body of `mass` stretched
+    /// by `scale` the same way the narrow phase stretches the shape.
+    pub fn principal_inertia(&self, mass: f32, scale: Vec3) -> Vec3 {
+        let scale = scale.abs();
+        match *self {
+            Collider::Sphere { radius } => {
+                let radius = radius.abs() * scale.max_element();
+                Vec3::splat(0.4 * mass * radius * radius)
+            }
+            Collider::Box { half_extents } => {
+                let size = half_extents.abs() * scale * 2.0;
+                let squared = size * size;
+                Vec3::new(squared.y + squared.z, squared.x + squared.z, squared.x + squared.y) * (mass / 12.0)
+            }
+            Collider::Capsule {
+                half_height,
+                radius,
+            } => {
+                // a cylinder and two hemispheres sharing the mass by volume
+                let radius = radius.abs() * scale.x.max(scale.z);
+                let height = half_height.abs() * scale.y * 2.0;
+                let cylinder_volume = std::f32::consts::PI * radius * radius * height;
+                let sphere_volume = 4.0 / 3.0 * std::f32::consts::PI * radius.powi(3);
+                let total_volume = cylinder_volume + sphere_volume;
+                if total_volume <= f32::EPSILON {
+                    return Vec3::ZERO;
+                }
+                let cylinder_mass = mass * cylinder_volume / total_volume;
+                let sphere_mass = mass - cylinder_mass;
+                let r2 = radius * radius;
+
+                let axial = cylinder_mass * r2 * 0.5 + sphere_mass * 0.4 * r2;
+                let transverse = cylinder_mass * (height * height / 12.0 + r2 / 4.0)
+                    + sphere_mass * (0.4 * r2 + height * height / 4.0 + 3.0 * height * radius / 8.0);
+                Vec3::new(transverse, axial, transverse)
+            }
+        }
+    }
+
     pub fn sphere(radius: f32) -> Self {
         Self::Sphere { radius }
     }
@@ -162,15 +219,46 @@ pub struct AngularVelocity(pub Vec3);
 #[derive(Component, Clone, Copy, Debug, Default)]
 pub struct ForceAccumulator(pub Vec3);
 
-#[derive(Component, Clone, Debug, Default)]
+impl ForceAccumulator {
+    /// Pushes at a world-space `point` on a body centered at `center`, which spins it as well
+    /// unless the force points through the center.
+    pub fn add_at_point(&mut self, torque: &mut TorqueAccumulator, center: Vec3, force: Vec3, point: Vec3) {
+        self.0 += force;
+        torque.0 += (point - center).cross(force);
+    }
+}
+
+/// Torque applied over the next physics step, in world space. Cleared once the step has used
+/// it, like [`ForceAccumulator`].
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct TorqueAccumulator(pub Vec3);
+
+/// Bodies without one bounce like clay and slide like wood.
+#[derive(Component, Clone, Debug)]
 pub struct PhysicsMaterial {
     pub restitution: f32,
     pub friction: f32,
 }
 
+impl Default for PhysicsMaterial {
+    fn default() -> Self {
+        Self {
+            restitution: 0.0,
+            friction: 0.5,
+        }
+    }
+}
+
+/// Whether the body is asleep: the physics step writes this back every frame, and setting it
+/// puts a body to sleep or wakes it.
 #[derive(Component, Clone, Copy, Debug, Default)]
 pub struct Sleeping(pub bool);
 
+/// Marks a collider that detects overlaps without pushing anything, reporting them as
+/// [`TriggerEvent`]s instead of contacts.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Sensor;
+
 #[derive(Component, Clone, Copy, Debug, Default)]
 pub struct PhysicsProxy;
 
@@ -185,60 +273,118 @@ pub struct PhysicsBody {
     pub rotation: Quat,
     pub scale: Vec3,
     pub accumulated_force: Vec3,
+    pub accumulated_torque: Vec3,
+    pub material: PhysicsMaterial,
+    pub groups: CollisionGroups,
+    pub sensor: bool,
+    /// Principal moments of inertia, about the body's own axes.
+    pub inertia: Vec3,
+    /// Sleeping bodies aren't moved or pushed until something wakes them.
+    pub sleeping: bool,
+    /// How long the body has been still enough to sleep.
+    sleep_timer: f32,
+    /// The `Transform` pose and `Sleeping` flag last read from or written to the ECS, to tell
+    /// gameplay's changes from the step's.
+    synced_pose: (Vec3, Quat),
+    synced_sleeping: bool,
+    /// Tick of the last sync that found the body's entity, to tell which entities are gone.
+    seen_at: Tick,
 }
 
 impl PhysicsBody {
-    fn new(
-        entity: u32,
-        rigid_body: RigidBody,
-        collider: Collider,
-        transform: &Transform,
-        velocity: Velocity,
-        angular_velocity: AngularVelocity,
-        accumulated_force: Vec3,
-    ) -> Self {
+    fn new(entity: u32, rigid_body: RigidBody, collider: Collider, transform: &Transform) -> Self {
+        let inertia = collider.principal_inertia(rigid_body.mass, transform.scale);
         Self {
             entity,
             rigid_body,
             collider,
-            velocity,
-            angular_velocity,
+            velocity: Velocity::default(),
+            angular_velocity: AngularVelocity::default(),
             position: transform.pos,
             rotation: transform.rot,
             scale: transform.scale,
-            accumulated_force,
+            accumulated_force: Vec3::ZERO,
+            accumulated_torque: Vec3::ZERO,
+            material: PhysicsMaterial::default(),
+            groups: CollisionGroups::default(),
+            sensor: false,
+            inertia,
+            sleeping: false,
+            sleep_timer: 0.0,
+            synced_pose: (transform.pos, transform.rot),
+            synced_sleeping: false,
+            seen_at: 0,
         }
     }
 
-    fn aabb(&self) -> (Vec3, Vec3) {
-        match &self.collider {
-            Collider::Sphere { radius } => {
-                let r = radius.abs() * self.scale.max_element();
-                let extents = Vec3::splat(r);
-                (self.position - extents, self.position + extents)
-            }
-            Collider::Box { half_extents } => {
-                let extents = Vec3::new(
-                    half_extents.x * self.scale.x.abs(),
-                    half_extents.y * self.scale.y.abs(),
-                    half_extents.z * self.scale.z.abs(),
-                );
-                (self.position - extents, self.position + extents)
-            }
-            Collider::Capsule {
-                half_height,
-                radius,
-            } => {
-                let radial = radius.abs();
-                let half_height = half_height.abs();
-                let extents = Vec3::new(
-                    radial * self.scale.x.abs(),
-                    (half_height + radial) * self.scale.y.abs(),
-                    radial * self.scale.z.abs(),
-                );
-                (self.position - extents, self.position + extents)
-            }
+    /// Whether the step moves this body at all; static bodies never do.
+    pub fn is_awake(&self) -> bool {
+        !self.rigid_body.is_static() && !self.sleeping
+    }
+
+    pub fn wake(&mut self) {
+        self.sleeping = false;
+        self.sleep_timer = 0.0;
+    }
+
+    /// Moves the body without sweeping it through what's in between, keeping its velocity.
+    pub fn teleport(&mut self, position: Vec3, rotation: Quat) {
+        self.position = position;
+        self.rotation = rotation;
+        self.wake();
+    }
+
+    /// Swaps the body's mass, shape or scale, working the inertia out again.
+    fn reshape(&mut self, rigid_body: RigidBody, collider: Collider, scale: Vec3) {
+        self.inertia = collider.principal_inertia(rigid_body.mass, scale);
+        self.rigid_body = rigid_body;
+        self.collider = collider;
+        self.scale = scale;
+        self.wake();
+    }
+
+    /// Inverse inertia tensor in world space; zero for static bodies, which nothing can spin.
+    pub fn inverse_inertia_world(&self) -> Mat3 {
+        if self.rigid_body.is_static() {
+            return Mat3::ZERO;
+        }
+        let inverse = Vec3::select(self.inertia.cmpgt(Vec3::ZERO), self.inertia.recip(), Vec3::ZERO);
+        let rotation = Mat3::from_quat(self.rotation);
+        rotation * Mat3::from_diagonal(inverse) * rotation.transpose()
+    }
+
+    pub fn angular_momentum(&self) -> Vec3 {
+        let rotation = Mat3::from_quat(self.rotation);
+        rotation * (self.inertia * (rotation.transpose() * self.angular_velocity.0))
+    }
+
+    /// Changes the velocities as if `impulse` hit the body at the world-space `point`.
+    pub fn apply_impulse_at_point(&mut self, impulse: Vec3, point: Vec3) {
+        if self.rigid_body.is_static() {
+            return;
         }
+        self.velocity.0 += impulse * self.rigid_body.inverse_mass();
+        self.apply_angular_impulse((point - self.position).cross(impulse));
+    }
+
+    pub fn apply_angular_impulse(&mut self, impulse: Vec3) {
+        self.wake();
+        self.angular_velocity.0 += self.inverse_inertia_world() * impulse;
+    }
+
+    /// Adds to the force and torque used by the next step.
+    pub fn apply_force_at_point(&mut self, force: Vec3, point: Vec3) {
+        self.wake();
+        self.accumulated_force += force;
+        self.accumulated_torque += (point - self.position).cross(force);
+    }
+
+    fn shape(&self) -> Shape {
+        Shape::new(&self.collider, self.position, self.rotation, self.scale)
+    }
+
+    fn aabb(&self) -> (Vec3, Vec3) {
+        self.shape().aabb()
     }
 }
 
@@ -258,16 +404,34 @@ pub struct PhysicsWorld {
     bodies: Vec<PhysicsBody>,
     entity_map: HashMap<u32, usize>,
     broad_phase_pairs: Vec<(u32, u32)>,
+    /// Fattened body AABBs, shared by the broad phase and spatial queries.
+    broad_phase: Box<dyn BroadPhase>,
+    contacts: Vec<PhysicsContactEvent>,
+    /// Sensors and the bodies overlapping them, as of the last narrow phase.
+    sensor_overlaps: Vec<(u32, u32)>,
+    /// Sensor overlaps as of the last step, which trigger events are the difference from.
+    triggered: Vec<(u32, u32)>,
+    /// Trigger events from this frame's steps, in order.
+    trigger_events: Vec<TriggerEvent>,
+    /// Joints by the entity holding them.
+    joints: Vec<joints::PhysicsJoint>,
+    /// Bodies joined by a joint that keeps them from colliding, lower entity first.
+    jointed_pairs: HashSet<(u32, u32)>,
+    /// Joints that broke in this frame's steps, in order.
+    joint_events: Vec<JointBroken>,
+    /// Impulses the solver settled on last step, per pair, to start the next step from.
+    warm_start: HashMap<(u32, u32), Vec<solver::CachedImpulse>>,
+    sleep_thresholds: SleepThresholds,
+    pair_filter: Option<PairFilter>,
+    /// Tick of the last sync from the ECS; components untouched since are already in the bodies.
+    last_synced: Tick,
+    /// Bodies the last sync from the ECS found changed.
+    synced_bodies: usize,
 }
 
 impl Default for PhysicsWorld {
     fn default() -> Self {
-        Self {
-            gravity: DEFAULT_GRAVITY,
-            bodies: Vec::new(),
-            entity_map: HashMap::new(),
-            broad_phase_pairs: Vec::new(),
-        }
+        Self::new(DEFAULT_GRAVITY)
     }
 }
 
@@ -278,6 +442,19 @@ impl PhysicsWorld {
             bodies: Vec::new(),
             entity_map: HashMap::new(),
             broad_phase_pairs: Vec::new(),
+            broad_phase: Box::new(DynamicTree::default()),
+            contacts: Vec::new(),
+            sensor_overlaps: Vec::new(),
+            triggered: Vec::new(),
+            trigger_events: Vec::new(),
+            joints: Vec::new(),
+            jointed_pairs: HashSet::new(),
+            joint_events: Vec::new(),
+            warm_start: HashMap::new(),
+            sleep_thresholds: SleepThresholds::default(),
+            pair_filter: None,
+            last_synced: 0,
+            synced_bodies: 0,
         }
     }
 
@@ -289,10 +466,42 @@ impl PhysicsWorld {
         self.gravity = gravity;
     }
 
+    pub fn sleep_thresholds(&self) -> SleepThresholds {
+        self.sleep_thresholds
+    }
+
+    pub fn set_sleep_thresholds(&mut self, thresholds: SleepThresholds) {
+        self.sleep_thresholds = thresholds;
+    }
+
+    /// Lets `filter` veto pairs whose collision groups would otherwise collide.
+    pub fn set_pair_filter(&mut self, filter: PairFilter) {
+        self.pair_filter = Some(filter);
+    }
+
+    pub fn clear_pair_filter(&mut self) {
+        self.pair_filter = None;
+    }
+
+    /// Swaps the broad phase, a [`DynamicTree`] by default, moving every body over to it.
+    pub fn set_broad_phase(&mut self, broad_phase: impl BroadPhase + 'static) {
+        self.broad_phase = Box::new(broad_phase);
+        for body in &self.bodies {
+            let (min, max) = body.aabb();
+            self.broad_phase.update(body.entity, min, max);
+        }
+    }
+
     pub fn body_count(&self) -> usize {
         self.bodies.len()
     }
 
+    /// How many bodies the last sync from the ECS took changes from, or added. Bodies nothing
+    /// touched since the sync before are skipped.
+    pub fn synced_body_count(&self) -> usize {
+        self.synced_bodies
+    }
+
     pub fn get_body(&self, entity: u32) -> Option<&PhysicsBody> {
         self.entity_map
             .get(&entity)
@@ -307,10 +516,37 @@ impl PhysicsWorld {
         &self.broad_phase_pairs
     }
 
-    fn clear(&mut self) {
-        self.bodies.clear();
-        self.entity_map.clear();
-        self.broad_phase_pairs.clear();
+    /// Broad phase pairs that actually touch, in the same order.
+    pub fn contacts(&self) -> &[PhysicsContactEvent] {
+        &self.contacts
+    }
+
+    /// Sensors and the bodies overlapping them, sensor first.
+    pub fn sensor_overlaps(&self) -> &[(u32, u32)] {
+        &self.sensor_overlaps
+    }
+
+    pub fn trigger_events(&self) -> &[TriggerEvent] {
+        &self.trigger_events
+    }
+
+    pub fn joint_count(&self) -> usize {
+        self.joints.len()
+    }
+
+    pub fn joint_events(&self) -> &[JointBroken] {
+        &self.joint_events
+    }
+
+    /// Moves a body to a new pose without breaking the contacts, warm starting or sensor
+    /// overlaps it had, so it carries on as the same body. Returns false if there's no body for
+    /// `entity`. Queries see the new pose once collisions are next updated.
+    pub fn teleport(&mut self, entity: u32, position: Vec3, rotation: Quat) -> bool {
+        let Some(&index) = self.entity_map.get(&entity) else {
+            return false;
+        };
+        self.bodies[index].teleport(position, rotation);
+        true
     }
 
     fn add_body(&mut self, body: PhysicsBody) {
@@ -319,46 +555,126 @@ impl PhysicsWorld {
         self.bodies.push(body);
     }
 
-    fn rebuild_broad_phase(&mut self) {
-        self.broad_phase_pairs.clear();
-
-        let mut entries: Vec<_> = self
+    /// Drops the bodies whose entities the sync at `tick` didn't find, of which there are
+    /// `bodies.len() - seen`, returning whether there were any. Their sensor overlaps are kept,
+    /// so the next step reports them leaving.
+    fn remove_bodies_unseen(&mut self, tick: Tick, seen: usize) -> bool {
+        if self.bodies.len() == seen {
+            return false;
+        }
+        for body in &self.bodies {
+            if body.seen_at != tick {
+                self.broad_phase.remove(body.entity);
+            }
+        }
+        self.bodies.retain(|body| body.seen_at == tick);
+        self.entity_map = self
             .bodies
             .iter()
-            .map(|body| {
-                let (min, max) = body.aabb();
-                (min, max, body.entity)
-            })
+            .enumerate()
+            .map(|(index, body)| (body.entity, index))
             .collect();
+        let entity_map = &self.entity_map;
+        self.warm_start
+            .retain(|(entity_a, entity_b), _| entity_map.contains_key(entity_a) && entity_map.contains_key(entity_b));
+        true
+    }
 
-        entries.sort_by(|a, b| {
-            a.0.x
-                .partial_cmp(&b.0.x)
-                .unwrap_or(Ordering::Equal)
-                .then_with(|| a.2.cmp(&b.2))
+    /// Brings the broad phase up to date, keeping the pairs whose real AABBs overlap.
+    fn update_broad_phase(&mut self) {
+        let aabbs: Vec<(Vec3, Vec3)> = self.bodies.iter().map(PhysicsBody::aabb).collect();
+        for (body, &(min, max)) in self.bodies.iter().zip(&aabbs) {
+            self.broad_phase.update(body.entity, min, max);
+        }
+
+        let mut pairs = self.broad_phase.pairs();
+        pairs.retain(|&(a, b)| {
+            let ((min_a, max_a), (min_b, max_b)) = (aabbs[self.entity_map[&a]], aabbs[self.entity_map[&b]]);
+            aabb_overlap(min_a, max_a, min_b, max_b) && self.may_collide(a, b)
         });
+        self.broad_phase_pairs = pairs;
+    }
 
-        for i in 0..entries.len() {
-            let (min_a, max_a, ent_a) = entries[i];
-            for j in (i + 1)..entries.len() {
-                let (min_b, max_b, ent_b) = entries[j];
-                if min_b.x > max_a.x {
-                    break;
-                }
+    fn may_collide(&self, entity_a: u32, entity_b: u32) -> bool {
+        let (Some(a), Some(b)) = (self.get_body(entity_a), self.get_body(entity_b)) else {
+            return false;
+        };
+        a.groups.interacts_with(&b.groups)
+            && !self.jointed_pairs.contains(&(entity_a.min(entity_b), entity_a.max(entity_b)))
+            && self.pair_filter.as_ref().is_none_or(|filter| filter.allows(a, b))
+    }
+
+    fn run_narrow_phase(&mut self) {
+        self.contacts.clear();
+        self.sensor_overlaps.clear();
+
+        for &(entity_a, entity_b) in &self.broad_phase_pairs {
+            let (Some(a), Some(b)) = (self.get_body(entity_a), self.get_body(entity_b)) else {
+                continue;
+            };
+            // nothing can push two static bodies apart, but gameplay moves static bodies by
+            // hand, so a static sensor still sees them come and go
+            if a.rigid_body.is_static() && b.rigid_body.is_static() && !a.sensor && !b.sensor {
+                continue;
+            }
 
-                if aabb_overlap(min_a, max_a, min_b, max_b) {
-                    let pair = if ent_a < ent_b {
-                        (ent_a, ent_b)
-                    } else {
-                        (ent_b, ent_a)
-                    };
-                    self.broad_phase_pairs.push(pair);
+            if a.sensor || b.sensor {
+                if a.shape().collide(&b.shape()).is_some() {
+                    let pair = if a.sensor { (entity_a, entity_b) } else { (entity_b, entity_a) };
+                    self.sensor_overlaps.push(pair);
                 }
+            } else if let Some(manifold) = a.shape().collide(&b.shape()) {
+                self.contacts.push(PhysicsContactEvent {
+                    entity_a,
+                    entity_b,
+                    normal: manifold.normal,
+                    depth: manifold.depth,
+                    points: manifold.points,
+                });
             }
         }
+    }
 
-        self.broad_phase_pairs.sort();
-        self.broad_phase_pairs.dedup();
+    fn update_collisions(&mut self) {
+        self.update_broad_phase();
+        self.run_narrow_phase();
+    }
+
+    fn integrate_velocities(&mut self, dt: f32) {
+        for body in self.bodies.iter_mut() {
+            if !body.is_awake() {
+                continue;
+            }
+
+            let inverse_mass = body.rigid_body.inverse_mass();
+            let external_acceleration = body.accumulated_force * inverse_mass;
+            body.velocity.0 += (self.gravity + external_acceleration) * dt;
+            body.angular_velocity.0 += body.inverse_inertia_world() * body.accumulated_torque * dt;
+            body.accumulated_force = Vec3::ZERO;
+            body.accumulated_torque = Vec3::ZERO;
+        }
+    }
+
+    fn integrate_positions(&mut self, dt: f32) {
+        for body in self.bodies.iter_mut() {
+            if !body.is_awake() {
+                continue;
+            }
+
+            body.position += body.velocity.0 * dt;
+
+            let angular_speed = body.angular_velocity.0.length();
+            if angular_speed > f32::EPSILON {
+                // angular momentum is what's conserved, so a body tumbling about anything but
+                // a principal axis changes its angular velocity as it turns
+                let momentum = body.angular_momentum();
+                let axis = body.angular_velocity.0 / angular_speed;
+                let delta_angle = angular_speed * dt;
+                let delta_rot = Quat::from_axis_angle(axis, delta_angle);
+                body.rotation = (delta_rot * body.rotation).normalize();
+                body.angular_velocity.0 = body.inverse_inertia_world() * momentum;
+            }
+        }
     }
 }
 
@@ -396,12 +712,18 @@ impl PhysicsTime {
 pub struct PhysicsEvents {
     pub contacts: Vec<PhysicsContactEvent>,
     pub broad_phase_pairs: Vec<(u32, u32)>,
+    pub triggers: Vec<TriggerEvent>,
+    pub joints_broken: Vec<JointBroken>,
 }
 
+/// Two bodies that touch. `normal` points from `entity_a` towards `entity_b`.
 #[derive(Clone, Debug, Default)]
 pub struct PhysicsContactEvent {
     pub entity_a: u32,
     pub entity_b: u32,
+    pub normal: Vec3,
+    pub depth: f32,
+    pub points: Vec<Vec3>,
 }
 
 #[derive(Default, Resource, Debug)]
@@ -409,6 +731,11 @@ pub struct PhysicsDebugSettings {
     pub show_contacts: bool,
 }
 
+/// Whether the entity's `T` was added, removed or borrowed mutably at `since` or later.
+fn changed_since<T: Component>(entity: &Entity, since: Tick) -> bool {
+    entity.changed_tick(get_component_id::<T>()) >= since
+}
+
 system!(
     fn sync_ecs_to_physics(
         physics_world: res &mut PhysicsWorld,
@@ -418,31 +745,114 @@ system!(
             &RigidBody,
             &Collider,
             &Velocity,
-            &AngularVelocity,
-            &mut ForceAccumulator
-        )
+            &AngularVelocity
+        ),
+        forces: query (&EntityId, &mut ForceAccumulator),
+        torques: query (&EntityId, &mut TorqueAccumulator),
+        joints: query (&EntityId, &Joint),
+        commands: commands
     ) {
         let Some(world) = physics_world else { return; };
 
-        world.clear();
-
-        for (entity_id, transform, rigid_body, collider, velocity, angular_velocity, force_accumulator) in bodies {
-            let accumulated_force = force_accumulator.0;
-            force_accumulator.0 = Vec3::ZERO;
-
-            let body = PhysicsBody::new(
-                entity_id.get(),
-                rigid_body.clone(),
-                collider.clone(),
-                transform,
-                *velocity,
-                *angular_velocity,
-                accumulated_force,
-            );
-            world.add_body(body);
+        // few bodies are pushed in any one frame, so only those are looked up
+        let forces: HashMap<u32, Vec3> = forces
+            .filter_map(|(entity_id, force)| {
+                let force = std::mem::take(&mut force.0);
+                (force != Vec3::ZERO).then_some((entity_id.get(), force))
+            })
+            .collect();
+        let torques: HashMap<u32, Vec3> = torques
+            .filter_map(|(entity_id, torque)| {
+                let torque = std::mem::take(&mut torque.0);
+                (torque != Vec3::ZERO).then_some((entity_id.get(), torque))
+            })
+            .collect();
+
+        // bodies live on between frames, so only components gameplay changed since the last
+        // sync are taken from the ECS; anything else is already in the body
+        let since = world.last_synced;
+        let tick = commands.tick();
+        let mut seen = 0;
+        let mut synced = 0;
+        for (entity_id, transform, rigid_body, collider, velocity, angular_velocity) in bodies {
+            let entity = entity_id.get();
+            let Some(record) = commands.get_entity(entity) else {
+                continue;
+            };
+            seen += 1;
+
+            let is_new = !world.entity_map.contains_key(&entity);
+            if is_new {
+                let mut body = PhysicsBody::new(entity, rigid_body.clone(), collider.clone(), transform);
+                body.velocity = *velocity;
+                body.angular_velocity = *angular_velocity;
+                world.add_body(body);
+            }
+            let body = &mut world.bodies[world.entity_map[&entity]];
+            body.seen_at = tick;
+
+            if is_new
+                || changed_since::<Transform>(record, since)
+                || changed_since::<RigidBody>(record, since)
+                || changed_since::<Collider>(record, since)
+                || changed_since::<PhysicsMaterial>(record, since)
+                || changed_since::<CollisionGroups>(record, since)
+                || changed_since::<Sensor>(record, since)
+            {
+                synced += 1;
+                if (transform.pos, transform.rot) != body.synced_pose {
+                    body.teleport(transform.pos, transform.rot);
+                    body.synced_pose = (transform.pos, transform.rot);
+                }
+                if *rigid_body != body.rigid_body || *collider != body.collider || transform.scale != body.scale {
+                    body.reshape(rigid_body.clone(), collider.clone(), transform.scale);
+                }
+                body.material = record.get_component::<PhysicsMaterial>().cloned().unwrap_or_default();
+                body.groups = record.get_component::<CollisionGroups>().copied().unwrap_or_default();
+                body.sensor = record.has_component::<Sensor>();
+            }
+
+            // sleeping bodies are left still, so any push came from outside
+            let pushed = if is_new {
+                velocity.0 != Vec3::ZERO || angular_velocity.0 != Vec3::ZERO
+            } else {
+                velocity.0 != body.velocity.0 || angular_velocity.0 != body.angular_velocity.0
+            };
+            body.velocity = *velocity;
+            body.angular_velocity = *angular_velocity;
+            body.accumulated_force += forces.get(&entity).copied().unwrap_or_default();
+            body.accumulated_torque += torques.get(&entity).copied().unwrap_or_default();
+
+            if body.rigid_body.is_static() {
+                continue;
+            }
+            let pushed = pushed || body.accumulated_force != Vec3::ZERO || body.accumulated_torque != Vec3::ZERO;
+            if changed_since::<Sleeping>(record, since)
+                && let Some(&Sleeping(sleeping)) = record.get_component::<Sleeping>()
+                && sleeping != body.synced_sleeping
+            {
+                body.synced_sleeping = sleeping;
+                if sleeping && !pushed {
+                    body.sleeping = true;
+                    body.velocity.0 = Vec3::ZERO;
+                    body.angular_velocity.0 = Vec3::ZERO;
+                } else {
+                    body.wake();
+                }
+            }
+            if body.sleeping && pushed {
+                body.wake();
+            }
         }
 
-        world.rebuild_broad_phase();
+        world.last_synced = tick;
+        world.synced_bodies = synced;
+        let removed = world.remove_bodies_unseen(tick, seen);
+        let rejoined = world.sync_joints(joints.map(|(entity_id, joint)| (entity_id.get(), joint)));
+        // the last step left the collisions up to date for bodies nothing has touched since
+        if synced > 0 || removed || rejoined {
+            world.update_collisions();
+        }
     }
 );
 
@@ -455,57 +865,88 @@ system!(
             return;
         };
 
-        let gravity = world.gravity;
         let dt = time.fixed_delta;
+        world.trigger_events.clear();
+        world.joint_events.clear();
 
         while time.consume_step() {
-            for body in world.bodies.iter_mut() {
-                if body.rigid_body.is_static() {
-                    continue;
-                }
-
-                let inverse_mass = body.rigid_body.inverse_mass();
-                let external_acceleration = body.accumulated_force * inverse_mass;
-                let total_acceleration = gravity + external_acceleration;
-
-                body.velocity.0 += total_acceleration * dt;
-                body.position += body.velocity.0 * dt;
-
-                let angular_speed = body.angular_velocity.0.length();
-                if angular_speed > f32::EPSILON {
-                    let axis = body.angular_velocity.0 / angular_speed;
-                    let delta_angle = angular_speed * dt;
-                    let delta_rot = Quat::from_axis_angle(axis, delta_angle);
-                    body.rotation = (delta_rot * body.rotation).normalize();
-                }
-
-                body.accumulated_force = Vec3::ZERO;
-            }
+            world.integrate_velocities(dt);
+            world.update_collisions();
+            world.update_triggers();
+            let islands = world.build_islands();
+            world.wake_touched_islands(&islands);
+            world.solve_constraints(dt);
+            world.integrate_positions(dt);
+            world.update_sleep(&islands, dt);
         }
 
-        world.rebuild_broad_phase();
+        world.update_collisions();
     }
 );
 
 system!(
     fn sync_physics_to_ecs(
-        physics_world: res &PhysicsWorld,
-        mut targets: query (
-            &EntityId,
-            &mut Velocity,
-            &mut AngularVelocity,
-            &mut Transform
-        ),
+        physics_world: res &mut PhysicsWorld,
+        commands: commands
     ) {
         let Some(world) = physics_world else { return; };
 
-        for (entity_id, velocity, angular_velocity, transform) in targets {
-            if let Some(body) = world.get_body(entity_id.get()) {
+        // only what the step changed is written, so components it didn't touch keep their
+        // change ticks and the next sync can skip them
+        for body in world.bodies.iter_mut() {
+            if !body.is_awake()
+                && body.sleeping == body.synced_sleeping
+                && (body.position, body.rotation) == body.synced_pose
+            {
+                continue;
+            }
+            let entity = body.entity;
+            let Some(record) = commands.get_entity(entity) else {
+                continue;
+            };
+
+            if record.get_component::<Velocity>().is_some_and(|velocity| velocity.0 != body.velocity.0)
+                && let Some(velocity) = commands.get_component_mut::<Velocity>(entity)
+            {
                 velocity.0 = body.velocity.0;
+            }
+            if record
+                .get_component::<AngularVelocity>()
+                .is_some_and(|angular_velocity| angular_velocity.0 != body.angular_velocity.0)
+                && let Some(angular_velocity) = commands.get_component_mut::<AngularVelocity>(entity)
+            {
                 angular_velocity.0 = body.angular_velocity.0;
+            }
+            // a transform gameplay moved since the last sync is kept, and picked up next frame
+            if record
+                .get_component::<Transform>()
+                .is_some_and(|transform| (transform.pos, transform.rot) == body.synced_pose)
+                && (body.position, body.rotation) != body.synced_pose
+                && let Some(transform) = commands.get_component_mut::<Transform>(entity)
+            {
                 transform.pos = body.position;
                 transform.rot = body.rotation;
-                transform.scale = body.scale;
+                body.synced_pose = (body.position, body.rotation);
+            }
+
+            match record.get_component::<Sleeping>() {
+                Some(sleeping) if sleeping.0 == body.synced_sleeping => {
+                    if sleeping.0 != body.sleeping
+                        && let Some(sleeping) = commands.get_component_mut::<Sleeping>(entity)
+                    {
+                        sleeping.0 = body.sleeping;
+                    }
+                    body.synced_sleeping = body.sleeping;
+                }
+                // gameplay's own change, picked up next frame
+                Some(_) => {}
+                None => body.synced_sleeping = body.sleeping,
+            }
+        }
+
+        for event in world.joint_events() {
+            if let Some(joint) = commands.get_component_mut::<Joint>(event.joint) {
+                joint.broken = true;
             }
         }
     }
@@ -522,6 +963,11 @@ system!(
 
         events.contacts.clear();
         events.broad_phase_pairs.clear();
+        events.triggers.clear();
+        events.joints_broken.clear();
         events.broad_phase_pairs.extend(world.broad_phase_pairs().iter().copied());
+        events.contacts.extend(world.contacts().iter().cloned());
+        events.triggers.extend(world.trigger_events().iter().copied());
+        events.joints_broken.extend(world.joint_events().iter().copied());
     }
 );
@@ -0,0 +1,724 @@
+use super::{Collider, Transform};
+use glam::{Mat3, Quat, Vec3};
+
+const EPSILON: f32 = 1e-6;
+/// Points closer than this are merged when building a manifold.
+const MERGE_DISTANCE: f32 = 1e-4;
+/// Edge axes only win over face axes when they're clearly shallower, which keeps resting
+/// boxes on stable face contacts.
+const EDGE_AXIS_BIAS: f32 = 0.95;
+/// Steps of the search for the deepest point along a capsule.
+const CAPSULE_SEARCH_STEPS: usize = 32;
+/// Rounds of conservative advancement a cast takes to close in on a shape before settling.
+const CAST_ITERATIONS: usize = 32;
+/// Gap at which conservative advancement counts a cast shape as touching.
+const CAST_TOLERANCE: f32 = 1e-5;
+
+/// How two overlapping colliders touch. `normal` points from the first collider towards the
+/// second, and the points sit halfway between the two surfaces, in world space.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContactManifold {
+    pub normal: Vec3,
+    pub depth: f32,
+    pub points: Vec<Vec3>,
+}
+
+impl ContactManifold {
+    fn flipped(mut self) -> Self {
+        self.normal = -self.normal;
+        self
+    }
+}
+
+/// Tests two colliders placed by their transforms for overlap.
+pub fn collide(
+    a: &Collider,
+    transform_a: &Transform,
+    b: &Collider,
+    transform_b: &Transform,
+) -> Option<ContactManifold> {
+    let a = Shape::new(a, transform_a.pos, transform_a.rot, transform_a.scale);
+    let b = Shape::new(b, transform_b.pos, transform_b.rot, transform_b.scale);
+    a.collide(&b)
+}
+
+/// A collider in world space, with its transform's rotation and scale baked in.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Shape {
+    Sphere {
+        center: Vec3,
+        radius: f32,
+    },
+    Box {
+        center: Vec3,
+        axes: Mat3,
+        half_extents: Vec3,
+    },
+    /// The segment from `a` to `b`, grown by `radius`.
+    Capsule {
+        a: Vec3,
+        b: Vec3,
+        radius: f32,
+    },
+}
+
+impl Shape {
+    pub(crate) fn new(collider: &Collider, position: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        let scale = scale.abs();
+        match *collider {
+            Collider::Sphere { radius } => Shape::Sphere {
+                center: position,
+                radius: radius.abs() * scale.max_element(),
+            },
+            Collider::Box { half_extents } => Shape::Box {
+                center: position,
+                axes: Mat3::from_quat(rotation),
+                half_extents: half_extents.abs() * scale,
+            },
+            Collider::Capsule {
+                half_height,
+                radius,
+            } => {
+                let offset = rotation * Vec3::Y * (half_height.abs() * scale.y);
+                Shape::Capsule {
+                    a: position - offset,
+                    b: position + offset,
+                    radius: radius.abs() * scale.x.max(scale.z),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn aabb(&self) -> (Vec3, Vec3) {
+        match *self {
+            Shape::Sphere { center, radius } => (center - radius, center + radius),
+            Shape::Box {
+                center,
+                axes,
+                half_extents,
+            } => {
+                let extents = axes.x_axis.abs() * half_extents.x
+                    + axes.y_axis.abs() * half_extents.y
+                    + axes.z_axis.abs() * half_extents.z;
+                (center - extents, center + extents)
+            }
+            Shape::Capsule { a, b, radius } => (a.min(b) - radius, a.max(b) + radius),
+        }
+    }
+
+    pub(crate) fn collide(&self, other: &Shape) -> Option<ContactManifold> {
+        match (*self, *other) {
+            (Shape::Sphere { center: ca, radius: ra }, Shape::Sphere { center: cb, radius: rb }) => {
+                spheres(ca, ra, cb, rb)
+            }
+            (Shape::Sphere { center, radius }, Shape::Capsule { a, b, radius: rc }) => {
+                spheres(center, radius, closest_point_on_segment(center, a, b), rc)
+            }
+            (Shape::Sphere { center, radius }, Shape::Box { center: cb, axes, half_extents }) => {
+                sphere_box(center, radius, cb, axes, half_extents)
+            }
+            (Shape::Capsule { a: a1, b: b1, radius: r1 }, Shape::Capsule { a: a2, b: b2, radius: r2 }) => {
+                let (p, q) = closest_points_on_segments(a1, b1, a2, b2);
+                spheres(p, r1, q, r2)
+            }
+            (Shape::Capsule { a, b, radius }, Shape::Box { center, axes, half_extents }) => {
+                capsule_box(a, b, radius, center, axes, half_extents)
+            }
+            (
+                Shape::Box { center: ca, axes: axes_a, half_extents: he_a },
+                Shape::Box { center: cb, axes: axes_b, half_extents: he_b },
+            ) => box_box(ca, axes_a, he_a, cb, axes_b, he_b),
+            _ => other.collide(self).map(ContactManifold::flipped),
+        }
+    }
+
+    /// Where a ray along the unit vector `direction` first enters the shape, as the distance
+    /// and the surface normal there. A ray starting inside hits at distance zero, facing back
+    /// along itself.
+    pub(crate) fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<(f32, Vec3)> {
+        let hit = match *self {
+            Shape::Sphere { center, radius } => ray_sphere(origin, direction, center, radius),
+            Shape::Box {
+                center,
+                axes,
+                half_extents,
+            } => ray_box(origin, direction, center, axes, half_extents),
+            Shape::Capsule { a, b, radius } => ray_capsule(origin, direction, a, b, radius),
+        };
+        hit.filter(|(distance, _)| *distance <= max_distance)
+    }
+
+    pub(crate) fn translated(self, offset: Vec3) -> Self {
+        match self {
+            Shape::Sphere { center, radius } => Shape::Sphere {
+                center: center + offset,
+                radius,
+            },
+            Shape::Box {
+                center,
+                axes,
+                half_extents,
+            } => Shape::Box {
+                center: center + offset,
+                axes,
+                half_extents,
+            },
+            Shape::Capsule { a, b, radius } => Shape::Capsule {
+                a: a + offset,
+                b: b + offset,
+                radius,
+            },
+        }
+    }
+
+    /// How far the shape can move along the unit vector `direction` before it touches `other`,
+    /// if it does within `max_distance`. Zero if the two already overlap.
+    ///
+    /// A sphere is a ray against `other` grown by its radius. Other shapes close in by
+    /// conservative advancement, each round moving up to where an axis would stop separating
+    /// them; two boxes meet in one round, and rounded targets within `CAST_ITERATIONS`.
+    pub(crate) fn cast(&self, other: &Shape, direction: Vec3, max_distance: f32) -> Option<f32> {
+        if let Shape::Sphere { center, radius } = *self {
+            return other.inflated_raycast(center, direction, radius, max_distance);
+        }
+
+        let mut travelled = 0.0;
+        for _ in 0..CAST_ITERATIONS {
+            let moved = self.translated(direction * travelled);
+            let mut advance = 0.0f32;
+            for axis in moved.separating_axes(other) {
+                let (min_a, max_a) = moved.project(axis);
+                let (min_b, max_b) = other.project(axis);
+                let (gap, closing) = if min_b > max_a {
+                    (min_b - max_a, direction.dot(axis))
+                } else if min_a > max_b {
+                    (min_a - max_b, -direction.dot(axis))
+                } else {
+                    continue;
+                };
+                if closing <= EPSILON {
+                    // apart along this axis for good
+                    return None;
+                }
+                advance = advance.max(gap / closing);
+            }
+
+            if advance <= CAST_TOLERANCE {
+                break;
+            }
+            travelled += advance;
+            if travelled > max_distance {
+                return None;
+            }
+        }
+        Some(travelled)
+    }
+
+    /// Where a ray along the unit vector `direction` first enters the shape grown by `radius`
+    /// all round, which is where a sphere of that radius moving along it first touches.
+    fn inflated_raycast(&self, origin: Vec3, direction: Vec3, radius: f32, max_distance: f32) -> Option<f32> {
+        let hit = match *self {
+            Shape::Sphere { center, radius: own } => ray_sphere(origin, direction, center, own + radius),
+            Shape::Capsule { a, b, radius: own } => ray_capsule(origin, direction, a, b, own + radius),
+            Shape::Box {
+                center,
+                axes,
+                half_extents,
+            } => {
+                // a rounded box: the box pushed out along each axis, and capsules along its edges
+                let slabs = (0..3).filter_map(|axis| {
+                    let mut grown = half_extents;
+                    grown[axis] += radius;
+                    ray_box(origin, direction, center, axes, grown)
+                });
+                let edges = box_edges(center, axes, half_extents)
+                    .into_iter()
+                    .filter_map(|(a, b)| ray_capsule(origin, direction, a, b, radius));
+                slabs.chain(edges).min_by(|x, y| x.0.total_cmp(&y.0))
+            }
+        };
+        hit.map(|(distance, _)| distance).filter(|distance| *distance <= max_distance)
+    }
+
+    /// Axes worth trying to separate the two shapes along: the boxes' faces, the crossings of
+    /// their edges, and the way from one to the other where they're closest.
+    fn separating_axes(&self, other: &Shape) -> Vec<Vec3> {
+        let mut candidates = Vec::new();
+        for shape in [self, other] {
+            if let Shape::Box { axes, .. } = *shape {
+                candidates.extend([axes.x_axis, axes.y_axis, axes.z_axis]);
+            }
+        }
+        for a in self.edge_directions() {
+            for b in other.edge_directions() {
+                candidates.push(a.cross(b));
+            }
+        }
+        if let Some((p, q)) = self.closest_core_points(other) {
+            candidates.push(q - p);
+        }
+        candidates
+            .into_iter()
+            .filter(|axis| axis.length_squared() > EPSILON)
+            .map(Vec3::normalize)
+            .collect()
+    }
+
+    fn edge_directions(&self) -> Vec<Vec3> {
+        match *self {
+            Shape::Sphere { .. } => Vec::new(),
+            Shape::Box { axes, .. } => vec![axes.x_axis, axes.y_axis, axes.z_axis],
+            Shape::Capsule { a, b, .. } => vec![b - a],
+        }
+    }
+
+    /// Closest points between the cores of two shapes: a sphere's center, a capsule's segment
+    /// and a box's whole volume. Two boxes have none; their faces and edges already settle it.
+    fn closest_core_points(&self, other: &Shape) -> Option<(Vec3, Vec3)> {
+        match (*self, *other) {
+            (Shape::Sphere { center: p, .. }, Shape::Sphere { center: q, .. }) => Some((p, q)),
+            (Shape::Sphere { center, .. }, Shape::Capsule { a, b, .. }) => {
+                Some((center, closest_point_on_segment(center, a, b)))
+            }
+            (Shape::Sphere { center, .. }, Shape::Box { center: cb, axes, half_extents }) => {
+                Some((center, closest_point_on_box(center, cb, axes, half_extents)))
+            }
+            (Shape::Capsule { a: a1, b: b1, .. }, Shape::Capsule { a: a2, b: b2, .. }) => {
+                Some(closest_points_on_segments(a1, b1, a2, b2))
+            }
+            (Shape::Capsule { a, b, .. }, Shape::Box { center, axes, half_extents }) => {
+                let p = a.lerp(b, deepest_along_segment(a, b, center, axes, half_extents));
+                Some((p, closest_point_on_box(p, center, axes, half_extents)))
+            }
+            (Shape::Box { .. }, Shape::Box { .. }) => None,
+            _ => other.closest_core_points(self).map(|(p, q)| (q, p)),
+        }
+    }
+
+    /// The span the shape covers along the unit vector `axis`.
+    fn project(&self, axis: Vec3) -> (f32, f32) {
+        match *self {
+            Shape::Sphere { center, radius } => {
+                let middle = center.dot(axis);
+                (middle - radius, middle + radius)
+            }
+            Shape::Box {
+                center,
+                axes,
+                half_extents,
+            } => {
+                let middle = center.dot(axis);
+                let reach = (axes.transpose() * axis).abs().dot(half_extents);
+                (middle - reach, middle + reach)
+            }
+            Shape::Capsule { a, b, radius } => {
+                let (p, q) = (a.dot(axis), b.dot(axis));
+                (p.min(q) - radius, p.max(q) + radius)
+            }
+        }
+    }
+}
+
+fn ray_sphere(origin: Vec3, direction: Vec3, center: Vec3, radius: f32) -> Option<(f32, Vec3)> {
+    let offset = origin - center;
+    let c = offset.length_squared() - radius * radius;
+    if c <= 0.0 {
+        return Some((0.0, -direction));
+    }
+    let b = offset.dot(direction);
+    let discriminant = b * b - c;
+    if b > 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let distance = -b - discriminant.sqrt();
+    let normal = (origin + direction * distance - center).normalize_or(-direction);
+    Some((distance, normal))
+}
+
+fn ray_box(origin: Vec3, direction: Vec3, center: Vec3, axes: Mat3, half_extents: Vec3) -> Option<(f32, Vec3)> {
+    let local_origin = axes.transpose() * (origin - center);
+    let local_direction = axes.transpose() * direction;
+
+    // slabs: the ray is inside the box between the last face it enters and the first it leaves
+    let (mut enter, mut exit) = (f32::NEG_INFINITY, f32::INFINITY);
+    let mut normal = -direction;
+    for axis in 0..3 {
+        let (o, d, h) = (local_origin[axis], local_direction[axis], half_extents[axis]);
+        if d.abs() < EPSILON {
+            if o.abs() > h {
+                return None;
+            }
+            continue;
+        }
+        let (t1, t2) = ((-h - o) / d, (h - o) / d);
+        let (near, far) = (t1.min(t2), t1.max(t2));
+        if near > enter {
+            enter = near;
+            normal = axes.col(axis) * -d.signum();
+        }
+        exit = exit.min(far);
+    }
+
+    if enter > exit || exit < 0.0 {
+        return None;
+    }
+    if enter < 0.0 {
+        return Some((0.0, -direction));
+    }
+    Some((enter, normal))
+}
+
+fn ray_capsule(origin: Vec3, direction: Vec3, a: Vec3, b: Vec3, radius: f32) -> Option<(f32, Vec3)> {
+    if origin.distance_squared(closest_point_on_segment(origin, a, b)) <= radius * radius {
+        return Some((0.0, -direction));
+    }
+
+    // the side of the cylinder between the caps, then the caps themselves
+    let mut best: Option<(f32, Vec3)> = None;
+    let axis = b - a;
+    let length = axis.length();
+    if length > EPSILON {
+        let axis = axis / length;
+        let offset = origin - a;
+        let direction_across = direction - axis * direction.dot(axis);
+        let offset_across = offset - axis * offset.dot(axis);
+        let qa = direction_across.length_squared();
+        let qb = offset_across.dot(direction_across);
+        let qc = offset_across.length_squared() - radius * radius;
+        let discriminant = qb * qb - qa * qc;
+        if qa > EPSILON && discriminant >= 0.0 {
+            let distance = (-qb - discriminant.sqrt()) / qa;
+            let along = (offset + direction * distance).dot(axis);
+            if distance >= 0.0 && (0.0..=length).contains(&along) {
+                let point = origin + direction * distance;
+                best = Some((distance, (point - (a + axis * along)) / radius));
+            }
+        }
+    }
+    for cap in [a, b] {
+        if let Some(hit) = ray_sphere(origin, direction, cap, radius)
+            && best.is_none_or(|best| hit.0 < best.0)
+        {
+            best = Some(hit);
+        }
+    }
+    best
+}
+
+fn spheres(ca: Vec3, ra: f32, cb: Vec3, rb: f32) -> Option<ContactManifold> {
+    let delta = cb - ca;
+    let distance = delta.length();
+    if distance >= ra + rb {
+        return None;
+    }
+
+    // concentric spheres have no preferred direction, so push them apart vertically
+    let normal = if distance > EPSILON { delta / distance } else { Vec3::Y };
+    let depth = ra + rb - distance;
+    Some(ContactManifold {
+        normal,
+        depth,
+        points: vec![ca + normal * (ra - depth * 0.5)],
+    })
+}
+
+fn sphere_box(center: Vec3, radius: f32, box_center: Vec3, axes: Mat3, half_extents: Vec3) -> Option<ContactManifold> {
+    let local = axes.transpose() * (center - box_center);
+    let clamped = local.clamp(-half_extents, half_extents);
+
+    if local != clamped {
+        let closest = box_center + axes * clamped;
+        let delta = center - closest;
+        let distance = delta.length();
+        if distance >= radius {
+            return None;
+        }
+        let normal = -delta / distance;
+        let depth = radius - distance;
+        return Some(ContactManifold {
+            normal,
+            depth,
+            points: vec![closest + normal * (depth * 0.5)],
+        });
+    }
+
+    // the center is inside the box, so leave through the nearest face
+    let face_distance = half_extents - local.abs();
+    let axis = if face_distance.x <= face_distance.y && face_distance.x <= face_distance.z {
+        0
+    } else if face_distance.y <= face_distance.z {
+        1
+    } else {
+        2
+    };
+    let outward = axes.col(axis) * local[axis].signum();
+    Some(ContactManifold {
+        normal: -outward,
+        depth: radius + face_distance[axis],
+        points: vec![center],
+    })
+}
+
+/// Signed distance from `point` to a box, negative inside.
+fn box_distance(point: Vec3, box_center: Vec3, axes: Mat3, half_extents: Vec3) -> f32 {
+    let q = (axes.transpose() * (point - box_center)).abs() - half_extents;
+    q.max(Vec3::ZERO).length() + q.max_element().min(0.0)
+}
+
+/// Where along the segment from `a` to `b`, from 0 to 1, it reaches deepest into the box. The
+/// distance to a convex shape along a segment is convex, so a ternary search finds it.
+fn deepest_along_segment(a: Vec3, b: Vec3, center: Vec3, axes: Mat3, half_extents: Vec3) -> f32 {
+    let (mut lo, mut hi) = (0.0f32, 1.0f32);
+    for _ in 0..CAPSULE_SEARCH_STEPS {
+        let m1 = lo + (hi - lo) / 3.0;
+        let m2 = hi - (hi - lo) / 3.0;
+        if box_distance(a.lerp(b, m1), center, axes, half_extents)
+            < box_distance(a.lerp(b, m2), center, axes, half_extents)
+        {
+            hi = m2;
+        } else {
+            lo = m1;
+        }
+    }
+    (lo + hi) * 0.5
+}
+
+fn capsule_box(a: Vec3, b: Vec3, radius: f32, center: Vec3, axes: Mat3, half_extents: Vec3) -> Option<ContactManifold> {
+    // the ends are tested too, so a capsule lying on a face rests on two points
+    let mut manifold: Option<ContactManifold> = None;
+    for t in [0.0, deepest_along_segment(a, b, center, axes, half_extents), 1.0] {
+        let Some(contact) = sphere_box(a.lerp(b, t), radius, center, axes, half_extents) else {
+            continue;
+        };
+        match &mut manifold {
+            None => manifold = Some(contact),
+            Some(manifold) => {
+                if contact.depth > manifold.depth {
+                    manifold.normal = contact.normal;
+                    manifold.depth = contact.depth;
+                }
+                push_point(&mut manifold.points, contact.points[0]);
+            }
+        }
+    }
+    manifold
+}
+
+fn box_box(ca: Vec3, axes_a: Mat3, he_a: Vec3, cb: Vec3, axes_b: Mat3, he_b: Vec3) -> Option<ContactManifold> {
+    let delta = cb - ca;
+    let project = |axes: Mat3, he: Vec3, axis: Vec3| {
+        he.x * axes.x_axis.dot(axis).abs() + he.y * axes.y_axis.dot(axis).abs() + he.z * axes.z_axis.dot(axis).abs()
+    };
+
+    // separating axis test over both boxes' faces and every pair of edges
+    let mut best: Option<(f32, Vec3, Feature)> = None;
+    let mut consider = |axis: Vec3, feature: Feature| -> bool {
+        let length = axis.length();
+        if length < EPSILON {
+            // parallel edges, already covered by the face axes
+            return true;
+        }
+        let axis = axis / length;
+        let overlap = project(axes_a, he_a, axis) + project(axes_b, he_b, axis) - delta.dot(axis).abs();
+        if overlap <= 0.0 {
+            return false;
+        }
+
+        let better = match (best, feature) {
+            (None, _) => true,
+            (Some((best_overlap, ..)), Feature::Edges(..)) => overlap < best_overlap * EDGE_AXIS_BIAS,
+            (Some((best_overlap, ..)), _) => overlap < best_overlap,
+        };
+        if better {
+            let normal = if delta.dot(axis) < 0.0 { -axis } else { axis };
+            best = Some((overlap, normal, feature));
+        }
+        true
+    };
+
+    for i in 0..3 {
+        if !consider(axes_a.col(i), Feature::FaceA(i)) || !consider(axes_b.col(i), Feature::FaceB(i)) {
+            return None;
+        }
+    }
+    for i in 0..3 {
+        for j in 0..3 {
+            if !consider(axes_a.col(i).cross(axes_b.col(j)), Feature::Edges(i, j)) {
+                return None;
+            }
+        }
+    }
+
+    let (depth, normal, feature) = best?;
+    let mut points = match feature {
+        Feature::FaceA(i) => clip_faces((ca, axes_a, he_a), i, normal, (cb, axes_b, he_b)),
+        Feature::FaceB(i) => clip_faces((cb, axes_b, he_b), i, -normal, (ca, axes_a, he_a)),
+        Feature::Edges(i, j) => {
+            let edge_a = support_edge(ca, axes_a, he_a, i, normal);
+            let edge_b = support_edge(cb, axes_b, he_b, j, -normal);
+            let (p, q) = closest_points_on_segments(edge_a.0, edge_a.1, edge_b.0, edge_b.1);
+            vec![(p + q) * 0.5]
+        }
+    };
+    if points.is_empty() {
+        // only reachable through rounding; the boxes still overlap, so report something
+        points.push(ca + delta * 0.5);
+    }
+
+    Some(ContactManifold { normal, depth, points })
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Feature {
+    FaceA(usize),
+    FaceB(usize),
+    Edges(usize, usize),
+}
+
+type OrientedBox = (Vec3, Mat3, Vec3);
+
+/// Clips the face of `incident` that faces the reference box against the reference face on
+/// `axis`, whose outward normal is `normal`.
+fn clip_faces(reference: OrientedBox, axis: usize, normal: Vec3, incident: OrientedBox) -> Vec<Vec3> {
+    let (ref_center, ref_axes, ref_he) = reference;
+    let (inc_center, inc_axes, inc_he) = incident;
+
+    // the incident face is the one most opposed to the reference normal
+    let inc_axis = (0..3)
+        .max_by(|&i, &j| {
+            inc_axes.col(i).dot(normal).abs().total_cmp(&inc_axes.col(j).dot(normal).abs())
+        })
+        .unwrap();
+    let inc_normal = inc_axes.col(inc_axis) * -inc_axes.col(inc_axis).dot(normal).signum();
+    let face_center = inc_center + inc_normal * inc_he[inc_axis];
+    let (u, v) = ((inc_axis + 1) % 3, (inc_axis + 2) % 3);
+    let du = inc_axes.col(u) * inc_he[u];
+    let dv = inc_axes.col(v) * inc_he[v];
+    let mut polygon = vec![
+        face_center + du + dv,
+        face_center - du + dv,
+        face_center - du - dv,
+        face_center + du - dv,
+    ];
+
+    for side in [(axis + 1) % 3, (axis + 2) % 3] {
+        let side_normal = ref_axes.col(side);
+        let offset = side_normal.dot(ref_center);
+        polygon = clip_polygon(&polygon, side_normal, offset + ref_he[side]);
+        polygon = clip_polygon(&polygon, -side_normal, -offset + ref_he[side]);
+    }
+
+    let face_offset = normal.dot(ref_center) + ref_he[axis];
+    let mut points = Vec::new();
+    for p in polygon {
+        let separation = normal.dot(p) - face_offset;
+        if separation <= MERGE_DISTANCE {
+            push_point(&mut points, p - normal * (separation * 0.5));
+        }
+    }
+    points
+}
+
+/// Keeps the part of `polygon` where `normal · p <= offset`.
+fn clip_polygon(polygon: &[Vec3], normal: Vec3, offset: f32) -> Vec<Vec3> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (i, &current) in polygon.iter().enumerate() {
+        let next = polygon[(i + 1) % polygon.len()];
+        let d_current = normal.dot(current) - offset;
+        let d_next = normal.dot(next) - offset;
+
+        if d_current <= 0.0 {
+            clipped.push(current);
+        }
+        if (d_current < 0.0) != (d_next < 0.0) && (d_current - d_next).abs() > EPSILON {
+            clipped.push(current.lerp(next, d_current / (d_current - d_next)));
+        }
+    }
+    clipped
+}
+
+/// The edge along `axis` of a box that lies furthest in `direction`.
+fn support_edge(center: Vec3, axes: Mat3, half_extents: Vec3, axis: usize, direction: Vec3) -> (Vec3, Vec3) {
+    let mut mid = center;
+    for k in (0..3).filter(|&k| k != axis) {
+        let sign = if axes.col(k).dot(direction) < 0.0 { -1.0 } else { 1.0 };
+        mid += axes.col(k) * (half_extents[k] * sign);
+    }
+    let along = axes.col(axis) * half_extents[axis];
+    (mid - along, mid + along)
+}
+
+/// The twelve edges of a box, as segments.
+fn box_edges(center: Vec3, axes: Mat3, half_extents: Vec3) -> Vec<(Vec3, Vec3)> {
+    let mut edges = Vec::with_capacity(12);
+    for axis in 0..3 {
+        let (j, k) = ((axis + 1) % 3, (axis + 2) % 3);
+        let along = axes.col(axis) * half_extents[axis];
+        for (sj, sk) in [(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)] {
+            let mid = center + axes.col(j) * (half_extents[j] * sj) + axes.col(k) * (half_extents[k] * sk);
+            edges.push((mid - along, mid + along));
+        }
+    }
+    edges
+}
+
+fn closest_point_on_box(point: Vec3, center: Vec3, axes: Mat3, half_extents: Vec3) -> Vec3 {
+    let local = axes.transpose() * (point - center);
+    center + axes * local.clamp(-half_extents, half_extents)
+}
+
+fn push_point(points: &mut Vec<Vec3>, point: Vec3) {
+    if points.iter().all(|p| p.distance_squared(point) > MERGE_DISTANCE * MERGE_DISTANCE) {
+        points.push(point);
+    }
+}
+
+fn closest_point_on_segment(point: Vec3, a: Vec3, b: Vec3) -> Vec3 {
+    let ab = b - a;
+    let length_squared = ab.length_squared();
+    if length_squared <= EPSILON {
+        return a;
+    }
+    a + ab * ((point - a).dot(ab) / length_squared).clamp(0.0, 1.0)
+}
+
+/// Closest points between the segments `p1`-`q1` and `p2`-`q2`.
+fn closest_points_on_segments(p1: Vec3, q1: Vec3, p2: Vec3, q2: Vec3) -> (Vec3, Vec3) {
+    let d1 = q1 - p1;
+    let d2 = q2 - p2;
+    let r = p1 - p2;
+    let a = d1.length_squared();
+    let e = d2.length_squared();
+    let f = d2.dot(r);
+
+    if a <= EPSILON && e <= EPSILON {
+        return (p1, p2);
+    }
+
+    let (s, t) = if a <= EPSILON {
+        (0.0, (f / e).clamp(0.0, 1.0))
+    } else {
+        let c = d1.dot(r);
+        if e <= EPSILON {
+            ((-c / a).clamp(0.0, 1.0), 0.0)
+        } else {
+            let b = d1.dot(d2);
+            let denominator = a * e - b * b;
+            let s = if denominator > EPSILON {
+                ((b * f - c * e) / denominator).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let t = (b * s + f) / e;
+            if t < 0.0 {
+                ((-c / a).clamp(0.0, 1.0), 0.0)
+            } else if t > 1.0 {
+                (((b - c) / a).clamp(0.0, 1.0), 1.0)
+            } else {
+                (s, t)
+            }
+        }
+    };
+
+    (p1 + d1 * s, p2 + d2 * t)
+}
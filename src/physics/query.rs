@@ -0,0 +1,264 @@
+use super::narrow_phase::Shape;
+use super::{CollisionGroups, PhysicsBody, PhysicsWorld, aabb_overlap};
+use glam::{Mat3, Quat, Vec3};
+use std::cmp::Ordering;
+
+/// How far past the touch a shape cast looks for the contact, so the shapes overlap enough
+/// to give it a normal and a point.
+const CAST_SKIN: f32 = 1e-3;
+
+/// A body hit by a ray or a cast shape. `normal` is the hit body's surface normal at `point`,
+/// and `distance` is how far the ray or shape travelled to get there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QueryHit {
+    pub entity: u32,
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
+/// Which bodies a spatial query can see. The default sees all of them.
+#[derive(Default)]
+pub struct QueryFilter<'a> {
+    /// Bodies to look through, such as the one casting the ray.
+    pub exclude: Vec<u32>,
+    pub skip_static: bool,
+    pub skip_dynamic: bool,
+    pub skip_sensors: bool,
+    /// Groups the query acts as, seeing only bodies these groups would collide with.
+    pub groups: CollisionGroups,
+    /// Bodies it returns false for are left out.
+    pub predicate: Option<&'a dyn Fn(&PhysicsBody) -> bool>,
+}
+
+impl<'a> QueryFilter<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn exclude(mut self, entity: u32) -> Self {
+        self.exclude.push(entity);
+        self
+    }
+
+    pub fn static_only(mut self) -> Self {
+        self.skip_dynamic = true;
+        self
+    }
+
+    pub fn dynamic_only(mut self) -> Self {
+        self.skip_static = true;
+        self
+    }
+
+    /// Looks through sensors, so pickups and trigger zones don't block the query.
+    pub fn skip_sensors(mut self) -> Self {
+        self.skip_sensors = true;
+        self
+    }
+
+    pub fn groups(mut self, groups: CollisionGroups) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    pub fn predicate(mut self, predicate: &'a dyn Fn(&PhysicsBody) -> bool) -> Self {
+        self.predicate = Some(predicate);
+        self
+    }
+
+    fn allows(&self, body: &PhysicsBody) -> bool {
+        let is_static = body.rigid_body.is_static();
+        !self.exclude.contains(&body.entity)
+            && !(self.skip_static && is_static)
+            && !(self.skip_dynamic && !is_static)
+            && !(self.skip_sensors && body.sensor)
+            && self.groups.interacts_with(&body.groups)
+            && self.predicate.is_none_or(|predicate| predicate(body))
+    }
+}
+
+impl PhysicsWorld {
+    /// The first body along the ray within `max_distance`. `direction` needn't be normalized.
+    pub fn raycast(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        filter: &QueryFilter,
+    ) -> Option<QueryHit> {
+        self.ray_hits(origin, direction, max_distance, filter)
+            .into_iter()
+            .min_by(closest_first)
+    }
+
+    /// Every body along the ray within `max_distance`, nearest first.
+    pub fn raycast_all(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        filter: &QueryFilter,
+    ) -> Vec<QueryHit> {
+        let mut hits = self.ray_hits(origin, direction, max_distance, filter);
+        hits.sort_by(closest_first);
+        hits
+    }
+
+    /// The first body a sphere touches as it moves from `center` along `direction`.
+    pub fn sphere_cast(
+        &self,
+        center: Vec3,
+        radius: f32,
+        direction: Vec3,
+        max_distance: f32,
+        filter: &QueryFilter,
+    ) -> Option<QueryHit> {
+        let shape = Shape::Sphere {
+            center,
+            radius: radius.abs(),
+        };
+        self.shape_cast(shape, direction, max_distance, filter)
+    }
+
+    /// The first body a box touches as it moves from `center` along `direction`.
+    pub fn box_cast(
+        &self,
+        center: Vec3,
+        half_extents: Vec3,
+        rotation: Quat,
+        direction: Vec3,
+        max_distance: f32,
+        filter: &QueryFilter,
+    ) -> Option<QueryHit> {
+        let shape = Shape::Box {
+            center,
+            axes: Mat3::from_quat(rotation),
+            half_extents: half_extents.abs(),
+        };
+        self.shape_cast(shape, direction, max_distance, filter)
+    }
+
+    /// Bodies overlapping the sphere, by entity.
+    pub fn overlap_sphere(&self, center: Vec3, radius: f32, filter: &QueryFilter) -> Vec<u32> {
+        let sphere = Shape::Sphere {
+            center,
+            radius: radius.abs(),
+        };
+        let (min, max) = sphere.aabb();
+        let mut entities: Vec<u32> = self
+            .candidates(min, max, filter)
+            .filter(|body| sphere.collide(&body.shape()).is_some())
+            .map(|body| body.entity)
+            .collect();
+        entities.sort();
+        entities
+    }
+
+    /// Bodies whose bounding boxes overlap the box from `min` to `max`, by entity.
+    pub fn overlap_aabb(&self, min: Vec3, max: Vec3, filter: &QueryFilter) -> Vec<u32> {
+        let mut entities: Vec<u32> = self
+            .candidates(min, max, filter)
+            .map(|body| body.entity)
+            .collect();
+        entities.sort();
+        entities
+    }
+
+    /// Bodies the filter allows whose AABBs overlap `min`..`max`, found through the broad
+    /// phase.
+    fn candidates<'w>(
+        &'w self,
+        min: Vec3,
+        max: Vec3,
+        filter: &'w QueryFilter,
+    ) -> impl Iterator<Item = &'w PhysicsBody> {
+        self.broad_phase
+            .query(min, max)
+            .into_iter()
+            .filter_map(|entity| self.get_body(entity))
+            .filter(move |body| {
+                let (body_min, body_max) = body.aabb();
+                aabb_overlap(body_min, body_max, min, max)
+            })
+            .filter(|body| filter.allows(body))
+    }
+
+    fn ray_hits(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        filter: &QueryFilter,
+    ) -> Vec<QueryHit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec3::ZERO || max_distance < 0.0 {
+            return Vec::new();
+        }
+        let end = origin + direction * max_distance;
+
+        self.candidates(origin.min(end), origin.max(end), filter)
+            .filter_map(|body| {
+                let (distance, normal) = body.shape().raycast(origin, direction, max_distance)?;
+                Some(QueryHit {
+                    entity: body.entity,
+                    point: origin + direction * distance,
+                    normal,
+                    distance,
+                })
+            })
+            .collect()
+    }
+
+    /// Finds where the shape first touches each body along the path, then takes the contact
+    /// there from a sliver further on.
+    fn shape_cast(
+        &self,
+        shape: Shape,
+        direction: Vec3,
+        max_distance: f32,
+        filter: &QueryFilter,
+    ) -> Option<QueryHit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec3::ZERO || max_distance < 0.0 {
+            return None;
+        }
+        let (start_min, start_max) = shape.aabb();
+        let offset = direction * max_distance;
+        let (min, max) = (
+            start_min.min(start_min + offset),
+            start_max.max(start_max + offset),
+        );
+
+        let mut best: Option<QueryHit> = None;
+        for body in self.candidates(min, max, filter) {
+            let target = body.shape();
+            let limit = best.map_or(max_distance, |best| best.distance);
+            let Some(distance) = shape.cast(&target, direction, limit) else {
+                continue;
+            };
+            // a graze that never actually overlaps has no contact to report
+            let Some(manifold) = shape.translated(direction * (distance + CAST_SKIN)).collide(&target) else {
+                continue;
+            };
+            let point = manifold.points.iter().sum::<Vec3>() / manifold.points.len().max(1) as f32;
+            let hit = QueryHit {
+                entity: body.entity,
+                point,
+                normal: -manifold.normal,
+                distance,
+            };
+            if best.is_none_or(|best| closest_first(&hit, &best) == Ordering::Less) {
+                best = Some(hit);
+            }
+        }
+        best
+    }
+}
+
+fn closest_first(a: &QueryHit, b: &QueryHit) -> Ordering {
+    a.distance
+        .partial_cmp(&b.distance)
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| a.entity.cmp(&b.entity))
+}
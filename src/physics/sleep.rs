@@ -0,0 +1,130 @@
+use super::PhysicsWorld;
+use glam::Vec3;
+use std::collections::HashMap;
+
+/// How still a body has to be, and for how long, before it's put to sleep.
+#[derive(Clone, Copy, Debug)]
+pub struct SleepThresholds {
+    pub linear_velocity: f32,
+    pub angular_velocity: f32,
+    /// Seconds a whole island has to stay under both thresholds.
+    pub time_to_sleep: f32,
+}
+
+impl Default for SleepThresholds {
+    fn default() -> Self {
+        Self {
+            linear_velocity: 0.05,
+            angular_velocity: 0.05,
+            time_to_sleep: 0.5,
+        }
+    }
+}
+
+/// Bodies connected through contacts or joints, each group listed by body index. Static bodies
+/// hold up whatever rests on them without joining it into one island.
+pub(super) struct Islands {
+    pub(super) islands: Vec<Vec<usize>>,
+}
+
+impl PhysicsWorld {
+    pub(super) fn build_islands(&self) -> Islands {
+        let mut parents: Vec<usize> = (0..self.bodies.len()).collect();
+
+        let contacts = self
+            .contacts
+            .iter()
+            .map(|contact| (contact.entity_a, contact.entity_b));
+        let joints = self
+            .joints
+            .iter()
+            .map(|physics_joint| (physics_joint.joint.entity_a, physics_joint.joint.entity_b));
+        for (entity_a, entity_b) in contacts.chain(joints) {
+            let (Some(&a), Some(&b)) = (
+                self.entity_map.get(&entity_a),
+                self.entity_map.get(&entity_b),
+            ) else {
+                continue;
+            };
+            if self.bodies[a].rigid_body.is_static() || self.bodies[b].rigid_body.is_static() {
+                continue;
+            }
+            let (root_a, root_b) = (find(&mut parents, a), find(&mut parents, b));
+            parents[root_a.max(root_b)] = root_a.min(root_b);
+        }
+
+        let mut by_root: HashMap<usize, usize> = HashMap::new();
+        let mut islands: Vec<Vec<usize>> = Vec::new();
+        for index in 0..self.bodies.len() {
+            if self.bodies[index].rigid_body.is_static() {
+                continue;
+            }
+            let root = find(&mut parents, index);
+            let island = *by_root.entry(root).or_insert_with(|| {
+                islands.push(Vec::new());
+                islands.len() - 1
+            });
+            islands[island].push(index);
+        }
+
+        Islands { islands }
+    }
+
+    /// Wakes every sleeping body that shares an island with an awake one, so a body knocked
+    /// into a sleeping pile gets pushed back before the solver skips the pile.
+    pub(super) fn wake_touched_islands(&mut self, islands: &Islands) {
+        for island in &islands.islands {
+            let awake = island.iter().any(|&index| !self.bodies[index].sleeping);
+            if !awake {
+                continue;
+            }
+            for &index in island {
+                let body = &mut self.bodies[index];
+                if body.sleeping {
+                    body.wake();
+                }
+            }
+        }
+    }
+
+    /// Counts how long each body has been still, and puts islands to sleep once all of their
+    /// bodies have been still for long enough.
+    pub(super) fn update_sleep(&mut self, islands: &Islands, dt: f32) {
+        let thresholds = self.sleep_thresholds;
+
+        for island in &islands.islands {
+            let mut ready = true;
+            for &index in island {
+                let body = &mut self.bodies[index];
+                if body.sleeping {
+                    continue;
+                }
+                if body.velocity.0.length() < thresholds.linear_velocity
+                    && body.angular_velocity.0.length() < thresholds.angular_velocity
+                {
+                    body.sleep_timer += dt;
+                } else {
+                    body.sleep_timer = 0.0;
+                }
+                ready &= body.sleep_timer >= thresholds.time_to_sleep;
+            }
+
+            if ready {
+                for &index in island {
+                    let body = &mut self.bodies[index];
+                    body.sleeping = true;
+                    body.velocity.0 = Vec3::ZERO;
+                    body.angular_velocity.0 = Vec3::ZERO;
+                }
+            }
+        }
+    }
+}
+
+fn find(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+    index
+}
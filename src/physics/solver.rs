@@ -0,0 +1,242 @@
+use super::{PhysicsBody, PhysicsMaterial, PhysicsWorld};
+use glam::{Mat3, Vec3};
+
+const SOLVER_ITERATIONS: usize = 10;
+/// Fraction of the penetration, or of a joint's drift, corrected per step.
+pub(super) const BAUMGARTE: f32 = 0.2;
+/// Penetration left alone, so resting contacts don't jitter in and out of touching.
+const PENETRATION_SLOP: f32 = 0.01;
+/// Bodies approaching slower than this don't bounce, so they can come to rest.
+const RESTITUTION_THRESHOLD: f32 = 1.0;
+/// How far a contact point may move between steps and still reuse its last impulse.
+const WARM_START_DISTANCE: f32 = 0.05;
+
+impl PhysicsMaterial {
+    /// Restitution and friction of a contact between two materials.
+    pub fn combine(&self, other: &PhysicsMaterial) -> (f32, f32) {
+        (
+            self.restitution.max(other.restitution),
+            (self.friction * other.friction).max(0.0).sqrt(),
+        )
+    }
+}
+
+struct ContactConstraint {
+    a: usize,
+    b: usize,
+    normal: Vec3,
+    tangents: [Vec3; 2],
+    friction: f32,
+    inverse_mass_a: f32,
+    inverse_mass_b: f32,
+    inverse_inertia_a: Mat3,
+    inverse_inertia_b: Mat3,
+    points: Vec<PointConstraint>,
+}
+
+#[derive(Debug)]
+pub(super) struct CachedImpulse {
+    point: Vec3,
+    normal_impulse: f32,
+    tangent_impulses: [f32; 2],
+}
+
+struct PointConstraint {
+    point: Vec3,
+    /// Offsets of the contact point from each body's center.
+    r_a: Vec3,
+    r_b: Vec3,
+    normal_mass: f32,
+    tangent_masses: [f32; 2],
+    /// Normal velocity the solver aims for, from restitution and position correction.
+    bias: f32,
+    normal_impulse: f32,
+    tangent_impulses: [f32; 2],
+}
+
+impl ContactConstraint {
+    /// Inverse of the mass the bodies put up against an impulse along `direction` at a point.
+    fn effective_mass(&self, r_a: Vec3, r_b: Vec3, direction: Vec3) -> f32 {
+        let angular_a = (self.inverse_inertia_a * r_a.cross(direction)).cross(r_a);
+        let angular_b = (self.inverse_inertia_b * r_b.cross(direction)).cross(r_b);
+        let k = self.inverse_mass_a + self.inverse_mass_b + (angular_a + angular_b).dot(direction);
+        if k > f32::EPSILON { 1.0 / k } else { 0.0 }
+    }
+}
+
+impl PhysicsWorld {
+    /// Sequential impulses over this step's joints and contacts: each contact point pushes the
+    /// bodies apart along the normal, never pulling, and friction holds them within the
+    /// friction cone.
+    pub(super) fn solve_constraints(&mut self, dt: f32) {
+        let mut joints = self.build_joint_constraints(dt);
+        let mut constraints = self.build_constraints(dt);
+
+        for constraint in &constraints {
+            for (i, point) in constraint.points.iter().enumerate() {
+                let [t1, t2] = constraint.tangents;
+                let impulse = constraint.normal * point.normal_impulse
+                    + t1 * point.tangent_impulses[0]
+                    + t2 * point.tangent_impulses[1];
+                self.apply_impulse(constraint, i, impulse);
+            }
+        }
+
+        for iteration in 0..SOLVER_ITERATIONS {
+            for joint in &mut joints {
+                self.solve_joint(joint);
+            }
+            for constraint in &mut constraints {
+                self.solve_constraint(constraint, iteration % 2 == 1);
+            }
+        }
+
+        self.warm_start.clear();
+        for constraint in &constraints {
+            let key = (self.bodies[constraint.a].entity, self.bodies[constraint.b].entity);
+            let cached = constraint
+                .points
+                .iter()
+                .map(|point| CachedImpulse {
+                    point: point.point,
+                    normal_impulse: point.normal_impulse,
+                    tangent_impulses: point.tangent_impulses,
+                })
+                .collect();
+            self.warm_start.insert(key, cached);
+        }
+        self.finish_joints(&joints, dt);
+    }
+
+    fn build_constraints(&self, dt: f32) -> Vec<ContactConstraint> {
+        let mut constraints = Vec::with_capacity(self.contacts.len());
+
+        for contact in &self.contacts {
+            let (Some(&a), Some(&b)) = (
+                self.entity_map.get(&contact.entity_a),
+                self.entity_map.get(&contact.entity_b),
+            ) else {
+                continue;
+            };
+            let (body_a, body_b) = (&self.bodies[a], &self.bodies[b]);
+            // islands are woken whole, so a pair with nothing awake is resting undisturbed
+            if !body_a.is_awake() && !body_b.is_awake() {
+                continue;
+            }
+            let inverse_mass_a = body_a.rigid_body.inverse_mass();
+            let inverse_mass_b = body_b.rigid_body.inverse_mass();
+            if inverse_mass_a + inverse_mass_b <= 0.0 {
+                continue;
+            }
+
+            let (restitution, friction) = body_a.material.combine(&body_b.material);
+            let normal = contact.normal;
+            let (t1, t2) = normal.any_orthonormal_pair();
+            let cached = self.warm_start.get(&(contact.entity_a, contact.entity_b));
+            let correction = BAUMGARTE / dt * (contact.depth - PENETRATION_SLOP).max(0.0);
+
+            let mut constraint = ContactConstraint {
+                a,
+                b,
+                normal,
+                tangents: [t1, t2],
+                friction,
+                inverse_mass_a,
+                inverse_mass_b,
+                inverse_inertia_a: body_a.inverse_inertia_world(),
+                inverse_inertia_b: body_b.inverse_inertia_world(),
+                points: Vec::with_capacity(contact.points.len()),
+            };
+
+            for &point in &contact.points {
+                let r_a = point - body_a.position;
+                let r_b = point - body_b.position;
+                let approach = relative_velocity(body_a, body_b, r_a, r_b).dot(normal);
+                let bounce = if approach < -RESTITUTION_THRESHOLD {
+                    -restitution * approach
+                } else {
+                    0.0
+                };
+                // a resting contact needs about the same impulse as last step, so starting
+                // there keeps the iterations from leaving a lopsided residue that tips stacks
+                let previous = cached.and_then(|cached| {
+                    cached
+                        .iter()
+                        .find(|previous| previous.point.distance(point) < WARM_START_DISTANCE)
+                });
+
+                constraint.points.push(PointConstraint {
+                    point,
+                    r_a,
+                    r_b,
+                    normal_mass: constraint.effective_mass(r_a, r_b, normal),
+                    tangent_masses: [
+                        constraint.effective_mass(r_a, r_b, t1),
+                        constraint.effective_mass(r_a, r_b, t2),
+                    ],
+                    bias: bounce.max(correction),
+                    normal_impulse: previous.map_or(0.0, |previous| previous.normal_impulse),
+                    tangent_impulses: previous.map_or([0.0; 2], |previous| previous.tangent_impulses),
+                });
+            }
+            constraints.push(constraint);
+        }
+
+        constraints
+    }
+
+    /// Alternate passes walk the points in reverse, so the first point of a face contact
+    /// doesn't always take the bulk of the impulse and tip the body over.
+    fn solve_constraint(&mut self, constraint: &mut ContactConstraint, reverse: bool) {
+        let (a, b, normal, tangents, friction) = (
+            constraint.a,
+            constraint.b,
+            constraint.normal,
+            constraint.tangents,
+            constraint.friction,
+        );
+
+        let count = constraint.points.len();
+        for step in 0..count {
+            let i = if reverse { count - 1 - step } else { step };
+            // along the normal, accumulated impulses only ever push
+            let point = &constraint.points[i];
+            let relative = relative_velocity(&self.bodies[a], &self.bodies[b], point.r_a, point.r_b);
+            let lambda = (point.bias - relative.dot(normal)) * point.normal_mass;
+            let accumulated = (point.normal_impulse + lambda).max(0.0);
+            let applied = accumulated - point.normal_impulse;
+            constraint.points[i].normal_impulse = accumulated;
+            self.apply_impulse(constraint, i, normal * applied);
+
+            // friction, bounded by the normal impulse holding the bodies together
+            for (k, tangent) in tangents.iter().enumerate() {
+                let point = &constraint.points[i];
+                let limit = friction * point.normal_impulse;
+                let relative = relative_velocity(&self.bodies[a], &self.bodies[b], point.r_a, point.r_b);
+                let lambda = -relative.dot(*tangent) * point.tangent_masses[k];
+                let accumulated = (point.tangent_impulses[k] + lambda).clamp(-limit, limit);
+                let applied = accumulated - point.tangent_impulses[k];
+                constraint.points[i].tangent_impulses[k] = accumulated;
+                self.apply_impulse(constraint, i, *tangent * applied);
+            }
+        }
+    }
+
+    /// Pushes body b by `impulse` at the point, and body a back by as much.
+    fn apply_impulse(&mut self, constraint: &ContactConstraint, point: usize, impulse: Vec3) {
+        let PointConstraint { r_a, r_b, .. } = constraint.points[point];
+
+        let body_a = &mut self.bodies[constraint.a];
+        body_a.velocity.0 -= impulse * constraint.inverse_mass_a;
+        body_a.angular_velocity.0 -= constraint.inverse_inertia_a * r_a.cross(impulse);
+
+        let body_b = &mut self.bodies[constraint.b];
+        body_b.velocity.0 += impulse * constraint.inverse_mass_b;
+        body_b.angular_velocity.0 += constraint.inverse_inertia_b * r_b.cross(impulse);
+    }
+}
+
+/// Velocity of b's contact point relative to a's.
+pub(super) fn relative_velocity(a: &PhysicsBody, b: &PhysicsBody, r_a: Vec3, r_b: Vec3) -> Vec3 {
+    (b.velocity.0 + b.angular_velocity.0.cross(r_b)) - (a.velocity.0 + a.angular_velocity.0.cross(r_a))
+}
@@ -0,0 +1,35 @@
+use super::PhysicsWorld;
+
+/// A body overlapping a sensor. `Stay` comes once per step for as long as the overlap lasts,
+/// between its `Enter` and `Exit`. When two sensors overlap, `sensor` is the one with the
+/// lower entity id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerEvent {
+    Enter { sensor: u32, other: u32 },
+    Stay { sensor: u32, other: u32 },
+    Exit { sensor: u32, other: u32 },
+}
+
+impl PhysicsWorld {
+    /// Compares the sensor overlaps the last narrow phase found with the previous step's.
+    pub(super) fn update_triggers(&mut self) {
+        self.sensor_overlaps.sort();
+
+        for &(sensor, other) in &self.sensor_overlaps {
+            let event = if self.triggered.binary_search(&(sensor, other)).is_ok() {
+                TriggerEvent::Stay { sensor, other }
+            } else {
+                TriggerEvent::Enter { sensor, other }
+            };
+            self.trigger_events.push(event);
+        }
+
+        for &(sensor, other) in &self.triggered {
+            if self.sensor_overlaps.binary_search(&(sensor, other)).is_err() {
+                self.trigger_events.push(TriggerEvent::Exit { sensor, other });
+            }
+        }
+
+        self.triggered.clone_from(&self.sensor_overlaps);
+    }
+}
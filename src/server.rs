@@ -4,43 +4,72 @@ pub use ecs::*;
 pub use networking::*;
 
 pub mod audio;
+pub mod netcode;
 pub mod physics;
 pub mod render;
 pub mod utils;
 
 pub use audio::*;
+pub use netcode::*;
 pub use physics::*;
 pub use render::*;
 pub use utils::time::*;
 pub use utils::*;
 
-#[derive(NetSend, Serialize, Deserialize)]
-pub struct TestMessage {
-    pub content: String,
+/// Takes `--token-key HEX` out of `args`. With a key, only players holding a connect token it
+/// signed get in.
+fn token_key_from_args(args: &mut Vec<String>) -> anyhow::Result<Option<TokenKey>> {
+    let Some(i) = args.iter().position(|a| a == "--token-key") else {
+        return Ok(None);
+    };
+    let value = args.get(i + 1).ok_or_else(|| anyhow::anyhow!("--token-key needs a value"))?;
+    let key = TokenKey::from_hex(value)?;
+    args.drain(i..i + 2);
+    Ok(Some(key))
 }
 
 #[tokio::main]
 async fn main() {
+    let mut args: Vec<String> = std::env::args().collect();
+    let parsed = token_key_from_args(&mut args).and_then(|token_key| {
+        let admin = utils::admin::AdminPlugin::from_args(&mut args)?;
+        Ok((token_key, admin, utils::runner::ServerRunner::from_args(args)?))
+    });
+    let (token_key, admin, runner) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            println!("{}", e);
+            println!(
+                "usage: server [--ticks N] [--tick-rate HZ] [--admin-tcp ADDR] [--admin-socket PATH] [--token-key HEX]"
+            );
+            std::process::exit(2);
+        }
+    };
+
     let mut app = App::new();
 
+    let mut networking = networking::NetworkingPlugin::server();
+    if let Some(key) = token_key {
+        networking = networking.with_token_key(key);
+    }
+
     let plugins = plugin_group!(
         physics::PhysicsPlugin,
         utils::UtilPlugin::server(),
-        networking::NetworkingPlugin::server(),
+        networking,
+        netcode::PredictionPlugin::server(),
+        admin,
     );
 
     app.add_plugin(plugins);
 
-    app.init();
-
-    loop {
-        app.run();
-        if app.should_exit() {
-            break;
-        }
-    }
-
-    app.de_init();
+    let clock = runner.run(&mut app);
+    println!(
+        "Ran {} ticks, {} overran, worst tick {:.1}ms",
+        clock.ticks,
+        clock.overruns,
+        clock.worst_tick.as_secs_f64() * 1000.0
+    );
 
     std::process::exit(0);
 }
@@ -11,11 +11,13 @@ use winit::{
 pub use ecs::*;
 pub use networking::*;
 
+pub mod netcode;
 pub mod physics;
 pub mod render;
 pub mod spin;
 pub mod utils;
 
+pub use netcode::*;
 pub use physics::*;
 pub use render::model::ModelHandle;
 use render::sprite::*;
@@ -41,19 +43,34 @@ static ENEMY_VISION_DIST: f32 = 10.0;
 static ENEMY_SUS_TIMER: f32 = 2.0;
 static ENEMY_VISION_RADIANS: f32 = 1.0;
 static ENEMY_SURPRISE_TIMER: f32 = 0.5;
-
-fn ray_intersects_segment(ray_origin: Vec3, ray_dir: Vec3, ray_len: f32, wall: &Wall) -> bool {
-    let wall_dir = wall.p2 - wall.p1;
-    let denom = ray_dir.x * wall_dir.y - ray_dir.y * wall_dir.x;
-    if denom.abs() < f32::EPSILON {
-        return false;
-    }
-
-    let diff = wall.p1 - ray_origin;
-    let t = (diff.x * wall_dir.y - diff.y * wall_dir.x) / denom;
-    let s = (diff.x * ray_dir.y - diff.y * ray_dir.x) / denom;
-
-    t >= 0.0 && t <= ray_len && s >= 0.0 && s <= 1.0
+static ENEMY_RADIUS: f32 = 1.0;
+
+static WALL_GROUP: u32 = 1 << 0;
+static ENEMY_GROUP: u32 = 1 << 1;
+
+static WALL_HALF_THICKNESS: f32 = 0.05;
+// tall enough that rays at any sprite's depth run into it
+static WALL_HALF_HEIGHT: f32 = 1.0;
+
+// walls block rays as thin static boxes standing on their segments
+fn spawn_wall_collider(commands: &mut Commands, wall: &Wall) {
+    let along = wall.p2 - wall.p1;
+    let entity = commands.spawn_entity();
+    commands.add_component(entity, Transform {
+        pos: (wall.p1 + wall.p2) * 0.5,
+        rot: Quat::from_rotation_z(along.y.atan2(along.x)),
+        ..Default::default()
+    });
+    commands.add_component(entity, RigidBody::static_body());
+    commands.add_component(entity, CollisionGroups::new(WALL_GROUP, CollisionGroups::ALL));
+    commands.add_component(entity, Collider::cuboid(Vec3::new(
+        along.length() * 0.5,
+        WALL_HALF_THICKNESS,
+        WALL_HALF_HEIGHT,
+    )));
+    commands.add_component(entity, Velocity(Vec3::ZERO));
+    commands.add_component(entity, AngularVelocity(Vec3::ZERO));
+    commands.add_component(entity, ForceAccumulator(Vec3::ZERO));
 }
 
 #[derive(Resource)]
@@ -85,10 +102,11 @@ async fn main() {
             self.app.insert_resource(gpu);
 
             let plugins = plugin_group!(
-                // physics::PhysicsPlugin,
+                physics::PhysicsPlugin,
                 render::RenderPlugin,
                 utils::UtilPlugin::client(),
                 // networking::NetworkingPlugin::client(),
+                netcode::PredictionPlugin::client(),
             );
             self.app.add_plugin(plugins);
 
@@ -249,6 +267,7 @@ system! {
             100.0,
         ));
         commands.add_component(player, Rotation2D(3.14 / 4.0));
+        commands.add_component(player, Predicted);
         commands.insert_resource(PlayerPosition(Vec3::ZERO));
 
         let enemy = commands.spawn_entity();
@@ -271,6 +290,13 @@ system! {
             last_position: Vec3::ZERO,
             state: AIState::Idle,
         });
+        // static, since the AI moves it; the collider is scaled along with the sprite
+        commands.add_component(enemy, RigidBody::static_body());
+        commands.add_component(enemy, Collider::sphere(ENEMY_RADIUS / enemy_scale));
+        commands.add_component(enemy, CollisionGroups::new(ENEMY_GROUP, CollisionGroups::ALL));
+        commands.add_component(enemy, Velocity(Vec3::ZERO));
+        commands.add_component(enemy, AngularVelocity(Vec3::ZERO));
+        commands.add_component(enemy, ForceAccumulator(Vec3::ZERO));
 
         // walls container
         let walls = commands.spawn_entity();
@@ -356,6 +382,9 @@ system! {
         //     p1: Vec3::new(4.0, -1.0, 0.0) * SPRITE_SCALE,
         //     p2: Vec3::new(4.0, 7.0, 0.0) * SPRITE_SCALE
         // });
+        for wall in walls_comp.0.iter() {
+            spawn_wall_collider(&mut commands, wall);
+        }
         commands.add_component(walls, walls_comp);
         commands.add_component(walls, SpriteBuilder {
             image_path: "rawr".to_string(),
@@ -448,12 +477,15 @@ system! {
     fn process_ai(
         time: res &Time,
         player_pos: res &PlayerPosition,
+        physics_world: res &PhysicsWorld,
         enemies: query (&mut Transform, &mut Rotation2D, &mut Ai),
-        walls_comp: query (&Walls)
     ) {
         let Some(time) = time else {return;};
         let Some(player_pos) = player_pos else {return;};
-        let Some(walls) = walls_comp.next() else {return;};
+        let Some(physics_world) = physics_world else {return;};
+
+        // enemies see past each other (and themselves), but not through walls
+        let vision = QueryFilter::new().groups(CollisionGroups::new(ENEMY_GROUP, !ENEMY_GROUP));
 
         for (enemy_transform, enemy_rotation, ai) in enemies {
             let displacement = player_pos.0 - enemy_transform.pos;
@@ -462,17 +494,14 @@ system! {
             let facing_dir = Vec3::new(enemy_rotation.0.cos(), enemy_rotation.0.sin(), 0.0);
 
             // Helper: Check if player is visible (in range, FOV, clear LOS)
-            let mut is_visible = |current_facing_dir: Vec3| -> bool {
+            let is_visible = |current_facing_dir: Vec3| -> bool {
                 if dist > ENEMY_VISION_DIST || dist < f32::EPSILON { return false; }
                 let dot = current_facing_dir.dot(player_dir);
                 if dot.acos() > ENEMY_VISION_RADIANS { return false; }  // Out of FOV half-angle
                 // Check LOS: ray to player
-                for wall in walls.0.iter() {
-                    if ray_intersects_segment(enemy_transform.pos, player_dir, dist, wall) {
-                        return false;  // Hits wall before player
-                    }
-                }
-                true
+                physics_world
+                    .raycast(enemy_transform.pos, player_dir, dist, &vision)
+                    .is_none()  // Hits wall before player
             };
 
             match ai.state {
@@ -615,15 +644,17 @@ system! {
     fn control_player(
         input: res &mut Input,
         time: res &Time,
+        prediction: res &mut ClientPrediction,
         mut player_pos: res &mut PlayerPosition,
-        player: query (&mut Transform, &Camera, &mut Rotation2D),
-        walls: query (&Walls),
+        physics_world: res &PhysicsWorld,
+        player: query (&Transform, &Camera, &mut Rotation2D),
     ) {
         let Some (input) = input else {return;};
         let Some (time) = time else {return;};
+        let Some(prediction) = prediction else {return;};
         let Some(player_pos) = player_pos else {return;};
         let Some((player_transform, _camera, rotation)) = player.next() else {return;};
-        let Some (walls_comp) = walls.next() else {return;};
+        let Some(physics_world) = physics_world else {return;};
 
         // WASD
         let mut movement = Vec3::ZERO;
@@ -631,25 +662,20 @@ system! {
         if input.is_key_pressed(winit::keyboard::KeyCode::KeyS) {movement += Vec3::Y;}
         if input.is_key_pressed(winit::keyboard::KeyCode::KeyA) {movement -= Vec3::X;}
         if input.is_key_pressed(winit::keyboard::KeyCode::KeyD) {movement += Vec3::X;}
-        movement = movement.normalize();
+        movement = movement.normalize_or_zero();
 
         // ray intersection
-        for wall in walls_comp.0.iter() {
-            if ray_intersects_segment(
-                player_transform.pos,
-                movement,
-                PLAYER_SPEED * time.delta_seconds * 8.0, // magic number 👻
-                wall
-            ) {
-                movement = Vec3::ZERO;
-            }
+        if physics_world.raycast(
+            player_transform.pos,
+            movement,
+            PLAYER_SPEED * time.delta_seconds * 8.0, // magic number 👻
+            &QueryFilter::new().groups(CollisionGroups::new(CollisionGroups::ALL, WALL_GROUP))
+        ).is_some() {
+            movement = Vec3::ZERO;
         }
 
-        // uses `length_squared` to avoid a square root calculation
-        if movement.length_squared() > 0.0 {
-            movement = movement * PLAYER_SPEED * time.delta_seconds;
-            player_transform.pos += movement;
-        }
+        // the move itself happens in fixed steps inside `predict_player`
+        prediction.set_input(movement * PLAYER_SPEED);
 
         let (mousex, mousey) = input.get_mouse_position();
         let to_mousex = mousex - SCREEN_W as f64 / 2.0;
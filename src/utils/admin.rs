@@ -0,0 +1,584 @@
+use crate::utils::runner::ServerClock;
+use crate::utils::snapshot::WorldSnapshot;
+use crate::*;
+use anyhow::{Result, anyhow};
+use glam::Vec3;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::time::Duration;
+
+/// Builds the entity a prefab describes onto a freshly spawned entity.
+pub type PrefabFn = fn(&mut Commands, u32);
+
+/// Where `save` writes snapshots unless the plugin is given another directory.
+pub const DEFAULT_SNAPSHOT_DIR: &str = "snapshots";
+
+const BUILTIN_HELP: [&str; 7] = [
+    "help",
+    "peers",
+    "kick <peer>",
+    "tickrate [hz]",
+    "stats",
+    "spawn <prefab> [x y z]",
+    "save <name>",
+];
+
+/// Line-based admin commands read from stdin and, optionally, from local sockets.
+///
+/// Each line is a command name followed by whitespace-separated arguments. The reply is any
+/// number of output lines followed by `ok` or `error: <reason>`; replies to stdin go to stdout.
+/// Commands reach game code through the [`AdminCommands`] resource, so systems can handle their
+/// own. The ECS has no event channel; like [`PhysicsEvents`](crate::physics::PhysicsEvents), a
+/// resource filled each tick stands in for one.
+///
+/// Nothing authenticates the sockets, so TCP is only served on loopback addresses.
+pub struct AdminPlugin {
+    stdin: bool,
+    tcp: Option<SocketAddr>,
+    #[cfg(unix)]
+    unix: Option<PathBuf>,
+    snapshot_dir: PathBuf,
+}
+
+impl AdminPlugin {
+    pub fn new() -> Self {
+        Self {
+            stdin: true,
+            tcp: None,
+            #[cfg(unix)]
+            unix: None,
+            snapshot_dir: PathBuf::from(DEFAULT_SNAPSHOT_DIR),
+        }
+    }
+
+    /// Takes `--admin-tcp ADDR` and, on Unix, `--admin-socket PATH` out of `args`, leaving the
+    /// rest for whoever parses them next.
+    pub fn from_args(args: &mut Vec<String>) -> Result<Self> {
+        let mut plugin = Self::new();
+
+        let mut i = 0;
+        while i < args.len() {
+            let flag = args[i].as_str();
+            if !matches!(flag, "--admin-tcp" | "--admin-socket") {
+                i += 1;
+                continue;
+            }
+            let value = args
+                .get(i + 1)
+                .cloned()
+                .ok_or_else(|| anyhow!("{} needs a value", flag))?;
+            match flag {
+                "--admin-tcp" => {
+                    let addr: SocketAddr = value.parse()?;
+                    if !addr.ip().is_loopback() {
+                        return Err(anyhow!("--admin-tcp only takes loopback addresses, not {}", addr));
+                    }
+                    plugin.tcp = Some(addr);
+                }
+                #[cfg(unix)]
+                _ => plugin.unix = Some(value.into()),
+                #[cfg(not(unix))]
+                _ => return Err(anyhow!("--admin-socket needs Unix sockets")),
+            }
+            args.drain(i..i + 2);
+        }
+
+        Ok(plugin)
+    }
+
+    pub fn without_stdin(mut self) -> Self {
+        self.stdin = false;
+        self
+    }
+
+    /// Must be a loopback address, since there is no authentication; anything else isn't
+    /// listened on.
+    pub fn with_tcp(mut self, addr: SocketAddr) -> Self {
+        self.tcp = Some(addr);
+        self
+    }
+
+    /// Directory `save` writes its snapshots to. Defaults to [`DEFAULT_SNAPSHOT_DIR`].
+    pub fn with_snapshot_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.snapshot_dir = dir.into();
+        self
+    }
+
+    /// Replaces a socket already at `path`, which an earlier run may have left behind. Any other
+    /// file there is left alone and no socket is served. Only the owner may connect.
+    #[cfg(unix)]
+    pub fn with_unix_socket(mut self, path: impl Into<PathBuf>) -> Self {
+        self.unix = Some(path.into());
+        self
+    }
+}
+
+impl Default for AdminPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plugin for AdminPlugin {
+    fn build(&self, app: &mut App) {
+        let (tx, rx) = channel();
+
+        if self.stdin {
+            let tx = tx.clone();
+            std::thread::spawn(move || {
+                for line in std::io::stdin().lock().lines() {
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    if tx.send((line, None)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        let mut tcp_addr = None;
+        if let Some(addr) = self.tcp.filter(|addr| {
+            let loopback = addr.ip().is_loopback();
+            if !loopback {
+                println!("Not listening for admin commands on {}, it isn't a loopback address", addr);
+            }
+            loopback
+        }) {
+            match TcpListener::bind(addr) {
+                Ok(listener) => {
+                    tcp_addr = listener.local_addr().ok();
+                    let tx = tx.clone();
+                    std::thread::spawn(move || {
+                        for stream in listener.incoming().flatten() {
+                            let Ok(writer) = stream.try_clone() else {
+                                continue;
+                            };
+                            serve(stream, writer, tx.clone());
+                        }
+                    });
+                }
+                Err(e) => println!("Failed to listen for admin commands on {}: {}", addr, e),
+            }
+        }
+
+        #[cfg(unix)]
+        if let Some(path) = &self.unix {
+            use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+            if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+                let _ = std::fs::remove_file(path);
+            }
+            match std::os::unix::net::UnixListener::bind(path) {
+                Ok(listener) => 'listen: {
+                    // nothing authenticates the socket, so nobody but the owner gets to connect
+                    if let Err(e) = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)) {
+                        println!("Failed to restrict admin socket {}: {}", path.display(), e);
+                        drop(listener);
+                        let _ = std::fs::remove_file(path);
+                        break 'listen;
+                    }
+                    let tx = tx.clone();
+                    std::thread::spawn(move || {
+                        for stream in listener.incoming().flatten() {
+                            let Ok(writer) = stream.try_clone() else {
+                                continue;
+                            };
+                            serve(stream, writer, tx.clone());
+                        }
+                    });
+                }
+                Err(e) => println!("Failed to listen for admin commands on {}: {}", path.display(), e),
+            }
+        }
+
+        let mut commands = AdminCommands::default();
+        for usage in BUILTIN_HELP {
+            commands.describe(usage);
+        }
+
+        app.insert_resource(AdminConsole {
+            tx: Mutex::new(tx),
+            rx: Mutex::new(rx),
+            tcp_addr,
+            snapshot_dir: self.snapshot_dir.clone(),
+        });
+        app.insert_resource(commands);
+        app.insert_resource(Prefabs::default());
+        app.add_system(receive_admin_commands, SystemStage::PreUpdate);
+        app.add_system(run_builtin_admin_commands, SystemStage::Update);
+        app.add_system(answer_admin_commands, SystemStage::PostUpdate);
+    }
+}
+
+type Incoming = (String, Option<Sender<String>>);
+
+/// Reads commands from one socket connection and writes their replies back.
+fn serve<S: Read + Write + Send + 'static>(reader: S, mut writer: S, tx: Sender<Incoming>) {
+    let (tx_reply, rx_reply) = channel::<String>();
+
+    std::thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if tx.send((line, Some(tx_reply.clone()))).is_err() {
+                break;
+            }
+        }
+    });
+    std::thread::spawn(move || {
+        for line in rx_reply {
+            if writeln!(writer, "{}", line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+#[derive(Resource)]
+pub struct AdminConsole {
+    tx: Mutex<Sender<Incoming>>,
+    rx: Mutex<Receiver<Incoming>>,
+    tcp_addr: Option<SocketAddr>,
+    snapshot_dir: PathBuf,
+}
+
+impl AdminConsole {
+    /// Where the TCP listener ended up, which matters when it was asked for port 0.
+    pub fn tcp_addr(&self) -> Option<SocketAddr> {
+        self.tcp_addr
+    }
+
+    /// Queues a command as if it had been typed in. The reply lines arrive on the returned
+    /// channel once the command has run.
+    pub fn submit(&self, line: &str) -> Receiver<String> {
+        let (tx_reply, rx_reply) = channel();
+        let _ = self.tx.lock().unwrap().send((line.to_string(), Some(tx_reply)));
+        rx_reply
+    }
+}
+
+/// One admin command. Handling it means calling [`reply`](Self::reply) or
+/// [`fail`](Self::fail); commands nobody handles are answered with an error.
+pub struct AdminCommand {
+    pub name: String,
+    pub args: Vec<String>,
+    output: Vec<String>,
+    outcome: Option<Result<(), String>>,
+    reply_to: Option<Sender<String>>,
+}
+
+impl AdminCommand {
+    fn parse(line: &str, reply_to: Option<Sender<String>>) -> Option<Self> {
+        let mut words = line.split_whitespace().map(str::to_string);
+        Some(Self {
+            name: words.next()?,
+            args: words.collect(),
+            output: Vec::new(),
+            outcome: None,
+            reply_to,
+        })
+    }
+
+    /// Adds a line to the reply and marks the command handled.
+    pub fn reply(&mut self, line: impl Into<String>) {
+        self.output.push(line.into());
+        self.outcome.get_or_insert(Ok(()));
+    }
+
+    pub fn succeed(&mut self) {
+        self.outcome.get_or_insert(Ok(()));
+    }
+
+    pub fn fail(&mut self, reason: impl Into<String>) {
+        self.outcome = Some(Err(reason.into()));
+    }
+
+    pub fn is_handled(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    /// Parses an argument, with an error message fit for [`fail`](Self::fail).
+    pub fn arg<T: FromStr>(&self, index: usize, what: &str) -> Result<T, String> {
+        let arg = self.args.get(index).ok_or_else(|| format!("missing {}", what))?;
+        arg.parse().map_err(|_| format!("bad {} {:?}", what, arg))
+    }
+
+    fn answer(self) {
+        let outcome = self
+            .outcome
+            .unwrap_or_else(|| Err(format!("unknown command {:?}, try help", self.name)));
+        let status = match outcome {
+            Ok(()) => "ok".to_string(),
+            Err(reason) => format!("error: {}", reason),
+        };
+
+        let lines = self.output.into_iter().chain(std::iter::once(status));
+        match self.reply_to {
+            Some(tx) => {
+                for line in lines {
+                    let _ = tx.send(line);
+                }
+            }
+            None => {
+                for line in lines {
+                    println!("{}", line);
+                }
+            }
+        }
+    }
+}
+
+/// Admin commands that arrived this tick, standing in for an event channel. Handle them from an
+/// `Update` system; they are answered and cleared in `PostUpdate`.
+#[derive(Resource, Default)]
+pub struct AdminCommands {
+    pub commands: Vec<AdminCommand>,
+    help: BTreeMap<String, String>,
+}
+
+impl AdminCommands {
+    /// Unhandled commands called `name`.
+    pub fn named<'a>(&'a mut self, name: &'a str) -> impl Iterator<Item = &'a mut AdminCommand> + 'a {
+        self.commands
+            .iter_mut()
+            .filter(move |c| c.name == name && !c.is_handled())
+    }
+
+    /// Lists a command in `help`. The usage starts with the command's name.
+    pub fn describe(&mut self, usage: &str) {
+        let name = usage.split_whitespace().next().unwrap_or_default();
+        self.help.insert(name.to_string(), usage.to_string());
+    }
+}
+
+/// Named entity templates the `spawn` command can build.
+#[derive(Resource, Default)]
+pub struct Prefabs {
+    prefabs: BTreeMap<String, PrefabFn>,
+}
+
+impl Prefabs {
+    pub fn register(&mut self, name: &str, prefab: PrefabFn) {
+        self.prefabs.insert(name.to_string(), prefab);
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.prefabs.keys().map(String::as_str)
+    }
+
+    /// Spawns an entity and builds `name` onto it.
+    pub fn spawn(&self, commands: &mut Commands, name: &str) -> Option<u32> {
+        let prefab = self.prefabs.get(name)?;
+        let id = commands.spawn_entity();
+        prefab(commands, id);
+        Some(id)
+    }
+}
+
+system! {
+    fn receive_admin_commands(
+        console: res &AdminConsole,
+        commands: res &mut AdminCommands,
+    ) {
+        let (Some(console), Some(commands)) = (console, commands) else {
+            return;
+        };
+
+        let rx = console.rx.lock().unwrap();
+        while let Ok((line, reply_to)) = rx.try_recv() {
+            if let Some(command) = AdminCommand::parse(&line, reply_to) {
+                commands.commands.push(command);
+            }
+        }
+    }
+}
+
+system! {
+    fn run_builtin_admin_commands(
+        admin: res &mut AdminCommands,
+        console: res &AdminConsole,
+        networking: res &Networking,
+        clock: res &mut ServerClock,
+        prefabs: res &Prefabs,
+        commands: commands,
+    ) {
+        let Some(admin) = admin else {
+            return;
+        };
+        if admin.commands.is_empty() {
+            return;
+        }
+        let mut clock = clock;
+
+        let help: Vec<String> = admin.help.values().cloned().collect();
+        for command in admin.commands.iter_mut().filter(|c| !c.is_handled()) {
+            match command.name.as_str() {
+                "help" => {
+                    for usage in &help {
+                        command.reply(usage.clone());
+                    }
+                }
+                "peers" => list_peers(networking, command),
+                "kick" => kick(networking, command),
+                "tickrate" => tick_rate(clock.as_deref_mut(), command),
+                "stats" => stats(&commands, clock.as_deref(), command),
+                "spawn" => spawn(&mut commands, prefabs, command),
+                "save" => save(&commands, console, command),
+                _ => {}
+            }
+        }
+    }
+}
+
+system! {
+    fn answer_admin_commands(commands: res &mut AdminCommands) {
+        let Some(commands) = commands else {
+            return;
+        };
+
+        for command in commands.commands.drain(..) {
+            command.answer();
+        }
+    }
+}
+
+fn list_peers(networking: Option<&Networking>, command: &mut AdminCommand) {
+    let Some(networking) = networking else {
+        command.fail("networking isn't running");
+        return;
+    };
+
+    let stats = networking.stats();
+    for peer in networking.peers() {
+        let s = stats.peer(peer).copied().unwrap_or_default();
+        command.reply(format!("{} in {}B out {}B", peer, s.bytes_in, s.bytes_out));
+    }
+    command.succeed();
+}
+
+fn kick(networking: Option<&Networking>, command: &mut AdminCommand) {
+    let Some(networking) = networking else {
+        command.fail("networking isn't running");
+        return;
+    };
+    let peer: u32 = match command.arg(0, "peer") {
+        Ok(peer) => peer,
+        Err(e) => return command.fail(e),
+    };
+
+    if !networking.peers().any(|p| p == peer) {
+        return command.fail(format!("peer {} isn't connected", peer));
+    }
+    match networking.disconnect(peer) {
+        Ok(()) => command.reply(format!("kicked {}", peer)),
+        Err(e) => command.fail(e.to_string()),
+    }
+}
+
+fn tick_rate(clock: Option<&mut ServerClock>, command: &mut AdminCommand) {
+    let Some(clock) = clock else {
+        command.fail("not running at a fixed tick rate");
+        return;
+    };
+
+    if !command.args.is_empty() {
+        let rate = match command.arg::<f64>(0, "tick rate") {
+            Ok(rate) if rate > 0.0 && rate.is_finite() => rate,
+            Ok(_) => return command.fail("tick rate must be positive and finite"),
+            Err(e) => return command.fail(e),
+        };
+        clock.tick_rate = rate;
+    }
+    command.reply(format!("{}Hz", clock.tick_rate));
+}
+
+fn stats(commands: &Commands, clock: Option<&ServerClock>, command: &mut AdminCommand) {
+    if let Some(clock) = clock {
+        command.reply(format!(
+            "{} ticks at {}Hz, {} overran, last {} worst {}",
+            clock.ticks,
+            clock.tick_rate,
+            clock.overruns,
+            millis(clock.last_tick),
+            millis(clock.worst_tick)
+        ));
+    }
+
+    for stage in commands.scheduler_stats().stages {
+        command.reply(format!("{:?} {}", stage.stage, millis(stage.last_run())));
+        for group in stage.groups {
+            command.reply(format!("  {} {}", millis(group.last_run), group.systems.join(", ")));
+        }
+    }
+    command.succeed();
+}
+
+fn spawn(commands: &mut Commands, prefabs: Option<&Prefabs>, command: &mut AdminCommand) {
+    let Some(prefabs) = prefabs else {
+        return command.fail("no prefabs registered");
+    };
+    let Some(name) = command.args.first().cloned() else {
+        let names: Vec<&str> = prefabs.names().collect();
+        return command.fail(format!("missing prefab, one of: {}", names.join(", ")));
+    };
+
+    let pos = if command.args.len() > 1 {
+        let pos = (1..4)
+            .map(|i| command.arg::<f32>(i, "coordinate"))
+            .collect::<Result<Vec<f32>, String>>();
+        match pos {
+            Ok(pos) => Some(Vec3::from_slice(&pos)),
+            Err(e) => return command.fail(e),
+        }
+    } else {
+        None
+    };
+
+    let Some(id) = prefabs.spawn(commands, &name) else {
+        return command.fail(format!("unknown prefab {:?}", name));
+    };
+    if let Some(pos) = pos {
+        let transform = match commands.get_entity(id).and_then(|e| e.get_component::<Transform>()) {
+            Some(t) => Transform { pos, scale: t.scale, rot: t.rot },
+            None => Transform { pos, ..Default::default() },
+        };
+        commands.insert_component_dyn(id, Box::new(transform));
+    }
+    command.reply(format!("spawned {} as {}", name, id));
+}
+
+fn save(commands: &Commands, console: Option<&AdminConsole>, command: &mut AdminCommand) {
+    let Some(console) = console else {
+        return command.fail("no snapshot directory");
+    };
+    let name: String = match command.arg(0, "name") {
+        Ok(name) => name,
+        Err(e) => return command.fail(e),
+    };
+    // a bare file name, so snapshots can't be written anywhere but the snapshot directory
+    let is_file_name = Path::new(&name).file_name().is_some_and(|file| file == name.as_str());
+    if !is_file_name || name.contains(['/', '\\']) {
+        return command.fail(format!("bad name {:?}, it must be a plain file name", name));
+    }
+
+    if let Err(e) = std::fs::create_dir_all(&console.snapshot_dir) {
+        return command.fail(format!("failed to create {}: {}", console.snapshot_dir.display(), e));
+    }
+    let path = console.snapshot_dir.join(&name);
+    let snapshot = WorldSnapshot::capture(commands);
+    match snapshot.save(&path) {
+        Ok(()) => command.reply(format!("saved {} entities to {}", snapshot.entities.len(), path.display())),
+        Err(e) => command.fail(format!("{:#}", e)),
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
@@ -5,7 +5,10 @@ use std::path::PathBuf;
 use crate::utils::input::Input;
 use crate::*;
 
+pub mod admin;
 pub mod input;
+pub mod runner;
+pub mod snapshot;
 pub mod time;
 
 pub struct UtilPlugin {
@@ -0,0 +1,200 @@
+use crate::*;
+use anyhow::{Result, anyhow};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_TICK_RATE: f64 = 60.0;
+/// The last stretch before a tick is due is spun rather than slept, since sleeps overshoot.
+const SPIN_MARGIN: Duration = Duration::from_millis(1);
+/// Falling further behind than this many ticks skips ahead instead of running them back to back.
+const MAX_CATCH_UP_TICKS: u32 = 5;
+const OVERRUN_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How the dedicated server is keeping up. Inserted by [`ServerRunner`]; changing `tick_rate`
+/// takes effect on the next tick.
+#[derive(Resource, Clone, Debug)]
+pub struct ServerClock {
+    pub tick_rate: f64,
+    pub ticks: u64,
+    /// Ticks that took longer than the tick interval to run.
+    pub overruns: u64,
+    pub last_tick: Duration,
+    pub worst_tick: Duration,
+}
+
+impl ServerClock {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.tick_rate.max(f64::EPSILON))
+    }
+}
+
+/// Runs an `App` at a fixed tick rate until it exits, is interrupted with SIGINT or SIGTERM, or
+/// has run the number of ticks given with `--ticks`.
+pub struct ServerRunner {
+    tick_rate: f64,
+    max_ticks: Option<u64>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl ServerRunner {
+    pub fn new(tick_rate: f64) -> Self {
+        Self {
+            tick_rate,
+            max_ticks: None,
+            shutdown: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Understands `--ticks N` and `--tick-rate HZ`. The first argument is the program name.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut runner = Self::new(DEFAULT_TICK_RATE);
+        let mut args = args.into_iter().skip(1);
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or_else(|| anyhow!("{} needs a value", name));
+            match arg.as_str() {
+                "--ticks" => runner.max_ticks = Some(value("--ticks")?.parse()?),
+                "--tick-rate" => {
+                    runner.tick_rate = value("--tick-rate")?.parse()?;
+                    if runner.tick_rate.is_nan() || runner.tick_rate <= 0.0 {
+                        return Err(anyhow!("--tick-rate must be positive"));
+                    }
+                }
+                other => return Err(anyhow!("unknown argument {:?}", other)),
+            }
+        }
+
+        Ok(runner)
+    }
+
+    pub fn with_tick_rate(mut self, tick_rate: f64) -> Self {
+        self.tick_rate = tick_rate;
+        self
+    }
+
+    pub fn with_max_ticks(mut self, ticks: u64) -> Self {
+        self.max_ticks = Some(ticks);
+        self
+    }
+
+    /// Setting the flag stops the runner after the current tick, as a signal would.
+    pub fn shutdown_handle(&self) -> Arc<AtomicBool> {
+        self.shutdown.clone()
+    }
+
+    /// Runs `Init`, ticks until told to stop, then shuts networking down and runs `DeInit`.
+    pub fn run(&self, app: &mut App) -> ServerClock {
+        self.listen_for_signals();
+
+        app.insert_resource(ServerClock {
+            tick_rate: self.tick_rate,
+            ticks: 0,
+            overruns: 0,
+            last_tick: Duration::ZERO,
+            worst_tick: Duration::ZERO,
+        });
+        app.init();
+
+        let mut next_tick = Instant::now();
+        let mut last_report = Instant::now();
+        let mut unreported = 0;
+        let mut worst_unreported = Duration::ZERO;
+
+        loop {
+            if self.shutdown.load(Ordering::Relaxed) || app.should_exit() {
+                break;
+            }
+            let clock = app.get_resource_mut::<ServerClock>().expect("ServerClock was removed");
+            if self.max_ticks.is_some_and(|max| clock.ticks >= max) {
+                break;
+            }
+
+            let started = Instant::now();
+            app.run();
+            let took = started.elapsed();
+
+            let clock = app.get_resource_mut::<ServerClock>().expect("ServerClock was removed");
+            let interval = clock.interval();
+            clock.ticks += 1;
+            clock.last_tick = took;
+            clock.worst_tick = clock.worst_tick.max(took);
+            if took > interval {
+                clock.overruns += 1;
+                unreported += 1;
+                worst_unreported = worst_unreported.max(took);
+            }
+
+            if unreported > 0 && last_report.elapsed() >= OVERRUN_REPORT_INTERVAL {
+                println!(
+                    "{} ticks overran the {:.1}ms budget in the last {:.1}s, the worst took {:.1}ms",
+                    unreported,
+                    interval.as_secs_f64() * 1000.0,
+                    last_report.elapsed().as_secs_f64(),
+                    worst_unreported.as_secs_f64() * 1000.0,
+                );
+                unreported = 0;
+                worst_unreported = Duration::ZERO;
+                last_report = Instant::now();
+            }
+
+            next_tick += interval;
+            let now = Instant::now();
+            if now > next_tick + interval * MAX_CATCH_UP_TICKS {
+                next_tick = now;
+            }
+            wait_until(next_tick);
+        }
+
+        if let Some(networking) = app.get_resource_mut::<Networking>() {
+            networking.shutdown();
+        }
+        app.de_init();
+
+        app.get_resource::<ServerClock>().unwrap().clone()
+    }
+
+    /// Signals need a tokio runtime to be delivered; without one only the shutdown handle works.
+    fn listen_for_signals(&self) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        let shutdown = self.shutdown.clone();
+        runtime.spawn(async move {
+            wait_for_signal().await;
+            println!("Shutting down");
+            shutdown.store(true, Ordering::Relaxed);
+        });
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let Ok(mut terminate) = signal(SignalKind::terminate()) else {
+        let _ = tokio::signal::ctrl_c().await;
+        return;
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Sleeps most of the way, then spins for precision.
+fn wait_until(deadline: Instant) {
+    let now = Instant::now();
+    if deadline > now + SPIN_MARGIN {
+        std::thread::sleep(deadline - now - SPIN_MARGIN);
+    }
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
@@ -0,0 +1,81 @@
+use crate::*;
+use anyhow::{Context, Result, anyhow};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The `#[replicate]` components of every entity that has any, keyed by component name. Only
+/// replicated components know how to serialize themselves, so everything else is left out.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct WorldSnapshot {
+    pub tick: Tick,
+    pub entities: BTreeMap<u32, BTreeMap<String, Vec<u8>>>,
+}
+
+impl WorldSnapshot {
+    pub fn capture(commands: &Commands) -> Self {
+        let mut entities = BTreeMap::new();
+
+        for (id, _) in unsafe { World::get_components::<EntityId>(commands.world) } {
+            let Some(entity) = commands.get_entity(id) else {
+                continue;
+            };
+            let components: BTreeMap<String, Vec<u8>> = REPLICATED
+                .iter()
+                .filter_map(|registration| {
+                    let component = entity.get_component_by_id((registration.component_id)())?;
+                    Some((registration.name.to_string(), (registration.encode)(component)))
+                })
+                .collect();
+            if !components.is_empty() {
+                entities.insert(id, components);
+            }
+        }
+
+        Self {
+            tick: commands.tick(),
+            entities,
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let json = serde_json::to_string(self)?;
+        std::fs::write(path, json).with_context(|| format!("failed to write snapshot {}", path.display()))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read snapshot {}", path.display()))?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Spawns a fresh entity for every saved one and returns the new id of each saved id.
+    pub fn restore(&self, commands: &mut Commands) -> Result<BTreeMap<u32, u32>> {
+        let registrations: BTreeMap<&str, &ReplicatedRegistration> =
+            REPLICATED.iter().map(|r| (r.name, *r)).collect();
+
+        // decode everything first so a bad snapshot doesn't leave half its entities behind
+        let mut decoded = Vec::new();
+        for (&saved, components) in &self.entities {
+            let mut entity = Vec::new();
+            for (name, bytes) in components {
+                let registration = registrations
+                    .get(name.as_str())
+                    .ok_or_else(|| anyhow!("unknown component {:?} on entity {}", name, saved))?;
+                entity.push((registration.decode)(bytes)?);
+            }
+            decoded.push((saved, entity));
+        }
+
+        let mut ids = BTreeMap::new();
+        for (saved, components) in decoded {
+            let id = commands.spawn_entity();
+            for component in components {
+                commands.insert_component_dyn(id, component);
+            }
+            ids.insert(saved, id);
+        }
+        Ok(ids)
+    }
+}
@@ -0,0 +1,277 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::Receiver;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use glam::Vec3;
+use rust_game_engine::utils::admin::{AdminCommands, AdminConsole, AdminPlugin, Prefabs};
+use rust_game_engine::utils::runner::ServerClock;
+use rust_game_engine::utils::snapshot::WorldSnapshot;
+// the system! and Resource macros expand to paths from the ecs prelude
+use rust_game_engine::*;
+
+#[derive(Resource, Default)]
+struct Health(u32);
+
+system! {
+    fn heal(admin: res &mut AdminCommands, health: res &mut Health) {
+        let (Some(admin), Some(health)) = (admin, health) else {
+            return;
+        };
+        for command in admin.named("heal") {
+            match command.arg::<u32>(0, "amount") {
+                Ok(amount) => {
+                    health.0 += amount;
+                    command.reply(format!("health {}", health.0));
+                }
+                Err(e) => command.fail(e),
+            }
+        }
+    }
+}
+
+fn crate_prefab(commands: &mut Commands, id: u32) {
+    commands.add_component(id, Transform { scale: Vec3::splat(2.0), ..Default::default() });
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugin(AdminPlugin::new().without_stdin());
+    app
+}
+
+/// Runs the app until the reply to `line` is complete.
+fn execute(app: &mut App, line: &str) -> Vec<String> {
+    let replies = app.get_resource::<AdminConsole>().unwrap().submit(line);
+    collect_reply(app, &replies)
+}
+
+fn collect_reply(app: &mut App, replies: &Receiver<String>) -> Vec<String> {
+    let mut lines = Vec::new();
+    for _ in 0..100 {
+        app.run();
+        lines.extend(replies.try_iter());
+        if lines.last().is_some_and(|l| l == "ok" || l.starts_with("error: ")) {
+            return lines;
+        }
+    }
+    panic!("no complete reply, got {:?}", lines);
+}
+
+#[test]
+fn built_in_commands_report_and_change_server_state() {
+    let mut app = app();
+    app.insert_resource(ServerClock {
+        tick_rate: 60.0,
+        ticks: 12,
+        overruns: 1,
+        last_tick: Duration::from_millis(2),
+        worst_tick: Duration::from_millis(20),
+    });
+
+    assert_eq!(execute(&mut app, "tickrate 30"), vec!["30Hz", "ok"]);
+    assert_eq!(app.get_resource::<ServerClock>().unwrap().tick_rate, 30.0);
+    assert_eq!(execute(&mut app, "tickrate"), vec!["30Hz", "ok"]);
+    assert_eq!(execute(&mut app, "tickrate -5"), vec!["error: tick rate must be positive and finite"]);
+    assert_eq!(execute(&mut app, "tickrate inf"), vec!["error: tick rate must be positive and finite"]);
+    assert_eq!(execute(&mut app, "tickrate NaN"), vec!["error: tick rate must be positive and finite"]);
+    assert_eq!(execute(&mut app, "tickrate fast"), vec!["error: bad tick rate \"fast\""]);
+
+    let stats = execute(&mut app, "stats");
+    assert!(stats[0].starts_with("12 ticks at 30Hz, 1 overran"), "{:?}", stats);
+    assert!(stats.iter().any(|l| l.starts_with("Update ")), "{:?}", stats);
+    assert!(stats.iter().any(|l| l.contains("run_builtin_admin_commands")), "{:?}", stats);
+
+    let help = execute(&mut app, "help");
+    assert!(help.contains(&"kick <peer>".to_string()), "{:?}", help);
+
+    // without networking there's nobody to list or kick
+    assert_eq!(execute(&mut app, "peers"), vec!["error: networking isn't running"]);
+    assert_eq!(execute(&mut app, "dance"), vec!["error: unknown command \"dance\", try help"]);
+}
+
+#[test]
+fn game_code_handles_its_own_commands() {
+    let mut app = app();
+    app.insert_resource(Health(10));
+    app.add_system(heal, SystemStage::Update);
+    app.get_resource_mut::<AdminCommands>().unwrap().describe("heal <amount>");
+
+    assert_eq!(execute(&mut app, "heal 5"), vec!["health 15", "ok"]);
+    assert_eq!(execute(&mut app, "heal"), vec!["error: missing amount"]);
+    assert!(execute(&mut app, "help").contains(&"heal <amount>".to_string()));
+}
+
+#[test]
+fn prefabs_spawn_and_snapshots_save_them() {
+    let dir = std::env::temp_dir().join(format!("admin-snapshots-{}", std::process::id()));
+    let mut app = App::new();
+    app.add_plugin(AdminPlugin::new().without_stdin().with_snapshot_dir(&dir));
+    app.get_resource_mut::<Prefabs>().unwrap().register("crate", crate_prefab);
+
+    assert_eq!(execute(&mut app, "spawn crate 1 2 3"), vec!["spawned crate as 0", "ok"]);
+    assert_eq!(execute(&mut app, "spawn crate"), vec!["spawned crate as 1", "ok"]);
+    assert_eq!(execute(&mut app, "spawn barrel"), vec!["error: unknown prefab \"barrel\""]);
+    assert_eq!(execute(&mut app, "spawn crate 1 2"), vec!["error: missing coordinate"]);
+    let moved = app.get_entity(0).unwrap().get_component::<Transform>().unwrap();
+    assert_eq!((moved.pos, moved.scale), (Vec3::new(1.0, 2.0, 3.0), Vec3::splat(2.0)));
+
+    let path = dir.join("world.json");
+    let reply = execute(&mut app, "save world.json");
+    assert_eq!(reply, vec![format!("saved 2 entities to {}", path.display()), "ok".to_string()]);
+    for escape in ["../world.json", "/tmp/world.json", "sub/world.json", ".."] {
+        let reply = execute(&mut app, &format!("save {}", escape));
+        assert!(reply[0].starts_with("error: bad name"), "{:?} was saved: {:?}", escape, reply);
+    }
+
+    let snapshot = WorldSnapshot::load(&path).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(snapshot.entities.len(), 2);
+    assert!(snapshot.entities[&0].contains_key("Transform"));
+
+    let mut restored = App::new();
+    let ids = snapshot.restore(&mut restored).unwrap();
+    let transform = restored.get_entity(ids[&0]).unwrap().get_component::<Transform>().unwrap();
+    assert_eq!(transform.pos, Vec3::new(1.0, 2.0, 3.0));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn peers_are_listed_and_kicked() {
+    let hub = LoopbackHub::new();
+    let mut server = app();
+    server.add_plugin(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&hub)));
+    let transport = LoopbackTransport::client(&hub);
+    let id = transport.id();
+    let mut client = App::new();
+    client.add_plugin(NetworkingPlugin::client().with_transport(transport));
+
+    let started = Instant::now();
+    while server.get_resource::<Networking>().unwrap().peers().count() == 0 {
+        assert!(started.elapsed() < Duration::from_secs(2), "client never connected");
+        server.run();
+        client.run();
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    let peers = execute(&mut server, "peers");
+    assert_eq!(peers.len(), 2);
+    assert!(peers[0].starts_with(&format!("{} in ", id)), "{:?}", peers);
+    assert_eq!(execute(&mut server, "kick 99"), vec!["error: peer 99 isn't connected"]);
+    assert_eq!(execute(&mut server, &format!("kick {}", id)), vec![format!("kicked {}", id), "ok".to_string()]);
+
+    let started = Instant::now();
+    while server.get_resource::<Networking>().unwrap().peers().count() > 0 {
+        assert!(started.elapsed() < Duration::from_secs(2), "peer was never dropped");
+        server.run();
+        client.run();
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(execute(&mut server, "peers"), vec!["ok"]);
+}
+
+/// Sends `lines` over a socket from another thread while the app runs, and returns the reply.
+fn over_socket<S: BufRead + Send + 'static>(
+    app: &mut App,
+    mut stream: S,
+    write: impl FnOnce(&mut S) + Send + 'static,
+) -> Vec<String> {
+    let handle = thread::spawn(move || {
+        write(&mut stream);
+        let mut reply = Vec::new();
+        for line in stream.lines() {
+            let line = line.unwrap();
+            let done = line == "ok" || line.starts_with("error: ");
+            reply.push(line);
+            if done {
+                break;
+            }
+        }
+        reply
+    });
+
+    let started = Instant::now();
+    while !handle.is_finished() {
+        assert!(started.elapsed() < Duration::from_secs(2), "no reply over the socket");
+        app.run();
+        thread::sleep(Duration::from_millis(1));
+    }
+    handle.join().unwrap()
+}
+
+#[test]
+fn commands_arrive_over_tcp() {
+    let mut app = App::new();
+    app.add_plugin(AdminPlugin::new().without_stdin().with_tcp("127.0.0.1:0".parse().unwrap()));
+    app.insert_resource(Health(1));
+    app.add_system(heal, SystemStage::Update);
+
+    let addr = app.get_resource::<AdminConsole>().unwrap().tcp_addr().unwrap();
+    let stream = BufReader::new(TcpStream::connect(addr).unwrap());
+    let reply = over_socket(&mut app, stream, |s| writeln!(s.get_mut(), "heal 2").unwrap());
+    assert_eq!(reply, vec!["health 3", "ok"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn commands_arrive_over_a_unix_socket() {
+    use std::os::unix::net::UnixStream;
+
+    let path = std::env::temp_dir().join(format!("admin-{}.sock", std::process::id()));
+    let mut app = App::new();
+    app.add_plugin(AdminPlugin::new().without_stdin().with_unix_socket(&path));
+    app.insert_resource(Health(1));
+    app.add_system(heal, SystemStage::Update);
+
+    let stream = BufReader::new(UnixStream::connect(&path).unwrap());
+    let reply = over_socket(&mut app, stream, |s| writeln!(s.get_mut(), "heal 4").unwrap());
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(reply, vec!["health 5", "ok"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_sockets_replace_only_stale_sockets_and_stay_private() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let path = std::env::temp_dir().join(format!("admin-stale-{}.sock", std::process::id()));
+    drop(UnixListener::bind(&path).unwrap());
+    let mut app = App::new();
+    app.add_plugin(AdminPlugin::new().without_stdin().with_unix_socket(&path));
+    app.insert_resource(Health(1));
+    app.add_system(heal, SystemStage::Update);
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    let stream = BufReader::new(UnixStream::connect(&path).unwrap());
+    let reply = over_socket(&mut app, stream, |s| writeln!(s.get_mut(), "heal 1").unwrap());
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(reply, vec!["health 2", "ok"]);
+
+    // a file that isn't a socket is somebody's data
+    let path = std::env::temp_dir().join(format!("admin-file-{}.sock", std::process::id()));
+    std::fs::write(&path, "keep me").unwrap();
+    let mut app = App::new();
+    app.add_plugin(AdminPlugin::new().without_stdin().with_unix_socket(&path));
+    let kept = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(kept, "keep me");
+}
+
+#[test]
+fn admin_flags_are_taken_out_of_the_arguments() {
+    let mut args: Vec<String> = ["server", "--ticks", "5", "--admin-tcp", "127.0.0.1:4000"]
+        .map(String::from)
+        .to_vec();
+    AdminPlugin::from_args(&mut args).unwrap();
+    assert_eq!(args, vec!["server", "--ticks", "5"]);
+
+    let mut args = vec!["server".to_string(), "--admin-tcp".to_string()];
+    assert!(AdminPlugin::from_args(&mut args).is_err());
+    let mut args = vec!["server".to_string(), "--admin-tcp".to_string(), "nowhere".to_string()];
+    assert!(AdminPlugin::from_args(&mut args).is_err());
+    // there's no authentication, so only loopback addresses are served
+    let mut args = vec!["server".to_string(), "--admin-tcp".to_string(), "0.0.0.0:4000".to_string()];
+    assert!(AdminPlugin::from_args(&mut args).is_err());
+}
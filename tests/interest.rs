@@ -0,0 +1,107 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rust_game_engine::netcode::{InputOwner, InterestPlugin, PredictionPlugin, SpatialInterest};
+use rust_game_engine::physics::Transform;
+use rust_game_engine::{
+    App, LoopbackHub, LoopbackTransport, Networking, NetworkingPlugin, Replicated,
+    ReplicationClient, ReplicationPlugin, World,
+};
+
+use glam::Vec3;
+
+#[test]
+fn peers_without_a_player_see_everything() {
+    let interest = SpatialInterest::new(10.0);
+    assert_eq!(interest.relevance(1, Vec3::new(500.0, 0.0, 0.0)), 1.0);
+}
+
+#[test]
+#[should_panic(expected = "InterestPlugin needs ReplicationPlugin::server()")]
+fn interest_without_replication_is_refused() {
+    let mut app = App::new();
+    app.add_plugin(InterestPlugin::new(20.0));
+}
+
+fn pump(apps: &mut [&mut App], timeout: Duration, mut done: impl FnMut(&mut [&mut App]) -> bool) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        for app in apps.iter_mut() {
+            app.run();
+        }
+        if done(apps) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(2));
+    }
+    false
+}
+
+fn client_app(hub: &LoopbackHub) -> (App, u32) {
+    let transport = LoopbackTransport::client(hub);
+    let peer = transport.id();
+    let mut app = App::new();
+    app.add_plugin(NetworkingPlugin::client().with_transport(transport));
+    app.add_plugin(ReplicationPlugin::client());
+    (app, peer)
+}
+
+fn mirrors(app: &mut App, server_entity: u32) -> bool {
+    app.get_resource::<ReplicationClient>()
+        .and_then(|replication| replication.local_entity(server_entity))
+        .is_some()
+}
+
+fn move_player(server: &mut App, peer: u32, pos: Vec3) {
+    let owners: Vec<(u32, u32)> = unsafe { World::get_components::<InputOwner>(server.world) }
+        .into_iter()
+        .map(|(entity, owner)| (entity, owner.0))
+        .collect();
+    for (entity, transform) in unsafe { World::get_components_mut::<Transform>(server.world) } {
+        if owners.contains(&(entity, peer)) {
+            transform.pos = pos;
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn entities_only_reach_peers_in_range() {
+    let hub = LoopbackHub::new();
+    let mut server = App::new();
+    server.add_plugin(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&hub)));
+    server.add_plugin(ReplicationPlugin::server());
+    server.add_plugin(PredictionPlugin::server());
+    server.add_plugin(InterestPlugin::new(20.0));
+
+    let (mut near, near_peer) = client_app(&hub);
+    let (mut far, far_peer) = client_app(&hub);
+    assert!(pump(&mut [&mut server, &mut near, &mut far], Duration::from_secs(2), |apps| {
+        apps[0].get_resource::<Networking>().unwrap().peers().count() == 2
+    }));
+    move_player(&mut server, near_peer, Vec3::new(5.0, 0.0, 0.0));
+    move_player(&mut server, far_peer, Vec3::new(100.0, 0.0, 0.0));
+
+    let crate_entity = server.spawn_entity();
+    server.add_component(crate_entity, Transform::default()).unwrap();
+    server.add_component(crate_entity, Replicated).unwrap();
+
+    assert!(pump(&mut [&mut server, &mut near, &mut far], Duration::from_secs(2), |apps| {
+        mirrors(apps[1], crate_entity)
+    }));
+    let interest = server.get_resource::<SpatialInterest>().unwrap();
+    assert_eq!(interest.player_pos(far_peer), Some(Vec3::new(100.0, 0.0, 0.0)));
+    assert!(interest.relevance(near_peer, Vec3::ZERO) > 1.0);
+    assert_eq!(interest.relevance(far_peer, Vec3::ZERO), 0.0);
+    assert!(!mirrors(&mut far, crate_entity));
+
+    // walking into range brings the entity in, walking back out takes it away again
+    move_player(&mut server, far_peer, Vec3::new(10.0, 0.0, 0.0));
+    assert!(pump(&mut [&mut server, &mut near, &mut far], Duration::from_secs(2), |apps| {
+        mirrors(apps[2], crate_entity)
+    }));
+    move_player(&mut server, far_peer, Vec3::new(100.0, 0.0, 0.0));
+    assert!(pump(&mut [&mut server, &mut near, &mut far], Duration::from_secs(2), |apps| {
+        !mirrors(apps[2], crate_entity)
+    }));
+    assert!(mirrors(&mut near, crate_entity));
+}
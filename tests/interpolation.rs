@@ -0,0 +1,131 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rust_game_engine::netcode::{Interpolation, InterpolationPlugin, Snapshot, SnapshotBuffer};
+use rust_game_engine::physics::Transform;
+use rust_game_engine::utils::UtilPlugin;
+use rust_game_engine::{
+    App, LoopbackHub, LoopbackTransport, NetworkingPlugin, Replicated, ReplicationClient,
+    ReplicationPlugin, World,
+};
+
+use glam::{Quat, Vec3};
+
+fn snapshot(tick: u64, x: f32, angle: f32) -> Snapshot {
+    Snapshot {
+        tick,
+        pos: Vec3::new(x, 0.0, 0.0),
+        rot: Quat::from_rotation_z(angle),
+    }
+}
+
+#[test]
+fn samples_between_snapshots_lerp_and_slerp() {
+    let mut buffer = SnapshotBuffer::default();
+    buffer.push(snapshot(10, 0.0, 0.0));
+    buffer.push(snapshot(20, 10.0, 1.0));
+
+    let (pos, rot) = buffer.sample(12.5, 0.0).unwrap();
+    assert!((pos.x - 2.5).abs() < 1e-5);
+    assert!(rot.angle_between(Quat::from_rotation_z(0.25)) < 1e-4);
+}
+
+#[test]
+fn out_of_order_snapshots_are_sorted_and_deduplicated() {
+    let mut buffer = SnapshotBuffer::default();
+    buffer.push(snapshot(20, 10.0, 0.0));
+    buffer.push(snapshot(10, 0.0, 0.0));
+    buffer.push(snapshot(20, 20.0, 0.0));
+
+    assert_eq!(buffer.len(), 2);
+    let (pos, _) = buffer.sample(15.0, 0.0).unwrap();
+    assert!((pos.x - 10.0).abs() < 1e-5);
+}
+
+#[test]
+fn extrapolation_is_limited() {
+    let mut buffer = SnapshotBuffer::default();
+    buffer.push(snapshot(10, 0.0, 0.0));
+    buffer.push(snapshot(20, 10.0, 1.0));
+
+    // one unit per tick past the end, capped at 5 ticks
+    let (pos, rot) = buffer.sample(23.0, 5.0).unwrap();
+    assert!((pos.x - 13.0).abs() < 1e-5);
+    assert!(rot.angle_between(Quat::from_rotation_z(1.0)) < 1e-4);
+
+    let (pos, _) = buffer.sample(100.0, 5.0).unwrap();
+    assert!((pos.x - 15.0).abs() < 1e-5);
+
+    // before the first snapshot there is nothing to blend with
+    let (pos, _) = buffer.sample(0.0, 5.0).unwrap();
+    assert_eq!(pos.x, 0.0);
+}
+
+fn pump(apps: &mut [&mut App], timeout: Duration, mut done: impl FnMut(&mut [&mut App]) -> bool) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        for app in apps.iter_mut() {
+            app.run();
+        }
+        if done(apps) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(2));
+    }
+    false
+}
+
+fn first_transform(app: &mut App) -> Option<Vec3> {
+    let transforms = unsafe { World::get_components::<Transform>(app.world) };
+    transforms.first().map(|(_, transform)| transform.pos)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn remote_transforms_trail_the_server_smoothly() {
+    let hub = LoopbackHub::new();
+
+    let mut server = App::new();
+    server.add_plugin(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&hub)));
+    server.add_plugin(ReplicationPlugin::server());
+    let mover = server.spawn_entity();
+    server.add_component(mover, Transform::default()).unwrap();
+    server.add_component(mover, Replicated).unwrap();
+
+    let mut client = App::new();
+    client.add_plugin(UtilPlugin::server());
+    client.add_plugin(NetworkingPlugin::client().with_transport(LoopbackTransport::client(&hub)));
+    client.add_plugin(ReplicationPlugin::client());
+    client.add_plugin(InterpolationPlugin);
+    client.init();
+
+    // the server ticks at roughly the configured rate and moves one unit per tick
+    let tick_rate = 1.0 / 0.004;
+    client.get_resource_mut::<Interpolation>().unwrap().tick_rate = tick_rate;
+
+    let mut samples = Vec::new();
+    assert!(pump(&mut [&mut server, &mut client], Duration::from_secs(3), |apps| {
+        let tick = apps[0].tick() as f32;
+        let transforms = unsafe { World::get_components_mut::<Transform>(apps[0].world) };
+        transforms.into_iter().next().unwrap().1.pos.x = tick;
+
+        if let Some(pos) = first_transform(apps[1]) {
+            samples.push((tick, pos.x));
+        }
+        samples.len() >= 100
+    }));
+
+    let interpolation = client.get_resource::<Interpolation>().unwrap();
+    let render_tick = interpolation.render_tick().unwrap();
+    let latest = client.get_resource::<ReplicationClient>().unwrap().last_tick() as f32;
+    assert!(render_tick < latest, "rendering {} but latest is {}", render_tick, latest);
+
+    // once warmed up, the client always shows a position behind the server and never jumps
+    // backwards
+    let settled = &samples[samples.len() / 2..];
+    for pair in settled.windows(2) {
+        assert!(pair[1].1 >= pair[0].1 - 1e-3, "moved backwards: {:?}", pair);
+    }
+    for (server_x, client_x) in settled {
+        assert!(client_x < server_x, "client {} not behind server {}", client_x, server_x);
+    }
+}
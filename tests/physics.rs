@@ -1,11 +1,14 @@
 use rust_game_engine::physics::{
-    AngularVelocity, BodyInit, Camera, Collider, ForceAccumulator, PhysicsDebugSettings,
-    PhysicsEvents, PhysicsPlugin, PhysicsTestWorld, PhysicsTime, PhysicsWorld, RigidBody,
-    Transform, Velocity,
+    AngularVelocity, BodyInit, BroadPhase, Camera, Collider, CollisionGroups, ContactManifold, DEFAULT_AABB_MARGIN,
+    DynamicTree, ForceAccumulator, Joint, JointBroken, JointKind, PairFilter, PhysicsBody, PhysicsDebugSettings,
+    PhysicsEvents, PhysicsMaterial, PhysicsPlugin, PhysicsTestWorld, QueryFilter, PhysicsTime, PhysicsWorld, RigidBody,
+    Sensor, SleepThresholds, Sleeping, TorqueAccumulator, TriggerEvent, Transform, UniformGrid, Velocity, collide,
 };
-use rust_game_engine::{App, Commands, World};
+use rust_game_engine::{App, Commands, World, get_component_id};
 
 use glam::{Mat4, Quat, Vec3};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::time::Instant;
 
 fn assert_mat4_close(a: Mat4, b: Mat4, epsilon: f32) {
     let a = a.to_cols_array();
@@ -424,3 +427,1570 @@ fn physics_plugin_applies_gravity_and_forces() {
         assert!(transform.pos.y < 0.0);
     }
 }
+
+fn at(pos: Vec3) -> Transform {
+    Transform {
+        pos,
+        scale: Vec3::ONE,
+        rot: Quat::IDENTITY,
+    }
+}
+
+fn contact(a: Collider, ta: Transform, b: Collider, tb: Transform) -> ContactManifold {
+    let manifold = collide(&a, &ta, &b, &tb).expect("colliders should touch");
+    assert!(manifold.normal.is_normalized());
+    assert!(manifold.depth > 0.0);
+    assert!(!manifold.points.is_empty());
+
+    // swapping the colliders flips the normal and nothing else
+    let swapped = collide(&b, &tb, &a, &ta).expect("contact should be symmetric");
+    assert!(swapped.normal.abs_diff_eq(-manifold.normal, 1e-4));
+    assert!((swapped.depth - manifold.depth).abs() < 1e-4);
+    manifold
+}
+
+#[test]
+fn narrow_phase_sphere_sphere() {
+    let manifold = contact(
+        Collider::sphere(1.0),
+        at(Vec3::ZERO),
+        Collider::sphere(0.5),
+        at(Vec3::new(1.25, 0.0, 0.0)),
+    );
+    assert!(manifold.normal.abs_diff_eq(Vec3::X, 1e-5));
+    assert!((manifold.depth - 0.25).abs() < 1e-5);
+    assert!(manifold.points[0].abs_diff_eq(Vec3::new(0.875, 0.0, 0.0), 1e-5));
+
+    assert!(collide(&Collider::sphere(1.0), &at(Vec3::ZERO), &Collider::sphere(0.5), &at(Vec3::X * 1.6)).is_none());
+}
+
+#[test]
+fn narrow_phase_sphere_box_honors_rotation() {
+    // a box turned 45 degrees about z reaches sqrt(2) along x
+    let mut rotated = at(Vec3::ZERO);
+    rotated.rot = Quat::from_rotation_z(std::f32::consts::FRAC_PI_4);
+    let sphere = Vec3::new(1.7, 0.0, 0.0);
+
+    let manifold = contact(Collider::sphere(0.5), at(sphere), Collider::cuboid(Vec3::ONE), rotated);
+    assert!(manifold.normal.abs_diff_eq(-Vec3::X, 1e-4));
+    assert!((manifold.depth - (0.5 - (1.7 - 2f32.sqrt()))).abs() < 1e-4);
+    assert!(collide(&Collider::sphere(0.5), &at(sphere), &Collider::cuboid(Vec3::ONE), &at(Vec3::ZERO)).is_none());
+
+    // a center inside the box leaves through the nearest face
+    let inside = contact(
+        Collider::sphere(0.25),
+        at(Vec3::new(0.0, 0.8, 0.0)),
+        Collider::cuboid(Vec3::ONE),
+        at(Vec3::ZERO),
+    );
+    assert!(inside.normal.abs_diff_eq(-Vec3::Y, 1e-5));
+    assert!((inside.depth - 0.45).abs() < 1e-5);
+}
+
+#[test]
+fn narrow_phase_sphere_capsule() {
+    // touches the side of the capsule's segment, not its center
+    let manifold = contact(
+        Collider::sphere(0.5),
+        at(Vec3::new(0.8, 0.9, 0.0)),
+        Collider::capsule(1.0, 0.5),
+        at(Vec3::ZERO),
+    );
+    assert!(manifold.normal.abs_diff_eq(-Vec3::X, 1e-5));
+    assert!((manifold.depth - 0.2).abs() < 1e-5);
+
+    // and past its end, the cap is round
+    let cap = contact(
+        Collider::sphere(0.5),
+        at(Vec3::new(0.0, 1.9, 0.0)),
+        Collider::capsule(1.0, 0.5),
+        at(Vec3::ZERO),
+    );
+    assert!(cap.normal.abs_diff_eq(-Vec3::Y, 1e-5));
+}
+
+#[test]
+fn narrow_phase_box_box_resting_face_gives_four_points() {
+    let manifold = contact(
+        Collider::cuboid(Vec3::splat(2.0)),
+        at(Vec3::ZERO),
+        Collider::cuboid(Vec3::splat(0.5)),
+        at(Vec3::new(0.0, 2.4, 0.0)),
+    );
+    assert!(manifold.normal.abs_diff_eq(Vec3::Y, 1e-5));
+    assert!((manifold.depth - 0.1).abs() < 1e-5);
+    assert_eq!(manifold.points.len(), 4);
+    assert!(manifold.points.iter().all(|p| (p.y - 1.95).abs() < 1e-5));
+}
+
+#[test]
+fn narrow_phase_box_box_rotated_face_is_clipped() {
+    // the same size box turned 45 degrees on top: the overlap is an octagon
+    let turned = |y: f32| Transform {
+        rot: Quat::from_rotation_y(std::f32::consts::FRAC_PI_4),
+        ..at(Vec3::new(0.0, y, 0.0))
+    };
+    let manifold = contact(Collider::cuboid(Vec3::ONE), at(Vec3::ZERO), Collider::cuboid(Vec3::ONE), turned(1.9));
+    assert!(manifold.normal.abs_diff_eq(Vec3::Y, 1e-5));
+    assert_eq!(manifold.points.len(), 8);
+    assert!(manifold.points.iter().all(|p| p.x.abs() <= 1.0 + 1e-4 && p.z.abs() <= 1.0 + 1e-4));
+
+    assert!(collide(&Collider::cuboid(Vec3::ONE), &at(Vec3::ZERO), &Collider::cuboid(Vec3::ONE), &turned(2.5)).is_none());
+}
+
+#[test]
+fn narrow_phase_box_box_edge_on_edge() {
+    // a box rolled 45 degrees about z lands its bottom edge across the top edge of one rolled
+    // about x
+    let mut crossed = at(Vec3::new(0.0, 2.0 * 2f32.sqrt() - 0.1, 0.0));
+    crossed.rot = Quat::from_rotation_z(std::f32::consts::FRAC_PI_4);
+    let mut base = at(Vec3::ZERO);
+    base.rot = Quat::from_rotation_x(std::f32::consts::FRAC_PI_4);
+
+    let manifold = contact(Collider::cuboid(Vec3::ONE), base, Collider::cuboid(Vec3::ONE), crossed);
+    assert!(manifold.normal.abs_diff_eq(Vec3::Y, 1e-4), "{:?}", manifold.normal);
+    assert!((manifold.depth - 0.1).abs() < 1e-4);
+    assert_eq!(manifold.points.len(), 1);
+    assert!(manifold.points[0].abs_diff_eq(Vec3::new(0.0, 2f32.sqrt() - 0.05, 0.0), 1e-4));
+}
+
+#[test]
+fn narrow_phase_capsule_box_lying_flat_rests_on_both_ends() {
+    let mut lying = at(Vec3::new(0.0, 1.4, 0.0));
+    lying.rot = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+    let manifold = contact(Collider::capsule(0.5, 0.5), lying, Collider::cuboid(Vec3::ONE), at(Vec3::ZERO));
+    assert!(manifold.normal.abs_diff_eq(-Vec3::Y, 1e-4));
+    assert!((manifold.depth - 0.1).abs() < 1e-4);
+    assert!(manifold.points.len() >= 2);
+    assert!(manifold.points.iter().any(|p| p.x < -0.4) && manifold.points.iter().any(|p| p.x > 0.4));
+}
+
+#[test]
+fn narrow_phase_capsule_capsule() {
+    // two capsules crossing at right angles touch between their segments
+    let mut across = at(Vec3::new(0.0, 0.0, 0.9));
+    across.rot = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+    let manifold = contact(Collider::capsule(1.0, 0.5), at(Vec3::ZERO), Collider::capsule(1.0, 0.5), across);
+    assert!(manifold.normal.abs_diff_eq(Vec3::Z, 1e-5));
+    assert!((manifold.depth - 0.1).abs() < 1e-5);
+    assert!(manifold.points[0].abs_diff_eq(Vec3::new(0.0, 0.0, 0.45), 1e-5));
+}
+
+#[test]
+fn narrow_phase_honors_scale() {
+    let mut big = at(Vec3::ZERO);
+    big.scale = Vec3::splat(2.0);
+    let far = at(Vec3::new(2.8, 0.0, 0.0));
+
+    assert!(collide(&Collider::sphere(1.0), &at(Vec3::ZERO), &Collider::sphere(1.0), &far).is_none());
+    let manifold = contact(Collider::sphere(1.0), big, Collider::sphere(1.0), far);
+    assert!((manifold.depth - 0.2).abs() < 1e-5);
+
+    let mut tall = at(Vec3::ZERO);
+    tall.scale = Vec3::new(1.0, 3.0, 1.0);
+    assert!(collide(&Collider::cuboid(Vec3::ONE), &tall, &Collider::sphere(0.5), &at(Vec3::Y * 3.2)).is_some());
+}
+
+#[test]
+fn physics_events_report_contacts() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+
+    let mut spawn = |pos: Vec3, rigid_body: RigidBody, collider: Collider| {
+        let entity = app.spawn_entity();
+        app.add_component(entity, at(pos)).unwrap();
+        app.add_component(entity, rigid_body).unwrap();
+        app.add_component(entity, collider).unwrap();
+        app.add_component(entity, Velocity(Vec3::ZERO)).unwrap();
+        app.add_component(entity, AngularVelocity(Vec3::ZERO)).unwrap();
+        app.add_component(entity, ForceAccumulator(Vec3::ZERO)).unwrap();
+        entity
+    };
+    let floor = spawn(Vec3::ZERO, RigidBody::static_body(), Collider::cuboid(Vec3::new(5.0, 0.5, 5.0)));
+    let ball = spawn(Vec3::new(0.0, 0.9, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    // overlapping aabbs, but the rounded corner misses
+    spawn(Vec3::new(5.4, 0.9, 5.4), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+
+    app.run();
+
+    let commands: &Commands = &app;
+    let events = unsafe { World::get_resource::<PhysicsEvents>(commands.world).unwrap() };
+    assert_eq!(events.broad_phase_pairs.len(), 2);
+    assert_eq!(events.contacts.len(), 1);
+    let contact = &events.contacts[0];
+    assert_eq!((contact.entity_a, contact.entity_b), (floor, ball));
+    assert!(contact.normal.abs_diff_eq(Vec3::Y, 1e-5));
+    assert!((contact.depth - 0.1).abs() < 1e-5);
+}
+
+fn spawn_body(app: &mut App, pos: Vec3, rigid_body: RigidBody, collider: Collider) -> u32 {
+    let entity = app.spawn_entity();
+    app.add_component(entity, at(pos)).unwrap();
+    app.add_component(entity, rigid_body).unwrap();
+    app.add_component(entity, collider).unwrap();
+    app.add_component(entity, Velocity(Vec3::ZERO)).unwrap();
+    app.add_component(entity, AngularVelocity(Vec3::ZERO)).unwrap();
+    app.add_component(entity, ForceAccumulator(Vec3::ZERO)).unwrap();
+    entity
+}
+
+fn set_velocity(app: &mut App, entity: u32, velocity: Vec3) {
+    let commands: &Commands = app;
+    for (id, v) in unsafe { World::get_components_mut::<Velocity>(commands.world) } {
+        if id == entity {
+            v.0 = velocity;
+        }
+    }
+}
+
+fn spawn_floor(app: &mut App) -> u32 {
+    spawn_body(app, Vec3::new(0.0, -0.5, 0.0), RigidBody::static_body(), Collider::cuboid(Vec3::new(20.0, 0.5, 20.0)))
+}
+
+/// Runs `steps` fixed physics steps, calling `each` with the world after every one.
+fn simulate(app: &mut App, steps: usize, mut each: impl FnMut(&PhysicsWorld)) {
+    for _ in 0..steps {
+        let commands: &Commands = app;
+        let world_ptr = commands.world;
+        unsafe {
+            let time = World::get_resource_mut::<PhysicsTime>(world_ptr).unwrap();
+            time.accumulate(time.fixed_delta);
+        }
+        app.run();
+        each(unsafe { World::get_resource::<PhysicsWorld>(world_ptr).unwrap() });
+    }
+}
+
+fn body_position(app: &App, entity: u32) -> Vec3 {
+    let commands: &Commands = app;
+    let world = unsafe { World::get_resource::<PhysicsWorld>(commands.world).unwrap() };
+    world.get_body(entity).unwrap().position
+}
+
+#[test]
+fn solver_rests_bodies_on_a_static_floor() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    let floor = spawn_floor(&mut app);
+    let ball = spawn_body(&mut app, Vec3::new(0.0, 2.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    let crate_ = spawn_body(&mut app, Vec3::new(3.0, 2.0, 0.0), RigidBody::dynamic(5.0), Collider::cuboid(Vec3::splat(0.5)));
+
+    simulate(&mut app, 180, |_| {});
+
+    for entity in [ball, crate_] {
+        let commands: &Commands = &app;
+        let world = unsafe { World::get_resource::<PhysicsWorld>(commands.world).unwrap() };
+        let body = world.get_body(entity).unwrap();
+        assert!((body.position.y - 0.5).abs() < 0.03, "{:?}", body.position);
+        assert!(body.velocity.0.length() < 0.05, "{:?}", body.velocity);
+    }
+    assert_eq!(body_position(&app, floor), Vec3::new(0.0, -0.5, 0.0));
+}
+
+#[test]
+fn solver_keeps_a_stack_standing() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    spawn_floor(&mut app);
+    let boxes: Vec<u32> = (0..3)
+        .map(|i| spawn_body(&mut app, Vec3::new(0.0, 0.5 + i as f32, 0.0), RigidBody::dynamic(1.0), Collider::cuboid(Vec3::splat(0.5))))
+        .collect();
+
+    simulate(&mut app, 240, |_| {});
+
+    for (i, &entity) in boxes.iter().enumerate() {
+        let pos = body_position(&app, entity);
+        assert!((pos.y - (0.5 + i as f32)).abs() < 0.05, "box {} at {:?}", i, pos);
+        assert!(pos.x.abs() < 0.01 && pos.z.abs() < 0.01, "box {} drifted to {:?}", i, pos);
+    }
+}
+
+fn bounce_height(restitution: f32) -> f32 {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    let floor = spawn_floor(&mut app);
+    app.add_component(floor, PhysicsMaterial { restitution, friction: 0.5 }).unwrap();
+    let ball = spawn_body(&mut app, Vec3::new(0.0, 3.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    app.add_component(ball, PhysicsMaterial { restitution, friction: 0.5 }).unwrap();
+
+    // fall, then track the highest point after the first bounce
+    let mut bounced = false;
+    let mut highest = f32::MIN;
+    simulate(&mut app, 150, |world| {
+        let body = world.get_body(ball).unwrap();
+        bounced |= body.velocity.0.y > 0.0;
+        if bounced {
+            highest = highest.max(body.position.y);
+        }
+    });
+    highest
+}
+
+#[test]
+fn solver_bounces_with_the_combined_restitution() {
+    let lively = bounce_height(0.9);
+    let dead = bounce_height(0.0);
+    // dropped from 2.5 above resting height, a 0.9 bounce climbs back about 0.81 of the way
+    assert!(lively > 0.5 + 2.5 * 0.6, "bounced to {}", lively);
+    assert!(dead < 0.6, "bounced to {}", dead);
+}
+
+fn slide_distance(friction: f32) -> f32 {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    let floor = spawn_floor(&mut app);
+    app.add_component(floor, PhysicsMaterial { restitution: 0.0, friction }).unwrap();
+    let block = spawn_body(&mut app, Vec3::new(0.0, 0.5, 0.0), RigidBody::dynamic(1.0), Collider::cuboid(Vec3::splat(0.5)));
+    app.add_component(block, PhysicsMaterial { restitution: 0.0, friction }).unwrap();
+    set_velocity(&mut app, block, Vec3::new(4.0, 0.0, 0.0));
+
+    simulate(&mut app, 120, |_| {});
+    body_position(&app, block).x
+}
+
+#[test]
+fn solver_friction_slows_sliding_bodies() {
+    let rough = slide_distance(0.8);
+    let slick = slide_distance(0.0);
+    // v^2 / (2 mu g) is about 1m with this much friction
+    assert!(rough < 1.5, "slid {}", rough);
+    assert!(slick > 7.5, "slid {}", slick);
+}
+
+#[test]
+fn solver_exchanges_momentum_between_dynamic_bodies() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    let commands: &Commands = &app;
+    unsafe { World::get_resource_mut::<PhysicsWorld>(commands.world).unwrap().set_gravity(Vec3::ZERO) };
+
+    let bouncy = PhysicsMaterial { restitution: 1.0, friction: 0.0 };
+    let a = spawn_body(&mut app, Vec3::new(-1.0, 0.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    let b = spawn_body(&mut app, Vec3::new(1.0, 0.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    app.add_component(a, bouncy.clone()).unwrap();
+    app.add_component(b, bouncy).unwrap();
+    set_velocity(&mut app, a, Vec3::new(3.0, 0.0, 0.0));
+
+    simulate(&mut app, 60, |_| {});
+
+    let commands: &Commands = &app;
+    let world = unsafe { World::get_resource::<PhysicsWorld>(commands.world).unwrap() };
+    let (va, vb) = (world.get_body(a).unwrap().velocity.0, world.get_body(b).unwrap().velocity.0);
+    // equal masses swap velocities in an elastic collision
+    assert!(va.abs_diff_eq(Vec3::ZERO, 0.05), "{:?}", va);
+    assert!(vb.abs_diff_eq(Vec3::new(3.0, 0.0, 0.0), 0.05), "{:?}", vb);
+}
+
+fn zero_gravity(app: &mut App) {
+    let commands: &Commands = app;
+    unsafe { World::get_resource_mut::<PhysicsWorld>(commands.world) }.unwrap().set_gravity(Vec3::ZERO);
+}
+
+fn set_angular_velocity(app: &mut App, entity: u32, angular_velocity: Vec3) {
+    let commands: &Commands = app;
+    for (id, w) in unsafe { World::get_components_mut::<AngularVelocity>(commands.world) } {
+        if id == entity {
+            w.0 = angular_velocity;
+        }
+    }
+}
+
+#[test]
+fn inertia_follows_collider_shape_and_mass() {
+    let sphere = Collider::sphere(0.5).principal_inertia(2.0, Vec3::ONE);
+    assert!(sphere.abs_diff_eq(Vec3::splat(0.2), 1e-6), "{:?}", sphere);
+
+    let brick = Collider::cuboid(Vec3::new(1.0, 0.5, 0.25)).principal_inertia(12.0, Vec3::ONE);
+    assert!(brick.abs_diff_eq(Vec3::new(1.25, 4.25, 5.0), 1e-5), "{:?}", brick);
+    // scaling the box is the same as making it bigger
+    let scaled = Collider::cuboid(Vec3::new(0.5, 0.5, 0.5)).principal_inertia(12.0, Vec3::new(2.0, 1.0, 0.5));
+    assert!(scaled.abs_diff_eq(brick, 1e-5), "{:?}", scaled);
+
+    // a capsule is harder to spin end over end than about its own axis
+    let capsule = Collider::capsule(1.0, 0.25).principal_inertia(1.0, Vec3::ONE);
+    assert!(capsule.x > capsule.y && capsule.x == capsule.z, "{:?}", capsule);
+}
+
+#[test]
+fn free_flight_conserves_angular_momentum() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    zero_gravity(&mut app);
+    let brick = spawn_body(&mut app, Vec3::ZERO, RigidBody::dynamic(3.0), Collider::cuboid(Vec3::new(1.0, 0.4, 0.2)));
+    let initial_spin = Vec3::new(0.3, 2.0, 0.6);
+    set_angular_velocity(&mut app, brick, initial_spin);
+
+    let mut momentum = None;
+    let mut spin = Vec3::ZERO;
+    simulate(&mut app, 300, |world| {
+        let body = world.get_body(brick).unwrap();
+        let current = body.angular_momentum();
+        let initial = *momentum.get_or_insert(current);
+        assert!(current.abs_diff_eq(initial, initial.length() * 1e-3), "{:?} became {:?}", initial, current);
+        spin = body.angular_velocity.0;
+    });
+
+    // tumbling about no principal axis, the spin itself wanders while the momentum holds
+    assert!(spin.angle_between(initial_spin) > 0.1, "{:?}", spin);
+    assert!(body_position(&app, brick).abs_diff_eq(Vec3::ZERO, 1e-5));
+}
+
+#[test]
+fn torque_and_off_center_forces_spin_bodies() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    zero_gravity(&mut app);
+    let wheel = spawn_body(&mut app, Vec3::ZERO, RigidBody::dynamic(2.0), Collider::sphere(0.5));
+    app.add_component(wheel, TorqueAccumulator(Vec3::new(0.0, 0.0, 1.0))).unwrap();
+    let pushed = spawn_body(&mut app, Vec3::new(5.0, 0.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    app.add_component(pushed, TorqueAccumulator::default()).unwrap();
+    {
+        let commands: &Commands = &app;
+        let forces = unsafe { World::get_components_mut::<ForceAccumulator>(commands.world) };
+        let torques = unsafe { World::get_components_mut::<TorqueAccumulator>(commands.world) };
+        let (_, force) = forces.into_iter().find(|(id, _)| *id == pushed).unwrap();
+        let (_, torque) = torques.into_iter().find(|(id, _)| *id == pushed).unwrap();
+        // pushing the top of the sphere forward
+        force.add_at_point(torque, Vec3::new(5.0, 0.0, 0.0), Vec3::new(6.0, 0.0, 0.0), Vec3::new(5.0, 0.5, 0.0));
+        assert_eq!(torque.0, Vec3::new(0.0, 0.0, -3.0));
+    }
+
+    simulate(&mut app, 1, |_| {});
+    let commands: &Commands = &app;
+    let dt = unsafe { World::get_resource::<PhysicsTime>(commands.world) }.unwrap().fixed_delta;
+    let world = unsafe { World::get_resource::<PhysicsWorld>(commands.world).unwrap() };
+
+    // solid sphere: I = 2/5 m r² = 0.2
+    let wheel = world.get_body(wheel).unwrap();
+    assert!(wheel.angular_velocity.0.abs_diff_eq(Vec3::new(0.0, 0.0, dt / 0.2), 1e-5), "{:?}", wheel.angular_velocity);
+    assert!(wheel.velocity.0.abs_diff_eq(Vec3::ZERO, 1e-6));
+
+    // I = 0.1, so the same push that moves it also spins it
+    let pushed = world.get_body(pushed).unwrap();
+    assert!(pushed.velocity.0.abs_diff_eq(Vec3::new(6.0 * dt, 0.0, 0.0), 1e-5), "{:?}", pushed.velocity);
+    assert!(pushed.angular_velocity.0.abs_diff_eq(Vec3::new(0.0, 0.0, -30.0 * dt), 1e-4), "{:?}", pushed.angular_velocity);
+}
+
+#[test]
+fn solver_friction_makes_sliding_spheres_roll() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    spawn_floor(&mut app);
+    let ball = spawn_body(&mut app, Vec3::new(0.0, 0.5, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    set_velocity(&mut app, ball, Vec3::new(4.0, 0.0, 0.0));
+
+    simulate(&mut app, 120, |_| {});
+
+    let commands: &Commands = &app;
+    let world = unsafe { World::get_resource::<PhysicsWorld>(commands.world).unwrap() };
+    let body = world.get_body(ball).unwrap();
+    // rolling without slipping: the contact point stands still, and a solid sphere keeps 5/7
+    // of its sliding speed once it rolls
+    assert!((body.velocity.0.x - 4.0 * 5.0 / 7.0).abs() < 0.1, "{:?}", body.velocity);
+    assert!((body.velocity.0.x + body.angular_velocity.0.z * 0.5).abs() < 0.05, "{:?} {:?}", body.velocity, body.angular_velocity);
+}
+
+#[test]
+fn solver_off_center_landing_tips_a_box() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    spawn_floor(&mut app);
+    let entity = app.spawn_entity();
+    let mut transform = at(Vec3::new(0.0, 1.5, 0.0));
+    transform.rot = Quat::from_rotation_z(0.3);
+    app.add_component(entity, transform).unwrap();
+    app.add_component(entity, RigidBody::dynamic(1.0)).unwrap();
+    app.add_component(entity, Collider::cuboid(Vec3::splat(0.5))).unwrap();
+    app.add_component(entity, Velocity(Vec3::ZERO)).unwrap();
+    app.add_component(entity, AngularVelocity(Vec3::ZERO)).unwrap();
+    app.add_component(entity, ForceAccumulator(Vec3::ZERO)).unwrap();
+
+    let mut spun = false;
+    simulate(&mut app, 240, |world| spun |= world.get_body(entity).unwrap().angular_velocity.0.z.abs() > 0.5);
+    assert!(spun);
+
+    // it lands on one edge, falls flat onto a face and settles there
+    let commands: &Commands = &app;
+    let world = unsafe { World::get_resource::<PhysicsWorld>(commands.world).unwrap() };
+    let body = world.get_body(entity).unwrap();
+    assert!((body.position.y - 0.5).abs() < 0.05, "{:?}", body.position);
+    let up = body.rotation * Vec3::Y;
+    let flat = [Vec3::X, Vec3::Y, Vec3::NEG_X].iter().any(|axis| up.dot(*axis) > 0.99);
+    assert!(flat, "{:?}", body.rotation);
+    assert!(body.angular_velocity.0.length() < 0.05, "{:?}", body.angular_velocity);
+}
+
+fn sleeping(app: &App, entity: u32) -> bool {
+    let commands: &Commands = app;
+    unsafe { World::get_components_mut::<Sleeping>(commands.world) }
+        .into_iter()
+        .find(|(id, _)| *id == entity)
+        .map(|(_, sleeping)| sleeping.0)
+        .unwrap()
+}
+
+fn spawn_sleepy_box(app: &mut App, pos: Vec3) -> u32 {
+    let entity = spawn_body(app, pos, RigidBody::dynamic(1.0), Collider::cuboid(Vec3::splat(0.5)));
+    app.add_component(entity, Sleeping(false)).unwrap();
+    entity
+}
+
+#[test]
+fn resting_bodies_fall_asleep_and_stay_put() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    spawn_floor(&mut app);
+    let entity = spawn_sleepy_box(&mut app, Vec3::new(0.0, 0.6, 0.0));
+
+    simulate(&mut app, 30, |_| {});
+    assert!(!sleeping(&app, entity), "still settling");
+    simulate(&mut app, 90, |_| {});
+    assert!(sleeping(&app, entity));
+
+    let resting = body_position(&app, entity);
+    simulate(&mut app, 60, |world| {
+        let body = world.get_body(entity).unwrap();
+        assert!(body.sleeping);
+        assert_eq!(body.velocity.0, Vec3::ZERO);
+        assert_eq!(body.position, resting);
+    });
+}
+
+#[test]
+fn stacks_sleep_as_one_island_and_wake_together() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    spawn_floor(&mut app);
+    let stack: Vec<u32> = (0..3).map(|i| spawn_sleepy_box(&mut app, Vec3::new(0.0, 0.5 + i as f32, 0.0))).collect();
+    // a box off to the side stays out of the stack's island
+    let bystander = spawn_sleepy_box(&mut app, Vec3::new(5.0, 0.5, 0.0));
+
+    simulate(&mut app, 120, |_| {});
+    assert!(stack.iter().chain([&bystander]).all(|&entity| sleeping(&app, entity)));
+
+    let dropped = spawn_sleepy_box(&mut app, Vec3::new(0.0, 4.0, 0.0));
+    // contacts seen after a step are acted on by the next one
+    let (mut touched, mut woke) = (false, false);
+    simulate(&mut app, 30, |world| {
+        if touched && !woke {
+            woke = true;
+            assert!(stack.iter().all(|&entity| !world.get_body(entity).unwrap().sleeping), "stack woke whole");
+        }
+        touched |= world.contacts().iter().any(|contact| contact.entity_a == dropped || contact.entity_b == dropped);
+        assert!(world.get_body(bystander).unwrap().sleeping);
+    });
+    assert!(woke, "dropped box never reached the stack");
+
+    simulate(&mut app, 180, |_| {});
+    for (i, &entity) in stack.iter().enumerate() {
+        assert!(sleeping(&app, entity), "box {} still awake", i);
+        assert!((body_position(&app, entity).y - (0.5 + i as f32)).abs() < 0.05);
+    }
+}
+
+#[test]
+fn forces_and_velocity_wake_sleeping_bodies() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    spawn_floor(&mut app);
+    let pushed = spawn_sleepy_box(&mut app, Vec3::new(0.0, 0.5, 0.0));
+    let kicked = spawn_sleepy_box(&mut app, Vec3::new(5.0, 0.5, 0.0));
+    simulate(&mut app, 120, |_| {});
+    assert!(sleeping(&app, pushed) && sleeping(&app, kicked));
+
+    {
+        let commands: &Commands = &app;
+        for (id, force) in unsafe { World::get_components_mut::<ForceAccumulator>(commands.world) } {
+            if id == pushed {
+                force.0 = Vec3::new(0.0, 30.0, 0.0);
+            }
+        }
+    }
+    set_velocity(&mut app, kicked, Vec3::new(2.0, 0.0, 0.0));
+    let before = (body_position(&app, pushed), body_position(&app, kicked));
+    simulate(&mut app, 1, |_| {});
+
+    assert!(!sleeping(&app, pushed) && !sleeping(&app, kicked));
+    assert!(body_position(&app, pushed).y > before.0.y);
+    assert!(body_position(&app, kicked).x > before.1.x);
+}
+
+#[test]
+fn setting_sleeping_freezes_a_body_until_woken() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    let entity = spawn_sleepy_box(&mut app, Vec3::new(0.0, 3.0, 0.0));
+    {
+        let commands: &Commands = &app;
+        for (_, sleeping) in unsafe { World::get_components_mut::<Sleeping>(commands.world) } {
+            sleeping.0 = true;
+        }
+    }
+
+    simulate(&mut app, 30, |_| {});
+    assert!(sleeping(&app, entity));
+    assert_eq!(body_position(&app, entity), Vec3::new(0.0, 3.0, 0.0));
+
+    {
+        let commands: &Commands = &app;
+        for (_, sleeping) in unsafe { World::get_components_mut::<Sleeping>(commands.world) } {
+            sleeping.0 = false;
+        }
+    }
+    simulate(&mut app, 30, |_| {});
+    assert!(!sleeping(&app, entity));
+    assert!(body_position(&app, entity).y < 3.0);
+}
+
+#[test]
+fn bodies_nothing_touched_are_not_synced_again() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    let floor = spawn_floor(&mut app);
+    let wall = spawn_body(&mut app, Vec3::new(5.0, 1.0, 0.0), RigidBody::static_body(), Collider::cuboid(Vec3::splat(1.0)));
+    let resting = spawn_sleepy_box(&mut app, Vec3::new(0.0, 0.5, 0.0));
+
+    simulate(&mut app, 1, |world| assert_eq!(world.synced_body_count(), 3));
+    // the box is moved by the steps until it falls asleep, the static bodies never are
+    simulate(&mut app, 120, |world| assert!(world.synced_body_count() <= 1));
+    assert!(physics_world(&app).get_body(resting).unwrap().sleeping);
+    simulate(&mut app, 5, |world| assert_eq!(world.synced_body_count(), 0));
+
+    // and writing the steps back leaves them be, so they stay unchanged
+    let transform_id = get_component_id::<Transform>();
+    assert_eq!(app.get_entity(floor).unwrap().changed_tick(transform_id), 0);
+    let dozed_off = app.get_entity(resting).unwrap().changed_tick(transform_id);
+    simulate(&mut app, 5, |_| {});
+    assert_eq!(app.get_entity(resting).unwrap().changed_tick(transform_id), dozed_off);
+
+    // moving one brings just that one back in
+    app.get_component_mut::<Transform>(wall).unwrap().pos = Vec3::new(7.0, 1.0, 0.0);
+    simulate(&mut app, 1, |world| {
+        assert_eq!(world.synced_body_count(), 1);
+        assert_eq!(world.get_body(wall).unwrap().position, Vec3::new(7.0, 1.0, 0.0));
+    });
+    simulate(&mut app, 1, |world| assert_eq!(world.synced_body_count(), 0));
+}
+
+struct QueryScene {
+    app: App,
+    floor: u32,
+    ball: u32,
+    crate_: u32,
+    pillar: u32,
+    wall: u32,
+}
+
+/// A capsule, a ball, a crate and a thin wall in a row along x, standing on a floor.
+fn query_scene() -> QueryScene {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    let floor = spawn_floor(&mut app);
+    let pillar = spawn_body(&mut app, Vec3::new(-3.0, 1.0, 0.0), RigidBody::dynamic(1.0), Collider::capsule(0.5, 0.5));
+    let ball = spawn_body(&mut app, Vec3::new(0.0, 0.5, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    let crate_ = spawn_body(&mut app, Vec3::new(3.0, 0.5, 0.0), RigidBody::dynamic(1.0), Collider::cuboid(Vec3::splat(0.5)));
+    let wall = spawn_body(&mut app, Vec3::new(8.0, 0.5, 0.0), RigidBody::static_body(), Collider::cuboid(Vec3::new(0.01, 2.0, 2.0)));
+    // no time has passed, so this only gathers the bodies
+    app.run();
+    QueryScene { app, floor, ball, crate_, pillar, wall }
+}
+
+fn physics_world(app: &App) -> &PhysicsWorld {
+    let commands: &Commands = app;
+    unsafe { World::get_resource::<PhysicsWorld>(commands.world).unwrap() }
+}
+
+#[test]
+fn raycast_reports_the_nearest_hit() {
+    let scene = query_scene();
+    let world = physics_world(&scene.app);
+    let all = QueryFilter::default();
+
+    let hit = world.raycast(Vec3::new(-10.0, 0.5, 0.0), Vec3::X * 3.0, 30.0, &all).unwrap();
+    assert_eq!(hit.entity, scene.pillar);
+    assert!((hit.distance - 6.5).abs() < 1e-4, "{:?}", hit);
+    assert!(hit.point.abs_diff_eq(Vec3::new(-3.5, 0.5, 0.0), 1e-4));
+    assert!(hit.normal.abs_diff_eq(Vec3::NEG_X, 1e-4));
+
+    // over the top of the capsule's upper cap
+    let hit = world.raycast(Vec3::new(-3.0, 5.0, 0.0), Vec3::NEG_Y, 10.0, &all).unwrap();
+    assert_eq!(hit.entity, scene.pillar);
+    assert!((hit.distance - 3.0).abs() < 1e-4 && hit.normal.abs_diff_eq(Vec3::Y, 1e-4), "{:?}", hit);
+
+    let hit = world.raycast(Vec3::new(5.0, 5.0, 1.0), Vec3::NEG_Y, 10.0, &all).unwrap();
+    assert_eq!(hit.entity, scene.floor);
+    assert!((hit.distance - 5.0).abs() < 1e-4 && hit.normal.abs_diff_eq(Vec3::Y, 1e-4), "{:?}", hit);
+
+    assert!(world.raycast(Vec3::new(-10.0, 0.5, 0.0), Vec3::X, 6.0, &all).is_none());
+    assert!(world.raycast(Vec3::new(-10.0, 3.0, 0.0), Vec3::X, 30.0, &all).is_none());
+    assert!(world.raycast(Vec3::new(-10.0, 0.5, 0.0), Vec3::ZERO, 30.0, &all).is_none());
+}
+
+#[test]
+fn raycast_all_lists_hits_nearest_first() {
+    let scene = query_scene();
+    let world = physics_world(&scene.app);
+
+    let hits = world.raycast_all(Vec3::new(-10.0, 0.5, 0.0), Vec3::X, 30.0, &QueryFilter::default());
+    let entities: Vec<u32> = hits.iter().map(|hit| hit.entity).collect();
+    assert_eq!(entities, vec![scene.pillar, scene.ball, scene.crate_, scene.wall]);
+    let distances: Vec<f32> = hits.iter().map(|hit| hit.distance).collect();
+    for (distance, expected) in distances.iter().zip([6.5, 9.5, 12.5, 17.99]) {
+        assert!((distance - expected).abs() < 1e-3, "{:?}", distances);
+    }
+}
+
+#[test]
+fn raycasts_honor_the_filter() {
+    let scene = query_scene();
+    let world = physics_world(&scene.app);
+    let origin = Vec3::new(-10.0, 0.5, 0.0);
+
+    let hit = world.raycast(origin, Vec3::X, 30.0, &QueryFilter::new().exclude(scene.pillar)).unwrap();
+    assert_eq!(hit.entity, scene.ball);
+    let hit = world.raycast(origin, Vec3::X, 30.0, &QueryFilter::new().static_only()).unwrap();
+    assert_eq!(hit.entity, scene.wall);
+    let not_round = |body: &PhysicsBody| !matches!(body.collider, Collider::Sphere { .. } | Collider::Capsule { .. });
+    let hit = world.raycast(origin, Vec3::X, 30.0, &QueryFilter::new().predicate(&not_round)).unwrap();
+    assert_eq!(hit.entity, scene.crate_);
+
+    // a ray starting inside a body hits it straight away, unless that body is excluded
+    let hit = world.raycast(Vec3::new(0.0, 0.5, 0.0), Vec3::NEG_Y, 10.0, &QueryFilter::default()).unwrap();
+    assert_eq!((hit.entity, hit.distance), (scene.ball, 0.0));
+    let hit = world.raycast(Vec3::new(0.0, 0.5, 0.0), Vec3::NEG_Y, 10.0, &QueryFilter::new().exclude(scene.ball)).unwrap();
+    assert_eq!(hit.entity, scene.floor);
+    assert!((hit.distance - 0.5).abs() < 1e-4);
+}
+
+#[test]
+fn shape_casts_stop_where_the_shape_first_touches() {
+    let scene = query_scene();
+    let world = physics_world(&scene.app);
+    let all = QueryFilter::default();
+
+    let hit = world.sphere_cast(Vec3::new(-10.0, 0.5, 0.0), 0.25, Vec3::X, 30.0, &all).unwrap();
+    assert_eq!(hit.entity, scene.pillar);
+    assert!((hit.distance - 6.25).abs() < 1e-3, "{:?}", hit);
+    assert!(hit.normal.abs_diff_eq(Vec3::NEG_X, 1e-2), "{:?}", hit);
+    assert!(hit.point.abs_diff_eq(Vec3::new(-3.5, 0.5, 0.0), 1e-2), "{:?}", hit);
+
+    let hit = world.box_cast(Vec3::new(3.0, 5.0, 0.0), Vec3::splat(0.25), Quat::IDENTITY, Vec3::NEG_Y, 10.0, &all).unwrap();
+    assert_eq!(hit.entity, scene.crate_);
+    assert!((hit.distance - 3.75).abs() < 1e-3, "{:?}", hit);
+    assert!(hit.normal.abs_diff_eq(Vec3::Y, 1e-2), "{:?}", hit);
+
+    // boxes close in on rounded bodies, and turned boxes land on their corners
+    let hit = world.box_cast(Vec3::new(-10.0, 0.5, 0.0), Vec3::splat(0.25), Quat::IDENTITY, Vec3::X, 30.0, &all).unwrap();
+    assert_eq!(hit.entity, scene.pillar);
+    assert!((hit.distance - 6.25).abs() < 1e-3, "{:?}", hit);
+    let hit = world.box_cast(Vec3::new(0.4, 5.0, 0.0), Vec3::splat(0.25), Quat::IDENTITY, Vec3::NEG_Y, 10.0, &all).unwrap();
+    assert_eq!(hit.entity, scene.ball);
+    let touching = 4.75 - (0.5 + (0.25f32 - 0.15 * 0.15).sqrt());
+    assert!((hit.distance - touching).abs() < 1e-3, "{:?}", hit);
+    let turned = Quat::from_rotation_z(std::f32::consts::FRAC_PI_4);
+    let hit = world.box_cast(Vec3::new(3.0, 5.0, 0.0), Vec3::splat(0.25), turned, Vec3::NEG_Y, 10.0, &all).unwrap();
+    assert_eq!(hit.entity, scene.crate_);
+    assert!((hit.distance - (4.0 - 0.25 * std::f32::consts::SQRT_2)).abs() < 1e-3, "{:?}", hit);
+
+    // a tiny sphere cast a long way costs no more than a short one
+    let hit = world.sphere_cast(Vec3::new(-1000.0, 0.5, 0.0), 0.001, Vec3::X, 2000.0, &all).unwrap();
+    assert_eq!(hit.entity, scene.pillar);
+    assert!((hit.distance - 996.499).abs() < 1e-2, "{:?}", hit);
+
+    // a thin wall can't be skipped over
+    let hit = world.sphere_cast(Vec3::new(5.0, 0.5, 0.0), 0.25, Vec3::X, 10.0, &all).unwrap();
+    assert_eq!(hit.entity, scene.wall);
+    assert!((hit.distance - 2.74).abs() < 1e-3, "{:?}", hit);
+
+    // too short, and passing over everything
+    assert!(world.sphere_cast(Vec3::new(-10.0, 0.5, 0.0), 0.25, Vec3::X, 6.0, &all).is_none());
+    assert!(world.box_cast(Vec3::new(-10.0, 3.0, 0.0), Vec3::splat(0.25), Quat::IDENTITY, Vec3::X, 30.0, &all).is_none());
+}
+
+#[test]
+fn overlap_queries_find_bodies_in_a_region() {
+    let scene = query_scene();
+    let world = physics_world(&scene.app);
+    let mut expected = vec![scene.floor, scene.ball, scene.crate_, scene.pillar];
+    expected.sort();
+
+    assert_eq!(world.overlap_sphere(Vec3::new(0.0, 0.5, 0.0), 2.6, &QueryFilter::default()), expected);
+    assert_eq!(world.overlap_sphere(Vec3::new(0.0, 0.5, 0.0), 2.4, &QueryFilter::new().dynamic_only()), vec![scene.ball]);
+
+    let mut expected = vec![scene.floor, scene.crate_];
+    expected.sort();
+    assert_eq!(world.overlap_aabb(Vec3::new(2.0, 0.0, -1.0), Vec3::new(4.0, 2.0, 1.0), &QueryFilter::default()), expected);
+    assert!(world.overlap_aabb(Vec3::new(2.0, 5.0, -1.0), Vec3::new(4.0, 6.0, 1.0), &QueryFilter::default()).is_empty());
+}
+
+fn world_mut(app: &mut App) -> &mut PhysicsWorld {
+    let commands: &Commands = app;
+    unsafe { World::get_resource_mut::<PhysicsWorld>(commands.world).unwrap() }
+}
+
+#[test]
+fn collision_groups_decide_which_bodies_touch() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    let floor = spawn_floor(&mut app);
+    app.add_component(floor, CollisionGroups::new(1 << 0, CollisionGroups::ALL)).unwrap();
+    let ball = spawn_body(&mut app, Vec3::new(0.0, 0.45, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    // a member of group 2 that only collides with group 2 drops through the floor
+    let ghost = spawn_body(&mut app, Vec3::new(3.0, 0.45, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    app.add_component(ghost, CollisionGroups::new(1 << 1, 1 << 1)).unwrap();
+
+    simulate(&mut app, 1, |world| {
+        let mut expected = vec![(floor.min(ball), floor.max(ball))];
+        expected.sort();
+        assert_eq!(world.broad_phase_pairs(), expected.as_slice());
+    });
+    simulate(&mut app, 60, |_| {});
+
+    assert!((body_position(&app, ball).y - 0.5).abs() < 0.05);
+    assert!(body_position(&app, ghost).y < -1.0);
+}
+
+#[test]
+fn collision_groups_need_both_sides_to_agree() {
+    let everything = CollisionGroups::default();
+    let walls = CollisionGroups::new(1, CollisionGroups::ALL);
+    let enemies = CollisionGroups::new(2, 1);
+    assert!(everything.interacts_with(&walls));
+    assert!(enemies.interacts_with(&walls) && walls.interacts_with(&enemies));
+    assert!(!enemies.interacts_with(&enemies));
+    assert!(!enemies.interacts_with(&CollisionGroups::new(4, CollisionGroups::ALL)));
+    assert!(!everything.interacts_with(&CollisionGroups::new(CollisionGroups::NONE, CollisionGroups::ALL)));
+}
+
+#[test]
+fn pair_filter_vetoes_pairs() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    spawn_floor(&mut app);
+    let kept = spawn_body(&mut app, Vec3::new(0.0, 0.5, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    let vetoed = spawn_body(&mut app, Vec3::new(3.0, 0.5, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    world_mut(&mut app).set_pair_filter(PairFilter::new(move |a, b| a.entity != vetoed && b.entity != vetoed));
+
+    simulate(&mut app, 60, |world| {
+        assert!(world.contacts().iter().all(|contact| contact.entity_a != vetoed && contact.entity_b != vetoed));
+    });
+    assert!((body_position(&app, kept).y - 0.5).abs() < 0.05);
+    assert!(body_position(&app, vetoed).y < -1.0);
+
+    world_mut(&mut app).clear_pair_filter();
+    let caught = spawn_body(&mut app, Vec3::new(6.0, 0.5, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    simulate(&mut app, 60, |_| {});
+    assert!((body_position(&app, caught).y - 0.5).abs() < 0.05);
+}
+
+#[test]
+fn queries_only_see_groups_they_collide_with() {
+    const WALLS: u32 = 1 << 0;
+    const ENEMIES: u32 = 1 << 1;
+
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    zero_gravity(&mut app);
+    let wall = spawn_body(&mut app, Vec3::new(10.0, 0.0, 0.0), RigidBody::static_body(), Collider::cuboid(Vec3::new(0.1, 5.0, 5.0)));
+    app.add_component(wall, CollisionGroups::new(WALLS, CollisionGroups::ALL)).unwrap();
+    let looking = spawn_body(&mut app, Vec3::ZERO, RigidBody::static_body(), Collider::sphere(0.5));
+    let in_the_way = spawn_body(&mut app, Vec3::new(5.0, 0.0, 0.0), RigidBody::static_body(), Collider::sphere(0.5));
+    for enemy in [looking, in_the_way] {
+        app.add_component(enemy, CollisionGroups::new(ENEMIES, CollisionGroups::ALL)).unwrap();
+    }
+    app.run();
+    let world = physics_world(&app);
+
+    // vision rays start inside the enemy casting them and look past other enemies
+    let vision = QueryFilter::new().groups(CollisionGroups::new(ENEMIES, !ENEMIES));
+    let hit = world.raycast(Vec3::ZERO, Vec3::X, 20.0, &vision).unwrap();
+    assert_eq!(hit.entity, wall);
+    assert!((hit.distance - 9.9).abs() < 1e-4);
+    assert_eq!(world.raycast(Vec3::ZERO, Vec3::X, 20.0, &QueryFilter::default()).unwrap().entity, looking);
+
+    assert_eq!(world.overlap_sphere(Vec3::new(7.5, 0.0, 0.0), 3.0, &vision), vec![wall]);
+    let hit = world.sphere_cast(Vec3::new(2.0, 0.0, 0.0), 0.25, Vec3::X, 20.0, &vision).unwrap();
+    assert_eq!(hit.entity, wall);
+}
+
+mod gameplay {
+    // the system! macro expands to paths from the ecs prelude
+    use rust_game_engine::*;
+
+    system! {
+        pub fn slide_static_bodies(bodies: query (&RigidBody, &mut Transform)) {
+            for (rigid_body, transform) in bodies {
+                if rigid_body.is_static() {
+                    transform.pos.x += 1.0;
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn gameplay_moves_static_bodies() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    app.add_system(gameplay::slide_static_bodies, rust_game_engine::SystemStage::Update);
+    let platform = spawn_body(&mut app, Vec3::ZERO, RigidBody::static_body(), Collider::cuboid(Vec3::splat(0.5)));
+
+    simulate(&mut app, 3, |_| {});
+
+    let commands: &Commands = &app;
+    let (_, transform) = unsafe { World::get_components_mut::<Transform>(commands.world) }
+        .into_iter()
+        .find(|(id, _)| *id == platform)
+        .unwrap();
+    assert_eq!(transform.pos, Vec3::new(3.0, 0.0, 0.0));
+    // the physics world picks the move up at the start of the next frame
+    assert_eq!(body_position(&app, platform), Vec3::new(2.0, 0.0, 0.0));
+}
+
+/// Runs `steps` steps, gathering the trigger events each frame hands to `PhysicsEvents`.
+fn trigger_events(app: &mut App, steps: usize) -> Vec<TriggerEvent> {
+    let mut events = Vec::new();
+    for _ in 0..steps {
+        simulate(app, 1, |_| {});
+        let commands: &Commands = app;
+        events.extend(unsafe { World::get_resource::<PhysicsEvents>(commands.world) }.unwrap().triggers.iter().copied());
+    }
+    events
+}
+
+#[test]
+fn sensors_report_enter_stay_and_exit() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    zero_gravity(&mut app);
+    let zone = spawn_body(&mut app, Vec3::ZERO, RigidBody::static_body(), Collider::cuboid(Vec3::splat(1.0)));
+    app.add_component(zone, Sensor).unwrap();
+    let ball = spawn_body(&mut app, Vec3::new(-3.0, 0.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    set_velocity(&mut app, ball, Vec3::new(6.0, 0.0, 0.0));
+
+    let events = trigger_events(&mut app, 90);
+
+    let enter = TriggerEvent::Enter { sensor: zone, other: ball };
+    let stay = TriggerEvent::Stay { sensor: zone, other: ball };
+    let exit = TriggerEvent::Exit { sensor: zone, other: ball };
+    assert_eq!(events.first(), Some(&enter));
+    assert_eq!(events.last(), Some(&exit));
+    let stays = &events[1..events.len() - 1];
+    assert!(stays.iter().all(|event| *event == stay), "{:?}", events);
+    // 3 across the zone at 6 per second is half a second, give or take a step at either end
+    assert!((28..=31).contains(&stays.len()), "{} stays", stays.len());
+
+    // sensors don't push back
+    let commands: &Commands = &app;
+    let world = unsafe { World::get_resource::<PhysicsWorld>(commands.world).unwrap() };
+    assert!(world.get_body(ball).unwrap().velocity.0.abs_diff_eq(Vec3::new(6.0, 0.0, 0.0), 1e-5));
+}
+
+#[test]
+fn sensors_never_touch_what_they_detect() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    spawn_floor(&mut app);
+    let plate = spawn_body(&mut app, Vec3::new(0.0, 2.0, 0.0), RigidBody::static_body(), Collider::cuboid(Vec3::new(2.0, 0.1, 2.0)));
+    app.add_component(plate, Sensor).unwrap();
+    let ball = spawn_body(&mut app, Vec3::new(0.0, 4.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+
+    let mut overlapped = false;
+    simulate(&mut app, 120, |world| {
+        overlapped |= world.sensor_overlaps() == [(plate, ball)];
+        assert!(world.contacts().iter().all(|contact| contact.entity_a != plate && contact.entity_b != plate));
+    });
+
+    // it fell straight through the plate onto the floor
+    assert!(overlapped);
+    assert!((body_position(&app, ball).y - 0.5).abs() < 0.05);
+    let world = physics_world(&app);
+    assert!(world.sensor_overlaps().is_empty());
+
+    let from_above = world.raycast(Vec3::new(0.0, 5.0, 0.0), Vec3::NEG_Y, 10.0, &QueryFilter::default()).unwrap();
+    assert_eq!(from_above.entity, plate);
+    let from_above = world.raycast(Vec3::new(0.0, 5.0, 0.0), Vec3::NEG_Y, 10.0, &QueryFilter::new().skip_sensors()).unwrap();
+    assert_eq!(from_above.entity, ball);
+}
+
+#[test]
+fn despawning_inside_a_sensor_exits_it() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    zero_gravity(&mut app);
+    let pickup = spawn_body(&mut app, Vec3::ZERO, RigidBody::static_body(), Collider::sphere(1.0));
+    app.add_component(pickup, Sensor).unwrap();
+    let player = spawn_body(&mut app, Vec3::new(0.5, 0.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+
+    assert_eq!(trigger_events(&mut app, 2), vec![
+        TriggerEvent::Enter { sensor: pickup, other: player },
+        TriggerEvent::Stay { sensor: pickup, other: player },
+    ]);
+
+    app.despawn_entity(pickup).unwrap();
+    assert_eq!(trigger_events(&mut app, 2), vec![TriggerEvent::Exit { sensor: pickup, other: player }]);
+}
+
+#[test]
+fn static_sensors_see_static_bodies_moved_into_them() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    zero_gravity(&mut app);
+    let zone = spawn_body(&mut app, Vec3::ZERO, RigidBody::static_body(), Collider::cuboid(Vec3::splat(1.0)));
+    app.add_component(zone, Sensor).unwrap();
+    let door = spawn_body(&mut app, Vec3::new(5.0, 0.0, 0.0), RigidBody::static_body(), Collider::cuboid(Vec3::splat(0.5)));
+
+    assert!(trigger_events(&mut app, 2).is_empty());
+
+    set_position(&mut app, door, Vec3::new(0.5, 0.0, 0.0));
+    assert_eq!(trigger_events(&mut app, 1), vec![TriggerEvent::Enter { sensor: zone, other: door }]);
+}
+
+fn set_position(app: &mut App, entity: u32, pos: Vec3) {
+    let commands: &Commands = app;
+    for (id, transform) in unsafe { World::get_components_mut::<Transform>(commands.world) } {
+        if id == entity {
+            transform.pos = pos;
+        }
+    }
+}
+
+#[test]
+fn bodies_persist_between_frames() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    spawn_floor(&mut app);
+    let dropped = spawn_sleepy_box(&mut app, Vec3::new(0.0, 0.5, 0.0));
+    simulate(&mut app, 90, |_| {});
+    assert!(sleeping(&app, dropped));
+
+    // frames without a step leave the bodies, and what the step worked out, alone
+    let before = physics_world(&app).get_body(dropped).unwrap().clone();
+    for _ in 0..5 {
+        app.run();
+    }
+    let after = physics_world(&app).get_body(dropped).unwrap();
+    assert!(after.sleeping);
+    assert_eq!(after.position, before.position);
+    assert_eq!(after.rotation, before.rotation);
+}
+
+#[test]
+fn despawned_entities_lose_their_bodies() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    let floor = spawn_floor(&mut app);
+    let first = spawn_body(&mut app, Vec3::new(-2.0, 0.5, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    let second = spawn_body(&mut app, Vec3::new(2.0, 0.5, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    simulate(&mut app, 5, |_| {});
+    assert_eq!(physics_world(&app).body_count(), 3);
+
+    app.despawn_entity(first).unwrap();
+    simulate(&mut app, 5, |_| {});
+
+    let world = physics_world(&app);
+    assert_eq!(world.body_count(), 2);
+    assert!(world.get_body(first).is_none());
+    assert_eq!(world.get_body(second).unwrap().entity, second);
+    assert_eq!(world.get_body(floor).unwrap().entity, floor);
+    assert!(world.contacts().iter().all(|contact| contact.entity_a != first && contact.entity_b != first));
+}
+
+#[test]
+fn moving_the_transform_teleports_the_body() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    zero_gravity(&mut app);
+    let zone = spawn_body(&mut app, Vec3::ZERO, RigidBody::static_body(), Collider::cuboid(Vec3::splat(2.0)));
+    app.add_component(zone, Sensor).unwrap();
+    let ball = spawn_body(&mut app, Vec3::new(-1.0, 0.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    set_velocity(&mut app, ball, Vec3::new(0.0, 1.0, 0.0));
+    assert_eq!(trigger_events(&mut app, 1), vec![TriggerEvent::Enter { sensor: zone, other: ball }]);
+
+    // a jump within the zone keeps the overlap going rather than leaving and entering again
+    set_position(&mut app, ball, Vec3::new(1.0, 0.0, 0.0));
+    assert_eq!(trigger_events(&mut app, 1), vec![TriggerEvent::Stay { sensor: zone, other: ball }]);
+
+    let body = physics_world(&app).get_body(ball).unwrap();
+    assert!(body.position.abs_diff_eq(Vec3::new(1.0, 1.0 / 60.0, 0.0), 1e-4), "{:?}", body.position);
+    assert!(body.velocity.0.abs_diff_eq(Vec3::new(0.0, 1.0, 0.0), 1e-5));
+}
+
+#[test]
+fn teleporting_through_the_physics_world_sticks() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    spawn_floor(&mut app);
+    let dropped = spawn_sleepy_box(&mut app, Vec3::new(0.0, 0.5, 0.0));
+    simulate(&mut app, 90, |_| {});
+    assert!(sleeping(&app, dropped));
+
+    let rotation = Quat::from_rotation_y(0.5);
+    assert!(world_mut(&mut app).teleport(dropped, Vec3::new(4.0, 3.0, 0.0), rotation));
+    assert!(!world_mut(&mut app).teleport(9999, Vec3::ZERO, Quat::IDENTITY));
+    app.run();
+
+    // the ECS takes the new pose instead of dragging the body back
+    let commands: &Commands = &app;
+    let (_, transform) = unsafe { World::get_components::<Transform>(commands.world) }
+        .into_iter()
+        .find(|(id, _)| *id == dropped)
+        .unwrap();
+    assert_eq!(transform.pos, Vec3::new(4.0, 3.0, 0.0));
+    assert_eq!(transform.rot, rotation);
+    assert!(!sleeping(&app, dropped));
+
+    simulate(&mut app, 120, |_| {});
+    assert!((body_position(&app, dropped).y - 0.5).abs() < 0.05);
+    assert!((body_position(&app, dropped).x - 4.0).abs() < 0.05);
+}
+
+#[test]
+fn changing_the_collider_reshapes_the_body() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    spawn_floor(&mut app);
+    let ball = spawn_body(&mut app, Vec3::new(0.0, 0.5, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    app.add_component(ball, Sleeping(false)).unwrap();
+    simulate(&mut app, 120, |_| {});
+    assert!(sleeping(&app, ball));
+    let small_inertia = physics_world(&app).get_body(ball).unwrap().inertia;
+
+    {
+        let commands: &Commands = &app;
+        for (_, collider) in unsafe { World::get_components_mut::<Collider>(commands.world) } {
+            if let Collider::Sphere { radius } = collider {
+                *radius = 1.0;
+            }
+        }
+        for (_, rigid_body) in unsafe { World::get_components_mut::<RigidBody>(commands.world) } {
+            if !rigid_body.is_static() {
+                rigid_body.mass = 2.0;
+            }
+        }
+    }
+    simulate(&mut app, 120, |_| {});
+
+    let body = physics_world(&app).get_body(ball).unwrap();
+    assert_eq!(body.collider, Collider::sphere(1.0));
+    assert!(body.inertia.abs_diff_eq(small_inertia * 8.0, 1e-4), "{:?}", body.inertia);
+    // it woke up and pushed itself out of the floor it grew into
+    assert!((body.position.y - 1.0).abs() < 0.05, "{:?}", body.position);
+}
+
+fn random_aabbs(rng: &mut StdRng, count: usize, spread: f32) -> Vec<(Vec3, Vec3)> {
+    (0..count)
+        .map(|_| {
+            let center = Vec3::new(
+                rng.random_range(-spread..=spread),
+                rng.random_range(-spread..=spread),
+                rng.random_range(-spread..=spread),
+            );
+            let half_extents = Vec3::splat(rng.random_range(0.2..=0.6));
+            (center - half_extents, center + half_extents)
+        })
+        .collect()
+}
+
+fn overlapping_pairs(broad_phase: &dyn BroadPhase, aabbs: &[(Vec3, Vec3)]) -> Vec<(u32, u32)> {
+    let mut pairs = broad_phase.pairs();
+    pairs.retain(|&(a, b)| {
+        let ((min_a, max_a), (min_b, max_b)) = (aabbs[a as usize], aabbs[b as usize]);
+        min_a.cmple(max_b).all() && min_b.cmple(max_a).all()
+    });
+    pairs
+}
+
+fn brute_force_pairs(aabbs: &[(Vec3, Vec3)], alive: impl Fn(u32) -> bool) -> Vec<(u32, u32)> {
+    let mut pairs = Vec::new();
+    for (a, &(min_a, max_a)) in aabbs.iter().enumerate() {
+        for (b, &(min_b, max_b)) in aabbs.iter().enumerate().skip(a + 1) {
+            let (a, b) = (a as u32, b as u32);
+            if alive(a) && alive(b) && min_a.cmple(max_b).all() && min_b.cmple(max_a).all() {
+                pairs.push((a, b));
+            }
+        }
+    }
+    pairs
+}
+
+#[test]
+fn broad_phases_find_every_overlapping_pair() {
+    let broad_phases: [Box<dyn BroadPhase>; 2] = [Box::new(DynamicTree::default()), Box::new(UniformGrid::new(1.0, 0.1))];
+    for mut broad_phase in broad_phases {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut aabbs = random_aabbs(&mut rng, 300, 6.0);
+        // a floor far larger than the grid's cells
+        aabbs[0] = (Vec3::new(-20.0, -7.0, -20.0), Vec3::new(20.0, -6.0, 20.0));
+        for (entity, &(min, max)) in aabbs.iter().enumerate() {
+            broad_phase.update(entity as u32, min, max);
+        }
+        assert_eq!(overlapping_pairs(&*broad_phase, &aabbs), brute_force_pairs(&aabbs, |_| true), "{:?}", broad_phase);
+
+        // nudge everything, some by more than the margin, and drop every tenth body
+        for (entity, aabb) in aabbs.iter_mut().enumerate().skip(1) {
+            let offset = Vec3::new(rng.random_range(-0.5..=0.5), rng.random_range(-0.05..=0.05), 0.0);
+            *aabb = (aabb.0 + offset, aabb.1 + offset);
+            broad_phase.update(entity as u32, aabb.0, aabb.1);
+        }
+        for entity in (5..aabbs.len() as u32).step_by(10) {
+            broad_phase.remove(entity);
+        }
+        let alive = |entity: u32| entity < 5 || entity % 10 != 5;
+        assert_eq!(overlapping_pairs(&*broad_phase, &aabbs), brute_force_pairs(&aabbs, alive), "{:?}", broad_phase);
+
+        let (min, max) = (Vec3::splat(-2.0), Vec3::splat(2.0));
+        let found: Vec<u32> = broad_phase
+            .query(min, max)
+            .into_iter()
+            .filter(|&entity| {
+                let (body_min, body_max) = aabbs[entity as usize];
+                body_min.cmple(max).all() && min.cmple(body_max).all()
+            })
+            .collect();
+        let expected: Vec<u32> = (0..aabbs.len() as u32)
+            .filter(|&entity| {
+                let (body_min, body_max) = aabbs[entity as usize];
+                alive(entity) && body_min.cmple(max).all() && min.cmple(body_max).all()
+            })
+            .collect();
+        assert_eq!(found, expected);
+    }
+}
+
+#[test]
+fn broad_phases_keep_fattened_aabbs_until_bodies_leave_them() {
+    let broad_phases: [Box<dyn BroadPhase>; 2] = [Box::new(DynamicTree::new(0.1)), Box::new(UniformGrid::new(1.0, 0.1))];
+    for mut broad_phase in broad_phases {
+        broad_phase.update(1, Vec3::ZERO, Vec3::ONE);
+        // just past the body, but inside its margin
+        let probe = (Vec3::splat(-0.08), Vec3::splat(-0.05));
+        assert_eq!(broad_phase.query(probe.0, probe.1), vec![1]);
+
+        // small moves stay inside the fattened AABB, which is left where it was
+        broad_phase.update(1, Vec3::splat(0.05), Vec3::splat(1.05));
+        assert_eq!(broad_phase.query(probe.0, probe.1), vec![1]);
+
+        broad_phase.update(1, Vec3::splat(0.5), Vec3::splat(1.5));
+        assert!(broad_phase.query(probe.0, probe.1).is_empty());
+        assert_eq!(broad_phase.query(Vec3::splat(1.55), Vec3::splat(1.58)), vec![1]);
+    }
+}
+
+#[test]
+fn broad_phases_give_the_same_simulation() {
+    let run = |broad_phase: Box<dyn FnOnce(&mut PhysicsWorld)>| {
+        let mut app = App::new();
+        app.add_plugin(PhysicsPlugin);
+        broad_phase(world_mut(&mut app));
+        spawn_floor(&mut app);
+        let mut rng = StdRng::seed_from_u64(11);
+        let entities: Vec<u32> = (0..40)
+            .map(|index| {
+                let pos = Vec3::new(rng.random_range(-2.0..=2.0), 1.0 + index as f32 * 0.6, rng.random_range(-2.0..=2.0));
+                spawn_body(&mut app, pos, RigidBody::dynamic(1.0), Collider::sphere(0.3))
+            })
+            .collect();
+        let mut pairs = Vec::new();
+        simulate(&mut app, 120, |world| pairs.push(world.broad_phase_pairs().to_vec()));
+        let positions: Vec<Vec3> = entities.iter().map(|&entity| body_position(&app, entity)).collect();
+        (pairs, positions)
+    };
+
+    let tree = run(Box::new(|_| {}));
+    let grid = run(Box::new(|world| world.set_broad_phase(UniformGrid::new(1.0, DEFAULT_AABB_MARGIN))));
+    assert!(tree.0.iter().any(|pairs| pairs.len() > 40));
+    assert_eq!(tree, grid);
+}
+
+/// Times filling, moving and pairing `count` bodies in each broad phase, and checks they agree.
+/// Run with `--release -- --include-ignored --nocapture` for numbers worth comparing.
+fn benchmark_broad_phases(count: usize) {
+    // about as crowded at any count
+    let spread = (count as f32).cbrt() * 1.5;
+    let mut rng = StdRng::seed_from_u64(count as u64);
+    let aabbs = random_aabbs(&mut rng, count, spread);
+    let moves: Vec<Vec3> = (0..count)
+        .map(|_| Vec3::new(rng.random_range(-0.2..=0.2), rng.random_range(-0.2..=0.2), rng.random_range(-0.2..=0.2)))
+        .collect();
+
+    let mut results = Vec::new();
+    let broad_phases: [(&str, Box<dyn BroadPhase>); 2] = [
+        ("dynamic tree", Box::new(DynamicTree::default())),
+        ("uniform grid", Box::new(UniformGrid::new(1.5, DEFAULT_AABB_MARGIN))),
+    ];
+    for (name, mut broad_phase) in broad_phases {
+        let started = Instant::now();
+        for (entity, &(min, max)) in aabbs.iter().enumerate() {
+            broad_phase.update(entity as u32, min, max);
+        }
+        let filled = started.elapsed();
+
+        let started = Instant::now();
+        let mut pairs = Vec::new();
+        for frame in 1..=10 {
+            for (entity, (&(min, max), &offset)) in aabbs.iter().zip(&moves).enumerate() {
+                let offset = offset * frame as f32;
+                broad_phase.update(entity as u32, min + offset, max + offset);
+            }
+            pairs = broad_phase.pairs();
+        }
+        let stepped = started.elapsed();
+
+        println!("{name}, {count} bodies: filled in {filled:?}, 10 frames of moves and pairs in {stepped:?}, {} pairs", pairs.len());
+        results.push(pairs);
+    }
+
+    let moved: Vec<(Vec3, Vec3)> = aabbs.iter().zip(&moves).map(|(&(min, max), &offset)| (min + offset * 10.0, max + offset * 10.0)).collect();
+    let real = |pairs: &[(u32, u32)]| -> Vec<(u32, u32)> {
+        pairs
+            .iter()
+            .copied()
+            .filter(|&(a, b)| {
+                let ((min_a, max_a), (min_b, max_b)) = (moved[a as usize], moved[b as usize]);
+                min_a.cmple(max_b).all() && min_b.cmple(max_a).all()
+            })
+            .collect()
+    };
+    assert_eq!(real(&results[0]), real(&results[1]));
+}
+
+#[test]
+fn benchmark_broad_phases_1k() {
+    benchmark_broad_phases(1_000);
+}
+
+#[test]
+#[ignore = "slow in debug builds"]
+fn benchmark_broad_phases_10k() {
+    benchmark_broad_phases(10_000);
+}
+
+fn spawn_joint(app: &mut App, joint: Joint) -> u32 {
+    let entity = app.spawn_entity();
+    app.add_component(entity, joint).unwrap();
+    entity
+}
+
+fn spawn_pivot(app: &mut App, pos: Vec3) -> u32 {
+    spawn_body(app, pos, RigidBody::static_body(), Collider::sphere(0.1))
+}
+
+fn body_rotation(app: &App, entity: u32) -> Quat {
+    physics_world(app).get_body(entity).unwrap().rotation
+}
+
+#[test]
+fn ball_joints_swing_bodies_about_the_anchor() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    let pivot = spawn_pivot(&mut app, Vec3::new(0.0, 5.0, 0.0));
+    let bob = spawn_body(&mut app, Vec3::new(2.0, 5.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.25));
+    spawn_joint(&mut app, Joint::new(pivot, bob, JointKind::Ball).with_anchors(Vec3::ZERO, Vec3::new(-2.0, 0.0, 0.0)));
+
+    let mut lowest = f32::MAX;
+    simulate(&mut app, 120, |world| {
+        let bob = world.get_body(bob).unwrap();
+        let anchor = bob.position + bob.rotation * Vec3::new(-2.0, 0.0, 0.0);
+        assert!(anchor.distance(Vec3::new(0.0, 5.0, 0.0)) < 0.05, "{:?}", anchor);
+        assert!((bob.position.distance(Vec3::new(0.0, 5.0, 0.0)) - 2.0).abs() < 0.05);
+        lowest = lowest.min(bob.position.y);
+    });
+    // it swung down through the bottom of the arc
+    assert!(lowest < 3.1, "{}", lowest);
+    assert_eq!(physics_world(&app).joint_count(), 1);
+}
+
+#[test]
+fn fixed_joints_hold_bodies_rigidly() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    let wall = spawn_body(&mut app, Vec3::ZERO, RigidBody::static_body(), Collider::cuboid(Vec3::splat(0.5)));
+    let shelf = spawn_body(&mut app, Vec3::new(1.5, 0.0, 0.0), RigidBody::dynamic(1.0), Collider::cuboid(Vec3::new(1.0, 0.1, 0.5)));
+    let start = at(Vec3::ZERO).rot;
+    spawn_joint(&mut app, Joint::new(wall, shelf, JointKind::Fixed).with_anchors(Vec3::new(0.5, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0)));
+
+    simulate(&mut app, 120, |_| {});
+
+    assert!(body_position(&app, shelf).abs_diff_eq(Vec3::new(1.5, 0.0, 0.0), 0.05), "{:?}", body_position(&app, shelf));
+    assert!(body_rotation(&app, shelf).angle_between(start) < 0.05);
+}
+
+#[test]
+fn hinges_turn_only_about_their_axis_within_limits() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    zero_gravity(&mut app);
+    let frame = spawn_pivot(&mut app, Vec3::ZERO);
+    let door = spawn_body(&mut app, Vec3::new(0.5, 0.0, 0.0), RigidBody::dynamic(1.0), Collider::cuboid(Vec3::new(0.5, 1.0, 0.05)));
+    let hinge = JointKind::Hinge {
+        axis: Vec3::Y,
+        limits: Some((0.0, 1.0)),
+    };
+    spawn_joint(&mut app, Joint::new(frame, door, hinge).with_anchors(Vec3::ZERO, Vec3::new(-0.5, 0.0, 0.0)));
+    // the hinge holds the edge still, so only a fraction of the spin about y swings the door
+    set_angular_velocity(&mut app, door, Vec3::new(2.0, 6.0, 0.0));
+
+    let mut widest = 0.0f32;
+    simulate(&mut app, 90, |world| {
+        let door = world.get_body(door).unwrap();
+        assert!((door.rotation * Vec3::Y).dot(Vec3::Y) > 0.99);
+        let hinge_point = door.position + door.rotation * Vec3::new(-0.5, 0.0, 0.0);
+        assert!(hinge_point.length() < 0.05, "{:?}", hinge_point);
+        widest = widest.max(2.0 * door.rotation.y.atan2(door.rotation.w));
+    });
+
+    // it swung open against the upper limit and stopped there
+    assert!(widest < 1.05, "{}", widest);
+    let angle = 2.0 * body_rotation(&app, door).y.atan2(body_rotation(&app, door).w);
+    assert!((angle - 1.0).abs() < 0.05, "{}", angle);
+}
+
+#[test]
+fn ropes_go_slack_but_never_stretch() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    let ceiling = spawn_pivot(&mut app, Vec3::new(0.0, 5.0, 0.0));
+    let bob = spawn_body(&mut app, Vec3::new(0.0, 4.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.25));
+    spawn_joint(&mut app, Joint::new(ceiling, bob, JointKind::Distance { min: 0.0, max: 2.0 }));
+
+    let mut lengths = Vec::new();
+    simulate(&mut app, 120, |world| lengths.push(world.get_body(bob).unwrap().position.distance(Vec3::new(0.0, 5.0, 0.0))));
+
+    // falling freely while the rope is slack, then caught at its length
+    let free_fall = 1.0 + 0.5 * 9.81 * (10.0f32 / 60.0).powi(2);
+    assert!((lengths[9] - free_fall).abs() < 0.02, "{} vs {}", lengths[9], free_fall);
+    assert!(lengths.iter().all(|&length| length < 2.05), "{:?}", lengths);
+    assert!((lengths.last().unwrap() - 2.0).abs() < 0.02);
+}
+
+#[test]
+fn rods_push_as_well_as_pull() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    zero_gravity(&mut app);
+    let pivot = spawn_pivot(&mut app, Vec3::ZERO);
+    let bob = spawn_body(&mut app, Vec3::new(1.0, 0.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.25));
+    spawn_joint(&mut app, Joint::new(pivot, bob, JointKind::Distance { min: 1.0, max: 1.0 }));
+    set_velocity(&mut app, bob, Vec3::new(-3.0, 1.0, 0.0));
+
+    simulate(&mut app, 60, |world| {
+        let length = world.get_body(bob).unwrap().position.length();
+        assert!((length - 1.0).abs() < 0.05, "{}", length);
+    });
+}
+
+#[test]
+fn springs_settle_where_gravity_balances_them() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    let ceiling = spawn_pivot(&mut app, Vec3::new(0.0, 5.0, 0.0));
+    let bob = spawn_body(&mut app, Vec3::new(0.0, 4.0, 0.0), RigidBody::dynamic(2.0), Collider::sphere(0.25));
+    let spring = JointKind::Spring {
+        rest_length: 1.0,
+        stiffness: 100.0,
+        damping: 10.0,
+    };
+    spawn_joint(&mut app, Joint::new(ceiling, bob, spring));
+    // a spring slows to a stop at either end of every bounce, which is no time to fall asleep
+    world_mut(&mut app).set_sleep_thresholds(SleepThresholds {
+        time_to_sleep: f32::INFINITY,
+        ..SleepThresholds::default()
+    });
+
+    let mut lowest = f32::MAX;
+    simulate(&mut app, 300, |world| lowest = lowest.min(world.get_body(bob).unwrap().position.y));
+
+    let stretch = 2.0 * 9.81 / 100.0;
+    assert!((body_position(&app, bob).y - (4.0 - stretch)).abs() < 0.01, "{:?}", body_position(&app, bob));
+    // it bounced past the balance point before settling
+    assert!(lowest < 4.0 - stretch - 0.01);
+}
+
+#[test]
+fn joints_break_past_their_threshold() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    let ceiling = spawn_pivot(&mut app, Vec3::new(0.0, 5.0, 0.0));
+    let light = spawn_body(&mut app, Vec3::new(-2.0, 4.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.25));
+    let heavy = spawn_body(&mut app, Vec3::new(2.0, 4.0, 0.0), RigidBody::dynamic(4.0), Collider::sphere(0.25));
+    let holds = spawn_joint(
+        &mut app,
+        Joint::new(ceiling, light, JointKind::Ball).with_anchors(Vec3::new(-2.0, -1.0, 0.0), Vec3::ZERO).with_break_force(20.0),
+    );
+    let snaps = spawn_joint(
+        &mut app,
+        Joint::new(ceiling, heavy, JointKind::Ball).with_anchors(Vec3::new(2.0, -1.0, 0.0), Vec3::ZERO).with_break_force(20.0),
+    );
+
+    let mut broken = Vec::new();
+    for _ in 0..60 {
+        simulate(&mut app, 1, |_| {});
+        let commands: &Commands = &app;
+        broken.extend(unsafe { World::get_resource::<PhysicsEvents>(commands.world) }.unwrap().joints_broken.iter().copied());
+    }
+
+    assert_eq!(broken, vec![JointBroken { joint: snaps, entity_a: ceiling, entity_b: heavy }]);
+    assert_eq!(physics_world(&app).joint_count(), 1);
+    assert!(body_position(&app, heavy).y < 2.0);
+    assert!(body_position(&app, light).abs_diff_eq(Vec3::new(-2.0, 4.0, 0.0), 0.05));
+
+    let commands: &Commands = &app;
+    let joints = unsafe { World::get_components::<Joint>(commands.world) };
+    let broken_flag = |entity: u32| joints.iter().find(|(id, _)| *id == entity).unwrap().1.broken;
+    assert!(broken_flag(snaps));
+    assert!(!broken_flag(holds));
+}
+
+#[test]
+fn jointed_bodies_only_collide_when_asked_to() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    zero_gravity(&mut app);
+    let a = spawn_body(&mut app, Vec3::ZERO, RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    let b = spawn_body(&mut app, Vec3::new(0.5, 0.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    let c = spawn_body(&mut app, Vec3::new(5.0, 0.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    let d = spawn_body(&mut app, Vec3::new(5.5, 0.0, 0.0), RigidBody::dynamic(1.0), Collider::sphere(0.5));
+    let anchors = (Vec3::new(0.25, 0.0, 0.0), Vec3::new(-0.25, 0.0, 0.0));
+    spawn_joint(&mut app, Joint::new(a, b, JointKind::Ball).with_anchors(anchors.0, anchors.1));
+    spawn_joint(&mut app, Joint::new(c, d, JointKind::Ball).with_anchors(anchors.0, anchors.1).with_collide_connected());
+
+    simulate(&mut app, 1, |world| assert_eq!(world.broad_phase_pairs(), [(c, d)]));
+}
+
+#[test]
+fn chains_stay_linked_when_knocked() {
+    let mut app = App::new();
+    app.add_plugin(PhysicsPlugin);
+    let mut previous = spawn_pivot(&mut app, Vec3::new(0.0, 10.0, 0.0));
+    let mut previous_anchor = Vec3::ZERO;
+    let mut links = Vec::new();
+    for index in 0..6 {
+        let link = spawn_body(
+            &mut app,
+            Vec3::new(0.0, 9.5 - index as f32, 0.0),
+            RigidBody::dynamic(0.5),
+            Collider::cuboid(Vec3::new(0.1, 0.5, 0.1)),
+        );
+        let joint = Joint::new(previous, link, JointKind::Ball).with_anchors(previous_anchor, Vec3::new(0.0, 0.5, 0.0));
+        spawn_joint(&mut app, joint);
+        previous = link;
+        previous_anchor = Vec3::new(0.0, -0.5, 0.0);
+        links.push(link);
+    }
+    set_velocity(&mut app, *links.last().unwrap(), Vec3::new(8.0, 0.0, 3.0));
+
+    let mut highest = f32::MIN;
+    simulate(&mut app, 180, |world| {
+        let mut anchor = Vec3::new(0.0, 10.0, 0.0);
+        for &link in &links {
+            let link = world.get_body(link).unwrap();
+            let top = link.position + link.rotation * Vec3::new(0.0, 0.5, 0.0);
+            assert!(top.distance(anchor) < 0.1, "{:?} vs {:?}", top, anchor);
+            anchor = link.position + link.rotation * Vec3::new(0.0, -0.5, 0.0);
+        }
+        highest = highest.max(world.get_body(*links.last().unwrap()).unwrap().position.y);
+    });
+    // the kick swung the end of the chain well up
+    assert!(highest > 5.0, "{}", highest);
+}
@@ -0,0 +1,245 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rust_game_engine::netcode::{
+    ClientPrediction, InputOwner, PlayerInput, PlayerInputs, PlayerState, Predicted,
+    PredictionPlugin, ServerPrediction, default_step,
+};
+use rust_game_engine::physics::Transform;
+use rust_game_engine::utils::UtilPlugin;
+use rust_game_engine::{
+    App, LinkConditions, LoopbackHub, LoopbackTransport, Networking, NetworkingPlugin,
+    Reliability, Target, World,
+};
+
+use glam::Vec3;
+
+const DT: f32 = 1.0 / 60.0;
+
+fn predict_steps(prediction: &mut ClientPrediction, mut pos: Vec3, steps: usize) -> Vec3 {
+    for _ in 0..steps {
+        pos = prediction.predict(pos);
+    }
+    pos
+}
+
+#[test]
+fn matching_server_state_only_drops_acknowledged_inputs() {
+    let mut prediction = ClientPrediction::new(default_step);
+    prediction.set_input(Vec3::X);
+    let pos = predict_steps(&mut prediction, Vec3::ZERO, 10);
+
+    let state = PlayerState {
+        tick: 3,
+        pos: Vec3::X * DT * 4.0,
+    };
+    assert_eq!(prediction.reconcile(state, pos), pos);
+    assert_eq!(prediction.corrections(), 0);
+    assert_eq!(prediction.acked(), Some(3));
+    assert_eq!(prediction.pending().map(|i| i.tick).collect::<Vec<_>>(), (4..10).collect::<Vec<_>>());
+}
+
+#[test]
+fn mismatched_server_state_rewinds_and_replays() {
+    let mut prediction = ClientPrediction::new(default_step);
+    prediction.set_input(Vec3::X);
+    let pos = predict_steps(&mut prediction, Vec3::ZERO, 10);
+
+    // the server had the player blocked one unit further back
+    let server_pos = Vec3::X * DT * 4.0 - Vec3::X;
+    let corrected = prediction.reconcile(PlayerState { tick: 3, pos: server_pos }, pos);
+
+    assert!((corrected - (server_pos + Vec3::X * DT * 6.0)).length() < 1e-5);
+    assert_eq!(prediction.corrections(), 1);
+
+    // an older state arriving late is ignored
+    let late = PlayerState { tick: 1, pos: Vec3::splat(100.0) };
+    assert_eq!(prediction.reconcile(late, corrected), corrected);
+    assert_eq!(prediction.acked(), Some(3));
+}
+
+fn pump(apps: &mut [&mut App], timeout: Duration, mut done: impl FnMut(&mut [&mut App]) -> bool) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        for app in apps.iter_mut() {
+            app.run();
+        }
+        if done(apps) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(2));
+    }
+    false
+}
+
+fn server_player(app: &mut App) -> Option<(u32, Vec3)> {
+    let world = app.world;
+    let owners = unsafe { World::get_components::<InputOwner>(world) };
+    let (entity, _) = owners.first()?;
+    let prediction = app.get_resource::<ServerPrediction>()?;
+    assert_eq!(prediction.player(owners[0].1.0), Some(*entity));
+    unsafe { World::get_components::<Transform>(world) }
+        .into_iter()
+        .find(|(id, _)| id == entity)
+        .map(|(id, transform)| (id, transform.pos))
+}
+
+fn client_player(app: &mut App) -> Vec3 {
+    let transforms = unsafe { World::get_components::<Transform>(app.world) };
+    transforms[0].1.pos
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn client_converges_on_server_over_lossy_link() {
+    let hub = LoopbackHub::new()
+        .with_conditions(LinkConditions {
+            latency: Duration::from_millis(10),
+            jitter: Duration::from_millis(5),
+            loss: 0.2,
+            reorder: 0.1,
+        })
+        .with_seed(3);
+
+    let mut server = App::new();
+    server.add_plugin(UtilPlugin::server());
+    server.add_plugin(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&hub)));
+    server.add_plugin(PredictionPlugin::server());
+    server.init();
+
+    let mut client = App::new();
+    client.add_plugin(UtilPlugin::server());
+    client.add_plugin(NetworkingPlugin::client().with_transport(LoopbackTransport::client(&hub)));
+    client.add_plugin(PredictionPlugin::client());
+    client.init();
+
+    let player = client.spawn_entity();
+    client.add_component(player, Transform::default()).unwrap();
+    client.add_component(player, Predicted).unwrap();
+
+    assert!(pump(&mut [&mut server, &mut client], Duration::from_secs(2), |apps| {
+        server_player(apps[0]).is_some()
+    }));
+
+    client
+        .get_resource_mut::<ClientPrediction>()
+        .unwrap()
+        .set_input(Vec3::new(3.0, -1.0, 0.0));
+    pump(&mut [&mut server, &mut client], Duration::from_millis(300), |_| false);
+    let prediction = client.get_resource_mut::<ClientPrediction>().unwrap();
+    prediction.set_input(Vec3::ZERO);
+    let stopped = prediction.tick();
+
+    // once the server has caught up with the last moving input, both ends agree
+    assert!(pump(&mut [&mut server, &mut client], Duration::from_secs(3), |apps| {
+        let prediction = apps[1].get_resource::<ClientPrediction>().unwrap();
+        let caught_up = prediction.acked().is_some_and(|acked| acked >= stopped);
+        let server_pos = server_player(apps[0]).unwrap().1;
+        caught_up && (server_pos - client_player(apps[1])).length() < 1e-4
+    }));
+
+    let moved = client_player(&mut client);
+    assert!(moved.x > 0.5 && moved.y < -0.1, "player barely moved: {:?}", moved);
+}
+
+#[test]
+fn prediction_slows_inputs_down_like_the_server() {
+    let mut prediction = ClientPrediction::new(default_step);
+    prediction.max_speed = 5.0;
+    prediction.set_input(Vec3::X * 1000.0);
+    let pos = predict_steps(&mut prediction, Vec3::ZERO, 10);
+    assert!((pos.x - 5.0 * DT * 10.0).abs() < 1e-5, "{:?}", pos);
+
+    prediction.set_input(Vec3::splat(f32::NAN));
+    assert_eq!(prediction.predict(pos), pos);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn server_queues_inputs_its_clock_has_not_allowed_yet() {
+    let hub = LoopbackHub::new();
+
+    let mut server = App::new();
+    server.add_plugin(UtilPlugin::server());
+    server.add_plugin(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&hub)));
+    server.add_plugin(PredictionPlugin::server());
+    server.init();
+
+    let mut client = App::new();
+    client.add_plugin(UtilPlugin::server());
+    client.add_plugin(NetworkingPlugin::client().with_transport(LoopbackTransport::client(&hub)));
+    client.init();
+
+    assert!(pump(&mut [&mut server, &mut client], Duration::from_secs(2), |apps| {
+        server_player(apps[0]).is_some()
+    }));
+
+    // one burst, far more than the server has banked, and then silence
+    let inputs = (0..40).map(|tick| PlayerInput { tick, velocity: Vec3::X }).collect();
+    let networking = client.get_resource::<Networking>().unwrap();
+    networking.send(Reliability::Unreliable, Target::All, PlayerInputs(inputs)).unwrap();
+
+    let mut acked = None;
+    pump(&mut [&mut server, &mut client], Duration::from_millis(500), |apps| {
+        let networking = apps[1].get_resource::<Networking>().unwrap();
+        acked = networking.collect::<PlayerState>().into_iter().map(|(_, state)| state.tick).max().or(acked);
+        false
+    });
+
+    // the queue holds as many inputs as a client may lead by; they all get their step
+    let queued = 16;
+    assert_eq!(acked, Some(queued - 1));
+    let (_, pos) = server_player(&mut server).unwrap();
+    assert!((pos.x - DT * queued as f32).abs() < 1e-5, "{:?}", pos);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn server_limits_inputs_to_its_own_clock_and_speed() {
+    let hub = LoopbackHub::new();
+
+    let mut server = App::new();
+    server.add_plugin(UtilPlugin::server());
+    server.add_plugin(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&hub)));
+    server.add_plugin(PredictionPlugin::server().with_max_speed(5.0));
+    server.init();
+
+    // a client that skips prediction and floods the server with fast inputs
+    let mut cheater = App::new();
+    cheater.add_plugin(UtilPlugin::server());
+    cheater.add_plugin(NetworkingPlugin::client().with_transport(LoopbackTransport::client(&hub)));
+    cheater.init();
+
+    assert!(pump(&mut [&mut server, &mut cheater], Duration::from_secs(2), |apps| {
+        server_player(apps[0]).is_some()
+    }));
+
+    let start = Instant::now();
+    let mut tick = 0;
+    pump(&mut [&mut server, &mut cheater], Duration::from_millis(300), |apps| {
+        let inputs = (0..200)
+            .map(|_| {
+                tick += 1;
+                PlayerInput { tick, velocity: Vec3::X * 1000.0 }
+            })
+            .collect();
+        let networking = apps[1].get_resource::<Networking>().unwrap();
+        networking.send(Reliability::Unreliable, Target::All, PlayerInputs(inputs)).unwrap();
+        false
+    });
+    // inputs from far beyond the server's clock don't get applied
+    let networking = cheater.get_resource::<Networking>().unwrap();
+    let ahead = PlayerInput { tick: tick + 10_000, velocity: Vec3::X };
+    networking.send(Reliability::Unreliable, Target::All, PlayerInputs(vec![ahead])).unwrap();
+    let mut acked = Vec::new();
+    pump(&mut [&mut server, &mut cheater], Duration::from_millis(50), |apps| {
+        let networking = apps[1].get_resource::<Networking>().unwrap();
+        acked.extend(networking.collect::<PlayerState>().into_iter().map(|(_, state)| state.tick));
+        false
+    });
+    assert!(acked.iter().all(|&acked| acked < ahead.tick));
+
+    let elapsed = start.elapsed().as_secs_f32();
+    let (_, pos) = server_player(&mut server).unwrap();
+    assert!(pos.x > 0.0, "no input was applied");
+    // one step of input per server step, plus the few it lets a client bank
+    let most = 5.0 * (elapsed + 20.0 * DT);
+    assert!(pos.x <= most, "moved {} in {}s, at most {} allowed", pos.x, elapsed, most);
+}
@@ -0,0 +1,133 @@
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rust_game_engine::netcode::PlayerInputs;
+use rust_game_engine::utils::runner::{ServerClock, ServerRunner};
+// the system! and Resource macros expand to paths from the ecs prelude
+use rust_game_engine::*;
+
+#[derive(Resource, Default)]
+struct Lifecycle {
+    updates: u64,
+    de_inits: u64,
+    slow: bool,
+}
+
+system! {
+    fn count_updates(lifecycle: res &mut Lifecycle) {
+        let Some(lifecycle) = lifecycle else {
+            return;
+        };
+        lifecycle.updates += 1;
+        if lifecycle.slow {
+            thread::sleep(Duration::from_millis(8));
+        }
+    }
+}
+
+system! {
+    fn count_de_inits(lifecycle: res &mut Lifecycle) {
+        if let Some(lifecycle) = lifecycle {
+            lifecycle.de_inits += 1;
+        }
+    }
+}
+
+fn app(slow: bool) -> App {
+    let mut app = App::new();
+    app.insert_resource(Lifecycle { slow, ..Default::default() });
+    app.add_system(count_updates, SystemStage::Update);
+    app.add_system(count_de_inits, SystemStage::DeInit);
+    app
+}
+
+#[test]
+fn runs_the_requested_ticks_at_the_tick_rate() {
+    let mut app = app(false);
+    let runner = ServerRunner::from_args(["server", "--ticks", "30", "--tick-rate", "200"].map(String::from)).unwrap();
+
+    let started = Instant::now();
+    let clock = runner.run(&mut app);
+    let elapsed = started.elapsed();
+
+    assert_eq!(clock.ticks, 30);
+    assert!(elapsed >= Duration::from_millis(140), "finished too early: {:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(2), "finished too late: {:?}", elapsed);
+
+    let lifecycle = app.get_resource::<Lifecycle>().unwrap();
+    assert_eq!((lifecycle.updates, lifecycle.de_inits), (30, 1));
+}
+
+#[test]
+fn slow_ticks_are_counted_as_overruns() {
+    let mut app = app(true);
+    let clock = ServerRunner::new(500.0).with_max_ticks(10).run(&mut app);
+
+    assert_eq!(clock.overruns, 10);
+    assert!(clock.worst_tick >= Duration::from_millis(8));
+}
+
+#[test]
+fn the_shutdown_handle_stops_the_loop_and_runs_de_init() {
+    let mut app = app(false);
+    let runner = ServerRunner::new(100.0);
+    let shutdown = runner.shutdown_handle();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        shutdown.store(true, Ordering::Relaxed);
+    });
+
+    let clock = runner.run(&mut app);
+    assert!(clock.ticks > 0 && clock.ticks < 50, "ran {} ticks", clock.ticks);
+    assert_eq!(app.get_resource::<Lifecycle>().unwrap().de_inits, 1);
+}
+
+#[test]
+fn tick_rate_changes_apply_while_running() {
+    let mut app = app(false);
+    app.add_system(speed_up, SystemStage::Update);
+
+    let started = Instant::now();
+    ServerRunner::new(1.0).with_max_ticks(20).run(&mut app);
+    assert!(started.elapsed() < Duration::from_secs(1), "still ticking at 1Hz");
+}
+
+system! {
+    fn speed_up(clock: res &mut ServerClock) {
+        if let Some(clock) = clock {
+            clock.tick_rate = 1000.0;
+        }
+    }
+}
+
+#[test]
+fn bad_arguments_are_rejected() {
+    let bad: [&[&str]; 4] = [
+        &["server", "--ticks"],
+        &["server", "--ticks", "many"],
+        &["server", "--tick-rate", "0"],
+        &["server", "--fast"],
+    ];
+    for args in bad {
+        assert!(ServerRunner::from_args(args.iter().map(|a| a.to_string())).is_err(), "{:?}", args);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn networking_is_shut_down_with_the_server() {
+    let hub = LoopbackHub::new();
+    let mut server = app(false);
+    server.add_plugin(NetworkingPlugin::server().with_transport(LoopbackTransport::server(&hub)));
+
+    let clock = ServerRunner::new(100.0).with_max_ticks(5).run(&mut server);
+    assert_eq!(clock.ticks, 5);
+
+    // once the networking task has exited, sends report the disconnect
+    let networking = server.get_resource::<Networking>().unwrap();
+    let started = Instant::now();
+    while networking.send(Reliability::Reliable, Target::All, PlayerInputs(Vec::new())).is_ok() {
+        assert!(started.elapsed() < Duration::from_secs(2), "networking task never stopped");
+        thread::sleep(Duration::from_millis(1));
+    }
+}